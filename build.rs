@@ -0,0 +1,48 @@
+//! Build script: captures build information (git commit, nostr-sdk version) as env variables,
+//! read by `build_info()`
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=KEYSTR_GIT_COMMIT={}", commit);
+
+    let nostr_sdk_version = fs::read_to_string("Cargo.lock")
+        .ok()
+        .and_then(|lock| locked_version(&lock, "nostr-sdk"))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!(
+        "cargo:rustc-env=KEYSTR_NOSTR_SDK_VERSION={}",
+        nostr_sdk_version
+    );
+
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    for git_file in [".git/HEAD", ".git/index"] {
+        if Path::new(git_file).exists() {
+            println!("cargo:rerun-if-changed={}", git_file);
+        }
+    }
+}
+
+/// Version of a package from the Cargo.lock content
+fn locked_version(lock: &str, package: &str) -> Option<String> {
+    let name_line = format!("name = \"{}\"", package);
+    let mut lines = lock.lines();
+    lines.find(|l| l.trim() == name_line)?;
+    lines
+        .next()?
+        .trim()
+        .strip_prefix("version = \"")?
+        .strip_suffix('"')
+        .map(|v| v.to_string())
+}
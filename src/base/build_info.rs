@@ -0,0 +1,105 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Version and build information, for support and bug reports
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct BuildInfo {
+    /// Crate version
+    pub version: &'static str,
+    /// Git commit hash (short) the app was built from, "unknown" if not built from git
+    pub git_commit: &'static str,
+    /// Version of the nostr-sdk library used
+    pub nostr_sdk_version: &'static str,
+    /// Numbers of the NIPs implemented
+    pub implemented_nips: Vec<u16>,
+}
+
+/// NIPs implemented by keystr, sorted. Keep it up to date as features land;
+/// this is the single source for any capability advertisement.
+const IMPLEMENTED_NIPS: &[u16] = &[
+    1,  // basic protocol: events, signing
+    4,  // encrypted messages, used by Nostr Connect
+    6,  // key derivation from mnemonic
+    19, // bech32 encoded keys (npub, nsec)
+    26, // delegated event signing
+    42, // relay authentication, for the signer connection
+    46, // Nostr Connect (signer)
+    49, // encrypted secret key import (ncryptsec)
+    65, // relay list metadata
+];
+
+/// Numbers of the NIPs implemented by keystr, sorted
+pub(crate) fn implemented_nips() -> Vec<u16> {
+    IMPLEMENTED_NIPS.to_vec()
+}
+
+/// List of NIPs in the usual form, e.g. "NIP-01, NIP-46"
+pub(crate) fn format_nips(nips: &[u16]) -> String {
+    nips.iter()
+        .map(|n| format!("NIP-{:02}", n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Version and build information of this build; values are captured by the build script
+pub(crate) fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("KEYSTR_GIT_COMMIT"),
+        nostr_sdk_version: env!("KEYSTR_NOSTR_SDK_VERSION"),
+        implemented_nips: implemented_nips(),
+    }
+}
+
+impl BuildInfo {
+    /// Machine-readable form, as JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "keystr {} (commit {}, nostr-sdk {})",
+            self.version, self.git_commit, self.nostr_sdk_version
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert!(!info.version.is_empty());
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_commit.is_empty());
+        assert!(!info.nostr_sdk_version.is_empty());
+        assert!(info.to_string().contains(info.version));
+
+        let json: serde_json::Value = serde_json::from_str(&info.to_json()).unwrap();
+        assert_eq!(json["version"], info.version);
+        assert_eq!(json["git_commit"], info.git_commit);
+        assert_eq!(json["nostr_sdk_version"], info.nostr_sdk_version);
+        assert_eq!(json["implemented_nips"][0], 1);
+    }
+
+    #[test]
+    fn test_implemented_nips() {
+        let nips = implemented_nips();
+        assert!(nips.contains(&46));
+        assert!(nips.contains(&49));
+        // sorted and deduplicated
+        let mut expected = nips.clone();
+        expected.sort_unstable();
+        expected.dedup();
+        assert_eq!(nips, expected);
+
+        assert_eq!(format_nips(&[1, 46]), "NIP-01, NIP-46");
+        assert_eq!(format_nips(&[]), "");
+    }
+}
@@ -0,0 +1,222 @@
+use crate::base::error::Error;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305,
+};
+use nostr::bech32::{self, FromBase32};
+use nostr::prelude::SecretKey;
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroize;
+
+/// Human-readable prefix of a NIP-49 encrypted secret key
+const NCRYPTSEC_HRP: &str = "ncryptsec";
+/// Field holding the encrypted key in a JSON client export
+const NCRYPTSEC_FIELD: &str = "ncryptsec";
+/// Version of the NIP-49 format supported
+const NCRYPTSEC_VERSION: u8 = 2;
+/// Length of the decoded NIP-49 payload: version, log_n, salt, nonce, key security, ciphertext
+const NCRYPTSEC_LEN: usize = 1 + 1 + 16 + 24 + 1 + 48;
+/// Largest scrypt log_n accepted; above that, decrypting takes too long and too much memory
+const MAX_LOG_N: u8 = 20;
+
+/// Warning: Security-sensitive method!
+/// Decrypt a secret key exported by another client, protected with a password.
+/// Supported format: NIP-49 encrypted key ("ncryptsec1..."), either as is,
+/// or in a JSON object with an "ncryptsec" field.
+/// It is recommend to zeroize() the password after use.
+pub(crate) fn import_client_export(json: &str, password: &str) -> Result<SecretKey, Error> {
+    let ncryptsec = extract_ncryptsec(json)?;
+    decrypt_ncryptsec(&ncryptsec, password)
+}
+
+/// Find the encrypted key in the export: the string itself, or the field of a JSON object
+fn extract_ncryptsec(export: &str) -> Result<String, Error> {
+    let export = export.trim();
+    if export.starts_with(NCRYPTSEC_HRP) {
+        return Ok(export.to_string());
+    }
+    let value: serde_json::Value = serde_json::from_str(export)
+        .map_err(|_e| Error::ClientExportInvalid("not an ncryptsec key or JSON".to_string()))?;
+    value
+        .get(NCRYPTSEC_FIELD)
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim().to_string())
+        .ok_or_else(|| Error::ClientExportInvalid(format!("no '{}' field", NCRYPTSEC_FIELD)))
+}
+
+/// Decrypt a NIP-49 encrypted key: scrypt key derivation from the (NFKC-normalized) password,
+/// XChaCha20-Poly1305 with the key security byte as associated data
+fn decrypt_ncryptsec(ncryptsec: &str, password: &str) -> Result<SecretKey, Error> {
+    let invalid = |reason: &str| Error::ClientExportInvalid(reason.to_string());
+    let (hrp, data, _variant) = bech32::decode(ncryptsec).map_err(|_e| invalid("bad encoding"))?;
+    if hrp != NCRYPTSEC_HRP {
+        return Err(invalid("not an ncryptsec key"));
+    }
+    let payload = Vec::<u8>::from_base32(&data).map_err(|_e| invalid("bad encoding"))?;
+    if payload.len() != NCRYPTSEC_LEN {
+        return Err(invalid("wrong length"));
+    }
+    if payload[0] != NCRYPTSEC_VERSION {
+        return Err(invalid(&format!("unsupported version {}", payload[0])));
+    }
+    let log_n = payload[1];
+    if log_n > MAX_LOG_N {
+        return Err(invalid(&format!("scrypt cost 2^{} too high", log_n)));
+    }
+    let salt = &payload[2..18];
+    let nonce = &payload[18..42];
+    let key_security = &payload[42..43];
+    let ciphertext = &payload[43..];
+
+    let mut symmetric_key = password_to_key(password, salt, log_n)?;
+    let res = XChaCha20Poly1305::new(&symmetric_key.into()).decrypt(
+        nonce.into(),
+        Payload {
+            msg: ciphertext,
+            aad: key_security,
+        },
+    );
+    symmetric_key.zeroize();
+    let mut inner_secret = res.map_err(|_e| Error::ClientExportWrongPassword)?;
+    let secret_key = SecretKey::from_slice(&inner_secret);
+    inner_secret.zeroize();
+    Ok(secret_key?)
+}
+
+/// Derive the symmetric key from the NFKC-normalized password, with scrypt
+fn password_to_key(password: &str, salt: &[u8], log_n: u8) -> Result<[u8; 32], Error> {
+    let params = scrypt::Params::new(log_n, 8, 1)
+        .map_err(|_e| Error::ClientExportInvalid("bad scrypt parameters".to_string()))?;
+    let mut normalized: String = password.nfkc().collect();
+    let mut symmetric_key: [u8; 32] = [0; 32];
+    let res = scrypt::scrypt(normalized.as_bytes(), salt, &params, &mut symmetric_key);
+    normalized.zeroize();
+    res.map_err(|_e| Error::KeyEncryption)?;
+    Ok(symmetric_key)
+}
+
+/// Encrypt a secret key in NIP-49 format, for tests (with a low scrypt cost, for speed)
+#[cfg(test)]
+pub(crate) fn encrypt_ncryptsec(secret_key: &SecretKey, password: &str, log_n: u8) -> String {
+    use chacha20poly1305::AeadCore;
+    use rand_core::{OsRng, RngCore};
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let key_security = [1u8];
+    let symmetric_key = password_to_key(password, &salt, log_n).unwrap();
+    let ciphertext = XChaCha20Poly1305::new(&symmetric_key.into())
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: &secret_key.secret_bytes(),
+                aad: &key_security,
+            },
+        )
+        .unwrap();
+    let mut payload = vec![NCRYPTSEC_VERSION, log_n];
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&nonce);
+    payload.extend_from_slice(&key_security);
+    payload.extend_from_slice(&ciphertext);
+    bech32::encode(
+        NCRYPTSEC_HRP,
+        bech32::ToBase32::to_base32(&payload),
+        bech32::Variant::Bech32,
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Test vector of NIP-49, with password "nostr" and log_n 16
+    const NCRYPTSEC: &str = "ncryptsec1qgg9947rlpvqu76pj5ecreduf9jxhselq2nae2kghhvd5g7dgjtcxfqtd67p9m0w57lspw8gsq6yphnm8623nsl8xn9j4jdzz84zm3frztj3z7s35vpzmqf6ksu8r89qk5z2zxfmu5gv8th8wclt0h4p";
+    const SECRET_HEX: &str = "3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683";
+
+    #[test]
+    fn test_import_client_export() {
+        let blob = format!(
+            r#"{{"pubkey": "npub1...", "ncryptsec": "{}", "created_at": 1700000000}}"#,
+            NCRYPTSEC
+        );
+        let sk = import_client_export(&blob, "nostr").unwrap();
+        assert_eq!(hex::encode(sk.secret_bytes()), SECRET_HEX);
+    }
+
+    #[test]
+    fn test_import_client_export_password() {
+        let sk = SecretKey::from_str(SECRET_HEX).unwrap();
+        // decomposed form of the password is normalized (NFKC)
+        let ncryptsec = encrypt_ncryptsec(&sk, "\u{00C5}pple", 4);
+        assert!(ncryptsec.starts_with("ncryptsec1"));
+        // the bare key works too
+        let imported = import_client_export(&format!(" {}\n", ncryptsec), "A\u{030A}pple").unwrap();
+        assert_eq!(imported, sk);
+
+        // wrong password
+        let blob = format!(r#"{{"ncryptsec": "{}"}}"#, ncryptsec);
+        assert!(matches!(
+            import_client_export(&blob, "apple"),
+            Err(Error::ClientExportWrongPassword)
+        ));
+        assert!(matches!(
+            import_client_export(&blob, ""),
+            Err(Error::ClientExportWrongPassword)
+        ));
+    }
+
+    #[test]
+    fn test_import_client_export_invalid() {
+        let reason = |export: &str| match import_client_export(export, "nostr") {
+            Err(Error::ClientExportInvalid(r)) => r,
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        };
+        assert_eq!(reason("not json"), "not an ncryptsec key or JSON");
+        assert_eq!(reason(r#"{"nsec": "x"}"#), "no 'ncryptsec' field");
+        assert_eq!(reason(r#"{"ncryptsec": 5}"#), "no 'ncryptsec' field");
+        // corrupted checksum
+        let corrupted = NCRYPTSEC.replace("qgg99", "qgg98");
+        assert_eq!(reason(&corrupted), "bad encoding");
+        // other bech32 entity
+        assert_eq!(
+            reason(
+                r#"{"ncryptsec": "npub1c82zv3aj04l8dmxlxywx5fsg6ngt5nyvwa9j0eqk03ntg2t2jtxqngn7ry"}"#
+            ),
+            "not an ncryptsec key"
+        );
+        // wrong version
+        let (_, data, variant) = bech32::decode(NCRYPTSEC).unwrap();
+        let mut payload = Vec::<u8>::from_base32(&data).unwrap();
+        payload[0] = 1;
+        let encoded = bech32::encode(
+            NCRYPTSEC_HRP,
+            bech32::ToBase32::to_base32(&payload),
+            variant,
+        )
+        .unwrap();
+        assert_eq!(reason(&encoded), "unsupported version 1");
+        // scrypt cost too high
+        payload[0] = NCRYPTSEC_VERSION;
+        payload[1] = MAX_LOG_N + 1;
+        let encoded = bech32::encode(
+            NCRYPTSEC_HRP,
+            bech32::ToBase32::to_base32(&payload),
+            variant,
+        )
+        .unwrap();
+        assert_eq!(reason(&encoded), "scrypt cost 2^21 too high");
+        payload[1] = 16;
+        // truncated
+        let encoded = bech32::encode(
+            NCRYPTSEC_HRP,
+            bech32::ToBase32::to_base32(&payload[..40].to_vec()),
+            variant,
+        )
+        .unwrap();
+        assert_eq!(reason(&encoded), "wrong length");
+    }
+}
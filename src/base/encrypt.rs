@@ -1,44 +1,138 @@
 use crate::base::error::Error;
+use aes_gcm::Aes256Gcm;
 use chacha20poly1305::{
     aead::{Aead, AeadCore, KeyInit, Payload},
     XChaCha20Poly1305,
 };
 use nostr::prelude::SecretKey;
 use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
 use zeroize::Zeroize;
 
+use std::fmt;
+
 /// Two-way encryption, used for secret keys
 pub(crate) struct Encrypt {}
 
+/// Symmetric encryption algorithm of an encrypted key; stored in the header,
+/// so decryption selects it automatically
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum EncryptionAlgorithm {
+    #[default]
+    XChaCha20Poly1305,
+    Aes256Gcm,
+}
+
+pub(crate) static ENCRYPTION_ALGORITHMS: &[EncryptionAlgorithm] = &[
+    EncryptionAlgorithm::XChaCha20Poly1305,
+    EncryptionAlgorithm::Aes256Gcm,
+];
+
+/// Header of an encrypted key, readable without decryption
+pub(crate) struct EncryptedKeyHeader {
+    pub version: u8,
+    pub log2_rounds: u8,
+    /// Encryption algorithm, None for an unknown version
+    pub algorithm: Option<EncryptionAlgorithm>,
+}
+
+/// Salt length, in bytes
+const SALT_LEN: usize = 16;
+/// Length of the encrypted secret key (32 bytes) with the authentication tag
+const CIPHERTEXT_LEN: usize = 48;
+
+impl EncryptionAlgorithm {
+    /// Id of the algorithm in the encrypted key header
+    fn id(&self) -> u8 {
+        match self {
+            EncryptionAlgorithm::XChaCha20Poly1305 => 1,
+            EncryptionAlgorithm::Aes256Gcm => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        ENCRYPTION_ALGORITHMS.iter().copied().find(|a| a.id() == id)
+    }
+
+    fn nonce_len(&self) -> usize {
+        match self {
+            EncryptionAlgorithm::XChaCha20Poly1305 => 24,
+            EncryptionAlgorithm::Aes256Gcm => 12,
+        }
+    }
+
+    /// Encrypt with a new random nonce; returns the nonce and the ciphertext
+    fn encrypt(&self, key: &[u8; 32], payload: Payload) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let res = match self {
+            EncryptionAlgorithm::XChaCha20Poly1305 => {
+                let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+                XChaCha20Poly1305::new(key.into())
+                    .encrypt(&nonce, payload)
+                    .map(|c| (nonce.to_vec(), c))
+            }
+            EncryptionAlgorithm::Aes256Gcm => {
+                let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+                Aes256Gcm::new(key.into())
+                    .encrypt(&nonce, payload)
+                    .map(|c| (nonce.to_vec(), c))
+            }
+        };
+        res.map_err(|_e| Error::KeyEncryption)
+    }
+
+    /// Decrypt; the nonce must be of the length of the algorithm
+    fn decrypt(&self, key: &[u8; 32], nonce: &[u8], payload: Payload) -> Result<Vec<u8>, Error> {
+        let res = match self {
+            EncryptionAlgorithm::XChaCha20Poly1305 => {
+                XChaCha20Poly1305::new(key.into()).decrypt(nonce.into(), payload)
+            }
+            EncryptionAlgorithm::Aes256Gcm => {
+                Aes256Gcm::new(key.into()).decrypt(nonce.into(), payload)
+            }
+        };
+        res.map_err(|_e| Error::KeyEncryption)
+    }
+}
+
+impl fmt::Display for EncryptionAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncryptionAlgorithm::XChaCha20Poly1305 => write!(f, "XChaCha20-Poly1305"),
+            EncryptionAlgorithm::Aes256Gcm => write!(f, "AES-256-GCM"),
+        }
+    }
+}
+
 impl Encrypt {
+    /// Current version of the encrypted key format: with algorithm id in the header
+    pub(crate) const CURRENT_VERSION: u8 = 2;
+    /// First version of the format, always XChaCha20-Poly1305, without algorithm id
+    const LEGACY_VERSION: u8 = 1;
+
     pub(crate) fn default_log2_rounds() -> u8 {
         13
     }
 
-    /// Encrypt a key.
+    /// Encrypt a key, with the given algorithm.
     /// It is recommend to zeroize() the password after use.
     pub(crate) fn encrypt_key(
         key: &SecretKey,
         password: &str,
         log2_rounds: u8,
+        algorithm: EncryptionAlgorithm,
     ) -> Result<Vec<u8>, Error> {
         // Generate a random 16-byte salt
         let salt = {
-            let mut salt: [u8; 16] = [0; 16];
+            let mut salt: [u8; SALT_LEN] = [0; SALT_LEN];
             OsRng.fill_bytes(&mut salt);
             salt
         };
 
-        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
-
         // placeholder for key security
         let associated_data: Vec<u8> = vec![1];
 
-        let ciphertext = {
-            let cipher = {
-                let symmetric_key = Self::password_to_key(password, &salt, log2_rounds)?;
-                XChaCha20Poly1305::new((&symmetric_key).into())
-            };
+        let (nonce, ciphertext) = {
+            let mut symmetric_key = Self::password_to_key(password, &salt, log2_rounds)?;
 
             // The inner secret. We don't have to drop this because we are encrypting-in-place
             let mut inner_secret: Vec<u8> = key.secret_bytes().to_vec();
@@ -48,61 +142,60 @@ impl Encrypt {
                 aad: &associated_data,
             };
 
-            let ciphertext = cipher
-                .encrypt(&nonce, payload)
-                .map_err(|_e| Error::KeyEncryption)?;
+            let res = algorithm.encrypt(&symmetric_key, payload);
 
             inner_secret.zeroize();
+            symmetric_key.zeroize();
 
-            ciphertext
+            res?
         };
 
         // Combine salt, IV and ciphertext
         let mut concat: Vec<u8> = Vec::new();
-        concat.push(0x1); // 1 byte version number
+        concat.push(Self::CURRENT_VERSION); // 1 byte version number
         concat.push(log2_rounds); // 1 byte for scrypt N (rounds)
+        concat.push(algorithm.id()); // 1 byte algorithm id
         concat.extend(salt); // 16 bytes of salt
-        concat.extend(nonce); // 24 bytes of nonce
+        concat.extend(nonce); // 24 (XChaCha20) or 12 (AES-GCM) bytes of nonce
         concat.extend(associated_data); // 1 byte of key security
         concat.extend(ciphertext); // 48 bytes of ciphertext expected
-                                   // Total length is 91 = 1 + 1 + 16 + 24 + 1 + 32
+                                   // Total length is 92 (XChaCha20) or 80 (AES-GCM)
 
         Ok(concat)
     }
 
-    /// Decrypt a key encrypted using `encrypt_key`
+    /// Decrypt a key encrypted using `encrypt_key`, the algorithm is taken from the header
     /// It is recommend to zeroize() the password after use.
     pub(crate) fn decrypt_key(encrypted: &Vec<u8>, password: &str) -> Result<SecretKey, Error> {
-        if encrypted.len() < 91 {
-            return Err(Error::KeyInvalidEncrypted);
-        }
-
         // Break into parts
-        let version: u8 = encrypted[0];
-        if version != 1 {
-            return Err(Error::KeyInvalidEncryptionVersion);
-        }
-        let log2_rounds: u8 = encrypted[1];
-        let salt: [u8; 16] = encrypted[2..2 + 16]
+        let EncryptedKeyHeader {
+            version,
+            log2_rounds,
+            algorithm,
+        } = Self::read_header(encrypted)?;
+        let algorithm = match algorithm {
+            None => return Err(Error::KeyInvalidEncryptionVersion),
+            Some(a) => a,
+        };
+        let body = &encrypted[Self::header_len(version)..];
+        let nonce_len = algorithm.nonce_len();
+        let salt: [u8; SALT_LEN] = body[..SALT_LEN]
             .try_into()
             .map_err(|_e| Error::KeyInvalidEncrypted)?;
-        let nonce = &encrypted[2 + 16..2 + 16 + 24];
-        let associated_data = &encrypted[2 + 16 + 24..2 + 16 + 24 + 1];
-        let ciphertext = &encrypted[2 + 16 + 24 + 1..];
+        let nonce = &body[SALT_LEN..SALT_LEN + nonce_len];
+        let associated_data = &body[SALT_LEN + nonce_len..SALT_LEN + nonce_len + 1];
+        let ciphertext = &body[SALT_LEN + nonce_len + 1..];
 
-        let cipher = {
-            let symmetric_key = Self::password_to_key(password, &salt, log2_rounds)?;
-            XChaCha20Poly1305::new((&symmetric_key).into())
-        };
+        let mut symmetric_key = Self::password_to_key(password, &salt, log2_rounds)?;
 
         let payload = Payload {
             msg: ciphertext,
             aad: associated_data,
         };
 
-        let mut inner_secret = cipher
-            .decrypt(nonce.into(), payload)
-            .map_err(|_e| Error::KeyEncryption)?;
+        let res = algorithm.decrypt(&symmetric_key, nonce, payload);
+        symmetric_key.zeroize();
+        let mut inner_secret = res?;
 
         if associated_data.is_empty() {
             return Err(Error::KeyInvalidEncrypted);
@@ -118,6 +211,46 @@ impl Encrypt {
         Ok(secret_key)
     }
 
+    /// Read the header (version, scrypt rounds, algorithm) of an encrypted key, without decrypting it.
+    /// The length is checked for known versions; for unknown ones the algorithm is None.
+    pub(crate) fn read_header(encrypted: &[u8]) -> Result<EncryptedKeyHeader, Error> {
+        if encrypted.len() < 2 {
+            return Err(Error::KeyInvalidEncrypted);
+        }
+        let version = encrypted[0];
+        let algorithm = match version {
+            Self::LEGACY_VERSION => Some(EncryptionAlgorithm::XChaCha20Poly1305),
+            Self::CURRENT_VERSION => match encrypted.get(2).copied() {
+                None => return Err(Error::KeyInvalidEncrypted),
+                Some(id) => Some(
+                    EncryptionAlgorithm::from_id(id).ok_or(Error::KeyInvalidEncryptionVersion)?,
+                ),
+            },
+            _ => None,
+        };
+        if let Some(algorithm) = algorithm {
+            let expected_len =
+                Self::header_len(version) + SALT_LEN + algorithm.nonce_len() + 1 + CIPHERTEXT_LEN;
+            if encrypted.len() < expected_len {
+                return Err(Error::KeyInvalidEncrypted);
+            }
+        }
+        Ok(EncryptedKeyHeader {
+            version,
+            log2_rounds: encrypted[1],
+            algorithm,
+        })
+    }
+
+    /// Length of the header (version, rounds, algorithm id if present)
+    fn header_len(version: u8) -> usize {
+        if version == Self::LEGACY_VERSION {
+            2
+        } else {
+            3
+        }
+    }
+
     // Hash/Stretch password with scrypt into a 32-byte (256-bit) key
     fn password_to_key(password: &str, salt: &[u8; 16], log_n: u8) -> Result<[u8; 32], Error> {
         let params = scrypt::Params::new(log_n, 8, 1).map_err(|_e| Error::KeyEncryption)?;
@@ -141,9 +274,44 @@ mod test {
         )
         .unwrap();
         let password = "password".to_string();
-        let encrypted = Encrypt::encrypt_key(&sk, &password, 13).unwrap();
+        for algorithm in ENCRYPTION_ALGORITHMS {
+            let encrypted = Encrypt::encrypt_key(&sk, &password, 13, *algorithm).unwrap();
+            let header = Encrypt::read_header(&encrypted).unwrap();
+            assert_eq!(header.algorithm, Some(*algorithm));
 
-        let _decrypted = Encrypt::decrypt_key(&encrypted, &password).unwrap();
+            let decrypted = Encrypt::decrypt_key(&encrypted, &password).unwrap();
+            assert_eq!(decrypted, sk);
+            assert!(matches!(
+                Encrypt::decrypt_key(&encrypted, "wrong"),
+                Err(Error::KeyEncryption)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_decrypt_selects_algorithm_from_header() {
+        let sk = SecretKey::from_bech32(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+        )
+        .unwrap();
+        let chacha =
+            Encrypt::encrypt_key(&sk, "password", 4, EncryptionAlgorithm::XChaCha20Poly1305)
+                .unwrap();
+        let aes = Encrypt::encrypt_key(&sk, "password", 4, EncryptionAlgorithm::Aes256Gcm).unwrap();
+        assert_eq!(Encrypt::decrypt_key(&chacha, "password").unwrap(), sk);
+        assert_eq!(Encrypt::decrypt_key(&aes, "password").unwrap(), sk);
+
+        // same data, other algorithm in the header: fails
+        let mut swapped = aes.clone();
+        swapped[2] = EncryptionAlgorithm::XChaCha20Poly1305.id();
+        assert!(Encrypt::decrypt_key(&swapped, "password").is_err());
+        // unknown algorithm id
+        let mut unknown = aes;
+        unknown[2] = 99;
+        assert!(matches!(
+            Encrypt::decrypt_key(&unknown, "password"),
+            Err(Error::KeyInvalidEncryptionVersion)
+        ));
     }
 
     #[test]
@@ -153,12 +321,20 @@ mod test {
         )
         .unwrap();
         let password = "password".to_string();
-        let encrypted = Encrypt::encrypt_key(&sk, &password, 13).unwrap();
+        let encrypted =
+            Encrypt::encrypt_key(&sk, &password, 13, EncryptionAlgorithm::XChaCha20Poly1305)
+                .unwrap();
         // Encrypted result is variable, cannot compare to const
-        assert_eq!(encrypted.len(), 91);
-        assert_eq!(hex::encode(encrypted)[0..4], "010d".to_string());
+        assert_eq!(encrypted.len(), 92);
+        assert_eq!(hex::encode(encrypted)[0..6], "020d01".to_string());
+
+        let encrypted =
+            Encrypt::encrypt_key(&sk, &password, 13, EncryptionAlgorithm::Aes256Gcm).unwrap();
+        assert_eq!(encrypted.len(), 80);
+        assert_eq!(hex::encode(encrypted)[0..6], "020d02".to_string());
     }
 
+    /// Version 1 (legacy) format, XChaCha20-Poly1305 without algorithm id
     #[test]
     fn test_decrypt() {
         let encrypted = hex::decode("010d6a32e0decd8553f02372df251c7f06dd0a54ba09bc0e8b2ea52e816c50f430fd0f051b2f7abcae05017f3c6f8a1ff7f3d694db4e624ef7dece7e3152b1ff536bc954eab1c85b3dbeb8e29140e84f0db5c473822e550d53a66e").unwrap();
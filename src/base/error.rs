@@ -18,6 +18,15 @@ pub enum Error {
     /// Secp256k1 key error
     #[error(transparent)]
     KeyErrorSecp256k1(#[from] nostr::secp256k1::Error),
+    /// Secret key is neither hex (64 characters) nor nsec
+    #[error("Invalid secret key, use nsec or 64 hex characters (optionally 0x-prefixed)")]
+    KeyInvalidSecretFormat,
+    /// Labeled key export has no secret key line (`sec:` or `nsec:`)
+    #[error("No secret key found in the labeled input, expected a 'sec:' or 'nsec:' line")]
+    KeyLabeledSecretMissing,
+    /// Labeled key export has more than one secret key line
+    #[error("Multiple secret keys found in the labeled input, paste only one")]
+    KeyLabeledSecretAmbiguous,
     /// Invalid encrypted key
     #[error("Invalid encrypted key")]
     KeyInvalidEncrypted,
@@ -27,21 +36,94 @@ pub enum Error {
     /// Invalid encryption version
     #[error("Invalid encryption version")]
     KeyInvalidEncryptionVersion,
+    /// Input field left empty or blank (field name)
+    #[error("{0} is empty, enter it first")]
+    EmptyInput(&'static str),
+    /// QR code could not be created for the data (reason)
+    #[error("Could not create QR code: {0}")]
+    QrEncode(String),
+    /// QR code image could not be saved (path, reason)
+    #[error("Could not save the QR code to '{0}': {1}")]
+    QrSaveFailed(String, String),
+    /// Invalid or unsupported client export (reason)
+    #[error("Invalid client export: {0}")]
+    ClientExportInvalid(String),
+    /// Client export could not be decrypted with the password
+    #[error("Could not decrypt the client export, check the password")]
+    ClientExportWrongPassword,
     /// Mandatory encryption password missing
     #[error("Mandatory encryption password missing. Check password and security settings")]
     KeyEncryptionPasswordMissing,
     /// Encryption passwords don't match
     #[error("Encryption passwords don't match")]
     KeyEncryptionPasswordMismatch,
+    /// Too many wrong unlock passwords, need to wait (seconds)
+    #[error("Too many wrong passwords, try again in {0} seconds")]
+    KeyUnlockThrottled(u64),
     /// Invalid BIP39 mnemonic
     #[error(transparent)]
     KeyMnemonic(#[from] bip39::Error),
+    /// Key was not imported from a mnemonic in this session, derivation not possible
+    #[error(
+        "Key is not derived from a mnemonic imported in this session, cannot derive child key"
+    )]
+    KeyNotMnemonicBacked,
+    /// Backup of the generated mnemonic was not confirmed
+    #[error("Mnemonic backup not confirmed, write down the words first")]
+    KeyBackupNotConfirmed,
+    /// Signing with the key does not work
+    #[error("Key self-check failed, the key cannot sign")]
+    KeySelfCheckFailed,
+    /// Selected signing key is not unlocked
+    #[error("Selected signing key is not unlocked")]
+    KeySignerLocked,
+    /// The key needs the password for each signature, and it was not entered (correctly)
+    #[error("Enter the password of the key, it is needed for each signature")]
+    KeyFreshUnlockRequired,
+    /// Requiring the password for each signature needs the encrypted secret key
+    #[error("Save and load the key encrypted first, to require the password for each signature")]
+    KeyFreshUnlockNotSaved,
+    /// Invalid derivation account index
+    #[error("Invalid derivation account index")]
+    KeyInvalidDerivationAccount,
+    /// Vanity prefix is empty or has non-bech32 characters
+    #[error("Invalid vanity prefix, use bech32 characters only")]
+    KeyInvalidVanityPrefix,
     /// BIP32 key derivation error
     #[error(transparent)]
     KeyDerivation(#[from] bip32::Error),
+    /// Invalid relay URL
+    #[error("Invalid relay URL '{0}'")]
+    InvalidRelayUrl(String),
+    /// Invalid relay read/write marker
+    #[error("Invalid relay marker '{0}', use 'read', 'write', or none")]
+    InvalidRelayMarker(String),
+    /// Relay list could not be parsed
+    #[error("Invalid relay list, expected a kind 3 or 10002 event, or a JSON list of relays")]
+    InvalidRelayList,
+    /// No relays given to publish to
+    #[error("No relays to publish")]
+    NoRelaysToPublish,
     /// Nip19 error
     #[error(transparent)]
     SignatureError(#[from] nostr::nips::nip19::Error),
+    /// Explicit created_at of a note is not a reasonable unix timestamp
+    #[error(
+        "Invalid created_at '{0}', expected a unix timestamp (seconds), not far in the future"
+    )]
+    NoteInvalidCreatedAt(String),
+    /// Recipient of a direct message is not a valid public key
+    #[error("Invalid recipient '{0}', use npub or hex public key")]
+    DmInvalidRecipient(String),
+    /// Delegation not created yet
+    #[error("No delegation created yet")]
+    DelegationNotCreated,
+    /// Delegation was created with a different key than the current one
+    #[error("Delegation was created with a different delegator key")]
+    DelegationWrongDelegator,
+    /// Selected received delegation does not exist
+    #[error("Delegation index out of bounds")]
+    DelegationIndexOutOfBounds,
     /// Nip26 error
     #[error(transparent)]
     Nip26Error(#[from] nostr::nips::nip26::Error),
@@ -57,16 +139,355 @@ pub enum Error {
     /// Relay client error
     #[error(transparent)]
     RelayClientError(#[from] nostr_sdk::client::Error),
+    /// Only a public key is loaded, signing needs the secret key
+    #[error(
+        "Only a public key is loaded (watch-only), a secret key is required to act as a signer"
+    )]
+    SignerWatchOnly,
     /// Signer is already connected, disconnect first
     #[error("Signer is already connected, disconnect first")]
     SignerAlreadyConnected,
+    /// Connect URI is for a client already connected through the same relay
+    #[error("Already connected to this client through this relay")]
+    SignerDuplicateConnection,
+    /// Event id in sign request does not match event content, request is tampered
+    #[error("Event id does not match event content, request may be tampered")]
+    SignerEventIdMismatch,
+    /// Event content in sign request is too large
+    #[error("Event content too large ({0} bytes, max {1})")]
+    SignerContentTooLarge(usize, usize),
+    /// Author of the event to sign is not in the allowed authors list
+    #[error("Signing for author {0} is not allowed, check allowed authors in the settings")]
+    SignerAuthorNotAllowed(String),
+    /// Signing events of this kind is refused by the kind policy
+    #[error("Signing {0} events is not allowed by the kind policy")]
+    SignerKindRejected(String),
+    /// Operation needs a sign event request, but the request is of another kind
+    #[error("Request is not a sign event request")]
+    SignerNotSignRequest,
+    /// Approving needs the app window to be focused (setting)
+    #[error("Approving requests is only possible while the window is focused")]
+    SignerWindowNotFocused,
+    /// Pending request index out of bounds
+    #[error("Pending request index out of bounds")]
+    SignerRequestIndexOutOfBounds,
+    /// Value to sign raw is not a 32-byte hex string
+    #[error("Raw value to sign must be 32 bytes, as 64 hex characters")]
+    SignerRawInvalid,
+    /// Permission to grant is not in the 'method' or 'method:kind' form, or the kind is invalid
+    #[error("Invalid permission '{0}', use 'method' or 'method:kind'")]
+    SignerPermissionInvalid(String),
+    /// The request is not covered by the permissions granted to the client at pairing
+    #[error("Permission '{0}' was not granted to this client")]
+    SignerPermissionDenied(String),
+    /// Tray icon support is not compiled in, or not available on this platform
+    #[cfg(not(all(feature = "tray", target_os = "linux")))]
+    #[error("Tray icon is not available in this build")]
+    TrayNotAvailable,
     /// Internal event queue receive error
     #[error(transparent)]
     InternalEventQueueReceive(#[from] crossbeam::channel::RecvError),
-    /// Internal event queue send error
-    #[error("Internal event queue send error")]
-    InternalEventQueueSend,
+    /// Internal event queue is full, event is dropped
+    #[error("Internal event queue full")]
+    InternalEventQueueFull,
+    /// Internal event queue is closed, the UI is gone (shutdown)
+    #[error("Internal event queue closed")]
+    InternalEventQueueClosed,
+    /// Internal background task failed (panicked or canceled)
+    #[error(transparent)]
+    InternalTaskJoin(#[from] tokio::task::JoinError),
+    /// Event signing or verification error, e.g. invalid signature
+    #[error(transparent)]
+    EventInvalid(#[from] nostr::event::unsigned::Error),
     /// Internal EventBuilder error
     #[error(transparent)]
     EventBuilderError(#[from] nostr::event::builder::Error),
 }
+
+impl Error {
+    /// Stable key for the help explanation of this error, see `help_text`.
+    /// Related variants share a key.
+    pub fn help_key(&self) -> &'static str {
+        match self {
+            Error::KeyNotSet => "key-not-set",
+            Error::KeyNoChangeToSave => "key-no-change",
+            Error::KeySaveNotAllowed | Error::KeyLoadNotAllowed => "persist-not-allowed",
+            Error::KeyError(_) | Error::KeyErrorSecp256k1(_) | Error::KeyInvalidSecretFormat => {
+                "key-invalid"
+            }
+            Error::KeyLabeledSecretMissing | Error::KeyLabeledSecretAmbiguous => {
+                "key-labeled-secret"
+            }
+            Error::KeyInvalidEncrypted
+            | Error::KeyEncryption
+            | Error::KeyInvalidEncryptionVersion => "encrypted-key-invalid",
+            Error::EmptyInput(_) => "input-empty",
+            Error::QrEncode(_) => "qr-encode",
+            Error::QrSaveFailed(_, _) => "qr-save",
+            Error::ClientExportInvalid(_) => "client-export-invalid",
+            Error::ClientExportWrongPassword => "client-export-password",
+            Error::KeyEncryptionPasswordMissing => "password-missing",
+            Error::KeyEncryptionPasswordMismatch => "password-mismatch",
+            Error::KeyUnlockThrottled(_) => "unlock-throttled",
+            Error::KeyMnemonic(_) => "mnemonic-invalid",
+            Error::KeyNotMnemonicBacked => "key-not-mnemonic",
+            Error::KeyBackupNotConfirmed => "backup-not-confirmed",
+            Error::KeySignerLocked => "key-locked",
+            Error::KeyFreshUnlockRequired => "key-fresh-unlock",
+            Error::KeyFreshUnlockNotSaved => "key-fresh-unlock-not-saved",
+            Error::KeySelfCheckFailed => "key-self-check",
+            Error::KeyInvalidDerivationAccount | Error::KeyDerivation(_) => "derivation-invalid",
+            Error::KeyInvalidVanityPrefix => "vanity-prefix-invalid",
+            Error::InvalidRelayUrl(_) => "relay-url-invalid",
+            Error::InvalidRelayMarker(_) => "relay-marker-invalid",
+            Error::InvalidRelayList => "relay-list-invalid",
+            Error::NoRelaysToPublish => "relay-list-empty",
+            Error::NoteInvalidCreatedAt(_) => "note-created-at-invalid",
+            Error::DmInvalidRecipient(_) => "dm-recipient-invalid",
+            Error::DelegationNotCreated => "delegation-not-created",
+            Error::DelegationWrongDelegator => "delegation-wrong-delegator",
+            Error::DelegationIndexOutOfBounds => "delegation-index",
+            Error::SignatureError(_) => "bech32-invalid",
+            Error::Nip26Error(_) => "delegation-invalid",
+            Error::IoError(_) => "io",
+            Error::JsonError(_) => "json",
+            Error::Nip46Error(_) => "nostr-connect",
+            Error::RelayClientError(_) => "relay-client",
+            Error::SignerWatchOnly => "signer-watch-only",
+            Error::SignerAlreadyConnected => "signer-already-connected",
+            Error::SignerDuplicateConnection => "signer-duplicate-connection",
+            Error::SignerEventIdMismatch => "signer-event-id-mismatch",
+            Error::SignerContentTooLarge(_, _) => "signer-content-too-large",
+            Error::SignerAuthorNotAllowed(_) => "signer-author-not-allowed",
+            Error::SignerKindRejected(_) => "signer-kind-rejected",
+            Error::SignerNotSignRequest => "signer-not-sign-request",
+            Error::SignerWindowNotFocused => "signer-window-not-focused",
+            Error::SignerRequestIndexOutOfBounds => "signer-request-index",
+            Error::SignerRawInvalid => "signer-raw-invalid",
+            Error::SignerPermissionInvalid(_) => "signer-permission-invalid",
+            Error::SignerPermissionDenied(_) => "signer-permission-denied",
+            #[cfg(not(all(feature = "tray", target_os = "linux")))]
+            Error::TrayNotAvailable => "tray-not-available",
+            Error::EventInvalid(_) => "event-invalid",
+            Error::InternalEventQueueReceive(_)
+            | Error::InternalEventQueueFull
+            | Error::InternalEventQueueClosed
+            | Error::InternalTaskJoin(_)
+            | Error::EventBuilderError(_) => "internal",
+        }
+    }
+}
+
+/// Short explanations and suggested fixes, by help key
+static HELP_TEXTS: &[(&str, &str)] = &[
+    ("key-not-set", "No key is loaded. Generate a new key, or import or load an existing one on the Keys tab."),
+    ("key-no-change", "The keys are already saved, there is nothing new to save."),
+    ("persist-not-allowed", "The security level does not allow storing keys. Change it at the top if you want keys persisted."),
+    ("key-invalid", "The key is not valid. Check that it was copied completely, as npub/nsec or hex."),
+    ("key-labeled-secret", "Paste the exported lines with exactly one secret key line, like 'sec: <hex>'; the 'pub:' line is ignored."),
+    ("encrypted-key-invalid", "The stored encrypted key could not be decrypted or is damaged. Check the password; restore the key from a backup if it persists."),
+    ("qr-encode", "The content is too long for a QR code. Show a shorter form, e.g. npub instead of nprofile with many relays."),
+    ("qr-save", "The image could not be written. Check that the folder exists and is writable, and the file name is valid."),
+    ("input-empty", "A required field was left empty. Fill it in, then try again."),
+    ("client-export-invalid", "The export is not in a supported format. Paste the encrypted key ('ncryptsec1...') or the JSON exported by the other client."),
+    ("client-export-password", "The export could not be decrypted. Check the password it was exported with."),
+    ("password-missing", "The security level requires a password. Enter and repeat a password before saving."),
+    ("password-mismatch", "The two passwords differ. Enter the same password in both fields."),
+    ("unlock-throttled", "Unlocking is paused after repeated wrong passwords. Wait until the delay is over, then enter the correct password."),
+    ("mnemonic-invalid", "The mnemonic is not a valid BIP39 phrase. Check the words, their order, and their count."),
+    ("key-not-mnemonic", "Child keys can only be derived from a key imported as a mnemonic in this session, the mnemonic is not saved. Import the mnemonic again."),
+    ("backup-not-confirmed", "Write down the mnemonic words and keep them safe, then confirm it to continue."),
+    ("key-self-check", "Signing with the loaded key failed. Unlock the key, or load or import it again."),
+    ("key-locked", "The secret key is encrypted. Enter the password and unlock it first."),
+    ("key-fresh-unlock", "This key is set to require the password for each signature. Enter it next to the SIGN button."),
+    ("key-fresh-unlock-not-saved", "The password can only be asked for a saved, encrypted key. Save the key with a password, and load it."),
+    ("derivation-invalid", "The account index is not valid. Use a small non-negative number, e.g. 0 or 1."),
+    ("vanity-prefix-invalid", "Use only bech32 characters for the prefix; 1, b, i and o are not allowed."),
+    ("relay-url-invalid", "Relay URLs must start with wss:// (or ws://) and have a host name."),
+    ("relay-marker-invalid", "Mark relays with 'read' or 'write', or leave the marker empty for both."),
+    ("relay-list-invalid", "Paste a kind 3 or kind 10002 event as JSON, or a JSON list of relay URLs."),
+    ("relay-list-empty", "Enter the relays to publish, or add response relays to publish those."),
+    ("note-created-at-invalid", "Enter the time as unix timestamp in seconds (e.g. 1686693500), from 2020-11-07 up to one day ahead; leave it empty for now."),
+    ("dm-recipient-invalid", "Enter the public key of the recipient, as npub or 64 hex characters."),
+    ("delegation-not-created", "Create a delegation first, then test it."),
+    ("delegation-index", "The selected delegation is no longer in the list. Select another one."),
+    ("delegation-wrong-delegator", "The delegation was created with another key. Create it again with the current key."),
+    ("bech32-invalid", "The value is not a valid bech32 string (npub, nsec, note). Check that it was copied completely."),
+    ("delegation-invalid", "The delegation is invalid, or its conditions (kind, time window) don't match. Check the conditions and create it again."),
+    ("io", "A file could not be read or written. Check that the data folder exists and is writable."),
+    ("json", "The data is not valid JSON. Check the pasted or stored content."),
+    ("nostr-connect", "The Nostr Connect URI or message is invalid. Copy the URI again from the client app."),
+    ("relay-client", "Communication with the relay failed. Check the network and the relay URL, and retry."),
+    ("signer-watch-only", "Only the public key is loaded, which can't sign. Import or load the secret key on the Keys tab to use the signer."),
+    ("signer-already-connected", "The signer is already connected. Disconnect first to connect to another client."),
+    ("signer-duplicate-connection", "This client is already connected through this relay. The existing connection is kept; no need to connect again."),
+    ("signer-event-id-mismatch", "The event to sign does not match its id; the request may have been tampered with. It was not signed."),
+    ("signer-content-too-large", "The event to sign is larger than allowed. Raise the size limit in the settings if you trust the client."),
+    ("signer-author-not-allowed", "The event to sign has an author that is not in the allowed authors list. Add the pubkey to the list if it is expected."),
+    ("signer-kind-rejected", "Events of this kind are always refused, by the kind policy in the settings. Change the policy if the client should sign them."),
+    ("signer-not-sign-request", "This is only available for requests to sign an event."),
+    ("signer-window-not-focused", "Bring the keystr window to the front, check the request, then approve it."),
+    ("signer-request-index", "The pending request is no longer in the queue. Refresh the list."),
+    ("signer-raw-invalid", "The client sent an invalid value to sign raw. It must be a 32-byte hash; report it to the client app."),
+    ("signer-permission-invalid", "Enter the event kinds to allow signing for as numbers separated by commas, or leave it empty for all kinds."),
+    ("signer-permission-denied", "The client asked for something not granted at pairing. Pair again with the needed permissions if it should be allowed."),
+    #[cfg(not(all(feature = "tray", target_os = "linux")))]
+    ("tray-not-available", "The tray icon is supported on Linux only, in builds with the 'tray' feature (on by default)."),
+    ("event-invalid", "The event could not be signed or its signature is invalid."),
+    ("internal", "An internal error occurred. Retry; restart the app if it persists."),
+];
+
+/// Explanation and suggested fix for a help key
+pub fn help_text(key: &str) -> Option<&'static str> {
+    HELP_TEXTS.iter().find(|(k, _)| *k == key).map(|(_, t)| *t)
+}
+
+/// Detailed info of an error, with its full source chain, for display and copying
+#[derive(Clone, Debug)]
+pub struct ErrorDetail {
+    pub message: String,
+    /// Source (cause) chain messages, outermost first
+    pub sources: Vec<String>,
+    /// Explanation and suggested fix, if known
+    pub help: Option<&'static str>,
+}
+
+impl ErrorDetail {
+    pub fn from_message(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            sources: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn from_error(error: &dyn std::error::Error) -> Self {
+        let mut sources = Vec::new();
+        let mut source = error.source();
+        while let Some(s) = source {
+            sources.push(s.to_string());
+            source = s.source();
+        }
+        Self {
+            message: error.to_string(),
+            sources,
+            help: None,
+        }
+    }
+
+    /// Detail of an own error, including the help text
+    pub fn from_app_error(error: &Error) -> Self {
+        Self {
+            help: help_text(error.help_key()),
+            ..Self::from_error(error)
+        }
+    }
+
+    /// Full text, including source chain, in one line
+    pub fn to_text(&self) -> String {
+        let mut text = self.message.clone();
+        for s in &self.sources {
+            text.push_str(&format!(" | caused by: {}", s));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_help_key_lookup() {
+        let aborted = tokio::spawn(std::future::pending::<()>());
+        aborted.abort();
+        let join_error = aborted.await.unwrap_err();
+        let errors = vec![
+            Error::KeyNotSet,
+            Error::KeyNoChangeToSave,
+            Error::KeySaveNotAllowed,
+            Error::KeyLoadNotAllowed,
+            Error::KeyError(nostr::key::Error::InvalidSecretKey),
+            Error::KeyErrorSecp256k1(nostr::secp256k1::Error::InvalidSecretKey),
+            Error::KeyInvalidSecretFormat,
+            Error::KeyLabeledSecretMissing,
+            Error::KeyLabeledSecretAmbiguous,
+            Error::KeyInvalidEncrypted,
+            Error::KeyEncryption,
+            Error::KeyInvalidEncryptionVersion,
+            Error::EmptyInput("Password"),
+            Error::QrEncode(String::new()),
+            Error::QrSaveFailed(String::new(), String::new()),
+            Error::ClientExportInvalid(String::new()),
+            Error::ClientExportWrongPassword,
+            Error::KeyEncryptionPasswordMissing,
+            Error::KeyEncryptionPasswordMismatch,
+            Error::KeyUnlockThrottled(1),
+            Error::KeyMnemonic(bip39::Error::BadWordCount(1)),
+            Error::KeyNotMnemonicBacked,
+            Error::KeyBackupNotConfirmed,
+            Error::KeySignerLocked,
+            Error::KeyFreshUnlockRequired,
+            Error::KeyFreshUnlockNotSaved,
+            Error::KeySelfCheckFailed,
+            Error::KeyInvalidDerivationAccount,
+            Error::KeyInvalidVanityPrefix,
+            Error::KeyDerivation(bip32::Error::Depth),
+            Error::InvalidRelayUrl("x".to_string()),
+            Error::InvalidRelayMarker("x".to_string()),
+            Error::InvalidRelayList,
+            Error::NoRelaysToPublish,
+            Error::NoteInvalidCreatedAt("x".to_string()),
+            Error::DmInvalidRecipient("x".to_string()),
+            Error::DelegationNotCreated,
+            Error::DelegationWrongDelegator,
+            Error::DelegationIndexOutOfBounds,
+            Error::SignatureError(nostr::nips::nip19::Error::WrongPrefixOrVariant),
+            Error::Nip26Error(nostr::nips::nip26::Error::DelegationTagParse),
+            Error::IoError(std::io::Error::new(std::io::ErrorKind::Other, "x")),
+            Error::JsonError(serde_json::from_str::<u32>("x").unwrap_err()),
+            Error::Nip46Error(nostr::nips::nip46::Error::Key(
+                nostr::key::Error::InvalidSecretKey,
+            )),
+            Error::RelayClientError(nostr_sdk::client::Error::RelayNotFound),
+            Error::SignerWatchOnly,
+            Error::SignerAlreadyConnected,
+            Error::SignerDuplicateConnection,
+            Error::SignerEventIdMismatch,
+            Error::SignerContentTooLarge(2, 1),
+            Error::SignerAuthorNotAllowed("x".to_string()),
+            Error::SignerKindRejected("x".to_string()),
+            Error::SignerNotSignRequest,
+            Error::SignerWindowNotFocused,
+            Error::SignerRequestIndexOutOfBounds,
+            Error::SignerRawInvalid,
+            Error::SignerPermissionInvalid("x".to_string()),
+            Error::SignerPermissionDenied("x".to_string()),
+            #[cfg(not(all(feature = "tray", target_os = "linux")))]
+            Error::TrayNotAvailable,
+            Error::InternalEventQueueReceive(crossbeam::channel::RecvError),
+            Error::InternalEventQueueFull,
+            Error::InternalEventQueueClosed,
+            Error::InternalTaskJoin(join_error),
+            Error::EventInvalid(nostr::event::unsigned::Error::Event(
+                nostr::event::Error::InvalidSignature,
+            )),
+            Error::EventBuilderError(nostr::event::builder::Error::Key(
+                nostr::key::Error::SkMissing,
+            )),
+        ];
+        for e in &errors {
+            assert!(!e.help_key().is_empty());
+            assert!(help_text(e.help_key()).is_some(), "{}", e.help_key());
+        }
+        // every help text is used, keys are unique
+        for (i, (key, text)) in HELP_TEXTS.iter().enumerate() {
+            assert!(!text.is_empty());
+            assert!(errors.iter().any(|e| e.help_key() == *key), "{}", key);
+            assert!(!HELP_TEXTS[i + 1..].iter().any(|(k, _)| k == key));
+        }
+
+        let detail = ErrorDetail::from_app_error(&Error::KeySignerLocked);
+        assert_eq!(detail.help, help_text("key-locked"));
+    }
+}
@@ -0,0 +1,40 @@
+use crate::base::error::Error;
+
+/// Check that a text input is filled, before any parsing.
+/// Returns it trimmed; empty or whitespace-only input is an error naming the field.
+pub(crate) fn require_input<'a>(input: &'a str, field: &'static str) -> Result<&'a str, Error> {
+    match input.trim() {
+        "" => Err(Error::EmptyInput(field)),
+        trimmed => Ok(trimmed),
+    }
+}
+
+/// Check that a password input is filled. The password is not trimmed: spaces are part of it,
+/// so only an empty one is an error.
+pub(crate) fn require_password(password: &str, field: &'static str) -> Result<(), Error> {
+    if password.is_empty() {
+        return Err(Error::EmptyInput(field));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_require_input() {
+        assert_eq!(require_input(" npub1x \n", "Public key").unwrap(), "npub1x");
+        for blank in ["", " ", " \t\n "] {
+            match require_input(blank, "Public key") {
+                Err(e) => assert_eq!(e.to_string(), "Public key is empty, enter it first"),
+                Ok(_) => panic!("Expected error"),
+            }
+        }
+        assert!(require_password(" ", "Password").is_ok());
+        assert!(matches!(
+            require_password("", "Password"),
+            Err(Error::EmptyInput("Password"))
+        ));
+    }
+}
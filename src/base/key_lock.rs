@@ -0,0 +1,124 @@
+use crate::base::error::Error;
+
+use nostr::prelude::XOnlyPublicKey;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// State of a key lock file, as found before acquiring
+#[derive(Debug, PartialEq)]
+pub(crate) enum LockState {
+    /// No lock file
+    Free,
+    /// Lock file of an instance which is not running any more (e.g. crashed), with its PID
+    Stale(u32),
+    /// Lock file of a running instance, with its PID
+    Live(u32),
+}
+
+/// Lock file marking that a running instance holds a given key.
+/// Contains the PID of the holder, removed on drop.
+pub(crate) struct KeyLock {
+    path: PathBuf,
+}
+
+impl KeyLock {
+    /// Lock file path for a key, in the given folder
+    pub fn path_for(dir: &Path, pubkey: &XOnlyPublicKey) -> PathBuf {
+        let mut p = dir.to_path_buf();
+        p.push(format!("key-{}.lock", &pubkey.to_string()[..16]));
+        p
+    }
+
+    /// Check the lock file, without modifying it
+    pub fn check(path: &Path) -> LockState {
+        let content = match fs::read_to_string(path) {
+            Err(_) => return LockState::Free,
+            Ok(c) => c,
+        };
+        match content.trim().parse::<u32>() {
+            Ok(pid) if is_process_alive(pid) => LockState::Live(pid),
+            Ok(pid) => LockState::Stale(pid),
+            Err(_) => LockState::Stale(0),
+        }
+    }
+
+    /// Try to take the lock for a key. If it is held by another running instance,
+    /// the lock is not taken (None is returned). Stale locks are taken over.
+    pub fn acquire(
+        dir: &Path,
+        pubkey: &XOnlyPublicKey,
+    ) -> Result<(Option<Self>, LockState), Error> {
+        let path = Self::path_for(dir, pubkey);
+        let state = Self::check(&path);
+        if let LockState::Live(_) = state {
+            return Ok((None, state));
+        }
+        fs::write(&path, std::process::id().to_string())?;
+        Ok((Some(Self { path }), state))
+    }
+}
+
+impl Drop for KeyLock {
+    fn drop(&mut self) {
+        let _res = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_process_alive(pid: u32) -> bool {
+    Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+/// No portable way to check, assume it is running
+#[cfg(not(target_os = "linux"))]
+fn is_process_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nostr::prelude::Keys;
+
+    #[test]
+    fn test_acquire_detect_release() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("keystr-lock-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let pubkey = Keys::generate().public_key();
+        let path = KeyLock::path_for(&dir, &pubkey);
+
+        // acquire
+        let (lock, state) = KeyLock::acquire(&dir, &pubkey).unwrap();
+        assert_eq!(state, LockState::Free);
+        assert!(lock.is_some());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+
+        // detect
+        assert_eq!(KeyLock::check(&path), LockState::Live(std::process::id()));
+        let (lock2, state2) = KeyLock::acquire(&dir, &pubkey).unwrap();
+        assert!(lock2.is_none());
+        assert_eq!(state2, LockState::Live(std::process::id()));
+
+        // release
+        drop(lock);
+        assert!(!path.exists());
+        assert_eq!(KeyLock::check(&path), LockState::Free);
+
+        // stale lock, PID above the max PID
+        #[cfg(target_os = "linux")]
+        {
+            fs::write(&path, "999999999").unwrap();
+            assert_eq!(KeyLock::check(&path), LockState::Stale(999999999));
+            let (lock3, state3) = KeyLock::acquire(&dir, &pubkey).unwrap();
+            assert!(lock3.is_some());
+            assert_eq!(state3, LockState::Stale(999999999));
+            assert_eq!(KeyLock::check(&path), LockState::Live(std::process::id()));
+        }
+
+        let _res = fs::remove_dir_all(&dir);
+    }
+}
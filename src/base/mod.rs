@@ -1,4 +1,12 @@
+pub mod build_info;
+pub mod client_export;
 pub mod encrypt;
 pub mod error;
+pub mod input;
+pub mod key_lock;
+pub mod qr_png;
+pub mod relay_url;
 // mod nostr_libs;
+pub mod scrub;
+pub mod secret_string;
 pub mod storage;
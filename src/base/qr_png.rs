@@ -0,0 +1,86 @@
+use crate::base::error::Error;
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Size of a QR module (dot) in the image, in pixels
+const MODULE_PIXELS: usize = 8;
+/// Blank border around the code, in modules (4 is the minimum of the QR standard)
+const QUIET_ZONE_MODULES: usize = 4;
+
+/// Save the QR code of the data (e.g. npub) as a PNG image file, black on white, for printing
+/// or sharing. Errors name the path, e.g. if the folder does not exist or is not writable.
+pub(crate) fn save_qr_png(data: &str, path: &Path) -> Result<(), Error> {
+    let (width, pixels) = qr_pixels(data)?;
+    let save_error =
+        |e: &dyn std::fmt::Display| Error::QrSaveFailed(path.display().to_string(), e.to_string());
+    let file = File::create(path).map_err(|e| save_error(&e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, width as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| save_error(&e))?;
+    writer
+        .write_image_data(&pixels)
+        .map_err(|e| save_error(&e))?;
+    writer.finish().map_err(|e| save_error(&e))?;
+    Ok(())
+}
+
+/// QR code of the data as grayscale pixels (row by row), with the image width (it is square)
+fn qr_pixels(data: &str) -> Result<(usize, Vec<u8>), Error> {
+    let code = qrcode::QrCode::new(data.as_bytes()).map_err(|e| Error::QrEncode(e.to_string()))?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let width = (modules + 2 * QUIET_ZONE_MODULES) * MODULE_PIXELS;
+    let mut pixels = vec![0xFF; width * width];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != qrcode::Color::Dark {
+            continue;
+        }
+        let x0 = (i % modules + QUIET_ZONE_MODULES) * MODULE_PIXELS;
+        let y0 = (i / modules + QUIET_ZONE_MODULES) * MODULE_PIXELS;
+        for y in y0..y0 + MODULE_PIXELS {
+            pixels[y * width + x0..y * width + x0 + MODULE_PIXELS].fill(0);
+        }
+    }
+    Ok((width, pixels))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    const NPUB: &str = "npub1c82zv3aj04l8dmxlxywx5fsg6ngt5nyvwa9j0eqk03ntg2t2jtxqngn7ry";
+
+    #[test]
+    fn test_save_qr_png() {
+        let dir = std::env::temp_dir().join(format!("keystr-test-qr-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("npub-qr.png");
+        save_qr_png(NPUB, &path).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        assert!(!bytes.is_empty());
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+        // decodes as a square grayscale image, with a white border
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!(info.width, info.height);
+        assert!(info.width as usize > 2 * QUIET_ZONE_MODULES * MODULE_PIXELS);
+        assert_eq!(info.color_type, png::ColorType::Grayscale);
+        assert_eq!(buf[0], 0xFF);
+        assert!(buf.contains(&0));
+
+        // folder does not exist
+        let missing = dir.join("missing").join("npub-qr.png");
+        match save_qr_png(NPUB, &missing) {
+            Err(Error::QrSaveFailed(p, _)) => assert_eq!(p, missing.display().to_string()),
+            _ => panic!("Expected save error"),
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
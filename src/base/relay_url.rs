@@ -0,0 +1,109 @@
+use crate::base::error::Error;
+
+use nostr::Url;
+use std::str::FromStr;
+
+/// Read/write marker of a relay, in a NIP-65 relay list
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RelayMarker {
+    Read,
+    Write,
+    /// Both read and write, no marker in the tag
+    ReadWrite,
+}
+
+impl RelayMarker {
+    /// Marker value in the `r` tag, none for read-write
+    pub fn tag_value(&self) -> Option<String> {
+        match self {
+            RelayMarker::Read => Some("read".to_string()),
+            RelayMarker::Write => Some("write".to_string()),
+            RelayMarker::ReadWrite => None,
+        }
+    }
+}
+
+impl FromStr for RelayMarker {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "read" => Ok(RelayMarker::Read),
+            "write" => Ok(RelayMarker::Write),
+            "" | "readwrite" => Ok(RelayMarker::ReadWrite),
+            other => Err(Error::InvalidRelayMarker(other.to_string())),
+        }
+    }
+}
+
+/// Check that a relay URL is valid: parseable, websocket scheme, with host.
+/// Returns it normalized (trimmed, without trailing slash).
+pub(crate) fn validate_relay_url(url: &str) -> Result<String, Error> {
+    let url = url.trim();
+    let parsed = Url::parse(url).map_err(|_| Error::InvalidRelayUrl(url.to_string()))?;
+    if !(parsed.scheme() == "wss" || parsed.scheme() == "ws") || parsed.host_str().is_none() {
+        return Err(Error::InvalidRelayUrl(url.to_string()));
+    }
+    Ok(url.trim_end_matches('/').to_string())
+}
+
+/// Parse a comma-separated list of relays, each with an optional marker,
+/// e.g. "wss://relay1.example.com read, wss://relay2.example.com"
+pub(crate) fn parse_marked_relays(input: &str) -> Result<Vec<(String, RelayMarker)>, Error> {
+    input
+        .split(',')
+        .map(|r| r.trim())
+        .filter(|r| !r.is_empty())
+        .map(|r| {
+            let (url, marker) = r.split_once(' ').unwrap_or((r, ""));
+            Ok((validate_relay_url(url)?, RelayMarker::from_str(marker)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_relay_url() {
+        assert_eq!(
+            validate_relay_url(" wss://relay.example.com/ ").unwrap(),
+            "wss://relay.example.com"
+        );
+        assert_eq!(
+            validate_relay_url("ws://localhost:7000").unwrap(),
+            "ws://localhost:7000"
+        );
+        assert!(validate_relay_url("https://relay.example.com").is_err());
+        assert!(validate_relay_url("relay.example.com").is_err());
+        assert!(validate_relay_url("").is_err());
+    }
+
+    #[test]
+    fn test_relay_marker() {
+        assert_eq!(RelayMarker::from_str("read").unwrap(), RelayMarker::Read);
+        assert_eq!(RelayMarker::from_str("write").unwrap(), RelayMarker::Write);
+        assert_eq!(RelayMarker::from_str("").unwrap(), RelayMarker::ReadWrite);
+        assert!(RelayMarker::from_str("readonly").is_err());
+        assert_eq!(RelayMarker::Read.tag_value(), Some("read".to_string()));
+        assert_eq!(RelayMarker::ReadWrite.tag_value(), None);
+    }
+
+    #[test]
+    fn test_parse_marked_relays() {
+        assert_eq!(
+            parse_marked_relays("wss://relay1.example.com read, wss://relay2.example.com,")
+                .unwrap(),
+            vec![
+                ("wss://relay1.example.com".to_string(), RelayMarker::Read),
+                (
+                    "wss://relay2.example.com".to_string(),
+                    RelayMarker::ReadWrite
+                ),
+            ]
+        );
+        assert!(parse_marked_relays("wss://relay1.example.com both").is_err());
+        assert!(parse_marked_relays("relay1.example.com read").is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use once_cell::sync::Lazy;
+use std::sync::{Arc, Mutex, TryLockError, Weak};
+
+/// Holder of secret material, that can be wiped in an emergency (e.g. on panic)
+pub(crate) trait Scrub {
+    fn scrub(&mut self);
+}
+
+type ScrubEntry = Weak<Mutex<dyn Scrub + Send>>;
+
+/// Registry of secret holders. Only weak references are kept.
+pub(crate) struct ScrubRegistry {
+    entries: Mutex<Vec<ScrubEntry>>,
+}
+
+impl ScrubRegistry {
+    pub fn new() -> Self {
+        ScrubRegistry {
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Register a secret holder
+    pub fn register(&self, holder: Arc<Mutex<dyn Scrub + Send>>) {
+        let mut entries = match self.entries.lock() {
+            Ok(r) => r,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        entries.retain(|w| w.strong_count() > 0);
+        entries.push(Arc::downgrade(&holder));
+    }
+
+    /// Wipe all registered secret holders, returns the number of holders wiped.
+    /// Uses only `try_lock`, never blocks, so it is safe to call from a panic hook,
+    /// even if the panicking thread holds some lock.
+    pub fn scrub_all(&self) -> usize {
+        let entries = match self.entries.try_lock() {
+            Ok(r) => r,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return 0,
+        };
+        let mut count = 0;
+        for holder in entries.iter().filter_map(|w| w.upgrade()) {
+            let mut locked = match holder.try_lock() {
+                Ok(l) => l,
+                Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+                Err(TryLockError::WouldBlock) => continue,
+            };
+            locked.scrub();
+            count += 1;
+        }
+        count
+    }
+}
+
+/// Registered secret holders of the process, to be wiped by the panic hook
+static SCRUB_REGISTRY: Lazy<ScrubRegistry> = Lazy::new(ScrubRegistry::new);
+
+/// Register a secret holder, to be wiped on panic
+pub(crate) fn register(holder: Arc<Mutex<dyn Scrub + Send>>) {
+    SCRUB_REGISTRY.register(holder);
+}
+
+/// Wipe all registered secret holders of the process, returns the number of holders wiped
+fn scrub_all() -> usize {
+    SCRUB_REGISTRY.scrub_all()
+}
+
+/// Install a panic hook that does not print the panic payload (it may contain sensitive data),
+/// wipes all registered secrets, and aborts the process.
+pub(crate) fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let location = match info.location() {
+            Some(l) => format!("{}:{}", l.file(), l.line()),
+            None => "(unknown)".to_string(),
+        };
+        eprintln!("ERROR: Keystr panicked at {location}, details suppressed, wiping secrets");
+        let _cnt = scrub_all();
+        std::process::abort();
+    }));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::process::Command;
+
+    const SECRET: &str = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+    const SUBPROCESS_ENV: &str = "KEYSTR_TEST_PANIC_SUBPROCESS";
+
+    struct TestSecret(String);
+
+    impl Scrub for TestSecret {
+        fn scrub(&mut self) {
+            self.0.clear();
+        }
+    }
+
+    #[test]
+    fn test_scrub_all() {
+        let registry = ScrubRegistry::new();
+        let holder = Arc::new(Mutex::new(TestSecret(SECRET.to_string())));
+        registry.register(holder.clone());
+        assert_eq!(registry.scrub_all(), 1);
+        assert!(holder.lock().unwrap().0.is_empty());
+
+        // dropped holders are not counted
+        drop(holder);
+        assert_eq!(registry.scrub_all(), 0);
+    }
+
+    #[test]
+    fn test_scrub_all_skips_locked() {
+        let registry = ScrubRegistry::new();
+        let holder = Arc::new(Mutex::new(TestSecret(SECRET.to_string())));
+        registry.register(holder.clone());
+        // must not deadlock while the holder is locked
+        let locked = holder.lock().unwrap();
+        assert_eq!(registry.scrub_all(), 0);
+        assert_eq!(locked.0, SECRET);
+    }
+
+    #[test]
+    fn test_panic_hook_no_secret_output() {
+        if std::env::var(SUBPROCESS_ENV).is_ok() {
+            // In the subprocess: panic with a secret in the payload
+            install_panic_hook();
+            panic!("panic with secret {}", SECRET);
+        }
+        let output = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "base::scrub::test::test_panic_hook_no_secret_output",
+                "--exact",
+                "--nocapture",
+            ])
+            .env(SUBPROCESS_ENV, "1")
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stderr.contains("Keystr panicked at"));
+        assert!(!stderr.contains(SECRET));
+        assert!(!stdout.contains(SECRET));
+    }
+}
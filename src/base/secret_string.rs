@@ -0,0 +1,91 @@
+use std::fmt;
+use zeroize::Zeroize;
+
+/// String holding sensitive input (e.g. a secret key to import).
+/// The buffer is wiped when the value is cleared, dropped, or replaced (the old value is dropped).
+/// Debug output does not show the content, so it does not end up in logs by accident.
+#[derive(Default)]
+pub(crate) struct SecretString(String);
+
+impl SecretString {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Wipe the content, leaving it empty
+    pub fn clear(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl From<String> for SecretString {
+    /// Takes over the buffer, without copying
+    fn from(s: String) -> Self {
+        Self(s)
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretString(***)")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SECRET: &str = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+
+    #[test]
+    fn test_clear_wipes_buffer() {
+        let mut s = SecretString::from(SECRET.to_string());
+        assert_eq!(s.as_str(), SECRET);
+        let ptr = s.0.as_ptr();
+        let capacity = s.0.capacity();
+
+        s.clear();
+        assert!(s.is_empty());
+        // the buffer is kept, wiped
+        assert_eq!(s.0.as_ptr(), ptr);
+        assert_eq!(s.0.capacity(), capacity);
+        // safety: the allocation is still owned by the string, and zeroize initialized it
+        let buffer = unsafe { std::slice::from_raw_parts(ptr, capacity) };
+        assert!(buffer.iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_replace_drops_old_value() {
+        let mut input = SecretString::from(SECRET.to_string());
+        // taking the value out leaves an empty input; the taken value is wiped when dropped
+        let mut taken = std::mem::take(&mut input);
+        assert!(input.is_empty());
+        assert_eq!(taken.as_str(), SECRET);
+        taken.clear();
+        assert!(taken.is_empty());
+
+        input = SecretString::from("new".to_string());
+        assert_eq!(input.as_str(), "new");
+        input = SecretString::default();
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn test_debug_hides_content() {
+        let s = SecretString::from(SECRET.to_string());
+        let debug = format!("{:?}", s);
+        assert!(!debug.contains("nsec"));
+        assert_eq!(debug, "SecretString(***)");
+    }
+}
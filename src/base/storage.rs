@@ -1,4 +1,6 @@
 use crate::base::error::Error;
+#[cfg(test)]
+use std::cell::RefCell;
 use std::fs;
 use std::path::PathBuf;
 
@@ -12,6 +14,10 @@ const PUBLIC_KEY_FILENAME: &str = "npub";
 const ENCRYPTED_SECRET_KEY_FILENAME: &str = ".ncrypt";
 /// Public key storage file name, relative to data folder.
 const SETTINGS_FILENAME: &str = "settings.json";
+/// Export file of pending signer requests, relative to data folder.
+const PENDING_EXPORT_FILENAME: &str = "pending-requests.json";
+/// Default file of the saved QR code image, relative to data folder.
+const QR_PNG_FILENAME: &str = "qr-code.png";
 
 impl Storage {
     pub fn public_key_file() -> PathBuf {
@@ -26,6 +32,18 @@ impl Storage {
         Self::full_file_path(SETTINGS_FILENAME)
     }
 
+    pub fn pending_export_file() -> PathBuf {
+        Self::full_file_path(PENDING_EXPORT_FILENAME)
+    }
+
+    pub fn qr_png_file() -> PathBuf {
+        Self::full_file_path(QR_PNG_FILENAME)
+    }
+
+    pub fn storage_folder() -> PathBuf {
+        Self::get_storage_folder()
+    }
+
     pub fn check_create_folder() -> Result<(), Error> {
         let p = Self::get_storage_folder();
         if p.is_dir() {
@@ -35,15 +53,66 @@ impl Storage {
         Ok(())
     }
 
+    #[cfg(not(test))]
     fn get_storage_folder() -> PathBuf {
         let mut p = dirs::data_local_dir().unwrap_or(PathBuf::from("."));
         p.push(LOCAL_STORAGE_FOLDER);
         p
     }
 
+    /// Tests must not touch the user's real data, use a temp folder
+    /// (the own folder of the test, if it has one)
+    #[cfg(test)]
+    fn get_storage_folder() -> PathBuf {
+        if let Some(p) = TEST_STORAGE_FOLDER.with(|f| f.borrow().clone()) {
+            return p;
+        }
+        let mut p = std::env::temp_dir();
+        p.push(format!("{}-test", LOCAL_STORAGE_FOLDER));
+        p
+    }
+
     fn full_file_path(file_name: &str) -> PathBuf {
         let mut p = Self::get_storage_folder();
         p.push(file_name);
         p
     }
 }
+
+#[cfg(test)]
+thread_local! {
+    /// Storage folder of the test running on this thread, if it has its own
+    static TEST_STORAGE_FOLDER: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Own storage folder for a test (on the current thread), for tests that save,
+/// so they don't race on the shared test folder. Removed when dropped.
+#[cfg(test)]
+pub(crate) struct TestStorage {
+    folder: PathBuf,
+}
+
+#[cfg(test)]
+impl TestStorage {
+    pub fn new(name: &str) -> Self {
+        let mut folder = std::env::temp_dir();
+        folder.push(format!(
+            "{}-test-{}-{}",
+            LOCAL_STORAGE_FOLDER,
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        TEST_STORAGE_FOLDER.with(|f| *f.borrow_mut() = Some(folder.clone()));
+        TestStorage { folder }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestStorage {
+    fn drop(&mut self) {
+        TEST_STORAGE_FOLDER.with(|f| *f.borrow_mut() = None);
+        let _ = fs::remove_dir_all(&self.folder);
+    }
+}
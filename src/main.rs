@@ -7,5 +7,15 @@ use iced::{Application, Settings};
 
 #[tokio::main]
 async fn main() {
-    let _res = KeystrApp::run(Settings::default());
+    base::scrub::install_panic_hook();
+    if std::env::args().any(|a| a == "--json-logs") {
+        model::status_messages::set_json_output(true);
+    }
+    // closing is handled by the app, to be able to hide to the tray
+    let _res = KeystrApp::run(Settings {
+        exit_on_close_request: false,
+        ..Settings::default()
+    });
+    // UI is gone, let background tasks stop
+    model::keystr_model::EVENT_QUEUE.close();
 }
@@ -0,0 +1,215 @@
+use crate::base::error::Error;
+use crate::model::status_messages::redact_secrets;
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Activity log file name, relative to data folder
+const ACTIVITY_LOG_FILENAME: &str = "activity.log";
+/// The log file is rotated when it would grow beyond this size, in bytes
+const DEFAULT_MAX_SIZE: u64 = 256 * 1024;
+/// Number of rotated files kept; ".1" is the most recent
+const ROTATED_FILES_KEPT: usize = 3;
+/// Number of entries kept in memory for the log view, older ones are dropped
+const MAX_ENTRIES: usize = 1000;
+
+/// An entry of the activity log, secrets redacted
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Entry {
+    /// Unix timestamp, in seconds
+    pub time: u64,
+    pub message: String,
+}
+
+impl Entry {
+    fn new(time: u64, message: &str) -> Self {
+        Self {
+            time,
+            message: redact_secrets(message),
+        }
+    }
+
+    /// Line format, as in the file: timestamp and message
+    pub fn to_line(&self) -> String {
+        format!("{} {}", self.time, self.message)
+    }
+}
+
+/// Activity log: the recent entries are kept in memory (newest first), for the log view;
+/// optionally also persisted to disk, appended to a file with size-based rotation.
+/// Secrets are redacted from entries before storing.
+pub(crate) struct ActivityLog {
+    /// Newest first, at most MAX_ENTRIES
+    entries: Vec<Entry>,
+    /// If set, entries are also appended to this file
+    file: Option<PathBuf>,
+    max_size: u64,
+}
+
+impl ActivityLog {
+    /// In-memory only, not persisted
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            file: None,
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_max_size(dir: &Path, max_size: u64) -> Self {
+        let mut log = Self::new();
+        log.set_persist_dir(Some(dir));
+        log.max_size = max_size;
+        log
+    }
+
+    /// Enable (or disable, with None) persisting new entries to the log file in the given folder
+    pub fn set_persist_dir(&mut self, dir: Option<&Path>) {
+        self.file = dir.map(|d| {
+            let mut file = d.to_path_buf();
+            file.push(ACTIVITY_LOG_FILENAME);
+            file
+        });
+    }
+
+    /// Add an entry with the current time; it is also persisted if enabled.
+    /// The entry is kept even if persisting fails.
+    pub fn add(&mut self, message: &str) -> Result<(), Error> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let entry = Entry::new(time, message);
+        let res = match &self.file {
+            None => Ok(()),
+            Some(file) => self.persist(file, &entry),
+        };
+        self.entries.insert(0, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        res
+    }
+
+    /// Number of entries kept in memory
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// A page of entries, newest first: skip `offset` entries, return at most `limit`.
+    /// Out-of-range offsets give an empty page.
+    pub fn page(&self, offset: usize, limit: usize) -> &[Entry] {
+        let start = offset.min(self.entries.len());
+        let end = start.saturating_add(limit).min(self.entries.len());
+        &self.entries[start..end]
+    }
+
+    /// Append an entry to the file; rotate first if the file would get too large
+    fn persist(&self, file: &Path, entry: &Entry) -> Result<(), Error> {
+        let line = format!("{}\n", entry.to_line());
+        let size = fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > self.max_size {
+            rotate(file)?;
+        }
+        let mut f = OpenOptions::new().create(true).append(true).open(file)?;
+        f.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn rotated_file(file: &Path, n: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}", file.display(), n))
+}
+
+/// Shift rotated files (the oldest is dropped), current file becomes ".1"
+fn rotate(file: &Path) -> Result<(), Error> {
+    for n in (1..ROTATED_FILES_KEPT).rev() {
+        let from = rotated_file(file, n);
+        if from.exists() {
+            fs::rename(from, rotated_file(file, n + 1))?;
+        }
+    }
+    fs::rename(file, rotated_file(file, 1))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_persist_and_rotate() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("keystr-activity-test-{}", std::process::id()));
+        let _res = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut log = ActivityLog::with_max_size(&dir, 200);
+        let file = log.file.clone().unwrap();
+        log.add("Keys loaded from storage").unwrap();
+        log.add("Secret key nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae")
+            .unwrap();
+        let content = fs::read_to_string(&file).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.contains("Keys loaded from storage"));
+        assert!(content.contains("nsec1***"));
+        assert!(!content.contains("ktekw0hr5evjs0n9"));
+        assert!(!rotated_file(&file, 1).exists());
+
+        // past the size cap
+        for i in 0..20 {
+            log.add(&format!("Entry number {}", i)).unwrap();
+        }
+        assert!(rotated_file(&file, 1).exists());
+        assert!(fs::metadata(&file).unwrap().len() <= 200);
+        assert!(fs::metadata(rotated_file(&file, 1)).unwrap().len() <= 200);
+        assert!(!rotated_file(&file, ROTATED_FILES_KEPT + 1).exists());
+        let content = fs::read_to_string(&file).unwrap();
+        assert!(content.ends_with("Entry number 19\n"));
+
+        // not persisted any more, but kept in memory
+        log.set_persist_dir(None);
+        log.add("Not persisted").unwrap();
+        assert!(!fs::read_to_string(&file).unwrap().contains("Not persisted"));
+        assert_eq!(log.page(0, 1)[0].message, "Not persisted");
+
+        let _res = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_page_newest_first() {
+        let mut log = ActivityLog::new();
+        assert!(log.page(0, 10).is_empty());
+        for i in 0..25 {
+            log.add(&format!("Entry {}", i)).unwrap();
+        }
+        assert_eq!(log.len(), 25);
+
+        let messages =
+            |page: &[Entry]| -> Vec<String> { page.iter().map(|e| e.message.clone()).collect() };
+        assert_eq!(
+            messages(log.page(0, 3)),
+            vec!["Entry 24", "Entry 23", "Entry 22"]
+        );
+        assert_eq!(messages(log.page(3, 2)), vec!["Entry 21", "Entry 20"]);
+        // last, partial page
+        assert_eq!(messages(log.page(20, 10)).len(), 5);
+        assert_eq!(log.page(20, 10)[4].message, "Entry 0");
+        // out of range
+        assert!(log.page(25, 10).is_empty());
+        assert!(log.page(100, 10).is_empty());
+        assert!(log.page(0, 0).is_empty());
+        assert_eq!(log.page(24, usize::MAX).len(), 1);
+        // timestamps are non-increasing
+        let all = log.page(0, 25);
+        assert!(all.windows(2).all(|w| w[0].time >= w[1].time));
+
+        // older entries are dropped past the cap
+        for i in 0..MAX_ENTRIES {
+            log.add(&format!("More {}", i)).unwrap();
+        }
+        assert_eq!(log.len(), MAX_ENTRIES);
+        assert_eq!(log.page(MAX_ENTRIES - 1, 1)[0].message, "More 0");
+    }
+}
@@ -1,8 +1,12 @@
 use crate::base::error::Error;
 
 use nostr::prelude::{
-    Conditions, DelegationTag, DelegationToken, FromBech32, Keys, ToBech32, XOnlyPublicKey,
+    Conditions, DelegationTag, DelegationToken, EventBuilder, EventId, EventProperties, FromBech32,
+    Keys, Kind, SecretKey, Tag, Timestamp, ToBech32, UnsignedEvent, XOnlyPublicKey,
 };
+use nostr::secp256k1::rand::{thread_rng, Rng};
+use nostr::secp256k1::schnorr::Signature;
+use serde::{Deserialize, Serialize};
 
 use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -27,6 +31,102 @@ pub(crate) struct Delegator {
     pub signature: String,
     // Compiled delegation tag (contains pubkey, conditions, signature)
     pub delegation_tag: String,
+    /// Delegations received from delegators (own key is the delegatee), to apply when signing
+    pub delegations: Vec<DelegationInfo>,
+    /// Index of the delegation to apply when signing, if any
+    pub selected_delegation: Option<usize>,
+    /// Input for adding a received delegation tag
+    pub delegation_import_input: String,
+}
+
+/// A delegation received from a delegator (NIP-26), for signing events on its behalf.
+/// Serialized as an object with the delegator pubkey, the conditions (canonical NIP-26 string),
+/// and the signature.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "DelegationInfoSerde", into = "DelegationInfoSerde")]
+pub(crate) struct DelegationInfo {
+    tag: DelegationTag,
+}
+
+/// Serialized form of `DelegationInfo`
+#[derive(Serialize, Deserialize)]
+struct DelegationInfoSerde {
+    delegator: XOnlyPublicKey,
+    conditions: Conditions,
+    sig: Signature,
+}
+
+impl From<DelegationInfo> for DelegationInfoSerde {
+    fn from(info: DelegationInfo) -> Self {
+        Self {
+            delegator: info.tag.delegator_pubkey(),
+            conditions: info.tag.conditions(),
+            sig: info.tag.signature(),
+        }
+    }
+}
+
+impl TryFrom<DelegationInfoSerde> for DelegationInfo {
+    type Error = nostr::nips::nip26::Error;
+
+    fn try_from(s: DelegationInfoSerde) -> Result<Self, Self::Error> {
+        let tag = DelegationTag::try_from(vec![
+            "delegation".to_string(),
+            s.delegator.to_string(),
+            s.conditions.to_string(),
+            s.sig.to_string(),
+        ])?;
+        Ok(Self { tag })
+    }
+}
+
+impl DelegationInfo {
+    /// Parse from a delegation tag in JSON format, as shown by the delegator
+    pub fn from_tag_str(tag_str: &str) -> Result<Self, Error> {
+        Ok(Self {
+            tag: DelegationTag::from_str(tag_str.trim())?,
+        })
+    }
+
+    pub fn delegator_pubkey(&self) -> XOnlyPublicKey {
+        self.tag.delegator_pubkey()
+    }
+
+    pub fn description(&self) -> String {
+        format!(
+            "from {}, conditions '{}'",
+            self.delegator_pubkey().to_bech32().unwrap_or_default(),
+            self.tag.conditions()
+        )
+    }
+
+    /// Check that the delegation is valid for the delegatee, and its conditions permit the event
+    pub fn permits(&self, delegatee: &XOnlyPublicKey, event: &UnsignedEvent) -> bool {
+        self.tag
+            .validate(
+                *delegatee,
+                &EventProperties::new(event.kind.as_u64(), event.created_at.as_u64()),
+            )
+            .is_ok()
+    }
+
+    /// Event with the delegation tag added (id updated)
+    pub fn apply(&self, event: &UnsignedEvent) -> UnsignedEvent {
+        let mut delegated = event.clone();
+        delegated.tags.push(Tag::Delegation {
+            delegator_pk: self.tag.delegator_pubkey(),
+            conditions: self.tag.conditions(),
+            sig: self.tag.signature(),
+        });
+        delegated.id = EventId::new(
+            &delegated.pubkey,
+            delegated.created_at,
+            &delegated.kind,
+            &delegated.tags,
+            &delegated.content,
+        );
+        delegated
+    }
 }
 
 impl Delegator {
@@ -41,6 +141,9 @@ impl Delegator {
             delegation_string: String::new(),
             signature: String::new(),
             delegation_tag: String::new(),
+            delegations: Vec::new(),
+            selected_delegation: None,
+            delegation_import_input: String::new(),
         };
         let _r = d.validate_and_update();
         d
@@ -100,7 +203,12 @@ impl Delegator {
     }
 
     pub fn generate_random_delegatee(&mut self) {
-        let key = Keys::generate().public_key();
+        self.generate_delegatee_with_rng(&mut thread_rng());
+    }
+
+    /// Generate a delegatee key using the given RNG; a seeded one gives a reproducible delegatee
+    pub fn generate_delegatee_with_rng<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let key = Keys::new(SecretKey::new(rng)).public_key();
         self.delegatee_npub_input = key.to_bech32().unwrap();
         let _r = self.validate_and_update();
     }
@@ -120,12 +228,105 @@ impl Delegator {
         self.signature = tag.signature().to_string();
         Ok(())
     }
+
+    /// Add a received delegation, from its tag; the first one added is selected
+    pub fn add_delegation(&mut self, tag_str: &str) -> Result<(), Error> {
+        let info = DelegationInfo::from_tag_str(tag_str)?;
+        if !self.delegations.contains(&info) {
+            self.delegations.push(info);
+        }
+        if self.selected_delegation.is_none() {
+            self.selected_delegation = Some(0);
+        }
+        Ok(())
+    }
+
+    /// Select the delegation to apply when signing, None for no delegation
+    pub fn select_delegation(&mut self, index: Option<usize>) -> Result<(), Error> {
+        if let Some(i) = index {
+            if i >= self.delegations.len() {
+                return Err(Error::DelegationIndexOutOfBounds);
+            }
+        }
+        self.selected_delegation = index;
+        Ok(())
+    }
+
+    pub fn get_selected_delegation(&self) -> Option<&DelegationInfo> {
+        self.selected_delegation
+            .and_then(|i| self.delegations.get(i))
+    }
+
+    /// Kind for a sample event satisfying the kind condition (first kind if several), 1 if none
+    fn sample_kind(&self) -> u64 {
+        self.conditions
+            .split('&')
+            .find_map(|c| c.strip_prefix("kind=").and_then(|k| k.parse::<u64>().ok()))
+            .unwrap_or(1)
+    }
+
+    /// Creation time for a sample event: now, or just after the validity start if that is not yet passed
+    fn sample_created_at(&self, now: Timestamp) -> Timestamp {
+        let start = self.conditions.split('&').find_map(|c| {
+            c.strip_prefix("created_at>")
+                .and_then(|t| t.parse::<u64>().ok())
+        });
+        match start {
+            Some(start) if start >= now.as_u64() => Timestamp::from(start + 1),
+            _ => now,
+        }
+    }
+
+    /// End-to-end check of the created delegation: build a sample event with the delegation tag,
+    /// sign it, and validate it under NIP-26 for the delegatee.
+    pub fn self_test(&self, keys: &Keys) -> Result<(), Error> {
+        if self.delegation_tag.is_empty() {
+            return Err(Error::DelegationNotCreated);
+        }
+        let dtag = DelegationTag::from_str(&self.delegation_tag)?;
+        if dtag.delegator_pubkey() != keys.public_key() {
+            return Err(Error::DelegationWrongDelegator);
+        }
+        let delegatee_key = XOnlyPublicKey::from_bech32(self.delegatee_npub_input.clone())?;
+
+        let tag = Tag::Delegation {
+            delegator_pk: dtag.delegator_pubkey(),
+            conditions: dtag.conditions(),
+            sig: dtag.signature(),
+        };
+        let mut unsigned = EventBuilder::new(
+            Kind::from(self.sample_kind()),
+            "Delegation self-test",
+            &[tag],
+        )
+        .to_unsigned_event(keys.public_key());
+        unsigned.created_at = self.sample_created_at(unsigned.created_at);
+        unsigned.id = EventId::new(
+            &unsigned.pubkey,
+            unsigned.created_at,
+            &unsigned.kind,
+            &unsigned.tags,
+            &unsigned.content,
+        );
+        let event = unsigned.sign(keys)?;
+        // check id and signature, as a receiver would
+        event
+            .verify()
+            .map_err(nostr::event::unsigned::Error::from)?;
+        dtag.validate(
+            delegatee_key,
+            &EventProperties::new(event.kind.as_u64(), event.created_at.as_u64()),
+        )?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use nostr::prelude::{DelegationTag, EventProperties, SecretKey};
+    use nostr::prelude::{DelegationTag, EventProperties};
+    use nostr::secp256k1::rand::rngs::StdRng;
+    use nostr::secp256k1::rand::SeedableRng;
 
     #[test]
     fn test_create_delegation() {
@@ -169,6 +370,67 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_self_test() {
+        let keys = Keys::generate();
+        let mut d = Delegator::new();
+        assert!(matches!(
+            d.self_test(&keys),
+            Err(Error::DelegationNotCreated)
+        ));
+
+        d.generate_random_delegatee();
+        d.kind_condition_input = "kind=1".to_string();
+        d.time_set_days("10");
+        d.create_delegation(&keys).unwrap();
+        assert!(d.self_test(&keys).is_ok());
+
+        // different delegator key
+        assert!(matches!(
+            d.self_test(&Keys::generate()),
+            Err(Error::DelegationWrongDelegator)
+        ));
+
+        // tampered signature
+        let mut broken = Delegator::new();
+        broken.delegatee_npub_input = d.delegatee_npub_input.clone();
+        broken.conditions = d.conditions.clone();
+        broken.delegation_tag = d.delegation_tag.replace("kind=1", "kind=2");
+        assert!(broken.self_test(&keys).is_err());
+
+        // expired validity period
+        d.time_cond_start = 1676067553.to_string();
+        d.time_cond_end = 1678659553.to_string();
+        d.create_delegation(&keys).unwrap();
+        assert!(matches!(d.self_test(&keys), Err(Error::Nip26Error(_))));
+    }
+
+    #[test]
+    fn test_generate_delegatee_with_rng() {
+        let mut d1 = Delegator::new();
+        d1.generate_delegatee_with_rng(&mut StdRng::seed_from_u64(42));
+        let mut d2 = Delegator::new();
+        d2.generate_delegatee_with_rng(&mut StdRng::seed_from_u64(42));
+        assert!(d1.delegatee_npub_input.starts_with("npub1"));
+        assert_eq!(d1.delegatee_npub_input, d2.delegatee_npub_input);
+
+        d2.generate_delegatee_with_rng(&mut StdRng::seed_from_u64(43));
+        assert_ne!(d1.delegatee_npub_input, d2.delegatee_npub_input);
+
+        // delegation string for the generated delegatee
+        // (StdRng output may change between versions, so no fixed key is expected)
+        d1.kind_condition_input = "kind=1".to_string();
+        d1.time_set_end("1678659553");
+        let delegatee = XOnlyPublicKey::from_bech32(d1.delegatee_npub_input.clone()).unwrap();
+        assert_eq!(
+            d1.delegation_string,
+            format!(
+                "nostr:delegation:{}:kind=1&created_at<1678659553",
+                delegatee
+            )
+        );
+    }
+
     #[test]
     fn test_lib_dtag_validate() {
         // nostr lib, tag validation
@@ -230,4 +492,78 @@ mod test {
             11 * 24 * 60 * 60
         );
     }
+
+    #[test]
+    fn test_add_select_delegations() {
+        let delegatee = Keys::generate().public_key();
+        let tag1 = DelegationTag::new(
+            &Keys::generate(),
+            delegatee,
+            Conditions::from_str("kind=1").unwrap(),
+        )
+        .unwrap();
+        let tag2 = DelegationTag::new(
+            &Keys::generate(),
+            delegatee,
+            Conditions::from_str("kind=7").unwrap(),
+        )
+        .unwrap();
+
+        let mut d = Delegator::new();
+        assert!(d.get_selected_delegation().is_none());
+        assert!(d.add_delegation("invalid").is_err());
+
+        d.add_delegation(&tag1.to_string()).unwrap();
+        d.add_delegation(&tag2.to_string()).unwrap();
+        // duplicate is not added again
+        d.add_delegation(&tag1.to_string()).unwrap();
+        assert_eq!(d.delegations.len(), 2);
+        // first one is selected
+        assert_eq!(
+            d.get_selected_delegation().unwrap().delegator_pubkey(),
+            tag1.delegator_pubkey()
+        );
+
+        d.select_delegation(Some(1)).unwrap();
+        assert_eq!(
+            d.get_selected_delegation().unwrap().delegator_pubkey(),
+            tag2.delegator_pubkey()
+        );
+        assert!(d.select_delegation(Some(2)).is_err());
+        assert_eq!(d.selected_delegation, Some(1));
+        d.select_delegation(None).unwrap();
+        assert!(d.get_selected_delegation().is_none());
+    }
+
+    #[test]
+    fn test_delegation_info_serde_roundtrip() {
+        let delegatee = Keys::generate().public_key();
+        let conditions_str = "kind=1&created_at>1680000000&created_at<1690000000";
+        let tag = DelegationTag::new(
+            &Keys::generate(),
+            delegatee,
+            Conditions::from_str(conditions_str).unwrap(),
+        )
+        .unwrap();
+        let info = DelegationInfo::from_tag_str(&tag.to_string()).unwrap();
+
+        let json = serde_json::to_string(&info).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["conditions"], conditions_str);
+        assert_eq!(value["delegator"], tag.delegator_pubkey().to_string());
+        assert_eq!(value["sig"], tag.signature().to_string());
+
+        let info2: DelegationInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(info2, info);
+        let mut event = EventBuilder::new_text_note("hello", &[]).to_unsigned_event(delegatee);
+        event.created_at = Timestamp::from(1685000000);
+        assert!(info2.permits(&delegatee, &event));
+
+        let conditions: Conditions = serde_json::from_value(value["conditions"].clone()).unwrap();
+        assert_eq!(conditions.to_string(), conditions_str);
+        assert!(
+            serde_json::from_str::<DelegationInfo>(&json.replace(conditions_str, "kind=x"))
+                .is_err()
+        );
+    }
 }
@@ -0,0 +1,131 @@
+use nostr::prelude::{Kind, ToBech32, XOnlyPublicKey};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::RwLock;
+
+/// Format used for displaying public keys; chosen by the user
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum DisplayFormat {
+    /// Bech32 format (npub...)
+    #[default]
+    Bech32,
+    /// Hex format
+    Hex,
+}
+
+impl fmt::Display for DisplayFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DisplayFormat::Bech32 => write!(f, "bech32 (npub)"),
+            DisplayFormat::Hex => write!(f, "hex"),
+        }
+    }
+}
+
+pub(crate) static DISPLAY_FORMATS: &[DisplayFormat] = &[DisplayFormat::Bech32, DisplayFormat::Hex];
+
+impl DisplayFormat {
+    /// The other format, for offering both
+    pub fn other(&self) -> DisplayFormat {
+        match self {
+            DisplayFormat::Bech32 => DisplayFormat::Hex,
+            DisplayFormat::Hex => DisplayFormat::Bech32,
+        }
+    }
+
+    pub fn format_public_key(&self, pubkey: &XOnlyPublicKey) -> String {
+        match self {
+            DisplayFormat::Bech32 => match pubkey.to_bech32() {
+                Err(_) => "(conversion error)".to_string(),
+                Ok(s) => s,
+            },
+            DisplayFormat::Hex => pubkey.to_string(),
+        }
+    }
+}
+
+/// Human-readable labels for event kinds, by kind number; configurable in the settings
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct KindLabels(pub BTreeMap<u64, String>);
+
+impl Default for KindLabels {
+    fn default() -> Self {
+        Self(
+            [
+                (0, "metadata"),
+                (1, "note"),
+                (3, "contacts"),
+                (4, "DM"),
+                (6, "repost"),
+                (7, "reaction"),
+            ]
+            .iter()
+            .map(|(k, l)| (*k, l.to_string()))
+            .collect(),
+        )
+    }
+}
+
+impl KindLabels {
+    /// Label of a kind, or "kind N" if there is none
+    pub fn label(&self, kind: Kind) -> String {
+        let n = kind.as_u64();
+        match self.0.get(&n) {
+            Some(l) => l.clone(),
+            None => format!("kind {}", n),
+        }
+    }
+}
+
+/// Kind labels in use, set from the settings
+static KIND_LABELS: Lazy<RwLock<KindLabels>> = Lazy::new(|| RwLock::new(KindLabels::default()));
+
+pub(crate) fn set_kind_labels(labels: &KindLabels) {
+    *KIND_LABELS.write().unwrap() = labels.clone();
+}
+
+/// Label of a kind for display, using the configured labels
+pub(crate) fn kind_label(kind: Kind) -> String {
+    KIND_LABELS.read().unwrap().label(kind)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nostr::prelude::FromBech32;
+
+    const NPUB: &str = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+    const PUBKEY_HEX: &str = "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4";
+
+    #[test]
+    fn test_format_public_key() {
+        let pk = XOnlyPublicKey::from_bech32(NPUB).unwrap();
+        assert_eq!(DisplayFormat::Bech32.format_public_key(&pk), NPUB);
+        assert_eq!(DisplayFormat::Hex.format_public_key(&pk), PUBKEY_HEX);
+        assert_eq!(
+            DisplayFormat::Bech32.other().format_public_key(&pk),
+            PUBKEY_HEX
+        );
+    }
+
+    #[test]
+    fn test_kind_label() {
+        assert_eq!(kind_label(Kind::Metadata), "metadata");
+        assert_eq!(kind_label(Kind::TextNote), "note");
+        assert_eq!(kind_label(Kind::ContactList), "contacts");
+        assert_eq!(kind_label(Kind::EncryptedDirectMessage), "DM");
+        assert_eq!(kind_label(Kind::Repost), "repost");
+        assert_eq!(kind_label(Kind::Reaction), "reaction");
+        assert_eq!(kind_label(Kind::Custom(12345)), "kind 12345");
+
+        let mut labels = KindLabels::default();
+        labels.0.insert(30023, "article".to_string());
+        assert_eq!(labels.label(Kind::Custom(30023)), "article");
+        let json = serde_json::to_string(&labels).unwrap();
+        assert_eq!(serde_json::from_str::<KindLabels>(&json).unwrap(), labels);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::base::error::Error;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Path of the health check
+const HEALTH_PATH: &str = "/healthz";
+/// Timeout for reading the request from a probe, so a stuck client can't block the server
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Readiness of the signer, for the health endpoint: a key is unlocked and a relay is connected.
+/// Updated by the model, read by the health server thread.
+#[derive(Default)]
+pub(crate) struct HealthState {
+    key_ready: AtomicBool,
+    relay_connected: AtomicBool,
+}
+
+impl HealthState {
+    pub fn update(&self, key_ready: bool, relay_connected: bool) {
+        self.key_ready.store(key_ready, Ordering::Relaxed);
+        self.relay_connected
+            .store(relay_connected, Ordering::Relaxed);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.key_ready.load(Ordering::Relaxed) && self.relay_connected.load(Ordering::Relaxed)
+    }
+}
+
+/// Minimal HTTP server answering `GET /healthz` with 200 if ready, 503 otherwise,
+/// e.g. for container orchestration probes. Runs in its own thread until exit.
+pub(crate) struct HealthServer {
+    pub local_addr: SocketAddr,
+}
+
+impl HealthServer {
+    pub fn start(bind_address: &str, state: Arc<HealthState>) -> Result<Self, Error> {
+        let listener = TcpListener::bind(bind_address)?;
+        let local_addr = listener.local_addr()?;
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _res_ignore = handle_connection(stream, state.is_ready());
+            }
+        });
+        Ok(Self { local_addr })
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, ready: bool) -> Result<(), Error> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    stream.write_all(response_for_request(&request_line, ready).as_bytes())?;
+    Ok(())
+}
+
+/// HTTP response for a request line (e.g. "GET /healthz HTTP/1.1")
+fn response_for_request(request_line: &str, ready: bool) -> String {
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(HEALTH_PATH)) if ready => ("200 OK", "ok"),
+        (Some("GET"), Some(HEALTH_PATH)) => ("503 Service Unavailable", "not ready"),
+        _ => ("404 Not Found", "not found"),
+    };
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    fn get_status_line(addr: &SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response.lines().next().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn test_health_readiness_flips() {
+        let state = Arc::new(HealthState::default());
+        let server = HealthServer::start("127.0.0.1:0", state.clone()).unwrap();
+        let addr = server.local_addr;
+        assert_eq!(
+            get_status_line(&addr, "/healthz"),
+            "HTTP/1.1 503 Service Unavailable"
+        );
+
+        // key only, no relay
+        state.update(true, false);
+        assert!(!state.is_ready());
+        assert_eq!(
+            get_status_line(&addr, "/healthz"),
+            "HTTP/1.1 503 Service Unavailable"
+        );
+
+        state.update(true, true);
+        assert!(state.is_ready());
+        assert_eq!(get_status_line(&addr, "/healthz"), "HTTP/1.1 200 OK");
+        assert_eq!(get_status_line(&addr, "/other"), "HTTP/1.1 404 Not Found");
+
+        // relay disconnected
+        state.update(true, false);
+        assert_eq!(
+            get_status_line(&addr, "/healthz"),
+            "HTTP/1.1 503 Service Unavailable"
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use crate::base::encrypt::Encrypt;
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extension (or full name) of encrypted secret key files
+const ENCRYPTED_KEY_FILE_SUFFIX: &str = ".ncrypt";
+/// Highest scrypt log2 rounds (from the file header) to derive a key with; the cost doubles with each
+const MAX_LOG2_ROUNDS: u8 = 20;
+
+/// An issue found by the key audit
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum KeyAuditIssue {
+    /// File could not be read or is not a valid encrypted key
+    InvalidFormat,
+    /// Encrypted with an older format version, should be re-saved
+    OutdatedEncryptionVersion(u8),
+    /// Encrypted with a newer, unknown format version
+    UnknownEncryptionVersion(u8),
+    /// Password stretching uses fewer rounds than the current default
+    WeakKeyDerivation(u8),
+    /// Password stretching uses too many rounds to be checked for an empty password
+    ExcessiveKeyDerivation(u8),
+    /// Encrypted with an empty password
+    EmptyPassword,
+}
+
+/// A finding of the key audit: an issue with a stored key file
+#[derive(Clone, Debug)]
+pub(crate) struct KeyAuditFinding {
+    pub file: PathBuf,
+    pub issue: KeyAuditIssue,
+}
+
+impl fmt::Display for KeyAuditIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyAuditIssue::InvalidFormat => write!(f, "invalid encrypted key file"),
+            KeyAuditIssue::OutdatedEncryptionVersion(v) => {
+                write!(f, "outdated encryption version {}, re-save it", v)
+            }
+            KeyAuditIssue::UnknownEncryptionVersion(v) => {
+                write!(f, "unknown encryption version {}", v)
+            }
+            KeyAuditIssue::WeakKeyDerivation(r) => write!(
+                f,
+                "weak password stretching (log2 rounds {}, default {}), re-save it",
+                r,
+                Encrypt::default_log2_rounds()
+            ),
+            KeyAuditIssue::ExcessiveKeyDerivation(r) => write!(
+                f,
+                "excessive password stretching (log2 rounds {}, max {}), not checked",
+                r, MAX_LOG2_ROUNDS
+            ),
+            KeyAuditIssue::EmptyPassword => write!(f, "encrypted without password"),
+        }
+    }
+}
+
+impl fmt::Display for KeyAuditFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.file.display(), self.issue)
+    }
+}
+
+/// Audit all encrypted key files in a folder.
+/// Does not require unlocking: works on the encryption header,
+/// only a decryption with empty password is attempted.
+/// Note: backups are not tracked by keystr, they are not audited.
+pub(crate) fn audit_keys(dir: &Path) -> Vec<KeyAuditFinding> {
+    let mut findings = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Err(_) => return findings,
+        Ok(e) => e,
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file()
+                && p.file_name()
+                    .and_then(|n| n.to_str())
+                    .map_or(false, |n| n.ends_with(ENCRYPTED_KEY_FILE_SUFFIX))
+        })
+        .collect();
+    files.sort();
+    for file in files {
+        for issue in audit_key_file(&file) {
+            findings.push(KeyAuditFinding {
+                file: file.clone(),
+                issue,
+            });
+        }
+    }
+    findings
+}
+
+fn audit_key_file(file: &Path) -> Vec<KeyAuditIssue> {
+    let encrypted = match fs::read_to_string(file)
+        .ok()
+        .and_then(|s| hex::decode(s.trim()).ok())
+    {
+        None => return vec![KeyAuditIssue::InvalidFormat],
+        Some(e) => e,
+    };
+    let header = match Encrypt::read_header(&encrypted) {
+        Err(_) => return vec![KeyAuditIssue::InvalidFormat],
+        Ok(h) => h,
+    };
+    if header.version > Encrypt::CURRENT_VERSION {
+        return vec![KeyAuditIssue::UnknownEncryptionVersion(header.version)];
+    }
+    let mut issues = Vec::new();
+    if header.version < Encrypt::CURRENT_VERSION {
+        issues.push(KeyAuditIssue::OutdatedEncryptionVersion(header.version));
+    }
+    if header.log2_rounds < Encrypt::default_log2_rounds() {
+        issues.push(KeyAuditIssue::WeakKeyDerivation(header.log2_rounds));
+    }
+    if header.log2_rounds > MAX_LOG2_ROUNDS {
+        // the rounds come from the file, don't derive a key with an unbounded cost
+        issues.push(KeyAuditIssue::ExcessiveKeyDerivation(header.log2_rounds));
+        return issues;
+    }
+    if header.version == Encrypt::CURRENT_VERSION && Encrypt::decrypt_key(&encrypted, "").is_ok() {
+        issues.push(KeyAuditIssue::EmptyPassword);
+    }
+    issues
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::encrypt::EncryptionAlgorithm;
+    use nostr::prelude::Keys;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("keystr-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_key(dir: &Path, name: &str, password: &str, log2_rounds: u8, version: u8) {
+        let sk = Keys::generate().secret_key().unwrap();
+        let mut data =
+            Encrypt::encrypt_key(&sk, password, log2_rounds, EncryptionAlgorithm::default())
+                .unwrap();
+        data[0] = version;
+        fs::write(dir.join(name), hex::encode(data)).unwrap();
+    }
+
+    #[test]
+    fn test_audit_keys_mixed() {
+        let dir = test_dir("audit");
+        write_key(&dir, "good.ncrypt", "password", 13, 2);
+        write_key(&dir, "old.ncrypt", "password", 13, 1);
+        write_key(&dir, "new.ncrypt", "password", 13, 3);
+        write_key(&dir, "weak.ncrypt", "password", 10, 2);
+        write_key(&dir, "nopw.ncrypt", "", 13, 2);
+        write_key(&dir, "costly.ncrypt", "", 13, 2);
+        // rounds raised in the header only, the audit must not derive with them
+        let mut costly =
+            hex::decode(fs::read_to_string(dir.join("costly.ncrypt")).unwrap()).unwrap();
+        costly[1] = 40;
+        fs::write(dir.join("costly.ncrypt"), hex::encode(costly)).unwrap();
+        fs::write(dir.join("bad.ncrypt"), "not hex").unwrap();
+        // not a key file, ignored
+        fs::write(dir.join("npub"), "npub1...").unwrap();
+
+        let findings = audit_keys(&dir);
+        let issues: Vec<(String, KeyAuditIssue)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.file.file_name().unwrap().to_str().unwrap().to_string(),
+                    f.issue.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                ("bad.ncrypt".to_string(), KeyAuditIssue::InvalidFormat),
+                (
+                    "costly.ncrypt".to_string(),
+                    KeyAuditIssue::ExcessiveKeyDerivation(40)
+                ),
+                (
+                    "new.ncrypt".to_string(),
+                    KeyAuditIssue::UnknownEncryptionVersion(3)
+                ),
+                ("nopw.ncrypt".to_string(), KeyAuditIssue::EmptyPassword),
+                (
+                    "old.ncrypt".to_string(),
+                    KeyAuditIssue::OutdatedEncryptionVersion(1)
+                ),
+                (
+                    "weak.ncrypt".to_string(),
+                    KeyAuditIssue::WeakKeyDerivation(10)
+                ),
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_audit_keys_no_dir() {
+        let dir = std::env::temp_dir().join("keystr-test-nonexistent-dir");
+        assert!(audit_keys(&dir).is_empty());
+    }
+}
@@ -1,84 +1,261 @@
-use crate::base::encrypt::Encrypt;
+use crate::base::client_export::import_client_export;
+use crate::base::encrypt::{Encrypt, EncryptionAlgorithm};
 use crate::base::error::Error;
+use crate::base::input::{require_input, require_password};
+use crate::base::relay_url::{validate_relay_url, RelayMarker};
+use crate::base::scrub::{self, Scrub};
+use crate::base::secret_string::SecretString;
 use crate::base::storage::Storage;
-use crate::model::security_settings::{SecurityLevel, SecuritySettings};
+use crate::model::display_settings::DisplayFormat;
+use crate::model::security_settings::{PersistStatus, SecurityLevel, SecuritySettings};
 use crate::model::status_messages::StatusMessages;
 
-use nostr::prelude::{FromPkStr, FromSkStr, Keys, SecretKey, ToBech32, XOnlyPublicKey, SECP256K1};
+use nostr::hashes::{sha256, Hash};
+use nostr::prelude::{
+    Event, EventBuilder, FromPkStr, FromSkStr, Keys, Kind, SecretKey, Tag, ToBech32,
+    XOnlyPublicKey, SECP256K1,
+};
 use nostr::secp256k1::schnorr::Signature;
+use nostr::Profile;
+use serde::Serialize;
 
 use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use zeroize::Zeroize;
 
 /// Model for KeyStore part
 #[readonly::make]
 pub(crate) struct Keystore {
     #[readonly]
     has_unsaved_change: bool,
-    keys: Option<Keys>,
+    /// Set if the current key has been saved to or loaded from storage; otherwise it is lost on exit
+    #[readonly]
+    persisted: bool,
+    /// Secret material, shared with the panic hook, to be wiped on panic
+    secrets: Arc<Mutex<KeySecrets>>,
     encrypted_secret_key: Option<Vec<u8>>,
     pub hide_secret_key: bool,
     /// Input for public key import
     pub public_key_input: String,
-    /// Input for secret key import
-    pub secret_key_input: String,
+    /// Input for secret key import, wiped when cleared
+    pub secret_key_input: SecretString,
     /// Input for BIP39 mnemonic
     pub mnemonic_input: String,
+    /// Input for a password-protected key exported by another client, wiped when cleared
+    pub client_export_input: SecretString,
+    /// Input for the password of the client export
+    pub client_export_password_input: String,
     /// Input for encryption password, for decrypt
     pub decrypt_password_input: String,
     /// Input for encryption password, for save
     pub save_password_input: String,
     /// Input for repeat encryption password, for save
     pub save_repeat_password_input: String,
+    /// Input for encryption password, for verifying the saved key
+    pub verify_password_input: String,
+    /// Input for the current encryption password, for changing it
+    pub old_password_input: String,
+    /// Input for optional NIP-05 identifier, for the public summary
+    pub nip05_input: String,
+    /// Input for relay list to publish, comma-separated, each with optional read/write marker
+    pub relay_list_input: String,
+    /// Consecutive wrong unlock passwords, for backoff
+    unlock_attempts: UnlockAttempts,
+}
+
+/// Number of wrong unlock passwords tolerated without delay
+const UNLOCK_FREE_ATTEMPTS: u32 = 3;
+/// Upper limit of the unlock delay; reaching it is effectively a temporary lockout
+const UNLOCK_MAX_DELAY_SECS: u64 = 300;
+
+/// Tracks consecutive failed unlock attempts, to slow down password brute-forcing.
+/// After a few free attempts the required wait doubles with each failure, up to a maximum.
+#[derive(Default)]
+struct UnlockAttempts {
+    failed_count: u32,
+    last_failed: Option<Instant>,
+}
+
+impl UnlockAttempts {
+    /// Required wait after the last failure, for the current failure count
+    fn delay(&self) -> Duration {
+        if self.failed_count < UNLOCK_FREE_ATTEMPTS {
+            return Duration::ZERO;
+        }
+        let exponent = (self.failed_count - UNLOCK_FREE_ATTEMPTS).min(16);
+        Duration::from_secs((1u64 << exponent).min(UNLOCK_MAX_DELAY_SECS))
+    }
+
+    /// Time left until the next attempt is allowed, zero if allowed now
+    fn remaining_wait(&self, now: Instant) -> Duration {
+        match self.last_failed {
+            None => Duration::ZERO,
+            Some(last) => self
+                .delay()
+                .saturating_sub(now.saturating_duration_since(last)),
+        }
+    }
+
+    fn record_failure(&mut self, now: Instant) {
+        self.failed_count = self.failed_count.saturating_add(1);
+        self.last_failed = Some(now);
+    }
+
+    fn reset(&mut self) {
+        self.failed_count = 0;
+        self.last_failed = None;
+    }
+}
+
+/// Non-secret summary of the own key, for sharing and verification
+#[derive(Debug, Serialize)]
+pub(crate) struct PublicSummary {
+    pub pubkey_hex: String,
+    pub npub: String,
+    pub nprofile: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nip05: Option<String>,
+    /// Short fingerprint, from the SHA256 hash of the public key
+    pub fingerprint: String,
+}
+
+impl PublicSummary {
+    fn new(pubkey: &XOnlyPublicKey, nip05: Option<String>) -> Result<Self, Error> {
+        let hash = sha256::Hash::hash(&pubkey.serialize());
+        let fingerprint = hash[..8]
+            .chunks(2)
+            .map(hex::encode)
+            .collect::<Vec<String>>()
+            .join(":");
+        Ok(Self {
+            pubkey_hex: pubkey.to_string(),
+            npub: pubkey.to_bech32()?,
+            nprofile: Profile::new(*pubkey, Vec::<String>::new()).to_bech32()?,
+            nip05,
+            fingerprint,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "pubkey: {}\nnpub: {}\nnprofile: {}\n",
+            self.pubkey_hex, self.npub, self.nprofile
+        );
+        if let Some(nip05) = &self.nip05 {
+            text.push_str(&format!("nip05: {}\n", nip05));
+        }
+        text.push_str(&format!("fingerprint: {}", self.fingerprint));
+        text
+    }
+}
+
+/// Result of a signing benchmark
+#[derive(Debug)]
+pub(crate) struct BenchmarkResult {
+    pub iterations: u32,
+    pub total: Duration,
+}
+
+impl BenchmarkResult {
+    /// Average time of one signature
+    pub fn average(&self) -> Duration {
+        self.total / self.iterations.max(1)
+    }
+
+    /// Signatures per second
+    pub fn rate(&self) -> f64 {
+        self.iterations as f64 / self.total.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Secret parts of the Keystore
+#[derive(Default)]
+struct KeySecrets {
+    keys: Option<Keys>,
+    /// BIP39 seed, set only if keys were imported from a mnemonic; used for child key derivation.
+    /// Kept in memory only, it is not saved.
+    mnemonic_seed: Option<[u8; 64]>,
 }
 
 impl Keystore {
     pub fn new() -> Self {
+        let secrets = Arc::new(Mutex::new(KeySecrets::default()));
+        scrub::register(secrets.clone());
         Keystore {
             has_unsaved_change: false,
-            keys: None,
+            persisted: false,
+            secrets,
             encrypted_secret_key: None,
             hide_secret_key: true,
             public_key_input: String::new(),
-            secret_key_input: String::new(),
+            secret_key_input: SecretString::default(),
             mnemonic_input: String::new(),
+            client_export_input: SecretString::default(),
+            client_export_password_input: String::new(),
             decrypt_password_input: String::new(),
             save_password_input: String::new(),
             save_repeat_password_input: String::new(),
+            verify_password_input: String::new(),
+            old_password_input: String::new(),
+            nip05_input: String::new(),
+            relay_list_input: String::new(),
+            unlock_attempts: UnlockAttempts::default(),
         }
     }
 
     /// Action to clear existing keys
     pub fn clear(&mut self) {
-        self.keys = None;
+        self.secrets().scrub();
         self.encrypted_secret_key = None;
         self.has_unsaved_change = false;
+        self.persisted = false;
     }
 
     /// Generate new random keys
     pub fn generate(&mut self) {
-        self.keys = Some(Keys::generate());
+        self.secrets().set_keys(Keys::generate(), None);
         self.encrypted_secret_key = None;
         self.has_unsaved_change = true;
+        self.persisted = false;
+    }
+
+    /// Use already generated keys (e.g. by vanity search)
+    pub fn import_generated(&mut self, keys: Keys) {
+        self.secrets().set_keys(keys, None);
+        self.encrypted_secret_key = None;
+        self.has_unsaved_change = true;
+        self.persisted = false;
     }
 
     /// Import public key only, in 'npub' bech32 or hex format. Signing will not be possible.
     pub fn import_public_key(&mut self, public_key_str: &str) -> Result<(), Error> {
         self.clear();
-        self.keys = Some(Keys::from_pk_str(public_key_str)?);
+        self.secrets()
+            .set_keys(Keys::from_pk_str(public_key_str)?, None);
         self.has_unsaved_change = true;
         Ok(())
     }
 
     /// Warning: Security-sensitive method!
-    /// Import secret key, in 'nsec' bech32 or hex format (pubkey is derived from it)
+    /// Import secret key, in 'nsec' bech32 or hex format (pubkey is derived from it).
+    /// Hex may be 0x-prefixed, in any case, and contain spaces.
     pub fn import_secret_key(
         &mut self,
         secret_key_str: &str,
         is_changed: bool,
     ) -> Result<(), Error> {
         self.clear();
-        self.keys = Some(Keys::from_sk_str(secret_key_str)?);
+        self.secrets()
+            .set_keys(parse_secret_key(secret_key_str)?, None);
         self.has_unsaved_change = is_changed;
+        self.persisted = !is_changed;
         Ok(())
     }
 
@@ -92,9 +269,23 @@ impl Keystore {
         self.encrypted_secret_key =
             Some(hex::decode(encrypted_key_str).map_err(|_e| Error::KeyInvalidEncrypted)?);
         self.has_unsaved_change = is_changed;
+        self.persisted = !is_changed;
         Ok(())
     }
 
+    /// Warning: Security-sensitive method!
+    /// Import a password-protected secret key exported by another client (see `import_client_export`).
+    /// It is recommend to zeroize() the password after use.
+    pub fn import_client_export(
+        &mut self,
+        export: &str,
+        password: &str,
+        is_changed: bool,
+    ) -> Result<(), Error> {
+        let sk = import_client_export(export, password)?;
+        self.import_secret_key(&sk.to_bech32()?, is_changed)
+    }
+
     /// Try to decrypt the already loaded encrypted key using the decryption password
     /// It is recommend to zeroize() the password after use.
     pub fn decrypt_secret_key(&mut self, password: &str) -> Result<(), Error> {
@@ -103,7 +294,50 @@ impl Keystore {
             Some(d) => d,
         };
         let sk = Encrypt::decrypt_key(&sk_bytes, &password)?;
-        self.import_secret_key(&sk.to_bech32()?, false)
+        // keep the encrypted key, so that it can be locked again
+        let sk_bytes = sk_bytes.clone();
+        self.import_secret_key(&sk.to_bech32()?, false)?;
+        self.encrypted_secret_key = Some(sk_bytes);
+        Ok(())
+    }
+
+    /// Lock the unlocked key: drop the secret key, keep the public key and the encrypted secret key
+    pub fn lock(&mut self) -> Result<(), Error> {
+        if self.encrypted_secret_key.is_none() {
+            return Err(Error::KeyFreshUnlockNotSaved);
+        }
+        let public_key = self.get_public_key()?;
+        self.secrets()
+            .set_keys(Keys::from_public_key(public_key), None);
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Decrypt the encrypted secret key with the password input, for a single signature.
+    /// The loaded keys stay locked, the password input is cleared.
+    pub fn fresh_unlock_signer(&mut self) -> Result<KeySigner, Error> {
+        let now = Instant::now();
+        self.check_unlock_throttle(now)?;
+        let sk_bytes = match &self.encrypted_secret_key {
+            None => return Err(Error::KeyFreshUnlockNotSaved),
+            Some(d) => d,
+        };
+        let res = Encrypt::decrypt_key(sk_bytes, &self.decrypt_password_input);
+        let password_empty = self.decrypt_password_input.is_empty();
+        self.decrypt_password_input.zeroize();
+        match res {
+            Err(_) if password_empty => Err(Error::KeyFreshUnlockRequired),
+            Err(e) => {
+                self.unlock_attempts.record_failure(now);
+                Err(e)
+            }
+            Ok(sk) => {
+                self.unlock_attempts.reset();
+                Ok(KeySigner {
+                    keys: Keys::new(sk),
+                })
+            }
+        }
     }
 
     /// Warning: Security-sensitive method!
@@ -112,20 +346,40 @@ impl Keystore {
         let mnemonic = bip39::Mnemonic::parse(mnemonic)?;
         let password = "".to_string();
         let seed = mnemonic.to_seed(password);
-        let account = 0;
-        let derivation_path = format!("m/44'/1237'/{account}'/0/0");
-        let child_xprv = bip32::XPrv::derive_from_path(&seed, &derivation_path.parse()?)?;
-        let private_key = child_xprv.private_key();
-        let secret_key = SecretKey::from_slice(&private_key.to_bytes())?;
+        let secret_key = Self::derive_from_seed(&seed, 0)?;
         self.clear();
-        self.keys = Some(Keys::new(secret_key));
+        self.secrets().set_keys(Keys::new(secret_key), Some(seed));
         self.has_unsaved_change = is_changed;
+        self.persisted = !is_changed;
         Ok(())
     }
 
     /// Warning: Security-sensitive method!
-    /// Save secret key to file.
-    pub fn save_encrypted_secret_key(&self) -> Result<(), Error> {
+    /// Derive secret key from BIP39 seed, at the given NIP-06 account index
+    fn derive_from_seed(seed: &[u8; 64], account: u32) -> Result<SecretKey, Error> {
+        let derivation_path = format!("m/44'/1237'/{account}'/0/0");
+        let child_xprv = bip32::XPrv::derive_from_path(seed, &derivation_path.parse()?)?;
+        let private_key = child_xprv.private_key();
+        Ok(SecretKey::from_slice(&private_key.to_bytes())?)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Derive a child key at the given NIP-06 account index, for app isolation.
+    /// Works only if keys were imported from a mnemonic in this session. Account 0 yields the main key.
+    pub fn derive_child(&self, account: u32) -> Result<KeySigner, Error> {
+        let secrets = self.secrets();
+        let seed = match &secrets.mnemonic_seed {
+            None => return Err(Error::KeyNotMnemonicBacked),
+            Some(s) => s,
+        };
+        Ok(KeySigner {
+            keys: Keys::new(Self::derive_from_seed(seed, account)?),
+        })
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Save secret key to file, encrypted with the given algorithm.
+    pub fn save_encrypted_secret_key(&self, algorithm: EncryptionAlgorithm) -> Result<(), Error> {
         let sk = self.get_secret_key()?;
 
         if self.save_password_input != self.save_repeat_password_input {
@@ -134,7 +388,7 @@ impl Keystore {
         let password = &self.save_password_input;
 
         Storage::check_create_folder()?;
-        let data = Encrypt::encrypt_key(&sk, &password, Encrypt::default_log2_rounds())?;
+        let data = Encrypt::encrypt_key(&sk, password, Encrypt::default_log2_rounds(), algorithm)?;
         let hex_string = hex::encode(data);
         let path = Storage::encrypted_secret_key_file();
         // create empty file
@@ -163,7 +417,7 @@ impl Keystore {
     /// Warning: Security-sensitive method!
     /// Save public/secret key to file(s).
     /// Returns if secret key has been saved
-    pub fn save_keys(&self) -> Result<bool, Error> {
+    pub fn save_keys(&self, algorithm: EncryptionAlgorithm) -> Result<bool, Error> {
         if !self.has_unsaved_change {
             return Err(Error::KeyNoChangeToSave);
         }
@@ -171,7 +425,7 @@ impl Keystore {
         self.save_public_key()?;
         // save secret key if set
         if self.is_secret_key_set() {
-            self.save_encrypted_secret_key()?;
+            self.save_encrypted_secret_key(algorithm)?;
             Ok(true)
         } else {
             Ok(false)
@@ -192,6 +446,48 @@ impl Keystore {
     pub fn load_public_key(&mut self) -> Result<(), Error> {
         let pk_string = fs::read_to_string(Storage::public_key_file())?;
         self.import_public_key(&pk_string)?;
+        self.persisted = true;
+        Ok(())
+    }
+
+    /// Public key of the saved key, read from the saved public key file.
+    /// The secret key is not decrypted, no password is needed; the loaded keys are not changed.
+    pub fn peek_saved_pubkey(security: &SecuritySettings) -> Result<XOnlyPublicKey, Error> {
+        read_public_key_file(&Storage::public_key_file(), security)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Check that the saved encrypted key file decrypts with the password.
+    /// Done in memory only, the loaded keys are not changed.
+    pub fn verify_saved_key(
+        &self,
+        password: &str,
+        security: &SecuritySettings,
+    ) -> Result<(), Error> {
+        verify_encrypted_key_file(&Storage::encrypted_secret_key_file(), password, security)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Change the encryption password of the saved key: decrypt with the old password,
+    /// encrypt with the new one, and replace the file atomically.
+    /// The loaded encrypted key (if any) is updated as well.
+    pub fn change_password(
+        &mut self,
+        old: &str,
+        new: &str,
+        new_confirm: &str,
+        security: &SecuritySettings,
+    ) -> Result<(), Error> {
+        let data = change_key_file_password(
+            &Storage::encrypted_secret_key_file(),
+            old,
+            new,
+            new_confirm,
+            security,
+        )?;
+        if self.encrypted_secret_key.is_some() {
+            self.encrypted_secret_key = Some(data);
+        }
         Ok(())
     }
 
@@ -209,25 +505,22 @@ impl Keystore {
     }
 
     /// Warning: Security-sensitive method!
-    ///.Action to save secret key to file
+    ///.Action to save secret key to file, returns true if saved
     pub fn save_action(
         &mut self,
         security_settings: &SecuritySettings,
         status: &mut StatusMessages,
-    ) {
-        let res = if !security_settings.allows_persist() {
-            Err(Error::KeySaveNotAllowed)
-        } else {
-            if security_settings.security_level == SecurityLevel::PersistMandatoryPassword
-                && self.save_password_input.is_empty()
-            {
-                Err(Error::KeyEncryptionPasswordMissing)
-            } else {
-                self.save_keys()
-            }
+    ) -> bool {
+        let res = match security_settings.persist_status(!self.save_password_input.is_empty()) {
+            PersistStatus::BlockedByLevel => Err(Error::KeySaveNotAllowed),
+            PersistStatus::NoPassword => Err(Error::KeyEncryptionPasswordMissing),
+            PersistStatus::Allowed => self.save_keys(security_settings.encryption_algorithm),
         };
         match res {
-            Err(e) => status.set_error_err(&e),
+            Err(e) => {
+                status.set_error_err(&e);
+                false
+            }
             Ok(ss) => {
                 if ss {
                     // Clear password input
@@ -237,6 +530,8 @@ impl Keystore {
                 } else {
                     status.set("Public key persisted to storage");
                 }
+                self.persisted = true;
+                true
             }
         }
     }
@@ -260,47 +555,120 @@ impl Keystore {
         }
     }
 
+    /// Warning: Security-sensitive method!
+    /// Action to verify that the saved key can be decrypted with the entered password
+    pub fn verify_saved_key_action(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        let res = self.verify_saved_key(&self.verify_password_input, security_settings);
+        self.verify_password_input.zeroize();
+        match res {
+            Err(e) => status.set_error(&format!("Backup verification failed, {}", e)),
+            Ok(_) => status.set("Backup verified, the saved key decrypts with this password"),
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to change the password of the saved key, from the old and new password inputs
+    pub fn change_password_action(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        let old = self.old_password_input.clone();
+        let new = self.save_password_input.clone();
+        let confirm = self.save_repeat_password_input.clone();
+        let res = self.change_password(&old, &new, &confirm, security_settings);
+        for mut s in [old, new, confirm] {
+            s.zeroize();
+        }
+        match res {
+            Err(e) => status.set_error(&format!("Password not changed, {}", e)),
+            Ok(_) => {
+                self.old_password_input.zeroize();
+                self.save_password_input.zeroize();
+                self.save_repeat_password_input.zeroize();
+                status.set("Password of the saved key changed");
+            }
+        }
+    }
+
+    /// Error if unlock attempts are throttled after wrong passwords
+    fn check_unlock_throttle(&self, now: Instant) -> Result<(), Error> {
+        let wait = self.unlock_attempts.remaining_wait(now);
+        if !wait.is_zero() {
+            // round up, not to show 0 seconds
+            let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            return Err(Error::KeyUnlockThrottled(secs));
+        }
+        Ok(())
+    }
+
     pub fn unlock_secret_key_action(
         &mut self,
         security_settings: &SecuritySettings,
         status: &mut StatusMessages,
     ) {
+        let now = Instant::now();
+        if let Err(e) = self.check_unlock_throttle(now) {
+            status.set_error_err(&e);
+            return;
+        }
         // check if password is set if needed
-        let res = if security_settings.security_level == SecurityLevel::PersistMandatoryPassword
-            && self.decrypt_password_input.is_empty()
-        {
-            Err(Error::KeyEncryptionPasswordMissing)
+        let res = if security_settings.security_level == SecurityLevel::PersistMandatoryPassword {
+            require_password(&self.decrypt_password_input, "Password")
         } else {
-            self.decrypt_secret_key(&self.decrypt_password_input.clone())
-        };
+            Ok(())
+        }
+        .and_then(|_| self.decrypt_secret_key(&self.decrypt_password_input.clone()));
         match res {
-            Err(e) => status.set(&format!(
-                "Could not decrypt secret key, check password! ({})",
-                e
-            )),
+            Err(e) => {
+                if self.encrypted_secret_key.is_some() {
+                    self.unlock_attempts.record_failure(now);
+                }
+                status.set(&format!(
+                    "Could not decrypt secret key, check password! ({})",
+                    e
+                ))
+            }
             Ok(_) => {
                 // cleanup
                 self.decrypt_password_input = "".to_string();
+                self.unlock_attempts.reset();
                 status.set("Secret key decrypted")
             }
         }
     }
 
+    /// Import public key from the input, in 'npub' bech32 or hex format
+    pub fn import_public_key_action(&mut self, status: &mut StatusMessages) {
+        let input = std::mem::take(&mut self.public_key_input);
+        match require_input(&input, "Public key").and_then(|pk| self.import_public_key(pk)) {
+            Err(e) => status.set_error(&e.to_string()),
+            Ok(_) => status.set("Public key imported"),
+        };
+    }
+
     /// Warning: Security-sensitive method!
     /// Import secret key, in 'nsec' bech32 or hex format (pubkey is derived from it)
     pub fn import_secret_key_action(&mut self, status: &mut StatusMessages) {
-        match self.import_secret_key(&self.secret_key_input.clone(), true) {
+        // taken out, so the input is cleared; it is wiped when dropped
+        let input = std::mem::take(&mut self.secret_key_input);
+        match require_input(input.as_str(), "Secret key")
+            .and_then(|sk| self.import_secret_key(sk, true))
+        {
             Err(e) => status.set_error(&format!("Error importing, {}", e.to_string())),
             Ok(_) => status.set("Secret key imported"),
         };
-        // cleanup
-        self.secret_key_input = String::new();
     }
 
     /// Warning: Security-sensitive method!
     /// Import secret key from BIP39 mnemonic
     pub fn import_mnemonic_action(&mut self, status: &mut StatusMessages) {
-        match self.import_mnemonic(&self.mnemonic_input.clone(), true) {
+        let mnemonic = self.mnemonic_input.clone();
+        match require_input(&mnemonic, "Mnemonic").and_then(|m| self.import_mnemonic(m, true)) {
             Err(e) => {
                 status.set_error(&format!("Error importing from mnemonic, {}", e.to_string()))
             }
@@ -310,14 +678,29 @@ impl Keystore {
         self.mnemonic_input = String::new();
     }
 
+    /// Warning: Security-sensitive method!
+    /// Import secret key from a password-protected export of another client
+    pub fn import_client_export_action(&mut self, status: &mut StatusMessages) {
+        // taken out, so the inputs are cleared; the export is wiped when dropped
+        let export = std::mem::take(&mut self.client_export_input);
+        let mut password = std::mem::take(&mut self.client_export_password_input);
+        match require_input(export.as_str(), "Client export")
+            .and_then(|export| self.import_client_export(export, &password, true))
+        {
+            Err(e) => status.set_error(&format!("Error importing client export, {}", e)),
+            Ok(_) => status.set("Secret key imported from client export"),
+        };
+        password.zeroize();
+    }
+
     pub fn get_signer(&self) -> Result<KeySigner, Error> {
         Ok(KeySigner {
-            keys: self.get_keys()?.clone(),
+            keys: self.get_keys()?,
         })
     }
 
     pub fn keys_is_set(&self) -> bool {
-        self.keys.is_some()
+        self.secrets().keys.is_some()
     }
 
     #[cfg(test)]
@@ -329,15 +712,33 @@ impl Keystore {
         self.get_secret_key().is_ok()
     }
 
+    /// Returns true if a key is set, but it has not been persisted (it will be lost on exit)
+    pub fn is_ephemeral(&self) -> bool {
+        self.keys_is_set() && !self.persisted
+    }
+
+    /// Returns true if a secret key is set, but it has not been persisted;
+    /// replacing it would lose it forever
+    pub fn has_unsaved_secret_key(&self) -> bool {
+        self.is_secret_key_set() && !self.persisted
+    }
+
     pub fn is_encrypted_secret_key_set(&self) -> bool {
         self.encrypted_secret_key.is_some()
     }
 
     /// Warning: Security-sensitive method!
-    pub(crate) fn get_keys(&self) -> Result<&Keys, Error> {
-        match &self.keys {
+    pub(crate) fn get_keys(&self) -> Result<Keys, Error> {
+        match &self.secrets().keys {
             None => Err(Error::KeyNotSet),
-            Some(kk) => Ok(kk),
+            Some(kk) => Ok(kk.clone()),
+        }
+    }
+
+    fn secrets(&self) -> MutexGuard<'_, KeySecrets> {
+        match self.secrets.lock() {
+            Ok(l) => l,
+            Err(poisoned) => poisoned.into_inner(),
         }
     }
 
@@ -350,6 +751,7 @@ impl Keystore {
         Ok(self.get_keys()?.secret_key()?)
     }
 
+    #[cfg(test)]
     pub fn get_npub(&self) -> String {
         match self.get_public_key() {
             Err(_e) => "(not set)".to_string(),
@@ -360,6 +762,69 @@ impl Keystore {
         }
     }
 
+    /// Summary of the public key, never contains secret material
+    pub fn public_summary(&self) -> Result<PublicSummary, Error> {
+        let nip05 = match self.nip05_input.trim() {
+            "" => None,
+            s => Some(s.to_string()),
+        };
+        PublicSummary::new(&self.get_public_key()?, nip05)
+    }
+
+    /// Sign the given number of dummy hashes (at least one) with the loaded key, and measure the time
+    pub fn benchmark_sign(&self, iterations: u32) -> Result<BenchmarkResult, Error> {
+        let signer = self.get_signer()?;
+        let iterations = iterations.max(1);
+        let start = Instant::now();
+        for i in 0..iterations {
+            let hash = sha256::Hash::hash(&i.to_be_bytes());
+            signer.sign(hash[..].to_vec())?;
+        }
+        Ok(BenchmarkResult {
+            iterations,
+            total: start.elapsed(),
+        })
+    }
+
+    /// Build a NIP-65 relay list event (kind 10002), with read/write markers in `r` tags,
+    /// signed with the own key
+    pub fn build_relay_list_event(&self, relays: &[(String, RelayMarker)]) -> Result<Event, Error> {
+        let tags = relays
+            .iter()
+            .map(|(url, marker)| {
+                Ok(Tag::RelayMetadata(
+                    validate_relay_url(url)?,
+                    marker.tag_value(),
+                ))
+            })
+            .collect::<Result<Vec<Tag>, Error>>()?;
+        let keys = self.get_keys()?;
+        Ok(EventBuilder::new(Kind::RelayList, "", &tags).to_event(&keys)?)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Build an encrypted direct message event (NIP-04, kind 4) to the recipient,
+    /// encrypted and signed with the own key
+    pub fn build_dm_event(
+        &self,
+        recipient: &XOnlyPublicKey,
+        plaintext: &str,
+    ) -> Result<Event, Error> {
+        let keys = self.get_keys()?;
+        Ok(
+            EventBuilder::new_encrypted_direct_msg(&keys, *recipient, plaintext)?
+                .to_event(&keys)?,
+        )
+    }
+
+    /// Return public key in the given display format
+    pub fn get_public_key_display(&self, format: DisplayFormat) -> String {
+        match self.get_public_key() {
+            Err(_e) => "(not set)".to_string(),
+            Ok(pk) => format.format_public_key(&pk),
+        }
+    }
+
     /// Warning: Security-sensitive method!
     /// Return secret key as nsec string, if set, and if Hide option is not active.
     pub fn get_nsec(&self) -> String {
@@ -379,6 +844,149 @@ impl Keystore {
     }
 }
 
+impl KeySecrets {
+    fn set_keys(&mut self, keys: Keys, mnemonic_seed: Option<[u8; 64]>) {
+        self.scrub();
+        self.keys = Some(keys);
+        self.mnemonic_seed = mnemonic_seed;
+    }
+}
+
+impl Scrub for KeySecrets {
+    /// Wipe secret material. The key itself cannot be zeroized in place, it is dropped.
+    fn scrub(&mut self) {
+        if let Some(seed) = &mut self.mnemonic_seed {
+            seed.zeroize();
+        }
+        self.mnemonic_seed = None;
+        self.keys = None;
+    }
+}
+
+/// Read the public key from a public key file (npub or hex)
+fn read_public_key_file(path: &Path, security: &SecuritySettings) -> Result<XOnlyPublicKey, Error> {
+    if !security.allows_persist() {
+        return Err(Error::KeyLoadNotAllowed);
+    }
+    let pk_string = fs::read_to_string(path)?;
+    Ok(Keys::from_pk_str(pk_string.trim())?.public_key())
+}
+
+/// Warning: Security-sensitive method!
+/// Read an encrypted key file and decrypt it, discarding the result
+fn verify_encrypted_key_file(
+    path: &Path,
+    password: &str,
+    security: &SecuritySettings,
+) -> Result<(), Error> {
+    if !security.allows_persist() {
+        return Err(Error::KeyLoadNotAllowed);
+    }
+    if security.security_level == SecurityLevel::PersistMandatoryPassword && password.is_empty() {
+        return Err(Error::KeyEncryptionPasswordMissing);
+    }
+    let sk_hex = fs::read_to_string(path)?;
+    let data = hex::decode(sk_hex.trim()).map_err(|_e| Error::KeyInvalidEncrypted)?;
+    let _sk = Encrypt::decrypt_key(&data, password)?;
+    Ok(())
+}
+
+/// Warning: Security-sensitive method!
+/// Re-encrypt an encrypted key file with a new password; returns the new encrypted data
+fn change_key_file_password(
+    path: &Path,
+    old: &str,
+    new: &str,
+    new_confirm: &str,
+    security: &SecuritySettings,
+) -> Result<Vec<u8>, Error> {
+    if !security.allows_persist() {
+        return Err(Error::KeySaveNotAllowed);
+    }
+    if new != new_confirm {
+        return Err(Error::KeyEncryptionPasswordMismatch);
+    }
+    if security.persist_status(!new.is_empty()) == PersistStatus::NoPassword {
+        return Err(Error::KeyEncryptionPasswordMissing);
+    }
+    let sk_hex = fs::read_to_string(path)?;
+    let old_data = hex::decode(sk_hex.trim()).map_err(|_e| Error::KeyInvalidEncrypted)?;
+    let sk = Encrypt::decrypt_key(&old_data, old)?;
+    let data = Encrypt::encrypt_key(
+        &sk,
+        new,
+        Encrypt::default_log2_rounds(),
+        security.encryption_algorithm,
+    )?;
+    write_key_file_atomic(path, &hex::encode(&data))?;
+    Ok(data)
+}
+
+/// Write a key file through a temporary file and rename, so it is never left half-written
+fn write_key_file_atomic(path: &Path, contents: &str) -> Result<(), Error> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = Path::new(&tmp_path);
+    fs::write(tmp_path, "")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(tmp_path, fs::Permissions::from_mode(0o600))?;
+    }
+    fs::write(tmp_path, contents)?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+/// Warning: Security-sensitive method!
+/// Extract the secret key value from a labeled export, as written by some CLI tools:
+/// lines like `sec: <hex>` or `nsec: <nsec>`, other lines (e.g. `pub:`) are ignored.
+/// None if the input is not labeled.
+fn labeled_secret(input: &str) -> Option<Result<&str, Error>> {
+    if !input.contains(':') {
+        return None;
+    }
+    let secrets: Vec<&str> = input
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(label, _)| {
+            let label = label.trim();
+            label.eq_ignore_ascii_case("sec") || label.eq_ignore_ascii_case("nsec")
+        })
+        .map(|(_, value)| value.trim())
+        .collect();
+    Some(match secrets[..] {
+        [secret] => Ok(secret),
+        [] => Err(Error::KeyLabeledSecretMissing),
+        _ => Err(Error::KeyLabeledSecretAmbiguous),
+    })
+}
+
+/// Warning: Security-sensitive method!
+/// Parse a secret key: hex (whitespace and a leading '0x' are tolerated, any case), or 'nsec' bech32.
+/// Labeled exports (lines like `sec: <hex>` / `pub: <hex>`) are also accepted.
+fn parse_secret_key(secret_key_str: &str) -> Result<Keys, Error> {
+    let secret_key_str = labeled_secret(secret_key_str).unwrap_or(Ok(secret_key_str))?;
+    let mut compact: String = secret_key_str
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let hex_part = compact
+        .strip_prefix("0x")
+        .or_else(|| compact.strip_prefix("0X"))
+        .unwrap_or(&compact);
+    let res = if hex_part.len() == 64 && hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
+        let mut lower = hex_part.to_ascii_lowercase();
+        let res = SecretKey::from_str(&lower).map(Keys::new);
+        lower.zeroize();
+        res.map_err(|_| Error::KeyInvalidSecretFormat)
+    } else {
+        Keys::from_sk_str(&compact).map_err(|_| Error::KeyInvalidSecretFormat)
+    };
+    compact.zeroize();
+    res
+}
+
 /// Can sign a hash with its own secret key
 #[derive(Clone)]
 pub(crate) struct KeySigner {
@@ -390,16 +998,47 @@ impl KeySigner {
         self.keys.public_key()
     }
 
+    /// Returns true if the secret key is available (set and unlocked), so signing is possible
+    pub fn can_sign(&self) -> bool {
+        self.keys.secret_key().is_ok()
+    }
+
     /// Perform signing of a hash using loaded secret key
     pub fn sign(&self, hash: Vec<u8>) -> Result<Signature, Error> {
         let kp = self.keys.key_pair()?;
         Ok(SECP256K1.sign_schnorr(&nostr::secp256k1::Message::from_slice(&hash)?, &kp))
     }
+
+    /// Check that signing works: sign a dummy hash, and verify the signature with the public key
+    pub fn self_check(&self) -> Result<(), Error> {
+        let hash = [0x5a_u8; 32];
+        let signature = self
+            .sign(hash.to_vec())
+            .map_err(|_| Error::KeySelfCheckFailed)?;
+        let message = nostr::secp256k1::Message::from_slice(&hash)?;
+        SECP256K1
+            .verify_schnorr(&signature, &message, &self.get_public_key())
+            .map_err(|_| Error::KeySelfCheckFailed)
+    }
+}
+
+impl Scrub for KeySigner {
+    /// Drop the secret key, keep only the public key
+    fn scrub(&mut self) {
+        self.keys = Keys::from_public_key(self.keys.public_key());
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::base::error::help_text;
+
+    fn temp_key_file(name: &str) -> std::path::PathBuf {
+        let mut p = std::env::temp_dir();
+        p.push(format!("keystr-test-{}-{}", name, std::process::id()));
+        p
+    }
 
     #[test]
     fn test_new() {
@@ -487,6 +1126,182 @@ mod test {
         assert_eq!(k.is_secret_key_set(), false);
     }
 
+    #[test]
+    fn test_import_secret_key_hex_lenient() {
+        let expected_nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        for input in [
+            "0xb2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17",
+            "0Xb2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17",
+            "  b2f3673e e3a65928 3e659908 0e0ab0e6 69a3c264 0914375a 9b0b357f aae08b17\n",
+            "B2F3673EE3A659283E6599080E0AB0E669A3C2640914375A9B0B357FAAE08B17",
+            " nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae ",
+        ] {
+            let mut k = Keystore::new();
+            k.import_secret_key(input, true).unwrap();
+            k.hide_secret_key = false;
+            assert_eq!(k.get_nsec(), expected_nsec, "{}", input);
+        }
+
+        for input in [
+            "0x",
+            "0xb2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b",
+            "0xg2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17",
+            "0x0xb2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17",
+        ] {
+            let mut k = Keystore::new();
+            assert!(matches!(
+                k.import_secret_key(input, true),
+                Err(Error::KeyInvalidSecretFormat)
+            ));
+            assert!(!k.is_secret_key_set());
+        }
+    }
+
+    #[test]
+    fn test_import_secret_key_labeled() {
+        let expected_nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        for input in [
+            "sec: b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17\npub: 1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4",
+            "pub: 1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4\r\n  SEC:0xb2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17\r\n",
+            "npub: npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4\nnsec: nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+        ] {
+            let mut k = Keystore::new();
+            k.import_secret_key(input, true).unwrap();
+            k.hide_secret_key = false;
+            assert_eq!(k.get_nsec(), expected_nsec, "{}", input);
+        }
+
+        let mut k = Keystore::new();
+        assert!(matches!(
+            k.import_secret_key(
+                "pub: 1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4",
+                true
+            ),
+            Err(Error::KeyLabeledSecretMissing)
+        ));
+        assert!(matches!(
+            k.import_secret_key(
+                "sec: b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17\nnsec: nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true
+            ),
+            Err(Error::KeyLabeledSecretAmbiguous)
+        ));
+        assert!(matches!(
+            k.import_secret_key("sec: not-a-key", true),
+            Err(Error::KeyInvalidSecretFormat)
+        ));
+        assert!(!k.is_secret_key_set());
+    }
+
+    #[test]
+    fn test_read_public_key_file() {
+        let security = SecuritySettings {
+            security_level: SecurityLevel::PersistMandatoryPassword,
+            ..Default::default()
+        };
+        let keys = Keys::generate();
+        let path = temp_key_file("peek-npub");
+        fs::write(&path, keys.public_key().to_bech32().unwrap()).unwrap();
+
+        // no password needed
+        assert_eq!(
+            read_public_key_file(&path, &security).unwrap(),
+            keys.public_key()
+        );
+        assert!(matches!(
+            read_public_key_file(
+                &path,
+                &SecuritySettings {
+                    security_level: SecurityLevel::Never,
+                    ..Default::default()
+                }
+            ),
+            Err(Error::KeyLoadNotAllowed)
+        ));
+
+        fs::write(&path, "not a key").unwrap();
+        assert!(read_public_key_file(&path, &security).is_err());
+        let _ = fs::remove_file(&path);
+        assert!(matches!(
+            read_public_key_file(&path, &security),
+            Err(Error::IoError(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_encrypted_key_file() {
+        let security = SecuritySettings {
+            security_level: SecurityLevel::PersistMandatoryPassword,
+            ..Default::default()
+        };
+        let sk = Keys::generate().secret_key().unwrap();
+        let path = temp_key_file("verify");
+        let data =
+            Encrypt::encrypt_key(&sk, "password", 4, EncryptionAlgorithm::default()).unwrap();
+        fs::write(&path, hex::encode(data)).unwrap();
+
+        // correct password
+        verify_encrypted_key_file(&path, "password", &security).unwrap();
+        // wrong password
+        assert!(matches!(
+            verify_encrypted_key_file(&path, "wrong", &security),
+            Err(Error::KeyEncryption)
+        ));
+        assert!(matches!(
+            verify_encrypted_key_file(&path, "", &security),
+            Err(Error::KeyEncryptionPasswordMissing)
+        ));
+        let _res = fs::remove_file(&path);
+        // missing file
+        assert!(matches!(
+            verify_encrypted_key_file(&path, "password", &security),
+            Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn test_change_key_file_password() {
+        let security = SecuritySettings {
+            security_level: SecurityLevel::PersistMandatoryPassword,
+            ..Default::default()
+        };
+        let sk = Keys::generate().secret_key().unwrap();
+        let path = temp_key_file("change-password");
+        let data = Encrypt::encrypt_key(&sk, "old", 4, EncryptionAlgorithm::default()).unwrap();
+        fs::write(&path, hex::encode(data)).unwrap();
+
+        // wrong old password
+        assert!(matches!(
+            change_key_file_password(&path, "wrong", "new", "new", &security),
+            Err(Error::KeyEncryption)
+        ));
+        // new and confirm mismatch
+        assert!(matches!(
+            change_key_file_password(&path, "old", "new", "other", &security),
+            Err(Error::KeyEncryptionPasswordMismatch)
+        ));
+        // empty new password, mandatory
+        assert!(matches!(
+            change_key_file_password(&path, "old", "", "", &security),
+            Err(Error::KeyEncryptionPasswordMissing)
+        ));
+        // file unchanged by the failures
+        verify_encrypted_key_file(&path, "old", &security).unwrap();
+
+        // success
+        let data = change_key_file_password(&path, "old", "new", "new", &security).unwrap();
+        assert_eq!(Encrypt::decrypt_key(&data, "new").unwrap(), sk);
+        verify_encrypted_key_file(&path, "new", &security).unwrap();
+        assert!(verify_encrypted_key_file(&path, "old", &security).is_err());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _res = fs::remove_file(&path);
+    }
+
     #[test]
     fn test_import_public_key() {
         let mut k = Keystore::new();
@@ -540,4 +1355,356 @@ mod test {
             "nsec16awa8nftexjs4nk8zfl5wrrtc6a7hhycj7p8ztlf4dfy5xa9dcnslmfkz5"
         );
     }
+
+    #[test]
+    fn test_import_client_export_action() {
+        let sk = Keys::generate().secret_key().unwrap();
+        let export = crate::base::client_export::encrypt_ncryptsec(&sk, "nostr", 4);
+        let mut k = Keystore::new();
+        let mut status = StatusMessages::new();
+        k.client_export_input = export.clone().into();
+        k.client_export_password_input = "wrong".to_string();
+        k.import_client_export_action(&mut status);
+        assert!(!k.is_secret_key_set());
+        assert!(status
+            .get_last()
+            .starts_with("Error: Error importing client export"));
+        // inputs are cleared also on error
+        assert!(k.client_export_input.as_str().is_empty());
+        assert!(k.client_export_password_input.is_empty());
+
+        k.client_export_input = export.clone().into();
+        k.client_export_password_input = "nostr".to_string();
+        k.import_client_export_action(&mut status);
+        assert!(k.is_secret_key_set());
+        assert!(k.has_unsaved_secret_key());
+        assert_eq!(status.get_last(), "Secret key imported from client export");
+    }
+
+    #[test]
+    fn test_derive_child() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_mnemonic("oil oil oil oil oil oil oil oil oil oil oil oil", true)
+            .unwrap();
+        // account 0 is the main key
+        assert_eq!(
+            k.derive_child(0)
+                .unwrap()
+                .get_public_key()
+                .to_bech32()
+                .unwrap(),
+            "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux"
+        );
+        let pk1 = k.derive_child(1).unwrap().get_public_key();
+        let pk2 = k.derive_child(2).unwrap().get_public_key();
+        assert_ne!(pk1, pk2);
+        assert_ne!(pk1, k.get_public_key().unwrap());
+        // deterministic
+        assert_eq!(k.derive_child(1).unwrap().get_public_key(), pk1);
+    }
+
+    #[test]
+    fn test_derive_child_not_mnemonic() {
+        let mut k = Keystore::new();
+        k.generate();
+        assert!(k.derive_child(1).is_err());
+    }
+
+    #[test]
+    fn test_derive_child_after_reload() {
+        let mut k = Keystore::new();
+        k.import_mnemonic("oil oil oil oil oil oil oil oil oil oil oil oil", true)
+            .unwrap();
+        assert!(k.derive_child(1).is_ok());
+        let sk = k.get_secret_key().unwrap();
+        let data = Encrypt::encrypt_key(
+            &sk,
+            "password",
+            Encrypt::default_log2_rounds(),
+            EncryptionAlgorithm::default(),
+        )
+        .unwrap();
+
+        // the seed is not saved, only the main key
+        let mut k2 = Keystore::new();
+        k2.import_encrypted_secret_key(&hex::encode(data), false)
+            .unwrap();
+        k2.decrypt_secret_key("password").unwrap();
+        assert_eq!(k2.get_public_key().unwrap(), k.get_public_key().unwrap());
+        assert!(matches!(
+            k2.derive_child(1),
+            Err(Error::KeyNotMnemonicBacked)
+        ));
+    }
+
+    #[test]
+    fn test_key_signer_scrub() {
+        let keys = Keys::generate();
+        let mut signer = KeySigner { keys: keys.clone() };
+        assert!(signer.can_sign());
+        signer.scrub();
+        assert!(!signer.can_sign());
+        assert_eq!(signer.get_public_key(), keys.public_key());
+    }
+
+    #[test]
+    fn test_public_summary() {
+        let mut k = Keystore::new();
+        assert!(k.public_summary().is_err());
+
+        k.import_secret_key(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+            true,
+        )
+        .unwrap();
+        k.nip05_input = "alice@example.com".to_string();
+        let summary = k.public_summary().unwrap();
+        assert_eq!(
+            summary.npub,
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert_eq!(
+            summary.pubkey_hex,
+            "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4"
+        );
+        assert!(summary.nprofile.starts_with("nprofile1"));
+        assert_eq!(summary.nip05, Some("alice@example.com".to_string()));
+        assert_eq!(summary.fingerprint.len(), 19);
+
+        let secret_hex = k
+            .get_keys()
+            .unwrap()
+            .secret_key()
+            .unwrap()
+            .display_secret()
+            .to_string();
+        for out in [summary.to_json().unwrap(), summary.to_text()] {
+            assert!(out.contains(&summary.npub));
+            assert!(out.contains(&summary.pubkey_hex));
+            assert!(!out.contains("nsec"));
+            assert!(!out.contains(&secret_hex));
+        }
+    }
+
+    #[test]
+    fn test_build_relay_list_event() {
+        let mut k = Keystore::new();
+        let relays = vec![
+            ("wss://relay1.example.com".to_string(), RelayMarker::Read),
+            ("wss://relay2.example.com/".to_string(), RelayMarker::Write),
+            (
+                "wss://relay3.example.com".to_string(),
+                RelayMarker::ReadWrite,
+            ),
+        ];
+        assert!(k.build_relay_list_event(&relays).is_err());
+
+        k.generate();
+        let event = k.build_relay_list_event(&relays).unwrap();
+        assert_eq!(event.kind, Kind::RelayList);
+        assert_eq!(event.kind.as_u64(), 10002);
+        assert_eq!(event.pubkey, k.get_public_key().unwrap());
+        assert!(event.verify().is_ok());
+        assert_eq!(
+            event.tags.iter().map(|t| t.as_vec()).collect::<Vec<_>>(),
+            vec![
+                vec!["r", "wss://relay1.example.com", "read"],
+                vec!["r", "wss://relay2.example.com", "write"],
+                vec!["r", "wss://relay3.example.com"],
+            ]
+        );
+
+        // invalid URL
+        assert!(k
+            .build_relay_list_event(&[("https://example.com".to_string(), RelayMarker::Read)])
+            .is_err());
+    }
+
+    #[test]
+    fn test_build_dm_event() {
+        let mut k = Keystore::new();
+        let recipient = Keys::generate();
+        assert!(k.build_dm_event(&recipient.public_key(), "hello").is_err());
+        // watch-only
+        k.import_public_key(&Keys::generate().public_key().to_string())
+            .unwrap();
+        assert!(k.build_dm_event(&recipient.public_key(), "hello").is_err());
+
+        k.generate();
+        let sender = k.get_public_key().unwrap();
+        let event = k
+            .build_dm_event(&recipient.public_key(), "Secret message")
+            .unwrap();
+        assert_eq!(event.kind, Kind::EncryptedDirectMessage);
+        assert_eq!(event.pubkey, sender);
+        assert!(event.verify().is_ok());
+        assert_eq!(event.tags, vec![Tag::PubKey(recipient.public_key(), None)]);
+        assert_ne!(event.content, "Secret message");
+
+        // the recipient can decrypt it
+        let decrypted =
+            nostr::nips::nip04::decrypt(&recipient.secret_key().unwrap(), &sender, &event.content)
+                .unwrap();
+        assert_eq!(decrypted, "Secret message");
+        // others can't
+        assert_ne!(
+            nostr::nips::nip04::decrypt(
+                &Keys::generate().secret_key().unwrap(),
+                &sender,
+                &event.content,
+            )
+            .unwrap_or_default(),
+            "Secret message"
+        );
+    }
+
+    #[test]
+    fn test_benchmark_sign() {
+        let mut k = Keystore::new();
+        assert!(matches!(k.benchmark_sign(5), Err(Error::KeyNotSet)));
+
+        k.generate();
+        let res = k.benchmark_sign(5).unwrap();
+        assert_eq!(res.iterations, 5);
+        assert!(res.rate() > 0.0);
+        assert!(res.average() <= res.total);
+    }
+
+    #[test]
+    fn test_key_signer_self_check() {
+        let good = KeySigner {
+            keys: Keys::generate(),
+        };
+        assert!(good.self_check().is_ok());
+
+        // public key only, can't sign
+        let broken = KeySigner {
+            keys: Keys::from_public_key(Keys::generate().public_key()),
+        };
+        assert!(matches!(
+            broken.self_check(),
+            Err(Error::KeySelfCheckFailed)
+        ));
+    }
+
+    #[test]
+    fn test_unlock_attempts_backoff() {
+        let start = Instant::now();
+        let mut a = UnlockAttempts::default();
+        assert_eq!(a.remaining_wait(start), Duration::ZERO);
+
+        // a few failures are free
+        for _ in 0..UNLOCK_FREE_ATTEMPTS - 1 {
+            a.record_failure(start);
+        }
+        assert_eq!(a.remaining_wait(start), Duration::ZERO);
+
+        // then the wait increases with each failure
+        let mut prev = Duration::ZERO;
+        for _ in 0..5 {
+            a.record_failure(start);
+            let wait = a.remaining_wait(start);
+            assert!(wait > prev);
+            prev = wait;
+        }
+        // wait passes with time
+        assert!(a.remaining_wait(start + Duration::from_secs(1)) < prev);
+        assert_eq!(a.remaining_wait(start + prev), Duration::ZERO);
+
+        // capped at the lockout maximum
+        for _ in 0..50 {
+            a.record_failure(start);
+        }
+        assert_eq!(
+            a.remaining_wait(start),
+            Duration::from_secs(UNLOCK_MAX_DELAY_SECS)
+        );
+
+        a.reset();
+        assert_eq!(a.remaining_wait(start), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_lock_and_fresh_unlock_signer() {
+        let keys = Keys::generate();
+        let encrypted = Encrypt::encrypt_key(
+            &keys.secret_key().unwrap(),
+            "password",
+            4,
+            EncryptionAlgorithm::default(),
+        )
+        .unwrap();
+        let mut k = Keystore::new();
+        k.generate();
+        assert!(matches!(k.lock(), Err(Error::KeyFreshUnlockNotSaved)));
+
+        k.import_encrypted_secret_key(&hex::encode(&encrypted), false)
+            .unwrap();
+        k.decrypt_secret_key("password").unwrap();
+        assert!(k.is_secret_key_set());
+        k.lock().unwrap();
+        assert!(!k.is_secret_key_set());
+        assert!(k.is_encrypted_secret_key_set());
+        assert_eq!(k.get_public_key().unwrap(), keys.public_key());
+
+        // single signature, stays locked
+        k.decrypt_password_input = "wrong".to_string();
+        assert!(matches!(k.fresh_unlock_signer(), Err(Error::KeyEncryption)));
+        assert!(k.decrypt_password_input.is_empty());
+        assert!(matches!(
+            k.fresh_unlock_signer(),
+            Err(Error::KeyFreshUnlockRequired)
+        ));
+        k.decrypt_password_input = "password".to_string();
+        let key_signer = k.fresh_unlock_signer().unwrap();
+        assert!(key_signer.can_sign());
+        assert_eq!(key_signer.get_public_key(), keys.public_key());
+        assert!(!k.is_secret_key_set());
+
+        // can be unlocked again
+        k.decrypt_secret_key("password").unwrap();
+        assert!(k.is_secret_key_set());
+    }
+
+    #[test]
+    fn test_unlock_action_throttle_and_reset() {
+        let sk = Keys::generate().secret_key().unwrap();
+        let encrypted =
+            Encrypt::encrypt_key(&sk, "password", 13, EncryptionAlgorithm::default()).unwrap();
+        let security = SecuritySettings::default();
+        let mut status = StatusMessages::new();
+        let mut k = Keystore::new();
+        k.import_encrypted_secret_key(&hex::encode(&encrypted), false)
+            .unwrap();
+
+        for _ in 0..UNLOCK_FREE_ATTEMPTS {
+            k.decrypt_password_input = "wrong".to_string();
+            k.unlock_secret_key_action(&security, &mut status);
+        }
+        assert_eq!(k.unlock_attempts.failed_count, UNLOCK_FREE_ATTEMPTS);
+        assert!(!k.is_secret_key_set());
+
+        // throttled now, even the correct password is not tried
+        // (last failure time is refreshed, as decryption itself is slow)
+        k.unlock_attempts.last_failed = Some(Instant::now());
+        k.decrypt_password_input = "password".to_string();
+        k.unlock_secret_key_action(&security, &mut status);
+        assert!(!k.is_secret_key_set());
+        assert_eq!(k.unlock_attempts.failed_count, UNLOCK_FREE_ATTEMPTS);
+        assert_eq!(
+            status.get_last_error().unwrap().help,
+            help_text("unlock-throttled")
+        );
+
+        // after the wait, success resets the count
+        k.unlock_attempts.last_failed = Some(Instant::now() - Duration::from_secs(2));
+        k.unlock_secret_key_action(&security, &mut status);
+        assert!(k.is_secret_key_set());
+        assert_eq!(k.unlock_attempts.failed_count, 0);
+        assert_eq!(
+            k.unlock_attempts.remaining_wait(Instant::now()),
+            Duration::ZERO
+        );
+    }
 }
@@ -1,36 +1,115 @@
-use crate::base::error::Error;
+use crate::base::error::{Error, ErrorDetail};
+use crate::base::key_lock::{KeyLock, LockState};
+use crate::base::qr_png::save_qr_png;
+use crate::base::relay_url::{parse_marked_relays, RelayMarker};
+use crate::base::storage::Storage;
 use crate::model::delegator::Delegator;
-use crate::model::keystore::Keystore;
+use crate::model::display_settings::{self, DisplayFormat};
+use crate::model::health::{HealthServer, HealthState};
+use crate::model::key_audit::audit_keys;
+use crate::model::keystore::{KeySigner, Keystore};
+use crate::model::new_identity::NewIdentity;
+use crate::model::note_signer::NoteSigner;
+use crate::model::security_settings::PersistStatus;
 use crate::model::settings::Settings;
-use crate::model::signer::{ConnectionStatus, Signer};
-use crate::model::status_messages::StatusMessages;
+use crate::model::signer::{publish_event, ConnectionStatus, Signer};
+use crate::model::status_messages::{StatusMessages, StatusSink};
+use crate::model::tray::{Tray, TrayAction, TrayState};
+use crate::model::vanity::Vanity;
+use crate::model::wizard::Wizard;
 
-use nostr::prelude::Keys;
+use nostr::prelude::{Keys, XOnlyPublicKey};
 
 use crossbeam::channel;
 use iced::widget::qr_code;
 use once_cell::sync::Lazy;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use zeroize::Zeroize;
+
+/// Number of signatures in the signing benchmark
+const BENCHMARK_ITERATIONS: u32 = 1000;
 
 /// Actions that can be triggerred from the UI
 #[derive(Clone, Debug)]
 pub(crate) enum Action {
     DelegateDeeGenerate,
     DelegateSign,
+    DelegateSelfTest,
+    /// Add a received delegation from the pasted tag
+    DelegationAdd,
+    /// Select the received delegation to apply when signing, None for none
+    DelegationSelect(Option<usize>),
+    KeysAudit,
+    /// Sign a number of dummy hashes with the loaded key, report the speed
+    KeysBenchmarkSign,
     KeysClearNoConfirm,
     KeysClear,
     KeysGenerate,
     KeysImportPubkey,
     KeysImportSecretkey,
     KeysImportMnemonic,
+    KeysImportClientExport,
     KeysLoad,
     KeysSave,
     KeysUnlock,
+    /// Check that the saved encrypted key decrypts with the entered password
+    KeysVerifyBackup,
+    /// Re-encrypt the saved key with the new password
+    KeysChangePassword,
+    KeysVanityStart,
+    /// Stop vanity search (cancel if running), and use the found key, if any
+    KeysVanityStop,
+    /// Sign a text note locally, with the explicit created_at if given
+    KeysSignNote,
+    /// Encrypt the note content as a direct message (NIP-04) to the recipient, and sign it
+    KeysSignDm,
+    /// Publish NIP-65 relay list (kind 10002) of the own key (asks for confirmation)
+    KeysPublishRelayList,
+    KeysPublishRelayListNoConfirm,
+    /// Quick new identity flow: generate a mnemonic-backed key and show the words
+    NewIdentityStart,
+    /// Quick new identity flow: words are written down (confirmed), go to password
+    NewIdentityBackupDone,
+    /// Quick new identity flow: save with the entered password, then finish
+    NewIdentitySave,
+    NewIdentityCancel,
     ConfirmationYes,
     ConfirmationNo,
+    /// Connect the signer (asks for confirmation, showing the relays)
     SignerConnect,
+    SignerConnectNoConfirm,
     SignerDisconnect,
+    SignerRotateAppId,
+    /// Open or close the viewer of relay events addressed to the signer
+    SignerToggleEventViewer,
+    /// Clear the last error shown for the connection
+    SignerClearError,
+    /// Assign the display name from the input to the connected client, persisted
+    SignerSetDisplayName,
     SignerPendingIgnoreFirst,
+    SignerPendingRejectFirst,
+    SignerPendingRejectAll,
+    /// Modify the first pending sign request to be signed with the current time
+    SignerPendingBumpTime,
+    /// Export the pending requests to a file, for offline review
+    SignerPendingExport,
     SignerPendingProcessFirst,
+    /// Move a pending request in the queue, from index to index
+    SignerPendingMove(usize, usize),
+    /// Trust the app (sender pubkey) or not: sign its requests without asking, for the session
+    SignerSetAutoApprove(XOnlyPublicKey, bool),
+    /// Save the QR code shown as a PNG image file
+    QrSavePng,
+    /// Import relays from the pasted relay list into the response relays
+    SettingsImportRelays,
+    /// Reset settings to defaults (asks for confirmation)
+    SettingsReset,
+    SettingsResetNoConfirm,
+    WizardNext,
+    WizardSkip,
 }
 
 /// Events that can affect the UI
@@ -38,7 +117,11 @@ pub(crate) enum Action {
 pub enum Event {
     SignerConnected,
     SignerNewRequest,
+    /// Pending requests are about to expire, last chance to act
+    SignerRequestExpiring,
     StatusUpdate,
+    /// Quick action chosen in the tray icon
+    TrayAction(TrayAction),
 }
 
 /// Modal dialogs
@@ -52,9 +135,25 @@ pub(crate) enum Modal {
     SignerRequest(String),
 }
 
+/// A confirmation dialog: if confirmed, the actions are performed in order
 #[derive(Clone)]
-pub(crate) enum Confirmation {
-    KeysClearBeforeAction(Option<Action>),
+pub(crate) struct Confirmation {
+    pub title: String,
+    pub body: String,
+    pub actions: Vec<Action>,
+}
+
+/// Key state, determines which features are available
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Readiness {
+    /// No key loaded
+    NoKey,
+    /// Only a public key is loaded, signing is not possible
+    WatchOnly,
+    /// Encrypted secret key is loaded, but not yet unlocked
+    Locked,
+    /// Secret key is available, all features can be used
+    Unlocked,
 }
 
 #[readonly::make]
@@ -62,6 +161,10 @@ pub(crate) struct KeystrModel {
     pub own_keys: Keystore,
     pub delegator: Delegator,
     pub signer: Signer,
+    pub vanity: Vanity,
+    pub note_signer: NoteSigner,
+    pub wizard: Wizard,
+    pub new_identity: NewIdentity,
     pub status: StatusMessages,
     pub settings: Settings,
     #[readonly]
@@ -69,15 +172,33 @@ pub(crate) struct KeystrModel {
     // QR code content; State must be stored somewhere outside of the UI
     #[readonly]
     qr_code: Option<(String, qr_code::State)>,
+    /// Input for the file to save the QR code image to; empty for the default file in the storage folder
+    pub qr_png_path_input: String,
+    /// Lock marking the loaded key as used by this instance; released on drop (exit)
+    key_lock: Option<KeyLock>,
+    /// Readiness reported by the health endpoint, if enabled
+    health: Arc<HealthState>,
+    /// Whether the app window has the focus, updated by the UI
+    window_focused: bool,
+    /// Public key of the saved key, for the unlock screen; read when the screen opens,
+    /// not on each render. None if the key is not locked, or if it could not be read.
+    #[readonly]
+    saved_pubkey: Option<XOnlyPublicKey>,
+    /// Whether the key was locked at the last update, to read the saved key once when it gets locked
+    unlock_screen_open: bool,
+    /// System tray icon, if the tray mode is on
+    tray: Tray,
 }
 
 pub(crate) struct EventQueue {
     sender: channel::Sender<Event>,
     receiver: channel::Receiver<Event>,
+    /// Set when the consumer (UI) is gone; pushes fail, background tasks should stop
+    closed: watch::Sender<bool>,
 }
 
 /// Event queue used for getting events out from Model. A static instance is used.
-pub(crate) static EVENT_QUEUE: Lazy<EventQueue> = Lazy::new(|| EventQueue::new());
+pub(crate) static EVENT_QUEUE: Lazy<Arc<EventQueue>> = Lazy::new(|| Arc::new(EventQueue::new()));
 
 // TODO remove
 /// Trait for someone who can consume our Events
@@ -93,10 +214,21 @@ impl KeystrModel {
             own_keys: Keystore::new(),
             delegator: Delegator::new(),
             signer: Signer::new(&app_id, status.clone()),
+            vanity: Vanity::new(),
+            note_signer: NoteSigner::new(),
+            wizard: Wizard::new(true),
+            new_identity: NewIdentity::new(),
             status,
             settings: Settings::default(),
             confirmation: None,
             qr_code: None,
+            qr_png_path_input: String::new(),
+            key_lock: None,
+            health: Arc::new(HealthState::default()),
+            window_focused: true,
+            saved_pubkey: None,
+            unlock_screen_open: false,
+            tray: Tray::default(),
         }
     }
 
@@ -109,13 +241,311 @@ impl KeystrModel {
         if let Ok(sett) = Settings::load() {
             model.settings = sett;
         }
+        model.wizard = Wizard::new(model.settings.first_run_completed);
+        model.apply_activity_log_setting();
+        model.apply_status_mirror_setting();
+        model.apply_tray_setting();
+        display_settings::set_kind_labels(&model.settings.kind_labels);
+        if model.settings.signer.health_endpoint {
+            model.start_health_endpoint();
+        }
         //. Try load keys
         if model.settings.security.allows_persist() {
             model.action(Action::KeysLoad);
         }
+        model.update_saved_pubkey();
         model
     }
 
+    /// First-run wizard is done (or skipped): apply the entered relays, and don't show it again
+    fn complete_first_run(&mut self) {
+        let relays = self.wizard.get_relays();
+        if !relays.is_empty() {
+            self.settings.set_signer_response_relays(relays);
+        }
+        self.settings.set_first_run_completed();
+        self.status.set("Setup completed");
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Generate a mnemonic and import the key from it, not saved until the backup is confirmed
+    fn start_new_identity(&mut self) {
+        let res = self.new_identity.start().and_then(|mut words| {
+            let res = self.own_keys.import_mnemonic(&words, true);
+            words.zeroize();
+            res
+        });
+        match res {
+            Err(e) => {
+                self.new_identity.finish();
+                self.status.set_error_err(&e);
+            }
+            Ok(_) => self
+                .status
+                .set("New identity generated, write down the mnemonic words"),
+        }
+    }
+
+    /// Mark the loaded key as used by this instance; warn if another running instance uses it
+    fn update_key_lock(&mut self) {
+        self.key_lock = None;
+        let pubkey = match self.own_keys.get_public_key() {
+            Err(_) => return,
+            Ok(pk) => pk,
+        };
+        match KeyLock::acquire(&Storage::storage_folder(), &pubkey) {
+            Err(e) => self.status.set_error_err(&e),
+            Ok((lock, state)) => {
+                self.key_lock = lock;
+                match state {
+                    LockState::Live(pid) => self.status.set_error(&format!(
+                        "Warning: this key is also used by another running keystr instance (PID {})",
+                        pid
+                    )),
+                    LockState::Stale(pid) => self.status.set(&format!(
+                        "Removed stale key lock of a non-running instance (PID {})",
+                        pid
+                    )),
+                    LockState::Free => {}
+                }
+            }
+        }
+    }
+
+    /// Relays for the relay list to publish, from the input, or the response relays if empty;
+    /// the list is published to these same relays
+    fn relays_to_publish(&self) -> Result<Vec<(String, RelayMarker)>, Error> {
+        let relays = if self.own_keys.relay_list_input.trim().is_empty() {
+            self.settings
+                .signer
+                .response_relays
+                .iter()
+                .map(|r| (r.clone(), RelayMarker::ReadWrite))
+                .collect::<Vec<_>>()
+        } else {
+            parse_marked_relays(&self.own_keys.relay_list_input)?
+        };
+        if relays.is_empty() {
+            return Err(Error::NoRelaysToPublish);
+        }
+        Ok(relays)
+    }
+
+    fn publish_relay_list(&mut self) {
+        let relays = match self.relays_to_publish() {
+            Err(e) => {
+                self.status.set_error_err(&e);
+                return;
+            }
+            Ok(r) => r,
+        };
+        match self.own_keys.build_relay_list_event(&relays) {
+            Err(e) => self.status.set_error_err(&e),
+            Ok(event) => {
+                self.status.set("Publishing relay list...");
+                let urls = relays.into_iter().map(|(url, _)| url).collect();
+                publish_event(
+                    event,
+                    urls,
+                    self.settings.signer.max_concurrent_connects,
+                    self.status.clone(),
+                );
+            }
+        }
+    }
+
+    pub fn set_persist_activity_log(&mut self, persist: bool) {
+        self.settings.set_persist_activity_log(persist);
+        self.apply_activity_log_setting();
+    }
+
+    fn apply_activity_log_setting(&self) {
+        let dir = if self.settings.persist_activity_log && Storage::check_create_folder().is_ok() {
+            Some(Storage::storage_folder())
+        } else {
+            None
+        };
+        self.status.set_activity_log_dir(dir.as_deref());
+    }
+
+    pub fn set_mirror_status_to_stderr(&mut self, mirror: bool) {
+        self.settings.set_mirror_status_to_stderr(mirror);
+        self.apply_status_mirror_setting();
+    }
+
+    fn apply_status_mirror_setting(&self) {
+        let sink: Option<StatusSink> = if self.settings.mirror_status_to_stderr {
+            Some(Box::new(std::io::stderr()))
+        } else {
+            None
+        };
+        self.status.set_mirror(sink);
+    }
+
+    /// Restore the default settings and persist them; keys are not affected
+    pub fn reset_settings(&mut self) {
+        self.settings = Settings::default();
+        let res = self.settings.save();
+        self.apply_activity_log_setting();
+        self.apply_status_mirror_setting();
+        display_settings::set_kind_labels(&self.settings.kind_labels);
+        match res {
+            Err(e) => self.status.set_error_err(&e),
+            Ok(_) => self.status.set("Settings reset to defaults"),
+        }
+    }
+
+    /// Persist changed keys right away, if auto-save is enabled and persistence is allowed
+    fn auto_save(&mut self) {
+        let security = &self.settings.security;
+        if !security.auto_save || !security.allows_persist() || !self.own_keys.has_unsaved_change {
+            return;
+        }
+        if security.persist_status(!self.own_keys.save_password_input.is_empty())
+            == PersistStatus::NoPassword
+        {
+            self.status
+                .set("Auto-save: enter an encryption password and Save to persist the key");
+            return;
+        }
+        self.own_keys.save_action(security, &mut self.status);
+    }
+
+    fn start_health_endpoint(&mut self) {
+        let bind_address = &self.settings.signer.health_bind_address;
+        match HealthServer::start(bind_address, self.health.clone()) {
+            Err(e) => self.status.set_error(&format!(
+                "Could not start health endpoint on {}: {}",
+                bind_address, e
+            )),
+            Ok(server) => self.status.set(&format!(
+                "Health endpoint listening on http://{}/healthz",
+                server.local_addr
+            )),
+        }
+    }
+
+    pub fn set_tray_mode(&mut self, tray_mode: bool) {
+        self.settings.set_tray_mode(tray_mode);
+        self.apply_tray_setting();
+    }
+
+    fn apply_tray_setting(&mut self) {
+        if !self.settings.tray_mode {
+            self.tray.stop();
+            return;
+        }
+        if let Err(e) = self.tray.start(self.tray_state(), self.status.clone()) {
+            self.status.set_error_err(&e);
+        }
+    }
+
+    /// Whether the tray icon is shown, so the window can be hidden to it
+    pub fn is_tray_running(&self) -> bool {
+        self.tray.is_running()
+    }
+
+    /// What the tray icon shows: the window title and the pending request count
+    pub fn tray_state(&self) -> TrayState {
+        TrayState::new(&self.window_title(), self.signer.get_pending_count())
+    }
+
+    /// Refresh the tray icon, e.g. after a new request
+    pub fn update_tray(&mut self) {
+        if self.settings.tray_mode {
+            self.tray.update(self.tray_state());
+        }
+    }
+
+    /// Periodic checks, called regularly by the UI: disconnect an idle signer connection,
+    /// update the readiness for the health endpoint, and the tray icon
+    pub fn status_poll(&mut self) {
+        let idle_timeout = Duration::from_secs(self.settings.signer.idle_disconnect_secs);
+        self.signer
+            .disconnect_if_idle(Instant::now(), idle_timeout, &mut self.status);
+        if self.signer.check_request_expiry(
+            Instant::now(),
+            Duration::from_secs(self.settings.signer.request_expiry_secs),
+            Duration::from_secs(self.settings.signer.expiry_warning_secs),
+            &mut self.status,
+        ) {
+            let _res = EVENT_QUEUE.notify(Event::SignerRequestExpiring);
+        }
+        if self.settings.signer.health_endpoint {
+            let relay_connected = matches!(
+                self.signer.get_connection_status(),
+                ConnectionStatus::Connected(_)
+            );
+            self.health
+                .update(self.readiness().can_sign(), relay_connected);
+        }
+        self.update_tray();
+    }
+
+    pub fn set_window_focused(&mut self, focused: bool) {
+        self.window_focused = focused;
+    }
+
+    /// Approving requests may require the window to be focused (setting)
+    pub fn check_can_approve(&self) -> Result<(), Error> {
+        if self.settings.signer.require_focus_to_approve && !self.window_focused {
+            return Err(Error::SignerWindowNotFocused);
+        }
+        Ok(())
+    }
+
+    /// Check that the signer can be connected with the current keys;
+    /// a watch-only key (public key only) is refused with a specific error
+    pub fn check_can_connect_signer(&self) -> Result<(), Error> {
+        match self.readiness() {
+            Readiness::WatchOnly => Err(Error::SignerWatchOnly),
+            _ => Ok(()),
+        }
+    }
+
+    /// Which features are available with the current keys
+    pub fn readiness(&self) -> Readiness {
+        if self.own_keys.is_secret_key_set() {
+            Readiness::Unlocked
+        } else if self.own_keys.is_encrypted_secret_key_set() {
+            Readiness::Locked
+        } else if self.own_keys.keys_is_set() {
+            Readiness::WatchOnly
+        } else {
+            Readiness::NoKey
+        }
+    }
+
+    /// Read the public key of the saved key when the unlock screen opens (the key gets locked),
+    /// and keep it for showing; cleared when the key is no longer locked
+    pub fn update_saved_pubkey(&mut self) {
+        let locked = self.readiness() == Readiness::Locked;
+        if locked && !self.unlock_screen_open {
+            self.saved_pubkey = Keystore::peek_saved_pubkey(&self.settings.security).ok();
+        }
+        if !locked {
+            self.saved_pubkey = None;
+        }
+        self.unlock_screen_open = locked;
+    }
+
+    /// Window title, showing the active key and its state, and the pending requests
+    pub fn window_title(&self) -> String {
+        let key_name = match self.own_keys.nip05_input.trim() {
+            "" => self
+                .own_keys
+                .get_public_key()
+                .ok()
+                .map(|pk| shortened_key(&DisplayFormat::Bech32.format_public_key(&pk))),
+            nip05 => Some(nip05.to_string()),
+        };
+        window_title(
+            key_name.as_deref(),
+            self.readiness(),
+            self.signer.get_pending_count(),
+        )
+    }
+
     pub fn action(&mut self, action: Action) {
         match action {
             Action::DelegateDeeGenerate => self.delegator.generate_random_delegatee(),
@@ -128,72 +558,210 @@ impl KeystrModel {
                     },
                 };
             }
+            Action::DelegateSelfTest => {
+                match self.own_keys.get_keys() {
+                    Err(e) => self.status.set_error(&e.to_string()),
+                    Ok(keys) => match self.delegator.self_test(&keys) {
+                        Err(e) => self
+                            .status
+                            .set_error(&format!("Delegation self-test failed: {}", e)),
+                        Ok(_) => self.status.set("Delegation self-test passed"),
+                    },
+                };
+            }
+            Action::DelegationAdd => {
+                let input = self.delegator.delegation_import_input.clone();
+                match self.delegator.add_delegation(&input) {
+                    Err(e) => self.status.set_error_err(&e),
+                    Ok(_) => {
+                        self.delegator.delegation_import_input = String::new();
+                        self.status.set("Delegation added");
+                    }
+                }
+            }
+            Action::DelegationSelect(index) => {
+                if let Err(e) = self.delegator.select_delegation(index) {
+                    self.status.set_error_err(&e);
+                }
+            }
+            Action::KeysAudit => {
+                let findings = audit_keys(&Storage::storage_folder());
+                for f in &findings {
+                    self.status.set(&format!("Key audit: {}", f));
+                }
+                self.status.set(&format!(
+                    "Key audit done, {} issue(s) found",
+                    findings.len()
+                ));
+            }
+            Action::KeysBenchmarkSign => match self.own_keys.benchmark_sign(BENCHMARK_ITERATIONS) {
+                Err(e) => self.status.set_error_err(&e),
+                Ok(res) => self.status.set(&format!(
+                    "Signing benchmark: {} signatures, average {} us, {:.0} signatures/s",
+                    res.iterations,
+                    res.average().as_micros(),
+                    res.rate()
+                )),
+            },
             Action::KeysClearNoConfirm => {
                 self.own_keys.clear();
+                self.key_lock = None;
                 self.status.set("Keys cleared");
             }
             Action::KeysClear => {
                 if self.own_keys.keys_is_set() {
-                    self.confirmation = Some(Confirmation::KeysClearBeforeAction(None));
+                    self.ask_confirmation(Confirmation::keys_clear_before(None));
                 } else {
                     self.action(Action::KeysClearNoConfirm);
                 }
             }
             Action::KeysGenerate => {
                 if self.own_keys.keys_is_set() {
-                    self.confirmation = Some(Confirmation::KeysClearBeforeAction(Some(
-                        Action::KeysGenerate,
-                    )));
+                    self.ask_confirmation(self.replace_keys_confirmation(Action::KeysGenerate));
                 } else {
                     self.confirmation = None;
                     self.own_keys.generate();
                     self.status.set("New keypair generated");
+                    self.auto_save();
+                }
+            }
+            Action::KeysSignNote => {
+                let key_signer = self.own_keys.get_signer();
+                self.note_signer.sign_action(key_signer, &mut self.status);
+            }
+            Action::KeysSignDm => self
+                .note_signer
+                .sign_dm_action(&self.own_keys, &mut self.status),
+            Action::KeysVanityStart => self.vanity.start_action(&mut self.status),
+            Action::KeysVanityStop => {
+                self.vanity.stop_action(&mut self.status);
+                if self.vanity.has_found() {
+                    if self.own_keys.keys_is_set() {
+                        self.ask_confirmation(
+                            self.replace_keys_confirmation(Action::KeysVanityStop),
+                        );
+                    } else if let Some(keys) = self.vanity.take_found() {
+                        self.confirmation = None;
+                        self.own_keys.import_generated(keys);
+                        self.status.set("Vanity keypair imported");
+                        self.auto_save();
+                    }
                 }
             }
             Action::KeysImportPubkey => {
-                match self
-                    .own_keys
-                    .import_public_key(&self.own_keys.public_key_input.clone())
-                {
-                    Err(e) => self.status.set_error(&e.to_string()),
-                    Ok(_) => self.status.set("Public key imported"),
-                };
-                // cleanup
-                self.own_keys.public_key_input = String::new();
+                if self.own_keys.keys_is_set() {
+                    self.ask_confirmation(self.replace_keys_confirmation(Action::KeysImportPubkey));
+                    return;
+                }
+                self.confirmation = None;
+                self.own_keys.import_public_key_action(&mut self.status);
+                self.auto_save();
             }
             Action::KeysImportSecretkey => {
+                if self.own_keys.keys_is_set() {
+                    self.ask_confirmation(
+                        self.replace_keys_confirmation(Action::KeysImportSecretkey),
+                    );
+                    return;
+                }
+                self.confirmation = None;
                 self.own_keys.import_secret_key_action(&mut self.status);
+                self.auto_save();
             }
             Action::KeysImportMnemonic => {
+                if self.own_keys.keys_is_set() {
+                    self.ask_confirmation(
+                        self.replace_keys_confirmation(Action::KeysImportMnemonic),
+                    );
+                    return;
+                }
+                self.confirmation = None;
                 self.own_keys.import_mnemonic_action(&mut self.status);
+                self.auto_save();
+            }
+            Action::KeysImportClientExport => {
+                if self.own_keys.keys_is_set() {
+                    self.ask_confirmation(
+                        self.replace_keys_confirmation(Action::KeysImportClientExport),
+                    );
+                    return;
+                }
+                self.confirmation = None;
+                self.own_keys.import_client_export_action(&mut self.status);
+                self.auto_save();
             }
             Action::KeysLoad => {
                 if self.own_keys.keys_is_set() {
-                    self.confirmation =
-                        Some(Confirmation::KeysClearBeforeAction(Some(Action::KeysLoad)));
+                    self.ask_confirmation(self.replace_keys_confirmation(Action::KeysLoad));
                 } else {
                     self.own_keys
                         .load_action(&self.settings.security, &mut self.status);
+                    self.lock_if_fresh_unlock();
+                    self.update_key_lock();
                 }
             }
             Action::KeysSave => {
                 self.own_keys
                     .save_action(&self.settings.security, &mut self.status);
             }
-            Action::KeysUnlock => self
+            Action::KeysUnlock => {
+                self.own_keys
+                    .unlock_secret_key_action(&self.settings.security, &mut self.status);
+                self.lock_if_fresh_unlock();
+            }
+            Action::KeysVerifyBackup => self
+                .own_keys
+                .verify_saved_key_action(&self.settings.security, &mut self.status),
+            Action::KeysChangePassword => self
                 .own_keys
-                .unlock_secret_key_action(&self.settings.security, &mut self.status),
+                .change_password_action(&self.settings.security, &mut self.status),
+            Action::KeysPublishRelayList => match self.relays_to_publish() {
+                Err(e) => self.status.set_error_err(&e),
+                Ok(relays) => {
+                    let urls: Vec<String> = relays.into_iter().map(|(url, _)| url).collect();
+                    self.ask_confirmation(Confirmation::relays_contacted(
+                        "Publish relay list?",
+                        &urls,
+                        Action::KeysPublishRelayListNoConfirm,
+                    ));
+                }
+            },
+            Action::KeysPublishRelayListNoConfirm => self.publish_relay_list(),
+            Action::NewIdentityStart => {
+                if !self.settings.security.allows_persist() {
+                    self.status.set_error_err(&Error::KeySaveNotAllowed);
+                } else if self.own_keys.keys_is_set() {
+                    self.ask_confirmation(self.replace_keys_confirmation(Action::NewIdentityStart));
+                } else {
+                    self.confirmation = None;
+                    self.start_new_identity();
+                }
+            }
+            Action::NewIdentityBackupDone => match self.new_identity.backup_done() {
+                Err(e) => self.status.set_error_err(&e),
+                Ok(_) => self.status.set("Mnemonic backup confirmed, set a password"),
+            },
+            Action::NewIdentitySave => {
+                if !self.new_identity.can_save() {
+                    self.status.set_error_err(&Error::KeyBackupNotConfirmed);
+                } else if self
+                    .own_keys
+                    .save_action(&self.settings.security, &mut self.status)
+                {
+                    self.new_identity.finish();
+                    self.update_key_lock();
+                    self.status.set("New identity created and saved");
+                }
+            }
+            Action::NewIdentityCancel => {
+                self.new_identity.finish();
+                self.status
+                    .set("New identity flow cancelled, key is not saved");
+            }
             Action::ConfirmationYes => {
-                if let Some(conf) = &self.confirmation {
-                    match conf {
-                        Confirmation::KeysClearBeforeAction(opt_next_action) => {
-                            let prev_next_action = opt_next_action.clone();
-                            self.confirmation = None;
-                            self.action(Action::KeysClearNoConfirm);
-                            if let Some(next_action) = prev_next_action {
-                                self.action(next_action);
-                            }
-                        }
+                if let Some(conf) = self.confirmation.take() {
+                    for action in conf.actions {
+                        self.action(action);
                     }
                 }
             }
@@ -202,24 +770,221 @@ impl KeystrModel {
                     self.confirmation = None;
                 }
             }
-            Action::SignerConnect => match self.own_keys.get_signer() {
-                Err(_) => self.status.set("Key pair is not loaded or unlocked!"),
-                Ok(signer) => {
-                    self.signer.connect_action(signer, &mut self.status);
+            Action::SignerConnect => {
+                match self.check_can_connect_signer().and_then(|_| {
+                    Signer::relays_for_connect(
+                        &self.signer.connect_uri_input,
+                        &self.settings.signer,
+                    )
+                }) {
+                    Err(e) => self.status.set_error_err(&e),
+                    Ok(relays) => self.ask_confirmation(Confirmation::relays_contacted(
+                        "Connect signer?",
+                        &relays,
+                        Action::SignerConnectNoConfirm,
+                    )),
                 }
+            }
+            Action::SignerConnectNoConfirm => match self
+                .check_can_connect_signer()
+                .and_then(|_| self.get_connection_key_signer())
+            {
+                Err(Error::KeyNotSet) => self.status.set("Key pair is not loaded or unlocked!"),
+                Err(e) => self.status.set_error_err(&e),
+                Ok(signer) => match self.signer.pairing_permissions() {
+                    Err(e) => self.status.set_error_err(&e),
+                    Ok((client_pubkey, permissions)) => {
+                        self.settings
+                            .set_client_permissions(&client_pubkey, permissions);
+                        self.signer.connect_action(
+                            signer,
+                            &self.settings.signer,
+                            self.requires_fresh_unlock(),
+                            &mut self.status,
+                        );
+                    }
+                },
             },
             Action::SignerDisconnect => {
                 self.signer.disconnect_action(&mut self.status);
             }
+            Action::SignerClearError => self.signer.clear_error_action(),
+            Action::SignerToggleEventViewer => self.signer.toggle_event_viewer_action(),
+            Action::SignerSetDisplayName => {
+                let name = self.signer.display_name_input.trim().to_string();
+                match self.signer.set_display_name(&name) {
+                    None => self.status.set_error("Not connected"),
+                    Some(client_pubkey) => {
+                        self.settings.set_connection_name(&client_pubkey, &name);
+                        self.signer.display_name_input = String::new();
+                        self.status.set("Connection name set");
+                    }
+                }
+            }
+            Action::SignerRotateAppId => {
+                self.signer.rotate_app_id_action(&mut self.status);
+            }
             Action::SignerPendingIgnoreFirst => {
                 self.signer.pending_ignore_first_action(&mut self.status);
             }
-            Action::SignerPendingProcessFirst => {
-                self.signer.pending_process_first_action(&mut self.status);
+            Action::SignerPendingRejectFirst => {
+                self.signer.pending_reject_first_action(&mut self.status);
+            }
+            Action::SignerPendingBumpTime => {
+                self.signer.pending_bump_time_action(&mut self.status);
+            }
+            Action::SignerPendingRejectAll => {
+                self.signer.pending_reject_all_action(&mut self.status);
+            }
+            Action::SignerPendingExport => {
+                self.signer.pending_export_action(&mut self.status);
+            }
+            Action::SignerPendingProcessFirst => match self
+                .check_can_approve()
+                .and_then(|_| self.get_process_key_signer())
+            {
+                Err(e) => self.status.set_error_err(&e),
+                Ok(override_signer) => {
+                    self.signer.pending_process_first_action(
+                        override_signer,
+                        self.delegator.get_selected_delegation(),
+                        &mut self.status,
+                    );
+                }
+            },
+            Action::QrSavePng => self.save_qr_png_action(),
+            Action::SettingsImportRelays => {
+                let input = self.settings.relays_import_input.clone();
+                match self.settings.import_relays_from_event(&input) {
+                    Err(e) => self.status.set_error_err(&e),
+                    Ok(added) => {
+                        self.settings.relays_import_input = String::new();
+                        self.status.set(&format!("Imported {} new relays", added));
+                    }
+                }
+            }
+            Action::SettingsReset => self.ask_confirmation(Confirmation::reset_settings()),
+            Action::SettingsResetNoConfirm => self.reset_settings(),
+            Action::WizardNext => {
+                if self.wizard.next() {
+                    self.complete_first_run();
+                }
+            }
+            Action::WizardSkip => {
+                self.wizard.skip();
+                self.complete_first_run();
+            }
+            Action::SignerPendingMove(from, to) => {
+                self.signer.pending_move_action(from, to, &mut self.status);
+            }
+            Action::SignerSetAutoApprove(pubkey, trusted) => {
+                self.signer
+                    .set_auto_approve_action(pubkey, trusted, &mut self.status);
+            }
+        }
+    }
+
+    /// Return the key signer to be used for a new signer connection:
+    /// the main key, or a derived child key if an account index is given
+    fn get_connection_key_signer(&self) -> Result<KeySigner, Error> {
+        let account_input = self.signer.child_account_input.trim();
+        if account_input.is_empty() {
+            let key_signer = self.own_keys.get_signer()?;
+            if self.requires_fresh_unlock() {
+                // connect with the public key only, the key is unlocked for each signature
+                return Ok(KeySigner {
+                    keys: Keys::from_public_key(key_signer.get_public_key()),
+                });
+            }
+            if !key_signer.can_sign() {
+                return Err(Error::KeySignerLocked);
+            }
+            return Ok(key_signer);
+        }
+        let account = account_input
+            .parse::<u32>()
+            .map_err(|_e| Error::KeyInvalidDerivationAccount)?;
+        self.own_keys.derive_child(account)
+    }
+
+    /// Return the key signer to override the connection key with for the next request, if set:
+    /// a derived child key at the given account index, which must be unlocked
+    fn get_override_key_signer(&self) -> Result<Option<KeySigner>, Error> {
+        let account_input = self.signer.sign_override_account_input.trim();
+        if account_input.is_empty() {
+            return Ok(None);
+        }
+        let account = account_input
+            .parse::<u32>()
+            .map_err(|_e| Error::KeyInvalidDerivationAccount)?;
+        let key_signer = self.own_keys.derive_child(account)?;
+        if !key_signer.can_sign() {
+            return Err(Error::KeySignerLocked);
+        }
+        Ok(Some(key_signer))
+    }
+
+    /// Key signer for processing the next request: the override key if set, otherwise,
+    /// if the key requires the password for each signature, the key unlocked for this signature only
+    fn get_process_key_signer(&mut self) -> Result<Option<KeySigner>, Error> {
+        let override_signer = self.get_override_key_signer()?;
+        if override_signer.is_none() && self.requires_fresh_unlock() {
+            return Ok(Some(self.own_keys.fresh_unlock_signer()?));
+        }
+        Ok(override_signer)
+    }
+
+    /// Whether the current key needs the password for each signature
+    pub fn requires_fresh_unlock(&self) -> bool {
+        match self.own_keys.get_public_key() {
+            Err(_) => false,
+            Ok(pk) => self.settings.security.requires_fresh_unlock(&pk),
+        }
+    }
+
+    /// Require the password for each signature with the current key, or not.
+    /// When enabled, the secret key is locked right away.
+    pub fn set_fresh_unlock(&mut self, enabled: bool) {
+        let pubkey = match self.own_keys.get_public_key() {
+            Err(e) => return self.status.set_error_err(&e),
+            Ok(pk) => pk,
+        };
+        if enabled && !self.own_keys.is_encrypted_secret_key_set() {
+            return self.status.set_error_err(&Error::KeyFreshUnlockNotSaved);
+        }
+        self.settings.set_fresh_unlock(&pubkey, enabled);
+        self.lock_if_fresh_unlock();
+    }
+
+    /// Lock the secret key if the key needs the password for each signature, not to keep it unlocked
+    fn lock_if_fresh_unlock(&mut self) {
+        if self.requires_fresh_unlock() && self.own_keys.is_secret_key_set() {
+            if let Err(e) = self.own_keys.lock() {
+                self.status.set_error_err(&e);
             }
         }
     }
 
+    /// Details of the last error (with source chain), if any
+    pub fn last_error(&self) -> Option<ErrorDetail> {
+        self.status.get_last_error()
+    }
+
+    /// Confirmation for replacing the loaded keys by the action;
+    /// warns specifically if the current secret key has not been saved and would be lost
+    fn replace_keys_confirmation(&self, next_action: Action) -> Confirmation {
+        if self.own_keys.has_unsaved_secret_key() {
+            Confirmation::unsaved_key_lost_before(next_action)
+        } else {
+            Confirmation::keys_clear_before(Some(next_action))
+        }
+    }
+
+    /// Ask for user confirmation before performing the actions of the confirmation
+    pub fn ask_confirmation(&mut self, confirmation: Confirmation) {
+        self.confirmation = Some(confirmation);
+    }
+
     /// Return the current modal dialog (operation for which user attention is needed)
     pub fn get_modal(&self) -> Option<Modal> {
         if let Some(conf) = &self.confirmation {
@@ -254,6 +1019,31 @@ impl KeystrModel {
         &self.qr_code
     }
 
+    /// Save the QR code shown as a PNG image, to the file of the path input,
+    /// or to the default file in the storage folder (there is no native file dialog)
+    fn save_qr_png_action(&mut self) {
+        let content = match &self.qr_code {
+            None => return,
+            Some((content, _)) => content.clone(),
+        };
+        let path_input = self.qr_png_path_input.trim();
+        let res = if path_input.is_empty() {
+            let path = Storage::qr_png_file();
+            Storage::check_create_folder()
+                .and_then(|_| save_qr_png(&content, &path))
+                .map(|_| path)
+        } else {
+            let path = PathBuf::from(path_input);
+            save_qr_png(&content, &path).map(|_| path)
+        };
+        match res {
+            Err(e) => self.status.set_error_err(&e),
+            Ok(path) => self
+                .status
+                .set(&format!("QR code saved to {}", path.display())),
+        }
+    }
+
     /*
     /// Blocking wait for an event from the model
     pub fn get_event() -> Result<Event, Error> {
@@ -262,16 +1052,160 @@ impl KeystrModel {
     */
 }
 
+impl Readiness {
+    /// Short label of the key state
+    pub fn label(&self) -> &'static str {
+        match self {
+            Readiness::NoKey => "no key",
+            Readiness::WatchOnly => "watch-only",
+            Readiness::Locked => "locked",
+            Readiness::Unlocked => "unlocked",
+        }
+    }
+
+    /// Delegation and signer need the secret key
+    pub fn can_sign(&self) -> bool {
+        *self == Readiness::Unlocked
+    }
+
+    /// Guidance on what to do to use a feature needing the secret key, None if it is available
+    pub fn guidance(&self, feature: &str) -> Option<String> {
+        if self.can_sign() {
+            return None;
+        }
+        match self {
+            Readiness::NoKey => Some(format!("Generate or import a key to use the {}", feature)),
+            Readiness::WatchOnly => Some(format!(
+                "Only a public key is loaded, import the secret key to use the {}",
+                feature
+            )),
+            Readiness::Locked | Readiness::Unlocked => {
+                Some(format!("Unlock the secret key to use the {}", feature))
+            }
+        }
+    }
+}
+
+/// Window title: app name, the active key (name) with its state, and the number of pending requests
+fn window_title(key_name: Option<&str>, readiness: Readiness, pending: usize) -> String {
+    let mut title = String::from("Keystr");
+    if let Some(name) = key_name {
+        title.push_str(&format!(" — {} ({})", name, readiness.label()));
+    }
+    if pending > 0 {
+        title.push_str(&format!(" — {} pending", pending));
+    }
+    title
+}
+
+/// Shortened form of a key string, keeping the start and the end
+fn shortened_key(key: &str) -> String {
+    if key.len() <= 16 {
+        return key.to_string();
+    }
+    format!("{}..{}", &key[..10], &key[key.len() - 4..])
+}
+
+impl Confirmation {
+    pub fn new(title: &str, body: &str, actions: Vec<Action>) -> Self {
+        Self {
+            title: title.to_string(),
+            body: body.to_string(),
+            actions,
+        }
+    }
+
+    /// Confirmation for clearing existing keys, optionally followed by another action
+    pub fn keys_clear_before(next_action: Option<Action>) -> Self {
+        let mut actions = vec![Action::KeysClearNoConfirm];
+        if let Some(a) = next_action {
+            actions.push(a);
+        }
+        Self::new(
+            "Remove existing keys?",
+            "The currently loaded keys will be removed.",
+            actions,
+        )
+    }
+
+    /// Confirmation for replacing a secret key that has not been saved, followed by another action
+    pub fn unsaved_key_lost_before(next_action: Action) -> Self {
+        Self::new(
+            "Discard unsaved key?",
+            "Your current key hasn't been saved and will be lost.\n\
+            Save it or back up the secret key first, if you want to keep it.",
+            vec![Action::KeysClearNoConfirm, next_action],
+        )
+    }
+
+    /// Confirmation before contacting relays, listing exactly which ones
+    pub fn relays_contacted(title: &str, relays: &[String], action: Action) -> Self {
+        Self::new(
+            title,
+            &format!(
+                "The following relays will be contacted:\n{}",
+                relays.join("\n")
+            ),
+            vec![action],
+        )
+    }
+
+    /// Confirmation for resetting all settings to defaults
+    pub fn reset_settings() -> Self {
+        Self::new(
+            "Reset settings?",
+            "All settings will be restored to their defaults. Keys are not affected.",
+            vec![Action::SettingsResetNoConfirm],
+        )
+    }
+}
+
 impl EventQueue {
-    fn new() -> Self {
+    pub fn new() -> Self {
         let (sender, receiver) = channel::bounded::<Event>(100);
-        Self { sender, receiver }
+        let (closed, _) = watch::channel(false);
+        Self {
+            sender,
+            receiver,
+            closed,
+        }
     }
 
+    /// Push without blocking; fails if the queue is full or closed
     pub fn push(&self, e: Event) -> Result<(), Error> {
-        self.sender
-            .send(e)
-            .map_err(|_e| Error::InternalEventQueueSend)
+        if self.is_closed() {
+            return Err(Error::InternalEventQueueClosed);
+        }
+        self.sender.try_send(e).map_err(|e| match e {
+            channel::TrySendError::Full(_) => Error::InternalEventQueueFull,
+            channel::TrySendError::Disconnected(_) => Error::InternalEventQueueClosed,
+        })
+    }
+
+    /// Push, tolerating a full queue (logged; the UI catches up on the next event).
+    /// Fails only if the queue is closed, background tasks should stop then.
+    pub fn notify(&self, e: Event) -> Result<(), Error> {
+        match self.push(e) {
+            Err(Error::InternalEventQueueFull) => {
+                eprintln!("WARNING: Event queue full, UI notification dropped");
+                Ok(())
+            }
+            res => res,
+        }
+    }
+
+    /// Mark the queue closed, on shutdown
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+
+    /// Receiver notified when the queue gets closed
+    pub fn closed_receiver(&self) -> watch::Receiver<bool> {
+        self.closed.subscribe()
     }
 
     pub fn pop(&self) -> Result<Event, Error> {
@@ -283,6 +1217,314 @@ impl EventQueue {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::base::encrypt::{Encrypt, EncryptionAlgorithm};
+    use crate::base::error::help_text;
+    use crate::base::storage::TestStorage;
+    use crate::model::display_settings::DisplayFormat;
+    use crate::model::new_identity::NewIdentityStep;
+    use crate::model::security_settings::SecurityLevel;
+    use nostr::prelude::ToBech32;
+
+    #[test]
+    fn test_event_queue_full_closed() {
+        let q = EventQueue::new();
+        for _i in 0..100 {
+            q.push(Event::StatusUpdate).unwrap();
+        }
+        assert!(matches!(
+            q.push(Event::StatusUpdate),
+            Err(Error::InternalEventQueueFull)
+        ));
+        assert!(q.notify(Event::StatusUpdate).is_ok());
+
+        let closed_rx = q.closed_receiver();
+        assert!(!q.is_closed());
+        q.close();
+        assert!(q.is_closed());
+        assert!(*closed_rx.borrow());
+        assert!(matches!(
+            q.notify(Event::StatusUpdate),
+            Err(Error::InternalEventQueueClosed)
+        ));
+    }
+
+    #[test]
+    fn test_first_run_wizard() {
+        let mut m = KeystrModel::new();
+        assert!(!m.settings.first_run_completed);
+        m.wizard = Wizard::new(m.settings.first_run_completed);
+        assert!(m.wizard.is_active());
+
+        m.action(Action::WizardNext);
+        m.action(Action::WizardNext);
+        m.wizard.relays_input = "wss://relay.example.com".to_string();
+        assert!(!m.settings.first_run_completed);
+        m.action(Action::WizardNext);
+        assert!(!m.wizard.is_active());
+        assert!(m.settings.first_run_completed);
+        assert_eq!(
+            m.settings.signer.response_relays,
+            vec!["wss://relay.example.com".to_string()]
+        );
+
+        // not shown again
+        assert!(!Wizard::new(m.settings.first_run_completed).is_active());
+
+        // skip also completes
+        let mut m = KeystrModel::new();
+        m.wizard = Wizard::new(m.settings.first_run_completed);
+        m.action(Action::WizardSkip);
+        assert!(!m.wizard.is_active());
+        assert!(m.settings.first_run_completed);
+    }
+
+    #[test]
+    fn test_auto_save_after_generate() {
+        let _storage = TestStorage::new("auto-save");
+        let mut m = KeystrModel::new();
+        m.settings
+            .set_security_level(SecurityLevel::PersistOptionalPassword);
+        m.settings.set_auto_save(true);
+
+        m.action(Action::KeysGenerate);
+        assert_eq!(m.status.get_last(), "Secret key persisted to storage");
+        let saved_npub = std::fs::read_to_string(Storage::public_key_file()).unwrap();
+        assert_eq!(saved_npub, m.own_keys.get_npub());
+
+        // mandatory password missing: not saved, user is prompted
+        let mut m = KeystrModel::new();
+        m.settings
+            .set_security_level(SecurityLevel::PersistMandatoryPassword);
+        m.settings.set_auto_save(true);
+        m.action(Action::KeysGenerate);
+        assert!(m.status.get_last().starts_with("Auto-save: enter"));
+    }
+
+    #[test]
+    fn test_new_identity_flow() {
+        let _storage = TestStorage::new("new-identity");
+        let mut m = KeystrModel::new();
+        m.settings
+            .set_security_level(SecurityLevel::PersistMandatoryPassword);
+
+        m.action(Action::NewIdentityStart);
+        assert!(m.own_keys.keys_is_set());
+        let words = m.new_identity.mnemonic().unwrap().to_string();
+        assert_eq!(words.split(' ').count(), 12);
+        // the key is derived from the shown words
+        let mut check = Keystore::new();
+        check.import_mnemonic(&words, false).unwrap();
+        assert_eq!(check.get_npub(), m.own_keys.get_npub());
+
+        // save is gated by the backup confirmation
+        m.action(Action::NewIdentitySave);
+        assert_eq!(
+            m.last_error().unwrap().help,
+            help_text("backup-not-confirmed")
+        );
+        m.action(Action::NewIdentityBackupDone);
+        assert_eq!(m.new_identity.step(), Some(NewIdentityStep::Backup));
+        assert!(m.own_keys.has_unsaved_change);
+
+        m.new_identity.backup_confirmed = true;
+        m.action(Action::NewIdentityBackupDone);
+        assert_eq!(m.new_identity.step(), Some(NewIdentityStep::Password));
+        assert!(m.new_identity.mnemonic().is_none());
+
+        // mandatory password missing
+        m.action(Action::NewIdentitySave);
+        assert_eq!(m.new_identity.step(), Some(NewIdentityStep::Password));
+
+        m.own_keys.save_password_input = "password".to_string();
+        m.own_keys.save_repeat_password_input = "password".to_string();
+        m.action(Action::NewIdentitySave);
+        assert_eq!(m.new_identity.step(), None);
+        assert_eq!(m.status.get_last(), "New identity created and saved");
+        let saved_npub = std::fs::read_to_string(Storage::public_key_file()).unwrap();
+        assert_eq!(saved_npub, m.own_keys.get_npub());
+    }
+
+    #[test]
+    fn test_ephemeral_key_after_save() {
+        let mut m = KeystrModel::new();
+        assert!(!m.own_keys.is_ephemeral());
+        m.settings
+            .set_security_level(SecurityLevel::PersistOptionalPassword);
+
+        m.action(Action::KeysGenerate);
+        assert!(m.own_keys.is_ephemeral());
+
+        m.action(Action::KeysSave);
+        assert_eq!(m.status.get_last(), "Secret key persisted to storage");
+        assert!(!m.own_keys.is_ephemeral());
+
+        // a new key is not persisted yet
+        m.own_keys.generate();
+        assert!(m.own_keys.is_ephemeral());
+    }
+
+    #[test]
+    fn test_saved_pubkey_read_once() {
+        let _storage = TestStorage::new("saved-pubkey");
+        let mut m = KeystrModel::new();
+        m.settings
+            .set_security_level(SecurityLevel::PersistOptionalPassword);
+        m.action(Action::KeysGenerate);
+        m.own_keys.save_password_input = "password".to_string();
+        m.own_keys.save_repeat_password_input = "password".to_string();
+        m.action(Action::KeysSave);
+        let pubkey = m.own_keys.get_public_key().unwrap();
+        m.update_saved_pubkey();
+        assert_eq!(m.saved_pubkey, None);
+
+        // read when the key gets locked
+        m.own_keys.load_keys().unwrap();
+        assert_eq!(m.readiness(), Readiness::Locked);
+        m.update_saved_pubkey();
+        assert_eq!(m.saved_pubkey, Some(pubkey));
+
+        // not read again while locked
+        std::fs::remove_file(Storage::public_key_file()).unwrap();
+        m.update_saved_pubkey();
+        assert_eq!(m.saved_pubkey, Some(pubkey));
+
+        m.own_keys.decrypt_password_input = "password".to_string();
+        m.action(Action::KeysUnlock);
+        m.update_saved_pubkey();
+        assert_eq!(m.saved_pubkey, None);
+    }
+
+    #[test]
+    fn test_readiness() {
+        let mut m = KeystrModel::new();
+        assert_eq!(m.readiness(), Readiness::NoKey);
+        assert!(!m.readiness().can_sign());
+        assert_eq!(
+            m.readiness().guidance("signer").unwrap(),
+            "Generate or import a key to use the signer"
+        );
+
+        m.own_keys
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert_eq!(m.readiness(), Readiness::WatchOnly);
+        assert!(!m.readiness().can_sign());
+        assert!(m.readiness().guidance("signer").is_some());
+
+        m.own_keys
+            .import_encrypted_secret_key("0102030405060708", false)
+            .unwrap();
+        assert_eq!(m.readiness(), Readiness::Locked);
+        assert!(!m.readiness().can_sign());
+        assert!(m.readiness().guidance("signer").is_some());
+
+        m.action(Action::KeysGenerate);
+        assert_eq!(m.readiness(), Readiness::Unlocked);
+        assert!(m.readiness().can_sign());
+        assert!(m.readiness().guidance("signer").is_none());
+    }
+
+    #[test]
+    fn test_connect_watch_only_refused() {
+        let mut m = KeystrModel::new();
+        m.own_keys
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert!(matches!(
+            m.check_can_connect_signer(),
+            Err(Error::SignerWatchOnly)
+        ));
+
+        m.signer.connect_uri_input = "nostrconnect://b889ff5b1513b641e2a139f661a661364979c5beee91842f8f0ef42ab558e9d4?relay=ws%3A%2F%2F127.0.0.1%3A9%2F&metadata=%7B%22name%22%3A%22Example%22%7D".to_string();
+        m.action(Action::SignerConnect);
+        assert!(m.confirmation.is_none());
+        let detail = m.status.get_last_error().unwrap();
+        assert!(detail.message.contains("watch-only"));
+
+        m.action(Action::SignerConnectNoConfirm);
+        assert!(matches!(
+            m.signer.get_connection_status(),
+            ConnectionStatus::NotConnected
+        ));
+        assert!(m
+            .status
+            .get_last_error()
+            .unwrap()
+            .message
+            .contains("watch-only"));
+
+        m.own_keys.generate();
+        assert!(m.check_can_connect_signer().is_ok());
+    }
+
+    #[test]
+    fn test_blank_inputs_friendly_error() {
+        let mut m = KeystrModel::new();
+        m.own_keys.public_key_input = " ".to_string();
+        m.action(Action::KeysImportPubkey);
+        assert!(m.status.get_last().contains("Public key is empty"));
+        m.own_keys.secret_key_input = " \t".to_string().into();
+        m.action(Action::KeysImportSecretkey);
+        assert!(m.status.get_last().contains("Secret key is empty"));
+        m.own_keys.mnemonic_input = "\n".to_string();
+        m.action(Action::KeysImportMnemonic);
+        assert!(m.status.get_last().contains("Mnemonic is empty"));
+        m.own_keys.client_export_input = "  ".to_string().into();
+        m.action(Action::KeysImportClientExport);
+        assert!(m.status.get_last().contains("Client export is empty"));
+        assert!(!m.own_keys.keys_is_set());
+
+        // the password is checked before decrypting
+        m.own_keys.import_encrypted_secret_key("00", false).unwrap();
+        assert_eq!(
+            m.settings.security.security_level,
+            SecurityLevel::PersistMandatoryPassword
+        );
+        m.action(Action::KeysUnlock);
+        assert!(m.status.get_last().contains("Password is empty"));
+
+        m.own_keys.generate();
+        m.signer.connect_uri_input = "   ".to_string();
+        m.action(Action::SignerConnect);
+        assert!(m.confirmation.is_none());
+        assert_eq!(
+            m.status.get_last_error().unwrap().message,
+            "Connect URI is empty, enter it first"
+        );
+    }
+
+    #[test]
+    fn test_save_qr_png_action() {
+        let npub = "npub1c82zv3aj04l8dmxlxywx5fsg6ngt5nyvwa9j0eqk03ntg2t2jtxqngn7ry";
+        let dir = std::env::temp_dir().join(format!("keystr-test-qr-model-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("my-npub.png");
+        let mut m = KeystrModel::new();
+        m.qr_png_path_input = format!(" {} ", path.display());
+
+        // no QR code shown: nothing to save
+        m.action(Action::QrSavePng);
+        assert!(!path.exists());
+
+        m.set_qr_code(npub.to_string());
+        m.action(Action::QrSavePng);
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        assert_eq!(
+            m.status.get_last(),
+            format!("QR code saved to {}", path.display())
+        );
+
+        // unwritable path: clear error
+        m.qr_png_path_input = dir.join("missing").join("x.png").display().to_string();
+        m.action(Action::QrSavePng);
+        assert!(m
+            .status
+            .get_last_error()
+            .unwrap()
+            .message
+            .starts_with("Could not save the QR code to"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 
     #[test]
     fn test_clear_generate_confirmation() {
@@ -320,4 +1562,268 @@ mod test {
         assert_eq!(m.own_keys.keys_is_set(), false);
         assert!(m.confirmation.is_none());
     }
+
+    #[test]
+    fn test_import_over_unsaved_key_confirmation() {
+        let nsec = Keys::generate().secret_key().unwrap().to_bech32().unwrap();
+
+        // no key: import without confirmation
+        let mut m = KeystrModel::new();
+        m.own_keys.secret_key_input = nsec.clone().into();
+        m.action(Action::KeysImportSecretkey);
+        assert!(m.confirmation.is_none());
+        assert!(m.own_keys.secret_key_input.is_empty());
+        assert_eq!(m.status.get_last(), "Secret key imported");
+
+        // unsaved key loaded: specific warning
+        let mut m = KeystrModel::new();
+        m.action(Action::KeysGenerate);
+        assert!(m.own_keys.has_unsaved_secret_key());
+        let generated = m.own_keys.get_npub();
+        m.own_keys.secret_key_input = nsec.clone().into();
+        m.action(Action::KeysImportSecretkey);
+        let conf = m.confirmation.clone().unwrap();
+        assert_eq!(conf.title, "Discard unsaved key?");
+        assert!(conf.body.contains("hasn't been saved and will be lost"));
+        assert_eq!(m.own_keys.get_npub(), generated);
+
+        // No keeps the key, and the input
+        m.action(Action::ConfirmationNo);
+        assert_eq!(m.own_keys.get_npub(), generated);
+        assert_eq!(m.own_keys.secret_key_input.as_str(), nsec);
+
+        // Yes replaces it
+        m.action(Action::KeysImportSecretkey);
+        m.action(Action::ConfirmationYes);
+        assert!(m.confirmation.is_none());
+        assert_ne!(m.own_keys.get_npub(), generated);
+        assert_eq!(m.status.get_last(), "Secret key imported");
+
+        // saved key loaded: the regular overwrite confirmation
+        let mut m = KeystrModel::new();
+        m.own_keys
+            .import_secret_key(
+                &Keys::generate().secret_key().unwrap().to_bech32().unwrap(),
+                false,
+            )
+            .unwrap();
+        assert!(!m.own_keys.has_unsaved_secret_key());
+        m.own_keys.mnemonic_input = "oil oil oil oil oil oil oil oil oil oil oil oil".to_string();
+        m.action(Action::KeysImportMnemonic);
+        assert_eq!(
+            m.confirmation.clone().unwrap().title,
+            "Remove existing keys?"
+        );
+
+        // watch-only key: nothing secret to lose
+        let mut m = KeystrModel::new();
+        m.own_keys.public_key_input = Keys::generate().public_key().to_string();
+        m.action(Action::KeysImportPubkey);
+        assert!(m.own_keys.is_ephemeral());
+        assert!(!m.own_keys.has_unsaved_secret_key());
+        m.action(Action::KeysGenerate);
+        assert_eq!(
+            m.confirmation.clone().unwrap().title,
+            "Remove existing keys?"
+        );
+    }
+
+    #[test]
+    fn test_publish_relay_list_confirmation_relays() {
+        let mut m = KeystrModel::new();
+        m.action(Action::KeysGenerate);
+
+        // nothing to publish
+        m.action(Action::KeysPublishRelayList);
+        assert!(m.confirmation.is_none());
+
+        m.settings
+            .set_signer_response_relays(vec!["wss://response.example.com".to_string()]);
+        m.action(Action::KeysPublishRelayList);
+        let conf = m.confirmation.take().unwrap();
+        assert!(conf.body.ends_with("\nwss://response.example.com"));
+
+        m.own_keys.relay_list_input =
+            "wss://relay1.example.com read, wss://relay2.example.com".to_string();
+        m.action(Action::KeysPublishRelayList);
+        let conf = m.confirmation.take().unwrap();
+        let urls: Vec<String> = m
+            .relays_to_publish()
+            .unwrap()
+            .into_iter()
+            .map(|(url, _)| url)
+            .collect();
+        assert_eq!(urls.len(), 2);
+        assert_eq!(conf.body.lines().skip(1).collect::<Vec<_>>(), urls);
+        assert!(matches!(
+            conf.actions[..],
+            [Action::KeysPublishRelayListNoConfirm]
+        ));
+    }
+
+    #[test]
+    fn test_window_title() {
+        assert_eq!(window_title(None, Readiness::NoKey, 0), "Keystr");
+        assert_eq!(
+            window_title(None, Readiness::NoKey, 2),
+            "Keystr — 2 pending"
+        );
+        for (readiness, label) in [
+            (Readiness::WatchOnly, "watch-only"),
+            (Readiness::Locked, "locked"),
+            (Readiness::Unlocked, "unlocked"),
+        ] {
+            assert_eq!(
+                window_title(Some("alice"), readiness, 0),
+                format!("Keystr — alice ({})", label)
+            );
+            assert_eq!(
+                window_title(Some("alice"), readiness, 3),
+                format!("Keystr — alice ({}) — 3 pending", label)
+            );
+        }
+
+        let mut m = KeystrModel::new();
+        assert_eq!(m.window_title(), "Keystr");
+        m.own_keys
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert_eq!(m.window_title(), "Keystr — npub1rfze4..lmd4 (watch-only)");
+        m.own_keys.nip05_input = "alice@example.com".to_string();
+        assert_eq!(m.window_title(), "Keystr — alice@example.com (watch-only)");
+    }
+
+    #[test]
+    fn test_generic_confirmation() {
+        let mut m = KeystrModel::new();
+        m.ask_confirmation(Confirmation::new(
+            "Generate?",
+            "Generate new keys",
+            vec![Action::KeysGenerate],
+        ));
+        assert!(m.confirmation.is_some());
+        assert!(!m.own_keys.keys_is_set());
+
+        // Yes runs the stored action
+        m.action(Action::ConfirmationYes);
+        assert!(m.confirmation.is_none());
+        assert!(m.own_keys.keys_is_set());
+
+        // No does not
+        m.ask_confirmation(Confirmation::new(
+            "Clear?",
+            "Clear keys",
+            vec![Action::KeysClearNoConfirm],
+        ));
+        m.action(Action::ConfirmationNo);
+        assert!(m.confirmation.is_none());
+        assert!(m.own_keys.keys_is_set());
+    }
+
+    #[test]
+    fn test_approve_requires_focus() {
+        let mut m = KeystrModel::new();
+        m.set_window_focused(false);
+        // not required by default
+        assert!(m.check_can_approve().is_ok());
+
+        m.settings.set_signer_require_focus_to_approve(true);
+        assert!(matches!(
+            m.check_can_approve(),
+            Err(Error::SignerWindowNotFocused)
+        ));
+        m.action(Action::SignerPendingProcessFirst);
+        assert_eq!(
+            m.last_error().unwrap().help,
+            help_text("signer-window-not-focused")
+        );
+
+        m.set_window_focused(true);
+        assert!(m.check_can_approve().is_ok());
+    }
+
+    #[test]
+    fn test_reset_settings() {
+        let mut m = KeystrModel::new();
+        m.action(Action::KeysGenerate);
+        let npub = m.own_keys.get_npub();
+        m.settings.set_display_format(DisplayFormat::Hex);
+        m.settings.set_signer_allow_sign_raw(true);
+        m.settings.set_first_run_completed();
+        let defaults = serde_json::to_string(&Settings::default()).unwrap();
+        assert_ne!(serde_json::to_string(&m.settings).unwrap(), defaults);
+
+        // requires confirmation
+        m.action(Action::SettingsReset);
+        assert!(m.confirmation.is_some());
+        m.action(Action::ConfirmationNo);
+        assert_ne!(serde_json::to_string(&m.settings).unwrap(), defaults);
+
+        m.action(Action::SettingsReset);
+        m.action(Action::ConfirmationYes);
+        assert_eq!(serde_json::to_string(&m.settings).unwrap(), defaults);
+        // keys untouched
+        assert_eq!(m.own_keys.get_npub(), npub);
+        assert!(m.own_keys.is_secret_key_set());
+    }
+
+    #[test]
+    fn test_fresh_unlock_each_sign() {
+        let _storage = TestStorage::new("fresh-unlock");
+        let keys = Keys::generate();
+        let encrypted = Encrypt::encrypt_key(
+            &keys.secret_key().unwrap(),
+            "password",
+            4,
+            EncryptionAlgorithm::default(),
+        )
+        .unwrap();
+        let mut m = KeystrModel::new();
+
+        // needs the encrypted key
+        m.own_keys.generate();
+        m.set_fresh_unlock(true);
+        assert!(!m.requires_fresh_unlock());
+
+        m.own_keys
+            .import_encrypted_secret_key(&hex::encode(&encrypted), false)
+            .unwrap();
+        m.own_keys.decrypt_password_input = "password".to_string();
+        m.action(Action::KeysUnlock);
+        assert_eq!(m.readiness(), Readiness::Unlocked);
+
+        // locked right away, the public key is kept
+        m.set_fresh_unlock(true);
+        assert!(m.requires_fresh_unlock());
+        assert_eq!(m.readiness(), Readiness::Locked);
+        assert_eq!(m.own_keys.get_public_key().unwrap(), keys.public_key());
+        // connected with the public key only
+        assert!(!m.get_connection_key_signer().unwrap().can_sign());
+
+        for _ in 0..2 {
+            // each process attempt needs the password
+            assert!(matches!(
+                m.get_process_key_signer(),
+                Err(Error::KeyFreshUnlockRequired)
+            ));
+            m.own_keys.decrypt_password_input = "password".to_string();
+            let key_signer = m.get_process_key_signer().unwrap().unwrap();
+            assert!(key_signer.can_sign());
+            assert_eq!(key_signer.get_public_key(), keys.public_key());
+            assert!(m.own_keys.decrypt_password_input.is_empty());
+            assert_eq!(m.readiness(), Readiness::Locked);
+        }
+
+        // unlocking does not keep the key unlocked
+        m.own_keys.decrypt_password_input = "password".to_string();
+        m.action(Action::KeysUnlock);
+        assert_eq!(m.readiness(), Readiness::Locked);
+
+        m.set_fresh_unlock(false);
+        assert!(!m.requires_fresh_unlock());
+        assert!(matches!(
+            m.get_connection_key_signer(),
+            Err(Error::KeySignerLocked)
+        ));
+    }
 }
@@ -0,0 +1,178 @@
+//! Minimal in-process Nostr relay, for tests talking to a relay.
+//! It records what the clients send, answers events with OK and subscriptions with EOSE,
+//! and can push messages (e.g. AUTH challenges, events) to the connected clients.
+
+use futures_util::{SinkExt, StreamExt};
+use nostr::prelude::{ClientMessage, Event, RelayMessage, SubscriptionId};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+/// Something to push to the connected clients
+#[derive(Clone)]
+enum Push {
+    Message(RelayMessage),
+    /// An event, sent on all subscriptions of the client
+    Event(Box<Event>),
+}
+
+struct RelayState {
+    received: Vec<ClientMessage>,
+    /// Answer to events: accepted, and the message
+    ok_reply: (bool, String),
+}
+
+pub(crate) struct MockRelay {
+    url: String,
+    state: Arc<Mutex<RelayState>>,
+    pushes: broadcast::Sender<Push>,
+}
+
+impl MockRelay {
+    /// Start listening on a free local port
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(RelayState {
+            received: Vec::new(),
+            ok_reply: (true, String::new()),
+        }));
+        let (pushes, _rx) = broadcast::channel(64);
+        let state_clone = state.clone();
+        let pushes_clone = pushes.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _addr)) = listener.accept().await {
+                tokio::spawn(serve_client(
+                    stream,
+                    state_clone.clone(),
+                    pushes_clone.subscribe(),
+                ));
+            }
+        });
+        MockRelay { url, state, pushes }
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Answer the next events with this OK message
+    pub fn set_ok_reply(&self, accepted: bool, message: &str) {
+        self.state.lock().unwrap().ok_reply = (accepted, message.to_string());
+    }
+
+    /// Messages received from the clients so far
+    pub fn received(&self) -> Vec<ClientMessage> {
+        self.state.lock().unwrap().received.clone()
+    }
+
+    /// Number of subscription requests (REQ) received so far
+    pub fn req_count(&self) -> usize {
+        self.received()
+            .iter()
+            .filter(|m| matches!(m, ClientMessage::Req { .. }))
+            .count()
+    }
+
+    /// Send a message to the connected clients
+    pub fn send(&self, msg: RelayMessage) {
+        let _res = self.pushes.send(Push::Message(msg));
+    }
+
+    /// Send an event to the connected clients, on their subscriptions
+    pub fn send_event(&self, event: Event) {
+        let _res = self.pushes.send(Push::Event(Box::new(event)));
+    }
+
+    /// Wait until the received messages satisfy the condition, false on timeout
+    pub async fn wait_for_received<F>(&self, timeout: Duration, cond: F) -> bool
+    where
+        F: Fn(&[ClientMessage]) -> bool,
+    {
+        wait_until(timeout, || cond(&self.received())).await
+    }
+}
+
+/// Poll the condition until it holds, false on timeout
+pub(crate) async fn wait_until<F>(timeout: Duration, cond: F) -> bool
+where
+    F: Fn() -> bool,
+{
+    let start = tokio::time::Instant::now();
+    while start.elapsed() < timeout {
+        if cond() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    cond()
+}
+
+async fn serve_client(
+    stream: TcpStream,
+    state: Arc<Mutex<RelayState>>,
+    mut pushes: broadcast::Receiver<Push>,
+) {
+    let ws = match tokio_tungstenite::accept_async(stream).await {
+        Err(_) => return,
+        Ok(ws) => ws,
+    };
+    let (mut sink, mut source) = ws.split();
+    let mut subscriptions: Vec<SubscriptionId> = Vec::new();
+    loop {
+        let replies = tokio::select! {
+            msg = source.next() => match msg {
+                Some(Ok(WsMessage::Text(text))) => match ClientMessage::from_json(text) {
+                    Ok(msg) => handle_client_message(&state, &mut subscriptions, msg),
+                    Err(_) => Vec::new(),
+                },
+                Some(Ok(_)) => Vec::new(),
+                _ => break,
+            },
+            push = pushes.recv() => match push {
+                Ok(Push::Message(msg)) => vec![msg],
+                Ok(Push::Event(event)) => subscriptions
+                    .iter()
+                    .map(|s| RelayMessage::new_event(s.clone(), *event.clone()))
+                    .collect(),
+                Err(broadcast::error::RecvError::Lagged(_)) => Vec::new(),
+                Err(_) => break,
+            },
+        };
+        for reply in replies {
+            if sink.send(WsMessage::Text(reply.as_json())).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn handle_client_message(
+    state: &Mutex<RelayState>,
+    subscriptions: &mut Vec<SubscriptionId>,
+    msg: ClientMessage,
+) -> Vec<RelayMessage> {
+    let mut state = state.lock().unwrap();
+    state.received.push(msg.clone());
+    match msg {
+        ClientMessage::Event(event) => {
+            let (accepted, message) = state.ok_reply.clone();
+            vec![RelayMessage::new_ok(event.id, accepted, message)]
+        }
+        ClientMessage::Req {
+            subscription_id, ..
+        } => {
+            if !subscriptions.contains(&subscription_id) {
+                subscriptions.push(subscription_id.clone());
+            }
+            vec![RelayMessage::new_eose(subscription_id)]
+        }
+        ClientMessage::Close(subscription_id) => {
+            subscriptions.retain(|s| *s != subscription_id);
+            Vec::new()
+        }
+        _ => Vec::new(),
+    }
+}
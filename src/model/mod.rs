@@ -1,7 +1,19 @@
+pub mod activity_log;
 pub mod delegator;
+pub mod display_settings;
+pub mod health;
+pub mod key_audit;
 pub mod keystore;
 pub mod keystr_model;
+#[cfg(test)]
+pub mod mock_relay;
+pub mod new_identity;
+pub mod note_signer;
 pub mod security_settings;
 pub mod settings;
 pub mod signer;
+pub mod signer_settings;
 pub mod status_messages;
+pub mod tray;
+pub mod vanity;
+pub mod wizard;
@@ -0,0 +1,131 @@
+use crate::base::error::Error;
+
+use nostr::secp256k1::rand::{thread_rng, RngCore};
+use zeroize::Zeroize;
+
+/// Size of the mnemonic entropy, 16 bytes yields 12 words
+const MNEMONIC_ENTROPY_LEN: usize = 16;
+
+/// Steps of the quick new identity flow, in order
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum NewIdentityStep {
+    /// Show the mnemonic words for backup, until confirmed
+    Backup,
+    /// Set password and save
+    Password,
+}
+
+/// Model for the quick new identity flow: generate a mnemonic-backed key,
+/// show the words once for backup, confirm, set a password, and save
+pub(crate) struct NewIdentity {
+    step: Option<NewIdentityStep>,
+    /// Generated mnemonic, shown only until the backup is confirmed
+    mnemonic: Option<String>,
+    /// Set by the user after writing down the words; gates the save
+    pub backup_confirmed: bool,
+}
+
+impl NewIdentity {
+    pub fn new() -> Self {
+        Self {
+            step: None,
+            mnemonic: None,
+            backup_confirmed: false,
+        }
+    }
+
+    pub fn step(&self) -> Option<NewIdentityStep> {
+        self.step
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Start the flow with a newly generated mnemonic; returns the words (to be imported)
+    pub fn start(&mut self) -> Result<String, Error> {
+        let mut entropy = [0u8; MNEMONIC_ENTROPY_LEN];
+        thread_rng().fill_bytes(&mut entropy);
+        let res = bip39::Mnemonic::from_entropy(&entropy);
+        entropy.zeroize();
+        let words = res?.to_string();
+        self.clear_mnemonic();
+        self.mnemonic = Some(words.clone());
+        self.backup_confirmed = false;
+        self.step = Some(NewIdentityStep::Backup);
+        Ok(words)
+    }
+
+    /// Mnemonic words to show for backup, only in the backup step
+    pub fn mnemonic(&self) -> Option<&str> {
+        match self.step {
+            Some(NewIdentityStep::Backup) => self.mnemonic.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Proceed to the password step, if the backup has been confirmed.
+    /// The words are not shown any more.
+    pub fn backup_done(&mut self) -> Result<(), Error> {
+        if self.step != Some(NewIdentityStep::Backup) || !self.backup_confirmed {
+            return Err(Error::KeyBackupNotConfirmed);
+        }
+        self.clear_mnemonic();
+        self.step = Some(NewIdentityStep::Password);
+        Ok(())
+    }
+
+    /// Save is allowed only in the password step, i.e. after the backup is confirmed
+    pub fn can_save(&self) -> bool {
+        self.step == Some(NewIdentityStep::Password) && self.backup_confirmed
+    }
+
+    /// End the flow (completed or cancelled)
+    pub fn finish(&mut self) {
+        self.clear_mnemonic();
+        self.backup_confirmed = false;
+        self.step = None;
+    }
+
+    fn clear_mnemonic(&mut self) {
+        if let Some(m) = &mut self.mnemonic {
+            m.zeroize();
+        }
+        self.mnemonic = None;
+    }
+}
+
+impl Drop for NewIdentity {
+    fn drop(&mut self) {
+        self.clear_mnemonic();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_new_identity_steps() {
+        let mut ni = NewIdentity::new();
+        assert_eq!(ni.step(), None);
+        assert!(ni.backup_done().is_err());
+
+        let words = ni.start().unwrap();
+        assert_eq!(words.split(' ').count(), 12);
+        assert_eq!(ni.step(), Some(NewIdentityStep::Backup));
+        assert_eq!(ni.mnemonic(), Some(words.as_str()));
+        assert!(!ni.can_save());
+
+        // not confirmed
+        assert!(ni.backup_done().is_err());
+        assert_eq!(ni.step(), Some(NewIdentityStep::Backup));
+
+        ni.backup_confirmed = true;
+        ni.backup_done().unwrap();
+        assert_eq!(ni.step(), Some(NewIdentityStep::Password));
+        assert_eq!(ni.mnemonic(), None);
+        assert!(ni.can_save());
+
+        ni.finish();
+        assert_eq!(ni.step(), None);
+        assert!(!ni.can_save());
+    }
+}
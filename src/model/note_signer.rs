@@ -0,0 +1,186 @@
+use crate::base::error::Error;
+use crate::model::keystore::{KeySigner, Keystore};
+use crate::model::status_messages::StatusMessages;
+
+use nostr::prelude::{Event, EventBuilder, EventId, FromPkStr, Keys, Timestamp, XOnlyPublicKey};
+
+/// Earliest accepted explicit created_at (2020-11-07, before the first nostr events)
+const MIN_CREATED_AT: u64 = 1_604_707_200;
+/// An explicit created_at may be at most this much in the future, in seconds
+const MAX_CREATED_AT_FUTURE_SECS: u64 = 24 * 60 * 60;
+
+/// Model for signing a text note or an encrypted direct message locally,
+/// e.g. to copy it to another client or relay
+pub(crate) struct NoteSigner {
+    pub content_input: String,
+    /// Input for an explicit created_at (unix timestamp, e.g. for backdated imports);
+    /// the current time is used if empty
+    pub created_at_input: String,
+    /// Recipient of a direct message (npub or hex)
+    pub recipient_input: String,
+    signed: Option<Event>,
+}
+
+/// Parse the recipient input of a direct message, npub or hex
+fn parse_recipient(input: &str) -> Result<XOnlyPublicKey, Error> {
+    let input = input.trim();
+    Keys::from_pk_str(input)
+        .map(|keys| keys.public_key())
+        .map_err(|_e| Error::DmInvalidRecipient(input.to_string()))
+}
+
+/// Parse an explicit created_at input: None if empty, error if not a number
+/// or not a reasonable time (before nostr, or too far in the future)
+fn parse_created_at(input: &str, now: Timestamp) -> Result<Option<Timestamp>, Error> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let secs = input
+        .parse::<u64>()
+        .map_err(|_e| Error::NoteInvalidCreatedAt(input.to_string()))?;
+    if secs < MIN_CREATED_AT || secs > now.as_u64() + MAX_CREATED_AT_FUTURE_SECS {
+        return Err(Error::NoteInvalidCreatedAt(input.to_string()));
+    }
+    Ok(Some(Timestamp::from(secs)))
+}
+
+impl NoteSigner {
+    pub fn new() -> Self {
+        Self {
+            content_input: String::new(),
+            created_at_input: String::new(),
+            recipient_input: String::new(),
+            signed: None,
+        }
+    }
+
+    /// Sign a text note with the content input, with the explicit created_at if given
+    /// (the id is computed for it)
+    pub fn sign(&self, key_signer: &KeySigner) -> Result<Event, Error> {
+        let mut unsigned = EventBuilder::new_text_note(&self.content_input, &[])
+            .to_unsigned_event(key_signer.get_public_key());
+        if let Some(created_at) = parse_created_at(&self.created_at_input, Timestamp::now())? {
+            unsigned.created_at = created_at;
+            unsigned.id = EventId::new(
+                &unsigned.pubkey,
+                unsigned.created_at,
+                &unsigned.kind,
+                &unsigned.tags,
+                &unsigned.content,
+            );
+        }
+        Ok(unsigned.sign(&key_signer.keys)?)
+    }
+
+    pub fn sign_action(
+        &mut self,
+        key_signer: Result<KeySigner, Error>,
+        status: &mut StatusMessages,
+    ) {
+        self.signed = None;
+        match key_signer.and_then(|ks| self.sign(&ks)) {
+            Err(e) => status.set_error_err(&e),
+            Ok(event) => {
+                status.set(&format!(
+                    "Note signed, created_at {}, id {}",
+                    event.created_at.as_u64(),
+                    event.id
+                ));
+                self.signed = Some(event);
+            }
+        }
+    }
+
+    /// Encrypt the content input to the recipient, as a direct message (NIP-04), and sign it
+    pub fn sign_dm_action(&mut self, keystore: &Keystore, status: &mut StatusMessages) {
+        self.signed = None;
+        match parse_recipient(&self.recipient_input)
+            .and_then(|recipient| keystore.build_dm_event(&recipient, &self.content_input))
+        {
+            Err(e) => status.set_error_err(&e),
+            Ok(event) => {
+                status.set(&format!(
+                    "Direct message encrypted and signed, id {}",
+                    event.id
+                ));
+                self.signed = Some(event);
+            }
+        }
+    }
+
+    /// The last signed note or direct message, as JSON
+    pub fn signed_json(&self) -> Option<String> {
+        self.signed.as_ref().map(|e| e.as_json())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nostr::prelude::{FromBech32, SecretKey, ToBech32};
+
+    const NSEC: &str = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+
+    #[test]
+    fn test_parse_created_at() {
+        let now = Timestamp::from(1_700_000_000);
+        assert!(parse_created_at("", now).unwrap().is_none());
+        assert!(parse_created_at("  ", now).unwrap().is_none());
+        assert_eq!(
+            parse_created_at("1686693500", now).unwrap(),
+            Some(Timestamp::from(1686693500))
+        );
+        assert!(parse_created_at("abc", now).is_err());
+        assert!(parse_created_at("-5", now).is_err());
+        assert!(parse_created_at("1000", now).is_err());
+        assert!(parse_created_at("1700090000", now).is_err());
+    }
+
+    #[test]
+    fn test_parse_recipient() {
+        let keys = Keys::generate();
+        let npub = keys.public_key().to_bech32().unwrap();
+        assert_eq!(parse_recipient(&npub).unwrap(), keys.public_key());
+        assert_eq!(
+            parse_recipient(&format!(" {} ", keys.public_key())).unwrap(),
+            keys.public_key()
+        );
+        assert!(matches!(
+            parse_recipient("npub1invalid"),
+            Err(Error::DmInvalidRecipient(s)) if s == "npub1invalid"
+        ));
+        assert!(parse_recipient("").is_err());
+        // a secret key is not accepted as recipient
+        assert!(parse_recipient(NSEC).is_err());
+    }
+
+    #[test]
+    fn test_sign_with_created_at() {
+        let key_signer = KeySigner {
+            keys: Keys::new(SecretKey::from_bech32(NSEC).unwrap()),
+        };
+        let mut note_signer = NoteSigner::new();
+        note_signer.content_input = "Backdated note".to_string();
+        note_signer.created_at_input = "1686693500".to_string();
+
+        let event = note_signer.sign(&key_signer).unwrap();
+        assert_eq!(event.created_at, Timestamp::from(1686693500));
+        assert_eq!(event.content, "Backdated note");
+        assert_eq!(event.pubkey, key_signer.get_public_key());
+        // checks id and signature
+        assert!(event.verify().is_ok());
+
+        // without explicit time, current time is used
+        note_signer.created_at_input = String::new();
+        let event = note_signer.sign(&key_signer).unwrap();
+        assert!(event.created_at.as_u64() >= Timestamp::now().as_u64() - 10);
+        assert!(event.verify().is_ok());
+
+        note_signer.created_at_input = "yesterday".to_string();
+        assert!(matches!(
+            note_signer.sign(&key_signer),
+            Err(Error::NoteInvalidCreatedAt(_))
+        ));
+    }
+}
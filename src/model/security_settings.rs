@@ -1,3 +1,5 @@
+use crate::base::encrypt::EncryptionAlgorithm;
+use nostr::prelude::XOnlyPublicKey;
 use serde::{Deserialize, Serialize};
 
 use std::fmt;
@@ -6,6 +8,15 @@ use std::fmt;
 #[derive(Default, Serialize, Deserialize)]
 pub struct SecuritySettings {
     pub security_level: SecurityLevel,
+    /// Persist keys right after they are generated or imported
+    #[serde(default)]
+    pub auto_save: bool,
+    /// Algorithm for encrypting keys when saving; decryption uses the one of the saved key
+    #[serde(default)]
+    pub encryption_algorithm: EncryptionAlgorithm,
+    /// Keys (pubkey hex) needing the password for each signature, the secret key is not kept unlocked
+    #[serde(default)]
+    pub fresh_unlock_keys: Vec<String>,
 }
 
 /// Security level regarding secret key handling/persistence; chosen by the user
@@ -27,6 +38,31 @@ impl fmt::Display for SecurityLevel {
     }
 }
 
+/// Whether keys can be persisted currently, and if not, why
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum PersistStatus {
+    Allowed,
+    /// The security level does not allow persisting
+    BlockedByLevel,
+    /// The security level requires a password, but none is set
+    NoPassword,
+}
+
+impl PersistStatus {
+    /// Explanation with the way to fix it, None if allowed
+    pub fn message(&self) -> Option<&'static str> {
+        match self {
+            PersistStatus::Allowed => None,
+            PersistStatus::BlockedByLevel => {
+                Some("Saving is disabled by the security level; choose a level that allows persisting to save")
+            }
+            PersistStatus::NoPassword => {
+                Some("The security level requires a password; enter and repeat a password to save")
+            }
+        }
+    }
+}
+
 pub(crate) static SECURITY_LEVELS: &[SecurityLevel] = &[
     SecurityLevel::Never,
     SecurityLevel::PersistMandatoryPassword,
@@ -47,8 +83,53 @@ impl SecuritySettings {
         }
     }
 
+    /// Whether keys can be persisted now, given whether a password is entered
+    pub fn persist_status(&self, password_set: bool) -> PersistStatus {
+        match self.security_level {
+            SecurityLevel::Never => PersistStatus::BlockedByLevel,
+            SecurityLevel::PersistMandatoryPassword if !password_set => PersistStatus::NoPassword,
+            SecurityLevel::PersistMandatoryPassword | SecurityLevel::PersistOptionalPassword => {
+                PersistStatus::Allowed
+            }
+        }
+    }
+
+    /// Whether the password has to be entered for each signature with this key
+    pub fn requires_fresh_unlock(&self, pubkey: &XOnlyPublicKey) -> bool {
+        self.fresh_unlock_keys.contains(&pubkey.to_string())
+    }
+
     pub fn allows_persist(&self) -> bool {
         self.security_level == SecurityLevel::PersistMandatoryPassword
             || self.security_level == SecurityLevel::PersistOptionalPassword
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_persist_status() {
+        let mut s = SecuritySettings {
+            security_level: SecurityLevel::Never,
+            auto_save: false,
+            encryption_algorithm: EncryptionAlgorithm::default(),
+            fresh_unlock_keys: Vec::new(),
+        };
+        assert_eq!(s.persist_status(true), PersistStatus::BlockedByLevel);
+        assert_eq!(s.persist_status(false), PersistStatus::BlockedByLevel);
+
+        s.security_level = SecurityLevel::PersistMandatoryPassword;
+        assert_eq!(s.persist_status(true), PersistStatus::Allowed);
+        assert_eq!(s.persist_status(false), PersistStatus::NoPassword);
+
+        s.security_level = SecurityLevel::PersistOptionalPassword;
+        assert_eq!(s.persist_status(true), PersistStatus::Allowed);
+        assert_eq!(s.persist_status(false), PersistStatus::Allowed);
+
+        assert!(PersistStatus::Allowed.message().is_none());
+        assert!(PersistStatus::BlockedByLevel.message().is_some());
+        assert!(PersistStatus::NoPassword.message().is_some());
+    }
+}
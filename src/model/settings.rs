@@ -1,7 +1,13 @@
+use crate::base::encrypt::EncryptionAlgorithm;
 use crate::base::error::Error;
+use crate::base::relay_url::validate_relay_url;
 use crate::base::storage::Storage;
+use crate::model::display_settings::{DisplayFormat, KindLabels};
 use crate::model::security_settings::{SecurityLevel, SecuritySettings};
+use crate::model::signer_settings::{Permissions, SignerSettings};
+use nostr::prelude::XOnlyPublicKey;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::fs;
 
 /// Settings
@@ -10,6 +16,36 @@ use std::fs;
 pub struct Settings {
     #[readonly]
     pub security: SecuritySettings,
+    /// Display format for public keys
+    #[readonly]
+    #[serde(default)]
+    pub display_format: DisplayFormat,
+    /// Labels shown for event kinds, instead of the numbers
+    #[readonly]
+    #[serde(default)]
+    pub kind_labels: KindLabels,
+    #[readonly]
+    #[serde(default)]
+    pub signer: SignerSettings,
+    /// Set once the first-run wizard has been completed or skipped
+    #[readonly]
+    #[serde(default)]
+    pub first_run_completed: bool,
+    /// Append the activity log (status messages) to a file in the data folder
+    #[readonly]
+    #[serde(default)]
+    pub persist_activity_log: bool,
+    /// Mirror status messages to stderr (secrets redacted), e.g. for terminal scrollback
+    #[readonly]
+    #[serde(default)]
+    pub mirror_status_to_stderr: bool,
+    /// Show a system tray icon; closing the window hides it to the tray
+    #[readonly]
+    #[serde(default)]
+    pub tray_mode: bool,
+    /// Input for relay list import, not persisted
+    #[serde(skip)]
+    pub relays_import_input: String,
 }
 
 impl Settings {
@@ -18,6 +54,135 @@ impl Settings {
         let _res = self.save();
     }
 
+    pub fn set_auto_save(&mut self, auto_save: bool) {
+        self.security.auto_save = auto_save;
+        let _res = self.save();
+    }
+
+    pub fn set_encryption_algorithm(&mut self, algorithm: EncryptionAlgorithm) {
+        self.security.encryption_algorithm = algorithm;
+        let _res = self.save();
+    }
+
+    /// Require the password for each signature with the key, or not
+    pub fn set_fresh_unlock(&mut self, pubkey: &XOnlyPublicKey, enabled: bool) {
+        let pubkey_hex = pubkey.to_string();
+        self.security.fresh_unlock_keys.retain(|k| *k != pubkey_hex);
+        if enabled {
+            self.security.fresh_unlock_keys.push(pubkey_hex);
+        }
+        let _res = self.save();
+    }
+
+    pub fn set_display_format(&mut self, format: DisplayFormat) {
+        self.display_format = format;
+        let _res = self.save();
+    }
+
+    pub fn set_persist_activity_log(&mut self, persist: bool) {
+        self.persist_activity_log = persist;
+        let _res = self.save();
+    }
+
+    pub fn set_mirror_status_to_stderr(&mut self, mirror: bool) {
+        self.mirror_status_to_stderr = mirror;
+        let _res = self.save();
+    }
+
+    pub fn set_tray_mode(&mut self, tray_mode: bool) {
+        self.tray_mode = tray_mode;
+        let _res = self.save();
+    }
+
+    pub fn set_first_run_completed(&mut self) {
+        self.first_run_completed = true;
+        let _res = self.save();
+    }
+
+    pub fn set_signer_response_relays(&mut self, relays: Vec<String>) {
+        self.signer.response_relays = relays;
+        let _res = self.save();
+    }
+
+    /// Import relays from a pasted relay list: a kind 10002 event (`r` tags), a kind 3 event
+    /// (relays in content), or a JSON list/object of relay URLs.
+    /// New ones are merged into the response relays; returns how many were added.
+    pub fn import_relays_from_event(&mut self, json: &str) -> Result<usize, Error> {
+        let urls = parse_relay_list(json)?
+            .iter()
+            .map(|u| validate_relay_url(u))
+            .collect::<Result<Vec<String>, Error>>()?;
+        let mut added = 0;
+        for url in urls {
+            if !self
+                .signer
+                .response_relays
+                .iter()
+                .any(|r| r.trim_end_matches('/') == url)
+            {
+                self.signer.response_relays.push(url);
+                added += 1;
+            }
+        }
+        if added > 0 {
+            let _res = self.save();
+        }
+        Ok(added)
+    }
+
+    pub fn set_signer_allow_sign_raw(&mut self, allow: bool) {
+        self.signer.allow_sign_raw = allow;
+        let _res = self.save();
+    }
+
+    pub fn set_signer_stealth_mode(&mut self, stealth: bool) {
+        self.signer.stealth_mode = stealth;
+        let _res = self.save();
+    }
+
+    pub fn set_signer_require_focus_to_approve(&mut self, require: bool) {
+        self.signer.require_focus_to_approve = require;
+        let _res = self.save();
+    }
+
+    pub fn set_signer_show_full_content(&mut self, show: bool) {
+        self.signer.show_full_content = show;
+        let _res = self.save();
+    }
+
+    /// Store the display name of a paired client; an empty name removes it
+    pub fn set_connection_name(&mut self, client_pubkey: &XOnlyPublicKey, name: &str) {
+        if name.is_empty() {
+            self.signer
+                .connection_names
+                .remove(&client_pubkey.to_string());
+        } else {
+            self.signer
+                .connection_names
+                .insert(client_pubkey.to_string(), name.to_string());
+        }
+        let _res = self.save();
+    }
+
+    /// Store the permissions granted to a client at pairing; None (not restricted) removes them
+    pub fn set_client_permissions(
+        &mut self,
+        client_pubkey: &XOnlyPublicKey,
+        permissions: Option<Permissions>,
+    ) {
+        match permissions {
+            None => self
+                .signer
+                .client_permissions
+                .remove(&client_pubkey.to_string()),
+            Some(p) => self
+                .signer
+                .client_permissions
+                .insert(client_pubkey.to_string(), p),
+        };
+        let _res = self.save();
+    }
+
     pub fn save(&self) -> Result<(), Error> {
         let str = serde_json::to_string(&self)?;
         Storage::check_create_folder()?;
@@ -30,3 +195,101 @@ impl Settings {
         Ok(serde_json::from_str::<Self>(&str)?)
     }
 }
+
+/// Extract relay URLs from a relay list event or JSON blob, without validation
+fn parse_relay_list(json: &str) -> Result<Vec<String>, Error> {
+    let value: Value = serde_json::from_str(json)?;
+    match value {
+        Value::Array(list) => list
+            .iter()
+            .map(|v| {
+                v.as_str()
+                    .map(str::to_string)
+                    .ok_or(Error::InvalidRelayList)
+            })
+            .collect(),
+        Value::Object(obj) => match obj.get("kind").and_then(Value::as_u64) {
+            // NIP-65 relay list, in "r" tags
+            Some(10002) => Ok(obj
+                .get("tags")
+                .and_then(Value::as_array)
+                .ok_or(Error::InvalidRelayList)?
+                .iter()
+                .filter_map(Value::as_array)
+                .filter(|t| t.first().and_then(Value::as_str) == Some("r"))
+                .filter_map(|t| t.get(1).and_then(Value::as_str).map(str::to_string))
+                .collect()),
+            // Contact list, relays in content, as object keys
+            Some(3) => match obj.get("content").and_then(Value::as_str) {
+                None | Some("") => Ok(Vec::new()),
+                Some(content) => parse_relay_list(content),
+            },
+            Some(_) => Err(Error::InvalidRelayList),
+            None => Ok(obj.keys().cloned().collect()),
+        },
+        _ => Err(Error::InvalidRelayList),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RELAY_LIST_EVENT: &str = r#"{
+        "id": "0000000000000000000000000000000000000000000000000000000000000000",
+        "pubkey": "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4",
+        "created_at": 1680000000,
+        "kind": 10002,
+        "tags": [
+            ["r", "wss://relay1.example.com"],
+            ["r", "wss://relay2.example.com/", "read"],
+            ["r", "wss://relay3.example.com", "write"],
+            ["p", "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4"]
+        ],
+        "content": "",
+        "sig": ""
+    }"#;
+
+    #[test]
+    fn test_import_relays_from_event() {
+        let mut s = Settings::default();
+        s.signer.response_relays = vec!["wss://relay1.example.com".to_string()];
+        assert_eq!(s.import_relays_from_event(RELAY_LIST_EVENT).unwrap(), 2);
+        assert_eq!(
+            s.signer.response_relays,
+            vec![
+                "wss://relay1.example.com",
+                "wss://relay2.example.com",
+                "wss://relay3.example.com"
+            ]
+        );
+        // again, all deduped
+        assert_eq!(s.import_relays_from_event(RELAY_LIST_EVENT).unwrap(), 0);
+        assert_eq!(s.signer.response_relays.len(), 3);
+    }
+
+    #[test]
+    fn test_import_relays_other_formats() {
+        let mut s = Settings::default();
+        let kind3 = r#"{"kind": 3, "tags": [], "content": "{\"wss://relay4.example.com\": {\"read\": true, \"write\": true}}"}"#;
+        assert_eq!(s.import_relays_from_event(kind3).unwrap(), 1);
+        assert_eq!(
+            s.import_relays_from_event(
+                r#"["wss://relay5.example.com", "wss://relay4.example.com/"]"#
+            )
+            .unwrap(),
+            1
+        );
+        assert_eq!(s.signer.response_relays.len(), 2);
+
+        // invalid URL: nothing added
+        assert!(s
+            .import_relays_from_event(r#"["wss://relay6.example.com", "https://example.com"]"#)
+            .is_err());
+        assert!(s
+            .import_relays_from_event(r#"{"kind": 1, "content": ""}"#)
+            .is_err());
+        assert!(s.import_relays_from_event("not json").is_err());
+        assert_eq!(s.signer.response_relays.len(), 2);
+    }
+}
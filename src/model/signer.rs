@@ -1,22 +1,54 @@
 use crate::base::error::Error;
+use crate::base::input::require_input;
+use crate::base::scrub;
+use crate::base::storage::Storage;
+use crate::model::delegator::DelegationInfo;
+use crate::model::display_settings::{kind_label, DisplayFormat};
 use crate::model::keystore::KeySigner;
-use crate::model::keystr_model::{Event, EVENT_QUEUE};
+use crate::model::keystr_model::{Event, EventQueue, EVENT_QUEUE};
+use crate::model::signer_settings::{ApprovalAction, Permission, Permissions, SignerSettings};
 use crate::model::status_messages::StatusMessages;
 
 use nostr::nips::nip46::{Message, Request};
 use nostr::prelude::{
-    DelegationResult, DelegationTag, EventBuilder, Filter, Keys, Kind, NostrConnectURI, ToBech32,
-    XOnlyPublicKey,
+    ClientMessage, DelegationResult, DelegationTag, EventBuilder, EventId, Filter, FromBech32,
+    Keys, Kind, NostrConnectURI, RelayMessage, Tag, ToBech32, UnsignedEvent, Url, XOnlyPublicKey,
 };
 use nostr_sdk::prelude::{
     decrypt, Client, Options, RelayPoolNotification, RelayStatus, Response, Timestamp,
 };
 
+use chrono::Timelike;
 use crossbeam::channel;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::future::Future;
+use std::path::Path;
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 use tokio::runtime::Handle;
+use tokio::sync::watch;
+use tokio::task::{AbortHandle, JoinHandle, JoinSet};
+
+/// Error returned to the client for requests rejected by the user
+const REJECTED_ERROR: &str = "Request rejected by user";
+/// Error returned to the client for requests not acted on within the expiry time
+const EXPIRED_ERROR: &str = "Request expired";
+/// Number of last signing latencies kept per connection
+const LATENCY_MAX_COUNT: usize = 20;
+/// Number of last signed event ids kept per connection, for auditing
+const SIGNED_IDS_MAX_COUNT: usize = 50;
+/// Method of the "sign raw" extension request: sign a client-provided 32-byte hash
+const SIGN_RAW_METHOD: &str = "sign_raw";
+/// Method of the NIP-26 delegation request
+const DELEGATE_METHOD: &str = "delegate";
+/// Number of last events kept by the relay event viewer
+const VIEWER_MAX_EVENTS: usize = 50;
+/// The relay event viewer asks for events of this recent period
+const VIEWER_SINCE: Duration = Duration::from_secs(60 * 60);
 
 /// Model for Signer
 #[readonly::make]
@@ -26,6 +58,29 @@ pub(crate) struct Signer {
     #[readonly]
     connection: Option<Arc<SignerConnection>>,
     pub connect_uri_input: String,
+    /// Input for NIP-06 account index of derived child key to sign with; main key if empty
+    pub child_account_input: String,
+    /// Input for NIP-06 account index of derived child key to sign the first pending request with,
+    /// overriding the connection's key; connection key is used if empty
+    pub sign_override_account_input: String,
+    /// Show the entire content of the first pending request (for this request only)
+    pub show_full_content: bool,
+    /// Input for the display name of the connected client
+    pub display_name_input: String,
+    /// Permissions to grant to the client at pairing
+    pub grant: PermissionSelection,
+}
+
+/// Inputs for the permissions to grant to a client at pairing
+#[derive(Default)]
+pub(crate) struct PermissionSelection {
+    /// Grant only the selected permissions; all supported methods if not set
+    pub restrict: bool,
+    pub sign_event: bool,
+    /// Event kinds to allow signing for, comma-separated; all kinds if empty
+    pub sign_event_kinds_input: String,
+    pub delegate: bool,
+    pub sign_raw: bool,
 }
 
 /// Represents an active Nostr Connect connection
@@ -37,15 +92,81 @@ pub(crate) struct SignerConnection {
     status: StatusMessages,
     pub relay_str: String,
     relay_client: Client,
-    key_signer: KeySigner,
+    /// Shared with the scrub registry, so it is wiped on panic
+    key_signer: Arc<Mutex<KeySigner>>,
+    settings: SignerSettings,
     /// Holds pending requests (mostly Sign requests), and can handle them
     requests: Mutex<Vec<SignatureReqest>>,
+    /// Signals the handler loop to stop; it also stops if this is dropped
+    stop_handler: watch::Sender<bool>,
+    /// Durations of the last processed requests, from receiving to sending the response
+    latencies: Mutex<VecDeque<Duration>>,
+    /// Ids of the last events signed in this session, oldest first (no content)
+    signed_ids: Mutex<VecDeque<EventId>>,
+    /// Queue for UI notifications; the handler loop stops when it is closed
+    event_queue: Arc<EventQueue>,
+    /// App name from the connect URI metadata, if any
+    metadata_name: Option<String>,
+    /// User-assigned name of the client, shown instead of metadata name or npub
+    display_name: Mutex<Option<String>>,
+    /// Time of the last message from the client (or of connecting), for idle disconnect
+    last_activity: Mutex<Instant>,
+    /// Time of the last keepalive (or of connecting)
+    last_keepalive: Mutex<Instant>,
+    /// The running handler loop; replaced on reconnect (the old one is aborted), so there is one
+    handler: Mutex<Option<AbortHandle>>,
+    /// Last error of connecting, handling or sending on this connection; cleared by a success
+    last_error: Mutex<Option<String>>,
+    /// Recent events addressed to our pubkey (newest first), if the relay event viewer is open
+    viewer: Mutex<Option<VecDeque<ViewedEvent>>>,
+    /// Permissions granted to the client at pairing, None if not restricted
+    permissions: Option<Permissions>,
+    /// Trusted apps (sender pubkeys): their sign requests are signed without asking,
+    /// except for the kinds to confirm; kept for the session
+    auto_approve: Mutex<HashSet<XOnlyPublicKey>>,
+}
+
+/// An event addressed to our pubkey, as listed by the relay event viewer (no content)
+#[derive(Clone, Debug)]
+pub(crate) struct ViewedEvent {
+    pub kind: Kind,
+    pub created_at: Timestamp,
+    pub sender: XOnlyPublicKey,
 }
 
 #[derive(Clone)]
 pub(crate) struct SignatureReqest {
     req: Message,
     sender_pubkey: XOnlyPublicKey,
+    received_at: Instant,
+    /// Modified version of the event to sign instead of the requested one, if any
+    modified: Option<UnsignedEvent>,
+    /// The user has been notified that it is about to expire
+    expiry_notified: bool,
+}
+
+/// A pending request as exported for offline review; contains no secrets
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct PendingRequestExport {
+    pub description: String,
+    /// Event kind, for sign requests
+    pub kind: Option<u64>,
+    /// Sender, in npub format
+    pub sender: String,
+    pub raw_json: String,
+}
+
+impl Drop for SignerConnection {
+    /// Stop the handler loop, and disconnect relays in the background (Drop can't be async)
+    fn drop(&mut self) {
+        self.stop_handler_loop();
+        if let Ok(handle) = Handle::try_current() {
+            let relay_client = self.relay_client.clone();
+            handle.spawn(async move {
+                let _ = relay_disconnect(relay_client).await;
+            });
+        }
+    }
 }
 
 /// Signer connection status: connected or not, or connection pending
@@ -55,6 +176,31 @@ pub(crate) enum ConnectionStatus {
     Connected(Arc<SignerConnection>),
 }
 
+impl PermissionSelection {
+    /// The selected permissions, None if not restricted
+    pub fn to_permissions(&self) -> Result<Option<Permissions>, Error> {
+        if !self.restrict {
+            return Ok(None);
+        }
+        let mut granted = Vec::new();
+        if self.sign_event {
+            let kinds = self.sign_event_kinds_input.trim();
+            if kinds.is_empty() {
+                granted.push("sign_event".to_string());
+            } else {
+                granted.extend(kinds.split(',').map(|k| format!("sign_event:{}", k.trim())));
+            }
+        }
+        if self.delegate {
+            granted.push("delegate".to_string());
+        }
+        if self.sign_raw {
+            granted.push(SIGN_RAW_METHOD.to_string());
+        }
+        Permissions::parse(&granted.join(",")).map(Some)
+    }
+}
+
 impl Signer {
     pub fn new(app_id: &Keys, status: StatusMessages) -> Self {
         Signer {
@@ -62,32 +208,62 @@ impl Signer {
             status,
             connection: None,
             connect_uri_input: String::new(),
+            child_account_input: String::new(),
+            sign_override_account_input: String::new(),
+            show_full_content: false,
+            display_name_input: String::new(),
+            grant: PermissionSelection::default(),
         }
     }
 
-    fn connect(&mut self, uri_str: &str, key_signer: &KeySigner) -> Result<(), Error> {
+    /// Connect to the client of the URI. With `fresh_unlock` the key is unlocked for each signature,
+    /// the key signer holds the public key only, so it is not self-checked.
+    fn connect(
+        &mut self,
+        uri_str: &str,
+        key_signer: &KeySigner,
+        settings: &SignerSettings,
+        fresh_unlock: bool,
+    ) -> Result<(), Error> {
+        let uri = &parse_connect_uri(uri_str)?;
+        // Same client through the same relay: don't duplicate, reconnect it if it dropped
+        if let Some(existing) = self.connection.clone() {
+            if existing.client_pubkey == uri.public_key
+                && existing.relay_str.trim_end_matches('/')
+                    == uri.relay_url.as_str().trim_end_matches('/')
+            {
+                return match self.get_connection_status() {
+                    ConnectionStatus::NotConnected => {
+                        relay_connect_async(existing, tokio::runtime::Handle::current())
+                    }
+                    _ => Err(Error::SignerDuplicateConnection),
+                };
+            }
+        }
         if let ConnectionStatus::Connected(_) = self.get_connection_status() {
             return Err(Error::SignerAlreadyConnected);
         }
 
-        let uri = &NostrConnectURI::from_str(uri_str)?;
+        // make sure the key works before reporting connected
+        if !fresh_unlock {
+            key_signer.self_check()?;
+        }
+
         let connect_client_id_pubkey = uri.public_key.clone();
         let relay = &uri.relay_url;
 
-        // Create relay client, but don't connect it yet
-        let opts = Options::new().wait_for_send(true);
-        let relay_client = Client::with_opts(&self.app_id_keys, opts);
-
-        let connection = Arc::new(SignerConnection {
-            // uri: uri.clone(),
-            relay_str: relay.to_string(),
-            relay_client,
-            client_pubkey: connect_client_id_pubkey,
-            status: self.status.clone(),
-            app_id_keys: self.app_id_keys.clone(),
-            key_signer: key_signer.clone(),
-            requests: Mutex::new(Vec::new()),
-        });
+        let mut connection = SignerConnection::new(
+            relay.to_string(),
+            connect_client_id_pubkey,
+            &self.app_id_keys,
+            key_signer,
+            settings,
+            self.status.clone(),
+        );
+        if !uri.metadata.name.is_empty() {
+            connection.metadata_name = Some(uri.metadata.name.clone());
+        }
+        let connection = Arc::new(connection);
 
         let handle = tokio::runtime::Handle::current();
         // Connect in the background
@@ -97,8 +273,43 @@ impl Signer {
         Ok(())
     }
 
+    /// Relays a connection to the given URI would contact, for previewing before connecting
+    pub fn relays_for_connect(
+        uri_str: &str,
+        settings: &SignerSettings,
+    ) -> Result<Vec<String>, Error> {
+        let uri = parse_connect_uri(uri_str)?;
+        Ok(contacted_relays(
+            uri.relay_url.as_str(),
+            &settings.response_relays,
+        ))
+    }
+
+    /// Client to connect to (from the connect URI input), with the permissions to grant to it;
+    /// None if not restricted
+    pub fn pairing_permissions(&self) -> Result<(XOnlyPublicKey, Option<Permissions>), Error> {
+        let uri = parse_connect_uri(&self.connect_uri_input)?;
+        Ok((uri.public_key, self.grant.to_permissions()?))
+    }
+
+    /// Number of pending requests, 0 if not connected
+    pub fn get_pending_count(&self) -> usize {
+        self.connection
+            .as_ref()
+            .map_or(0, |conn| conn.get_pending_count())
+    }
+
+    /// Set the display name of the connected client; returns the client pubkey, None if not connected
+    pub fn set_display_name(&self, name: &str) -> Option<XOnlyPublicKey> {
+        let conn = self.connection.as_ref()?;
+        conn.set_display_name(name.to_string());
+        Some(conn.client_pubkey)
+    }
+
     fn disconnect(&mut self) -> Result<(), Error> {
         if let Some(conn) = &self.connection {
+            // stop it explicitly: the connection may outlive this, if referenced elsewhere (e.g. UI)
+            conn.stop_handler_loop();
             let handle = tokio::runtime::Handle::current();
             let _res = relay_disconnect_blocking(conn.relay_client.clone(), handle)?;
         }
@@ -106,9 +317,59 @@ impl Signer {
         Ok(())
     }
 
-    pub fn connect_action(&mut self, key_signer: KeySigner, status: &mut StatusMessages) {
+    /// Rotate the relay identity (app ID) and reconnect the active session (if any) under the new identity.
+    /// Pending requests are preserved.
+    pub fn rotate_app_id_and_reconnect(&mut self, new_keys: &Keys) -> Result<(), Error> {
+        let old_connection = self.connection.clone();
+        if old_connection.is_some() {
+            let _res_ignore = self.disconnect();
+        }
+        self.app_id_keys = new_keys.clone();
+        if let Some(old_conn) = old_connection {
+            let mut connection = SignerConnection::new(
+                old_conn.relay_str.clone(),
+                old_conn.client_pubkey,
+                &self.app_id_keys,
+                &old_conn.key_signer(),
+                &old_conn.settings,
+                self.status.clone(),
+            );
+            connection.metadata_name = old_conn.metadata_name.clone();
+            *connection.display_name.lock().unwrap() =
+                old_conn.display_name.lock().unwrap().clone();
+            *connection.auto_approve.lock().unwrap() =
+                old_conn.auto_approve.lock().unwrap().clone();
+            let connection = Arc::new(connection);
+            connection.take_requests_from(&old_conn);
+            let handle = tokio::runtime::Handle::current();
+            relay_connect_async(connection.clone(), handle)?;
+            self.connection = Some(connection);
+        }
+        Ok(())
+    }
+
+    pub fn rotate_app_id_action(&mut self, status: &mut StatusMessages) {
+        match self.rotate_app_id_and_reconnect(&Keys::generate()) {
+            Err(e) => status.set_error(&format!("Could not rotate app ID: {}", e)),
+            Ok(_) => status.set(&format!(
+                "App ID rotated, new relay identity {}",
+                self.app_id_keys
+                    .public_key()
+                    .to_bech32()
+                    .unwrap_or_default()
+            )),
+        }
+    }
+
+    pub fn connect_action(
+        &mut self,
+        key_signer: KeySigner,
+        settings: &SignerSettings,
+        fresh_unlock: bool,
+        status: &mut StatusMessages,
+    ) {
         let uri_input = self.connect_uri_input.clone();
-        match self.connect(&uri_input, &key_signer) {
+        match self.connect(&uri_input, &key_signer, settings, fresh_unlock) {
             Err(e) => status.set_error(&format!("Could not connect to relay: {}", e.to_string())),
             Ok(_) => status.set(&format!("Signer connecting...")),
         }
@@ -122,6 +383,68 @@ impl Signer {
         self.connection = None;
     }
 
+    /// Disconnect if the connection has been idle (no messages from the client, no pending
+    /// requests) for at least the timeout; zero timeout disables it. Returns true if disconnected.
+    pub fn disconnect_if_idle(
+        &mut self,
+        now: Instant,
+        idle_timeout: Duration,
+        status: &mut StatusMessages,
+    ) -> bool {
+        if idle_timeout.is_zero() {
+            return false;
+        }
+        let is_idle = match &self.connection {
+            None => false,
+            Some(conn) => conn.get_pending_count() == 0 && conn.idle_time(now) >= idle_timeout,
+        };
+        if !is_idle {
+            return false;
+        }
+        let _res_ignore = self.disconnect();
+        status.set(&format!(
+            "Signer disconnected after {} seconds idle, connect again when needed",
+            idle_timeout.as_secs()
+        ));
+        true
+    }
+
+    /// Reject the pending requests older than the expiry; zero expiry disables it.
+    /// The requests expiring within the lead time are notified once (zero lead time for none).
+    /// Returns true if requests are about to expire, so the user can be alerted.
+    pub fn check_request_expiry(
+        &self,
+        now: Instant,
+        expiry: Duration,
+        lead_time: Duration,
+        status: &mut StatusMessages,
+    ) -> bool {
+        let conn = match &self.connection {
+            Some(conn) if !expiry.is_zero() => conn,
+            _ => return false,
+        };
+        let expiring = match lead_time.is_zero() {
+            true => 0,
+            false => conn.take_expiring(now, expiry, lead_time),
+        };
+        if expiring > 0 {
+            status.set(&format!(
+                "{} pending request(s) expire in {} seconds, approve or reject them now",
+                expiring,
+                lead_time.as_secs()
+            ));
+        }
+        let expired = conn.prune_expired(now, expiry);
+        if expired > 0 {
+            status.set(&format!(
+                "{} pending request(s) expired after {} seconds, rejected",
+                expired,
+                expiry.as_secs()
+            ));
+        }
+        expiring > 0
+    }
+
     pub fn get_connection_status(&self) -> ConnectionStatus {
         match &self.connection {
             None => ConnectionStatus::NotConnected,
@@ -141,12 +464,27 @@ impl Signer {
         }
     }
 
-    pub fn pending_process_first_action(&mut self, status: &mut StatusMessages) {
+    /// Process the first pending request, optionally signing with an override key instead of the connection key
+    pub fn pending_process_first_action(
+        &mut self,
+        override_signer: Option<KeySigner>,
+        delegation: Option<&DelegationInfo>,
+        status: &mut StatusMessages,
+    ) {
         if let Some(conn) = &self.connection {
             let first_desc = conn.get_first_request_description();
-            conn.action_first_req_process();
-            status.set(&format!("Processed request '{}'", first_desc));
+            let conn_signer = conn.key_signer();
+            let key_signer = override_signer.as_ref().unwrap_or(&conn_signer);
+            match conn.action_first_req_process(key_signer, delegation) {
+                Err(e) => status.set_error(&format!(
+                    "Refused to process request '{}', {}",
+                    first_desc, e
+                )),
+                Ok(_) => status.set(&format!("Processed request '{}'", first_desc)),
+            }
         }
+        self.sign_override_account_input = String::new();
+        self.show_full_content = false;
     }
 
     pub fn pending_ignore_first_action(&mut self, status: &mut StatusMessages) {
@@ -155,6 +493,173 @@ impl Signer {
             conn.action_first_req_remove();
             status.set(&format!("Removed request '{}'", first_desc));
         }
+        self.show_full_content = false;
+    }
+
+    /// Open the relay event viewer if closed, close it if open
+    pub fn toggle_event_viewer_action(&mut self) {
+        if let Some(conn) = &self.connection {
+            conn.set_viewer_open(!conn.is_viewer_open(), Handle::current());
+        }
+    }
+
+    pub fn clear_error_action(&mut self) {
+        if let Some(conn) = &self.connection {
+            conn.clear_last_error();
+        }
+    }
+
+    /// Export the pending requests to a file in the storage folder, for offline review
+    pub fn pending_export_action(&mut self, status: &mut StatusMessages) {
+        if let Some(conn) = &self.connection {
+            let path = Storage::pending_export_file();
+            match Storage::check_create_folder().and_then(|_| conn.export_pending(&path)) {
+                Err(e) => status.set_error_err(&e),
+                Ok(count) => status.set(&format!(
+                    "Exported {} pending request(s) to {}",
+                    count,
+                    path.display()
+                )),
+            }
+        }
+    }
+
+    pub fn pending_reject_first_action(&mut self, status: &mut StatusMessages) {
+        if let Some(conn) = &self.connection {
+            let first_desc = conn.get_first_request_description();
+            match conn.action_first_req_reject() {
+                Err(e) => status.set_error(&format!(
+                    "Rejected request '{}', but could not notify the client, {}",
+                    first_desc, e
+                )),
+                Ok(_) => status.set(&format!("Rejected request '{}'", first_desc)),
+            }
+        }
+        self.show_full_content = false;
+    }
+
+    /// Modify the first pending sign request to be signed with the current time
+    pub fn pending_bump_time_action(&mut self, status: &mut StatusMessages) {
+        if let Some(conn) = &self.connection {
+            match conn.bump_first_request_time(Timestamp::now()) {
+                Err(e) => status.set_error_err(&e),
+                Ok(_) => status.set("Request modified: signed with the current time"),
+            }
+        }
+    }
+
+    pub fn pending_reject_all_action(&mut self, status: &mut StatusMessages) {
+        if let Some(conn) = &self.connection {
+            let count = conn.action_reject_all();
+            status.set(&format!("Rejected {} pending request(s)", count));
+        }
+    }
+
+    /// Returns false if the first pending request has a tampered event id
+    pub fn is_first_request_event_id_valid(&self) -> bool {
+        match &self.connection {
+            None => true,
+            Some(conn) => conn.is_first_request_event_id_valid(),
+        }
+    }
+
+    /// Descriptions of all pending requests of the connection (if any), in queue order
+    pub fn get_pending_descriptions(&self) -> Vec<String> {
+        match &self.connection {
+            None => Vec::new(),
+            Some(conn) => conn.get_request_descriptions(),
+        }
+    }
+
+    /// Sender pubkeys of all pending requests of the connection (if any), in queue order,
+    /// with whether the app is trusted
+    pub fn get_pending_senders(&self) -> Vec<(XOnlyPublicKey, bool)> {
+        match &self.connection {
+            None => Vec::new(),
+            Some(conn) => conn
+                .get_request_senders()
+                .into_iter()
+                .map(|pk| (pk, conn.is_auto_approved(&pk)))
+                .collect(),
+        }
+    }
+
+    /// Trust the app for the rest of the session: its sign requests are signed without asking,
+    /// except for the kinds to confirm. No effect if not connected.
+    pub fn add_auto_approve(&self, pubkey: XOnlyPublicKey) {
+        if let Some(conn) = &self.connection {
+            conn.add_auto_approve(pubkey);
+        }
+    }
+
+    pub fn remove_auto_approve(&self, pubkey: &XOnlyPublicKey) {
+        if let Some(conn) = &self.connection {
+            conn.remove_auto_approve(pubkey);
+        }
+    }
+
+    pub fn set_auto_approve_action(
+        &mut self,
+        pubkey: XOnlyPublicKey,
+        trusted: bool,
+        status: &mut StatusMessages,
+    ) {
+        let npub = pubkey.to_bech32().unwrap_or_default();
+        if trusted {
+            self.add_auto_approve(pubkey);
+            status.set(&format!(
+                "Trusted app {}: its sign requests are signed without asking",
+                npub
+            ));
+        } else {
+            self.remove_auto_approve(&pubkey);
+            status.set(&format!("App {} not trusted anymore", npub));
+        }
+    }
+
+    /// Raw JSON of all pending requests of the connection (if any), in queue order
+    pub fn get_pending_raw_jsons(&self) -> Vec<String> {
+        match &self.connection {
+            None => Vec::new(),
+            Some(conn) => conn.get_request_raw_jsons(),
+        }
+    }
+
+    /// Changes made to the event of the first pending request (if any), see [`describe_modifications`]
+    pub fn get_first_request_modifications(&self) -> Vec<String> {
+        match &self.connection {
+            None => Vec::new(),
+            Some(conn) => conn.get_first_request_modifications(),
+        }
+    }
+
+    /// Entities referenced by the first pending request (if any), see [`referenced_entities`]
+    pub fn get_first_request_references(&self) -> Vec<String> {
+        match &self.connection {
+            None => Vec::new(),
+            Some(conn) => conn.get_first_request_references(),
+        }
+    }
+
+    /// Canonical serialization of the event of the first pending request, if it is a sign request
+    pub fn get_first_request_canonical_serialization(&self) -> Option<String> {
+        self.connection
+            .as_ref()
+            .and_then(|conn| conn.get_first_request_canonical_serialization())
+    }
+
+    pub fn get_first_request_full_content(&self) -> Option<String> {
+        self.connection
+            .as_ref()
+            .and_then(|conn| conn.get_first_request_full_content())
+    }
+
+    pub fn pending_move_action(&mut self, from: usize, to: usize, status: &mut StatusMessages) {
+        if let Some(conn) = &self.connection {
+            if let Err(e) = conn.move_request(from, to) {
+                status.set_error_err(&e);
+            }
+        }
     }
 
     /*
@@ -176,534 +681,4431 @@ impl Signer {
 }
 
 impl SignerConnection {
-    pub fn get_client_npub(&self) -> String {
-        self.client_pubkey.to_bech32().unwrap_or_default()
+    /// Check that the request is covered by the permissions granted at pairing, if restricted
+    fn check_permission(&self, msg: &Message) -> Result<(), Error> {
+        let (permissions, method) = match (&self.permissions, msg) {
+            (Some(p), Message::Request { method, .. }) => (p, method),
+            _ => return Ok(()),
+        };
+        let kind = match msg.to_request() {
+            Ok(Request::SignEvent(unsigned_event)) => Some(unsigned_event.kind),
+            _ => None,
+        };
+        if permissions.allows(method, kind) {
+            Ok(())
+        } else {
+            let denied = Permission::new(method, kind.map(|k| k.as_u64()));
+            Err(Error::SignerPermissionDenied(denied.to_string()))
+        }
     }
 
-    pub fn add_request(&self, req: Message, sender_pubkey: XOnlyPublicKey) {
-        self.requests
-            .lock()
-            .unwrap()
-            .push(SignatureReqest { req, sender_pubkey });
+    /// Response to describe: the supported methods, limited to the ones granted at pairing;
+    /// delegation is advertised only if it was granted explicitly
+    fn describe_response(&self, req_id: &str) -> Message {
+        let mut methods: Vec<String> = supported_methods(&self.settings)
+            .into_iter()
+            .filter(|method| match &self.permissions {
+                None => true,
+                Some(p) => p.allows(method, None) || p.0.iter().any(|g| &g.method == method),
+            })
+            .collect();
+        if let Some(p) = &self.permissions {
+            if p.allows(DELEGATE_METHOD, None) {
+                methods.push(DELEGATE_METHOD.to_string());
+            }
+        }
+        Message::response(req_id.to_string(), Response::Describe(methods))
     }
 
-    pub fn get_pending_count(&self) -> usize {
-        self.requests.lock().unwrap().len()
+    /// Create a connection object; relay client is created but not connected yet
+    fn new(
+        relay_str: String,
+        client_pubkey: XOnlyPublicKey,
+        app_id_keys: &Keys,
+        key_signer: &KeySigner,
+        settings: &SignerSettings,
+        status: StatusMessages,
+    ) -> Self {
+        let opts = Options::new().wait_for_send(true);
+        let relay_client = Client::with_opts(app_id_keys, opts);
+        let key_signer = Arc::new(Mutex::new(key_signer.clone()));
+        scrub::register(key_signer.clone());
+        SignerConnection {
+            // uri: uri.clone(),
+            relay_str,
+            relay_client,
+            client_pubkey,
+            status,
+            app_id_keys: app_id_keys.clone(),
+            key_signer,
+            settings: settings.clone(),
+            requests: Mutex::new(Vec::new()),
+            stop_handler: watch::channel(false).0,
+            latencies: Mutex::new(VecDeque::new()),
+            signed_ids: Mutex::new(VecDeque::new()),
+            event_queue: EVENT_QUEUE.clone(),
+            metadata_name: None,
+            display_name: Mutex::new(
+                settings
+                    .connection_names
+                    .get(&client_pubkey.to_string())
+                    .cloned(),
+            ),
+            last_activity: Mutex::new(Instant::now()),
+            last_keepalive: Mutex::new(Instant::now()),
+            handler: Mutex::new(None),
+            last_error: Mutex::new(None),
+            viewer: Mutex::new(None),
+            permissions: settings.permissions_for(&client_pubkey).cloned(),
+            auto_approve: Mutex::new(HashSet::new()),
+        }
     }
 
-    pub fn get_first_request_description(&self) -> String {
-        let locked = self.requests.lock().unwrap();
-        let first = locked.get(0);
-        match first {
-            None => "-".to_string(),
-            Some(f) => f.description(),
+    /// Send a message to the client, on the connection relay and the response relays.
+    /// The outcome is recorded as the last error of the connection (cleared on success).
+    async fn send(&self, msg: &Message, receiver_pubkey: &XOnlyPublicKey) -> Result<(), Error> {
+        let res = send_message(&self.relay_client, msg, receiver_pubkey).await;
+        self.record_result(&res);
+        res
+    }
+
+    /// Blocking version of [`Self::send`]
+    fn send_blocking(&self, msg: &Message, receiver_pubkey: &XOnlyPublicKey) -> Result<(), Error> {
+        let res = send_message_blocking(
+            &self.relay_client,
+            msg,
+            receiver_pubkey,
+            tokio::runtime::Handle::current(),
+        );
+        self.record_result(&res);
+        res
+    }
+
+    /// Keep the error of a failed operation (connect, handling, sending) as the last error,
+    /// clear it on success
+    fn record_result<T>(&self, res: &Result<T, Error>) {
+        *self.last_error.lock().unwrap() = res.as_ref().err().map(|e| e.to_string());
+    }
+
+    /// The last error on this connection, if not cleared or resolved since
+    pub fn get_last_error(&self) -> Option<String> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    pub fn clear_last_error(&self) {
+        *self.last_error.lock().unwrap() = None;
+    }
+
+    /// Open or close the relay event viewer; the relay subscription is updated in the background
+    /// (on close the viewer part is dropped from it)
+    pub fn set_viewer_open(&self, open: bool, handle: Handle) {
+        *self.viewer.lock().unwrap() = if open { Some(VecDeque::new()) } else { None };
+        let relay_client = self.relay_client.clone();
+        let filters = self.subscription_filters();
+        handle.spawn(async move {
+            relay_client.subscribe(filters).await;
+        });
+    }
+
+    pub fn is_viewer_open(&self) -> bool {
+        self.viewer.lock().unwrap().is_some()
+    }
+
+    /// Events listed by the relay event viewer, newest first; None if it is closed
+    pub fn get_viewed_events(&self) -> Option<Vec<ViewedEvent>> {
+        self.viewer
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|events| events.iter().cloned().collect())
+    }
+
+    /// List a received event in the viewer, if it is open and the event is addressed to us
+    fn record_viewed_event(&self, event: &nostr::Event) {
+        let our_pubkey = self.app_id_keys.public_key();
+        let addressed = event.tags.iter().any(|t| match t {
+            Tag::PubKey(pk, _) => *pk == our_pubkey,
+            _ => false,
+        });
+        if !addressed {
+            return;
+        }
+        if let Some(events) = self.viewer.lock().unwrap().as_mut() {
+            events.push_front(ViewedEvent {
+                kind: event.kind,
+                created_at: event.created_at,
+                sender: event.pubkey,
+            });
+            events.truncate(VIEWER_MAX_EVENTS);
         }
     }
 
-    pub fn action_first_req_process(&self) {
-        let mut locked = self.requests.lock().unwrap();
-        let first = locked.first();
-        if let Some(req) = first {
-            if let Message::Request { id, .. } = &req.req {
-                if let Ok(request) = &req.req.to_request() {
-                    match request {
-                        Request::SignEvent(_unsigned_event) => {
-                            if let Ok(resp_opt) =
-                                response_for_message(id, request, &self.key_signer)
-                            {
-                                if let Some(response_msg) = resp_opt {
-                                    let _ = send_message_blocking(
-                                        &self.relay_client,
-                                        &response_msg,
-                                        &req.sender_pubkey,
-                                        tokio::runtime::Handle::current(),
-                                    );
-                                }
-                            }
-                        }
-                        Request::Delegate {
-                            public_key: _,
-                            conditions: _,
-                        } => {
-                            if let Ok(resp_opt) =
-                                response_for_message(id, request, &self.key_signer)
-                            {
-                                if let Some(response_msg) = resp_opt {
-                                    let _ = send_message_blocking(
-                                        &self.relay_client,
-                                        &response_msg,
-                                        &req.sender_pubkey,
-                                        tokio::runtime::Handle::current(),
-                                    );
-                                }
-                            }
-                        }
-                        // ignore other requests
-                        _ => {}
-                    }
-                }
-            }
+    /// Relay subscription filters: Nostr Connect messages to us,
+    /// and all recent events to us while the event viewer is open
+    fn subscription_filters(&self) -> Vec<Filter> {
+        let mut filters = handler_filters(&self.app_id_keys);
+        if self.is_viewer_open() {
+            filters.push(
+                Filter::new()
+                    .pubkey(self.app_id_keys.public_key())
+                    .since(Timestamp::now() - VIEWER_SINCE)
+                    .limit(VIEWER_MAX_EVENTS),
+            );
         }
-        let _ = locked.remove(0);
+        filters
     }
 
-    /// Remove the (first) pending request
-    pub fn action_first_req_remove(&self) {
-        let _ = self.requests.lock().unwrap().remove(0);
+    /// Signal the handler loop to stop; it unsubscribes from the relay events
+    fn stop_handler_loop(&self) {
+        let _ = self.stop_handler.send(true);
     }
 
-    /// Get number of relays that are Connected / Connecting
-    pub async fn get_connected_count_bg(relay_client: &Client) -> (u32, u32) {
-        let relays = relay_client.relays().await;
-        let (mut cnt_cncted, mut cnt_cncting) = (0, 0);
-        for (_k, r) in relays {
-            match r.status().await {
-                RelayStatus::Connected => cnt_cncted = cnt_cncted + 1,
-                RelayStatus::Connecting => cnt_cncting = cnt_cncting + 1,
-                _ => (),
-            }
+    /// Start the handler loop (subscribing to the relay), stopping the previous one, if any
+    fn restart_handler_loop(self: &Arc<Self>, handle: Handle) {
+        let new_handler = start_handler_loop(self, handle).abort_handle();
+        if let Some(old_handler) = self.handler.lock().unwrap().replace(new_handler) {
+            old_handler.abort();
         }
-        (cnt_cncted, cnt_cncting)
     }
 
-    /// Get number of relays that are Connected / Connecting, blocking version
-    pub fn get_connected_count(&self) -> Result<(u32, u32), Error> {
-        let (tx, rx) = channel::bounded(1);
-        let relay_client_clone = self.relay_client.clone();
-        let handle = tokio::runtime::Handle::current();
-        handle.spawn(async move {
-            let count = Self::get_connected_count_bg(&relay_client_clone).await;
-            let _ = tx.send(count);
-        });
-        Ok(rx.recv()?)
+    /// Drop the relay connection as if the network died: unlike disconnecting,
+    /// the connection is kept and its handler loop is not stopped, so it can be recovered
+    #[cfg(test)]
+    pub async fn force_disconnect_for_test(&self) -> Result<(), Error> {
+        relay_disconnect(self.relay_client.clone()).await
     }
-}
 
-const PREVIEW_CONTENT_LEN: usize = 100;
+    /// Record activity (a message from the client) at the given time
+    fn touch_activity(&self, now: Instant) {
+        *self.last_activity.lock().unwrap() = now;
+    }
 
-fn shortened_text(text: &str, max_len: usize) -> String {
-    if text.len() < max_len {
-        text.to_string()
-    } else {
-        format!("{}..", text[0..max_len].to_string())
+    /// Time since the last activity
+    pub fn idle_time(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(*self.last_activity.lock().unwrap())
     }
-}
 
-impl SignatureReqest {
-    pub fn description(&self) -> String {
-        match self.req.to_request() {
-            Err(_) => "(not request, no action needed)".to_string(),
-            Ok(req) => match req {
-                Request::SignEvent(unsigned_event) => {
-                    format!(
-                        "Signature requested for message: '{}'",
-                        shortened_text(&unsigned_event.content, PREVIEW_CONTENT_LEN)
-                    )
-                }
-                Request::Delegate {
-                    public_key,
-                    conditions,
-                } => {
-                    format!(
-                        "Delegation requested, for pubkey '{}', with conditions '{}'",
-                        public_key.to_bech32().unwrap_or_default(),
-                        conditions.to_string()
-                    )
-                }
-                _ => format!("({}, no action needed)", req.method()),
-            },
+    /// Time until the next keepalive is due (zero if overdue), None if keepalive is disabled
+    fn keepalive_wait(&self, now: Instant) -> Option<Duration> {
+        let interval = Duration::from_secs(self.settings.keepalive_secs);
+        if interval.is_zero() {
+            return None;
         }
+        let elapsed = now.saturating_duration_since(*self.last_keepalive.lock().unwrap());
+        Some(interval.saturating_sub(elapsed))
     }
-}
 
-async fn send_message(
-    relay_client: &Client,
-    msg: &Message,
-    receiver_pubkey: &XOnlyPublicKey,
-) -> Result<(), Error> {
-    let keys = relay_client.keys();
-    let event =
-        EventBuilder::nostr_connect(&keys, *receiver_pubkey, msg.clone())?.to_event(&keys)?;
-    relay_client.send_event(event).await?;
-    println!("DEBUG: Message sent, {:?}", msg);
-    Ok(())
-}
+    fn record_keepalive(&self, now: Instant) {
+        *self.last_keepalive.lock().unwrap() = now;
+    }
 
-fn send_message_blocking(
-    relay_client: &Client,
-    msg: &Message,
-    receiver_pubkey: &XOnlyPublicKey,
-    handle: Handle,
-) -> Result<(), Error> {
-    let (tx, rx) = channel::bounded(1);
-    let relay_client_clone = relay_client.clone();
-    let msg_clone = msg.clone();
-    let receiver_pubkey_clone = receiver_pubkey.clone();
-    handle.spawn(async move {
-        let res = send_message(&relay_client_clone, &msg_clone, &receiver_pubkey_clone).await;
-        let _ = tx.send(res);
-    });
-    let res = rx.recv()?;
-    res
+    /// Set user-assigned name of the client; empty clears it
+    pub fn set_display_name(&self, name: String) {
+        let name = name.trim().to_string();
+        *self.display_name.lock().unwrap() = if name.is_empty() { None } else { Some(name) };
+    }
+
+    /// Name to show for the client: user-assigned name, or app name from metadata,
+    /// or the client pubkey in the given display format
+    pub fn get_display_name(&self, format: DisplayFormat) -> String {
+        if let Some(name) = self.display_name.lock().unwrap().as_ref() {
+            return name.clone();
+        }
+        match &self.metadata_name {
+            Some(name) => name.clone(),
+            None => self.get_client_pubkey_display(format),
+        }
+    }
+
+    /// Return client pubkey in the given display format
+    pub fn get_client_pubkey_display(&self, format: DisplayFormat) -> String {
+        format.format_public_key(&self.client_pubkey)
+    }
+
+    /// The key signer of the connection (a copy)
+    fn key_signer(&self) -> KeySigner {
+        self.key_signer.lock().unwrap().clone()
+    }
+
+    pub fn add_request(&self, req: Message, sender_pubkey: XOnlyPublicKey) {
+        self.requests
+            .lock()
+            .unwrap()
+            .push(SignatureReqest::new(req, sender_pubkey));
+    }
+
+    /// Queue a sign request as if it was received over the relay from the given sender.
+    /// Allows driving the approval flow without a live relay.
+    #[cfg(test)]
+    pub fn inject_request(
+        &self,
+        unsigned: UnsignedEvent,
+        sender: XOnlyPublicKey,
+    ) -> Result<(), Error> {
+        self.add_request(Message::request(Request::SignEvent(unsigned)), sender);
+        self.event_queue.notify(Event::SignerNewRequest)?;
+        self.status.set("New Signing request received");
+        Ok(())
+    }
+
+    /// Move all pending requests from another connection to this one (appended)
+    fn take_requests_from(&self, other: &SignerConnection) {
+        let mut taken = std::mem::take(&mut *other.requests.lock().unwrap());
+        self.requests.lock().unwrap().append(&mut taken);
+    }
+
+    pub fn get_pending_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// Sender pubkeys of all pending requests, in queue order
+    pub fn get_request_senders(&self) -> Vec<XOnlyPublicKey> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.sender_pubkey)
+            .collect()
+    }
+
+    /// Trust the app: its sign requests are signed without asking (see `SignerSettings::auto_approves`)
+    pub fn add_auto_approve(&self, pubkey: XOnlyPublicKey) {
+        self.auto_approve.lock().unwrap().insert(pubkey);
+    }
+
+    pub fn remove_auto_approve(&self, pubkey: &XOnlyPublicKey) {
+        self.auto_approve.lock().unwrap().remove(pubkey);
+    }
+
+    pub fn is_auto_approved(&self, pubkey: &XOnlyPublicKey) -> bool {
+        self.auto_approve.lock().unwrap().contains(pubkey)
+    }
+
+    /// Descriptions of all pending requests, in queue order
+    pub fn get_request_descriptions(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.description())
+            .collect()
+    }
+
+    /// Raw JSON of all pending requests, in queue order, see [`SignatureReqest::raw_json`]
+    pub fn get_request_raw_jsons(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.raw_json())
+            .collect()
+    }
+
+    /// Write all pending requests (in queue order) to a JSON file, for offline review.
+    /// Returns the number of requests written.
+    pub fn export_pending(&self, path: &Path) -> Result<usize, Error> {
+        let exported: Vec<PendingRequestExport> = self
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.export())
+            .collect();
+        fs::write(path, serde_json::to_string_pretty(&exported)?)?;
+        Ok(exported.len())
+    }
+
+    /// Move a pending request from one queue position to another, other requests keep their order
+    pub fn move_request(&self, from: usize, to: usize) -> Result<(), Error> {
+        let mut locked = self.requests.lock().unwrap();
+        if from >= locked.len() || to >= locked.len() {
+            return Err(Error::SignerRequestIndexOutOfBounds);
+        }
+        let req = locked.remove(from);
+        locked.insert(to, req);
+        Ok(())
+    }
+
+    /// Returns false if the first pending request is a sign request with a tampered event id
+    pub fn is_first_request_event_id_valid(&self) -> bool {
+        match self.requests.lock().unwrap().first() {
+            None => true,
+            Some(f) => f.is_event_id_valid(),
+        }
+    }
+
+    pub fn get_first_request_description(&self) -> String {
+        let locked = self.requests.lock().unwrap();
+        let first = locked.get(0);
+        match first {
+            None => "-".to_string(),
+            Some(f) => f.description(),
+        }
+    }
+
+    fn record_latency(&self, duration: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        if latencies.len() >= LATENCY_MAX_COUNT {
+            latencies.pop_front();
+        }
+        latencies.push_back(duration);
+    }
+
+    /// Signing latency of the last processed request, and the average of the last few, if any
+    pub fn get_latency_stats(&self) -> Option<(Duration, Duration)> {
+        let latencies = self.latencies.lock().unwrap();
+        let last = *latencies.back()?;
+        let average = latencies.iter().sum::<Duration>() / latencies.len() as u32;
+        Some((last, average))
+    }
+
+    fn record_signed(&self, event_id: EventId) {
+        let mut signed_ids = self.signed_ids.lock().unwrap();
+        if signed_ids.len() >= SIGNED_IDS_MAX_COUNT {
+            signed_ids.pop_front();
+        }
+        signed_ids.push_back(event_id);
+    }
+
+    /// Ids of the last events signed through this connection, newest first
+    pub fn get_signed_event_ids(&self) -> Vec<EventId> {
+        self.signed_ids
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .copied()
+            .collect()
+    }
+
+    /// Replace the event to be signed of the first pending request with a modified version.
+    /// The client receives the whole signed event, as its id changes.
+    pub fn modify_first_request(&self, modified: UnsignedEvent) -> Result<(), Error> {
+        if !event_id_matches(&modified) {
+            return Err(Error::SignerEventIdMismatch);
+        }
+        match self.requests.lock().unwrap().first_mut() {
+            None => Err(Error::SignerRequestIndexOutOfBounds),
+            Some(f) => {
+                f.modified = Some(modified);
+                Ok(())
+            }
+        }
+    }
+
+    /// Modify the first pending sign request to be signed with the given time as created_at
+    pub fn bump_first_request_time(&self, now: Timestamp) -> Result<(), Error> {
+        let event = match self.requests.lock().unwrap().first() {
+            None => return Err(Error::SignerRequestIndexOutOfBounds),
+            Some(f) => match f.req.to_request() {
+                Ok(Request::SignEvent(requested)) => f.modified.clone().unwrap_or(requested),
+                _ => return Err(Error::SignerNotSignRequest),
+            },
+        };
+        let mut bumped = event;
+        bumped.created_at = now;
+        bumped.id = EventId::new(
+            &bumped.pubkey,
+            bumped.created_at,
+            &bumped.kind,
+            &bumped.tags,
+            &bumped.content,
+        );
+        self.modify_first_request(bumped)
+    }
+
+    pub fn get_first_request_modifications(&self) -> Vec<String> {
+        match self.requests.lock().unwrap().first() {
+            None => Vec::new(),
+            Some(f) => f.modifications(),
+        }
+    }
+
+    pub fn get_first_request_references(&self) -> Vec<String> {
+        match self.requests.lock().unwrap().first() {
+            None => Vec::new(),
+            Some(f) => f.referenced_entities(),
+        }
+    }
+
+    /// Untruncated content of the first request, see [`SignatureReqest::full_content`]
+    pub fn get_first_request_full_content(&self) -> Option<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .first()
+            .and_then(|f| f.full_content())
+    }
+
+    pub fn get_first_request_canonical_serialization(&self) -> Option<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .first()
+            .and_then(|f| f.canonical_serialization().ok())
+    }
+
+    /// Process the first pending request (sign it with the given key and send response), and remove it.
+    /// Returns error if processing was refused, e.g. because of a tampered event id,
+    /// or if the response could not be sent.
+    /// The delegation, if given, is applied to sign requests whose event it permits.
+    /// Requests queued behind it that need no approval are answered then, in order.
+    pub fn action_first_req_process(
+        &self,
+        key_signer: &KeySigner,
+        delegation: Option<&DelegationInfo>,
+    ) -> Result<(), Error> {
+        let first = self.take_first_request();
+        let res = match &first {
+            None => Ok(()),
+            Some(req) => self.process_request(req, key_signer, delegation),
+        };
+        let _ = self.answer_ready_requests();
+        res
+    }
+
+    fn take_first_request(&self) -> Option<SignatureReqest> {
+        let mut locked = self.requests.lock().unwrap();
+        if locked.is_empty() {
+            None
+        } else {
+            Some(locked.remove(0))
+        }
+    }
+
+    /// Sign a request with the given key and send the response; fails if it could not be sent
+    fn process_request(
+        &self,
+        req: &SignatureReqest,
+        key_signer: &KeySigner,
+        delegation: Option<&DelegationInfo>,
+    ) -> Result<(), Error> {
+        if let Some(response_msg) = self.approved_response(req, key_signer, delegation)? {
+            let res = self.send_blocking(&response_msg, &req.sender_pubkey);
+            self.record_latency(req.received_at.elapsed());
+            res?;
+        }
+        Ok(())
+    }
+
+    /// Response for an approved request, signed with the given key; None if there is nothing to send.
+    /// For sign requests the delegation, if given, is applied if it permits the event,
+    /// and the event is recorded as signed.
+    /// If the key differs from the connection key (override key), the event is rebuilt with it
+    /// as the author. If the event differs from the requested one (rebuilt, modified or delegated),
+    /// the whole signed event is returned, as a signature alone would not verify against it.
+    fn approved_response(
+        &self,
+        req: &SignatureReqest,
+        key_signer: &KeySigner,
+        delegation: Option<&DelegationInfo>,
+    ) -> Result<Option<Message>, Error> {
+        let id = match &req.req {
+            Message::Request { id, .. } => id,
+            Message::Response { .. } => return Ok(None),
+        };
+        match req.req.to_request() {
+            Ok(Request::SignEvent(unsigned_event)) => {
+                let signer_pubkey = key_signer.get_public_key();
+                let mut event = event_to_sign(
+                    &unsigned_event,
+                    req.modified.as_ref(),
+                    delegation,
+                    &signer_pubkey,
+                );
+                let is_override = signer_pubkey != self.key_signer().get_public_key();
+                if is_override {
+                    event = with_author(&event, &signer_pubkey);
+                }
+                let event_id = event.id;
+                check_author_allowed(&event, &self.settings.allowed_authors)?;
+                let response = if event_id != unsigned_event.id {
+                    Some(signed_event_response(id, event, key_signer)?)
+                } else {
+                    response_for_message(
+                        id,
+                        &Request::SignEvent(event),
+                        key_signer,
+                        &self.settings,
+                    )?
+                };
+                if response.is_some() {
+                    self.record_signed(event_id);
+                }
+                Ok(response)
+            }
+            Ok(Request::Describe) => Ok(Some(self.describe_response(id))),
+            Ok(request) => response_for_message(id, &request, key_signer, &self.settings),
+            Err(_) => match sign_raw_value(&req.req) {
+                Some(Ok(value)) => sign_raw_response(id, &value, key_signer).map(Some),
+                _ => Ok(None),
+            },
+        }
+    }
+
+    /// Remove the (first) pending request.
+    /// Requests queued behind it that need no approval are answered then, in order.
+    pub fn action_first_req_remove(&self) {
+        let _ = self.take_first_request();
+        let _ = self.answer_ready_requests();
+    }
+
+    /// Reject the (first) pending request: remove it, and send an error response to the client,
+    /// so it doesn't wait for it until timing out. The request is removed even if sending fails.
+    /// Requests queued behind it that need no approval are answered then, in order.
+    pub fn action_first_req_reject(&self) -> Result<(), Error> {
+        let res = match self.take_first_request() {
+            Some(SignatureReqest {
+                req: Message::Request { id, .. },
+                sender_pubkey,
+                ..
+            }) => self.send_blocking(&error_response(&id, REJECTED_ERROR), &sender_pubkey),
+            _ => Ok(()),
+        };
+        let _ = self.answer_ready_requests();
+        res
+    }
+
+    /// Returns true if there is a pending request from the sender
+    fn has_pending_from(&self, sender_pubkey: &XOnlyPublicKey) -> bool {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|r| r.sender_pubkey == *sender_pubkey)
+    }
+
+    /// Remove the queued requests that need no approval (e.g. get_public_key),
+    /// and have no earlier pending request from the same sender; in queue order
+    fn take_ready_requests(&self, local_hour: u32) -> Vec<SignatureReqest> {
+        let can_sign = self.key_signer().can_sign();
+        let mut locked = self.requests.lock().unwrap();
+        let mut waiting_senders: Vec<XOnlyPublicKey> = Vec::new();
+        let mut ready = Vec::new();
+        let mut index = 0;
+        while index < locked.len() {
+            let sender_pubkey = locked[index].sender_pubkey;
+            let trusted = self.is_auto_approved(&sender_pubkey);
+            if !locked[index].needs_approval(&self.settings, trusted, local_hour, can_sign)
+                && !waiting_senders.contains(&sender_pubkey)
+            {
+                ready.push(locked.remove(index));
+            } else {
+                waiting_senders.push(sender_pubkey);
+                index += 1;
+            }
+        }
+        ready
+    }
+
+    /// Answer the queued requests that became ready (see `take_ready_requests`), in order;
+    /// sign requests auto-approved by the kind policy are signed with the connection key.
+    /// Returns the responses sent.
+    fn answer_ready_requests(&self) -> Vec<Message> {
+        let mut responses = Vec::new();
+        for req in self.take_ready_requests(local_hour()) {
+            let response = match (
+                self.approved_response(&req, &self.key_signer(), None),
+                &req.req,
+            ) {
+                (Ok(response), _) => response,
+                (Err(e), Message::Request { id, .. }) => {
+                    self.status
+                        .set(&format!("Warning: Queued request refused, {}", e));
+                    Some(error_response(id, &e.to_string()))
+                }
+                (Err(_), Message::Response { .. }) => None,
+            };
+            if let Some(response_msg) = response {
+                let _ = self.send_blocking(&response_msg, &req.sender_pubkey);
+                responses.push(response_msg);
+            }
+        }
+        responses
+    }
+
+    /// Pending requests expiring within the lead time (not expired yet), not notified before;
+    /// they are marked as notified, so each is notified only once. Returns their number.
+    fn take_expiring(&self, now: Instant, expiry: Duration, lead_time: Duration) -> usize {
+        let mut count = 0;
+        for req in self.requests.lock().unwrap().iter_mut() {
+            let age = now.saturating_duration_since(req.received_at);
+            if !req.expiry_notified && age < expiry && age + lead_time >= expiry {
+                req.expiry_notified = true;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Remove the pending requests older than the expiry, send them error responses.
+    /// Returns the number of expired requests.
+    fn prune_expired(&self, now: Instant, expiry: Duration) -> usize {
+        let expired: Vec<SignatureReqest> = {
+            let mut locked = self.requests.lock().unwrap();
+            let (expired, kept) = std::mem::take(&mut *locked)
+                .into_iter()
+                .partition(|req| now.saturating_duration_since(req.received_at) >= expiry);
+            *locked = kept;
+            expired
+        };
+        for req in &expired {
+            if let Message::Request { id, .. } = &req.req {
+                let _ = self.send_blocking(&error_response(id, EXPIRED_ERROR), &req.sender_pubkey);
+            }
+        }
+        expired.len()
+    }
+
+    /// Reject all pending requests: send an error response for each, and clear the queue.
+    /// Returns the number of rejected requests.
+    pub fn action_reject_all(&self) -> usize {
+        let rejected = std::mem::take(&mut *self.requests.lock().unwrap());
+        for req in &rejected {
+            if let Message::Request { id, .. } = &req.req {
+                let _ = self.send_blocking(&error_response(id, REJECTED_ERROR), &req.sender_pubkey);
+            }
+        }
+        rejected.len()
+    }
+
+    /// Get number of relays that are Connected / Connecting
+    pub async fn get_connected_count_bg(relay_client: &Client) -> (u32, u32) {
+        let relays = relay_client.relays().await;
+        let (mut cnt_cncted, mut cnt_cncting) = (0, 0);
+        for (_k, r) in relays {
+            match r.status().await {
+                RelayStatus::Connected => cnt_cncted = cnt_cncted + 1,
+                RelayStatus::Connecting => cnt_cncting = cnt_cncting + 1,
+                _ => (),
+            }
+        }
+        (cnt_cncted, cnt_cncting)
+    }
+
+    /// Get number of relays that are Connected / Connecting, blocking version
+    pub fn get_connected_count(&self) -> Result<(u32, u32), Error> {
+        let (tx, rx) = channel::bounded(1);
+        let relay_client_clone = self.relay_client.clone();
+        let handle = tokio::runtime::Handle::current();
+        handle.spawn(async move {
+            let count = Self::get_connected_count_bg(&relay_client_clone).await;
+            let _ = tx.send(count);
+        });
+        Ok(rx.recv()?)
+    }
 }
 
-async fn relay_connect(
-    connection: Arc<SignerConnection>,
-    connect_id_keys: &Keys,
-) -> Result<(), Error> {
-    connection
-        .relay_client
-        .add_relay(&connection.relay_str, None)
-        .await?;
-    // TODO: SDK does not give an error here
-    connection.relay_client.connect().await;
+const PREVIEW_CONTENT_LEN: usize = 100;
 
-    let _res = start_handler_loop(connection.clone(), tokio::runtime::Handle::current())?;
+/// Check that the event id corresponds to the event fields (pubkey, created_at, kind, tags, content).
+/// A mismatch means the request is tampered, the id to be signed is not the one of the shown content.
+fn event_id_matches(unsigned_event: &UnsignedEvent) -> bool {
+    let computed_id = EventId::new(
+        &unsigned_event.pubkey,
+        unsigned_event.created_at,
+        &unsigned_event.kind,
+        &unsigned_event.tags,
+        &unsigned_event.content,
+    );
+    computed_id == unsigned_event.id
+}
 
-    // Send connect ACK
-    let msg = Message::request(Request::Connect(connect_id_keys.public_key()));
-    let _ = send_message(&connection.relay_client, &msg, &connection.client_pubkey).await?;
+/// The NIP-01 serialization of an event, `[0, pubkey, created_at, kind, tags, content]`,
+/// whose sha256 hash is the event id
+fn canonical_serialization(unsigned_event: &UnsignedEvent) -> String {
+    serde_json::json!([
+        0,
+        unsigned_event.pubkey,
+        unsigned_event.created_at,
+        unsigned_event.kind,
+        unsigned_event.tags,
+        unsigned_event.content
+    ])
+    .to_string()
+}
 
-    EVENT_QUEUE.push(Event::SignerConnected)?;
-    connection.status.set(&format!(
-        "Signer connected (relay: {}, client npub: {})",
-        connection.relay_str,
-        connection.client_pubkey.to_bech32().unwrap(),
-    ));
+/// Length of mentioned entities kept in a note preview, e.g. 'npub1abcdefg..'
+const MENTION_PREVIEW_LEN: usize = 12;
 
-    Ok(())
+/// Readable preview of note content: `nostr:` mentions (npub, note, ...) are shortened,
+/// whitespace is collapsed within lines, and repeated or trailing empty lines are removed
+pub(crate) fn render_note_preview(content: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let rendered = line
+            .split_whitespace()
+            .map(render_mention)
+            .collect::<Vec<String>>()
+            .join(" ");
+        if rendered.is_empty() && lines.last().map_or(true, |l| l.is_empty()) {
+            continue;
+        }
+        lines.push(rendered);
+    }
+    while lines.last().map_or(false, |l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
 }
 
-async fn relay_disconnect(relay_client: Client) -> Result<(), Error> {
-    let _res = relay_client.disconnect().await?;
-    Ok(())
+/// Replace a `nostr:` mention in a word with the shortened entity, keeping surrounding text
+fn render_mention(word: &str) -> String {
+    let pos = match word.find("nostr:") {
+        None => return word.to_string(),
+        Some(p) => p,
+    };
+    let (before, rest) = (&word[..pos], &word[pos + "nostr:".len()..]);
+    let end = rest
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(rest.len());
+    let (entity, after) = rest.split_at(end);
+    let is_entity = ["npub1", "note1", "nprofile1", "nevent1", "naddr1"]
+        .iter()
+        .any(|p| entity.starts_with(p));
+    if !is_entity {
+        return word.to_string();
+    }
+    format!(
+        "{}@{}{}",
+        before,
+        shortened_text(entity, MENTION_PREVIEW_LEN),
+        after
+    )
 }
 
-/*
-fn relay_connect_blocking(connection: Arc<SignerConnection>, handle: Handle) -> Result<(), Error> {
-    let (tx, rx) = channel::bounded(1);
-    let connect_id_keys_clone = connection.app_id_keys.clone();
-    let connection_clone = connection.clone();
-    handle.spawn(async move {
-        let conn_res = relay_connect(connection_clone, &connect_id_keys_clone).await;
-        let _ = tx.send(conn_res);
-    });
-    let _ = rx.recv()?;
-    Ok(())
+/// Text cut to at most `max_len` characters (not bytes, to not split multi-byte characters)
+fn shortened_text(text: &str, max_len: usize) -> String {
+    if text.chars().count() < max_len {
+        text.to_string()
+    } else {
+        format!("{}..", text.chars().take(max_len).collect::<String>())
+    }
 }
-*/
 
-/// Do connect in the background
-fn relay_connect_async(connection: Arc<SignerConnection>, handle: Handle) -> Result<(), Error> {
-    let connect_id_keys_clone = connection.app_id_keys.clone();
-    let connection_clone = connection.clone();
-    handle.spawn(async move {
-        let _ = relay_connect(connection_clone, &connect_id_keys_clone).await;
-    });
-    Ok(())
+/// Entities referenced by the event (`e` and `p` tags), in bech32 form ('note' or 'npub')
+fn referenced_entities(unsigned_event: &UnsignedEvent) -> Vec<String> {
+    unsigned_event
+        .tags
+        .iter()
+        .filter_map(|t| match t {
+            Tag::Event(id, ..) => id.to_bech32().ok(),
+            Tag::PubKey(pubkey, _) => pubkey.to_bech32().ok(),
+            _ => None,
+        })
+        .collect()
 }
 
-fn relay_disconnect_blocking(relay_client: Client, handle: Handle) -> Result<(), Error> {
-    let (tx, rx) = channel::bounded(1);
-    let relay_client_clone = relay_client.clone();
-    handle.spawn(async move {
-        let disconn_res = relay_disconnect(relay_client_clone).await;
-        let _ = tx.send(disconn_res);
-    });
-    rx.recv()?
-}
+/// Human-readable list of the differences between an event and its modified version
+pub(crate) fn describe_modifications(
+    original: &UnsignedEvent,
+    modified: &UnsignedEvent,
+) -> Vec<String> {
+    let mut changes = Vec::new();
+    if original.id != modified.id {
+        changes.push(format!("id: {} -> {}", original.id, modified.id));
+    }
+    if original.created_at != modified.created_at {
+        changes.push(format!(
+            "created_at: {} -> {}",
+            original.created_at.as_u64(),
+            modified.created_at.as_u64()
+        ));
+    }
+    if original.kind != modified.kind {
+        changes.push(format!(
+            "kind: {} -> {}",
+            kind_label(original.kind),
+            kind_label(modified.kind)
+        ));
+    }
+    if original.content != modified.content {
+        changes.push(format!(
+            "content: '{}' -> '{}'",
+            shortened_text(&original.content, 40),
+            shortened_text(&modified.content, 40)
+        ));
+    }
+    for tag in &modified.tags {
+        if !original.tags.contains(tag) {
+            changes.push(format!("added tag: {}", tag.as_vec().join(", ")));
+        }
+    }
+    for tag in &original.tags {
+        if !modified.tags.contains(tag) {
+            changes.push(format!("removed tag: {}", tag.as_vec().join(", ")));
+        }
+    }
+    changes
+}
+
+impl SignatureReqest {
+    fn new(req: Message, sender_pubkey: XOnlyPublicKey) -> Self {
+        Self {
+            req,
+            sender_pubkey,
+            received_at: Instant::now(),
+            modified: None,
+            expiry_notified: false,
+        }
+    }
+
+    /// The request message as raw JSON (decrypted), for inspection.
+    /// May contain the content to be signed, but no secrets.
+    pub fn raw_json(&self) -> String {
+        self.req.as_json()
+    }
+
+    fn export(&self) -> PendingRequestExport {
+        PendingRequestExport {
+            description: self.description(),
+            kind: match self.req.to_request() {
+                Ok(Request::SignEvent(unsigned_event)) => Some(unsigned_event.kind.as_u64()),
+                _ => None,
+            },
+            sender: self.sender_pubkey.to_bech32().unwrap_or_default(),
+            raw_json: self.raw_json(),
+        }
+    }
+
+    /// Changes made to the event to sign, empty if not modified
+    pub fn modifications(&self) -> Vec<String> {
+        match (self.req.to_request(), &self.modified) {
+            (Ok(Request::SignEvent(original)), Some(modified)) => {
+                describe_modifications(&original, modified)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Entities referenced by a sign request, empty for other requests
+    pub fn referenced_entities(&self) -> Vec<String> {
+        match self.req.to_request() {
+            Ok(Request::SignEvent(unsigned_event)) => referenced_entities(&unsigned_event),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Entire content of the event to sign (the modified version, if any), without
+    /// the truncation of the description; None if not a sign request
+    pub fn full_content(&self) -> Option<String> {
+        match self.req.to_request() {
+            Ok(Request::SignEvent(unsigned_event)) => Some(
+                self.modified
+                    .as_ref()
+                    .unwrap_or(&unsigned_event)
+                    .content
+                    .clone(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Canonical serialization of the event to sign (the modified version, if any),
+    /// for verifying its id independently; error if not a sign request
+    pub fn canonical_serialization(&self) -> Result<String, Error> {
+        match self.req.to_request()? {
+            Request::SignEvent(unsigned_event) => Ok(canonical_serialization(
+                self.modified.as_ref().unwrap_or(&unsigned_event),
+            )),
+            _ => Err(Error::SignerNotSignRequest),
+        }
+    }
+
+    /// Returns true if the request needs user approval (signing, delegation);
+    /// others (e.g. get_public_key, or signing auto-approved by the kind policy at the hour) can be answered without it.
+    /// Signing is never auto-approved if the connection key cannot sign (fresh unlock for each signature).
+    pub fn needs_approval(
+        &self,
+        settings: &SignerSettings,
+        trusted: bool,
+        local_hour: u32,
+        can_sign: bool,
+    ) -> bool {
+        match self.req.to_request() {
+            Ok(Request::SignEvent(unsigned_event)) => {
+                !can_sign || !settings.auto_approves(unsigned_event.kind, trusted, local_hour)
+            }
+            Ok(Request::Delegate { .. }) => true,
+            Ok(Request::Describe) => settings.stealth_mode,
+            Ok(_) => false,
+            Err(_) => sign_raw_value(&self.req).is_some(),
+        }
+    }
+
+    /// Returns false if this is a sign request with an event id not matching the event content
+    pub fn is_event_id_valid(&self) -> bool {
+        match self.req.to_request() {
+            Ok(Request::SignEvent(unsigned_event)) => event_id_matches(&unsigned_event),
+            _ => true,
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self.req.to_request() {
+            Err(_) => match sign_raw_value(&self.req) {
+                Some(Ok(value)) => format!(
+                    "WARNING: RAW signature requested for hash '{}'; what is signed cannot be checked, approve only if you fully trust the client!",
+                    hex::encode(value)
+                ),
+                _ => "(not request, no action needed)".to_string(),
+            },
+            Ok(req) => match req {
+                Request::SignEvent(unsigned_event) => {
+                    format!(
+                        "{}Signature requested for {}: '{}'",
+                        if event_id_matches(&unsigned_event) {
+                            ""
+                        } else {
+                            "WARNING: TAMPERED request, event id does not match content! "
+                        },
+                        kind_label(unsigned_event.kind),
+                        if unsigned_event.kind == Kind::TextNote {
+                            shortened_text(
+                                &render_note_preview(&unsigned_event.content),
+                                PREVIEW_CONTENT_LEN,
+                            )
+                        } else {
+                            shortened_text(&unsigned_event.content, PREVIEW_CONTENT_LEN)
+                        }
+                    )
+                }
+                Request::Delegate {
+                    public_key,
+                    conditions,
+                } => {
+                    format!(
+                        "Delegation requested, for pubkey '{}', with conditions '{}'",
+                        public_key.to_bech32().unwrap_or_default(),
+                        conditions.to_string()
+                    )
+                }
+                Request::Describe => {
+                    "Capabilities requested (describe); answering reveals this app as a signer"
+                        .to_string()
+                }
+                _ => format!("({}, no action needed)", req.method()),
+            },
+        }
+    }
+}
+
+/// Send a message to the relays of the client (the connection relay and the response relays,
+/// added on connect)
+async fn send_message(
+    relay_client: &Client,
+    msg: &Message,
+    receiver_pubkey: &XOnlyPublicKey,
+) -> Result<(), Error> {
+    let keys = relay_client.keys();
+    let event =
+        EventBuilder::nostr_connect(&keys, *receiver_pubkey, msg.clone())?.to_event(&keys)?;
+    relay_client.send_event(event).await?;
+    // not the content: it may hold signed events, user content
+    match msg {
+        Message::Request { id, method, .. } => {
+            eprintln!("DEBUG: Request sent, method {} id {}", method, id)
+        }
+        Message::Response { id, .. } => eprintln!("DEBUG: Response sent, id {}", id),
+    }
+    Ok(())
+}
+
+fn send_message_blocking(
+    relay_client: &Client,
+    msg: &Message,
+    receiver_pubkey: &XOnlyPublicKey,
+    handle: Handle,
+) -> Result<(), Error> {
+    let (tx, rx) = channel::bounded(1);
+    let relay_client_clone = relay_client.clone();
+    let msg_clone = msg.clone();
+    let receiver_pubkey_clone = receiver_pubkey.clone();
+    handle.spawn(async move {
+        let res = send_message(&relay_client_clone, &msg_clone, &receiver_pubkey_clone).await;
+        let _ = tx.send(res);
+    });
+    let res = rx.recv()?;
+    res
+}
+
+/// Publish an event to the given relays, in the background, using a throwaway client.
+/// The outcome is reported in the status.
+pub(crate) fn publish_event(
+    event: nostr::Event,
+    relays: Vec<String>,
+    max_concurrent_connects: usize,
+    mut status: StatusMessages,
+) {
+    let handle = match Handle::try_current() {
+        Err(_) => {
+            status.set_error("No async runtime, cannot publish");
+            return;
+        }
+        Ok(h) => h,
+    };
+    handle.spawn(async move {
+        let client = Client::with_opts(&Keys::generate(), Options::new().wait_for_send(true));
+        let res = publish_event_bg(&client, event, &relays, max_concurrent_connects).await;
+        let _res = client.disconnect().await;
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(id) => status.set(&format!("Event published, id {}", id)),
+        }
+    });
+}
+
+async fn publish_event_bg(
+    client: &Client,
+    event: nostr::Event,
+    relays: &[String],
+    max_concurrent_connects: usize,
+) -> Result<EventId, Error> {
+    connect_relays_limited(client, relays, max_concurrent_connects).await?;
+    Ok(client.send_event(event).await?)
+}
+
+/// Add and connect the given relays, with at most `max_concurrent` connection attempts
+/// in flight at once (0 for no limit), the rest are queued
+async fn connect_relays_limited(
+    client: &Client,
+    relays: &[String],
+    max_concurrent: usize,
+) -> Result<(), Error> {
+    let client = client.clone();
+    run_limited(relays, max_concurrent, move |relay| {
+        let client = client.clone();
+        async move {
+            client.add_relay(relay.as_str(), None).await?;
+            client.connect_relay(relay.as_str()).await?;
+            Ok(())
+        }
+    })
+    .await
+}
+
+/// Add and connect the response relays of the connection, so they also receive the responses.
+/// Done once on connect; a failing relay is logged and skipped, the others are still used.
+async fn connect_response_relays(connection: &SignerConnection) {
+    let client = connection.relay_client.clone();
+    let status = connection.status.clone();
+    let _res = run_limited(
+        &connection.settings.response_relays,
+        connection.settings.max_concurrent_connects,
+        move |relay| {
+            let client = client.clone();
+            let mut status = status.clone();
+            async move {
+                let res = match client.add_relay(relay.as_str(), None).await {
+                    Ok(_) => client.connect_relay(relay.as_str()).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = res {
+                    status.set_error(&format!("Could not add response relay {}, {}", relay, e));
+                }
+                Ok(())
+            }
+        },
+    )
+    .await;
+}
+
+/// Run a task for each item, with at most `max_concurrent` running at once (0 for no limit).
+/// All are run; the first error is returned, if any.
+async fn run_limited<F, Fut>(items: &[String], max_concurrent: usize, task: F) -> Result<(), Error>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<(), Error>> + Send + 'static,
+{
+    let mut running = JoinSet::new();
+    let mut results = Vec::new();
+    for item in items {
+        if max_concurrent > 0 && running.len() >= max_concurrent {
+            if let Some(res) = running.join_next().await {
+                results.push(res);
+            }
+        }
+        running.spawn(task(item.clone()));
+    }
+    while let Some(res) = running.join_next().await {
+        results.push(res);
+    }
+    for res in results {
+        res??;
+    }
+    Ok(())
+}
+
+async fn relay_connect(
+    connection: Arc<SignerConnection>,
+    connect_id_keys: &Keys,
+) -> Result<(), Error> {
+    connection
+        .relay_client
+        .add_relay(&connection.relay_str, None)
+        .await?;
+    // TODO: SDK does not give an error here
+    connection.relay_client.connect().await;
+    connect_response_relays(&connection).await;
+
+    connection.restart_handler_loop(tokio::runtime::Handle::current());
+
+    // Send connect ACK
+    let msg = connect_message(
+        &connect_id_keys.public_key(),
+        connection.permissions.as_ref(),
+    );
+    send_message(&connection.relay_client, &msg, &connection.client_pubkey).await?;
+
+    connection.event_queue.notify(Event::SignerConnected)?;
+    connection.status.set(&format!(
+        "Signer connected (relay: {}, client npub: {})",
+        connection.relay_str,
+        connection.client_pubkey.to_bech32().unwrap(),
+    ));
+
+    Ok(())
+}
+
+/// Connect message to the client; if restricted, with the granted permissions (NIP-46 form)
+/// as an additional parameter
+fn connect_message(signer_pubkey: &XOnlyPublicKey, permissions: Option<&Permissions>) -> Message {
+    match (
+        Message::request(Request::Connect(*signer_pubkey)),
+        permissions,
+    ) {
+        (
+            Message::Request {
+                id,
+                method,
+                mut params,
+            },
+            Some(p),
+        ) => {
+            params.push(json!(p.to_string()));
+            Message::Request { id, method, params }
+        }
+        (msg, _) => msg,
+    }
+}
+
+async fn relay_disconnect(relay_client: Client) -> Result<(), Error> {
+    let _res = relay_client.disconnect().await?;
+    Ok(())
+}
+
+/*
+fn relay_connect_blocking(connection: Arc<SignerConnection>, handle: Handle) -> Result<(), Error> {
+    let (tx, rx) = channel::bounded(1);
+    let connect_id_keys_clone = connection.app_id_keys.clone();
+    let connection_clone = connection.clone();
+    handle.spawn(async move {
+        let conn_res = relay_connect(connection_clone, &connect_id_keys_clone).await;
+        let _ = tx.send(conn_res);
+    });
+    let _ = rx.recv()?;
+    Ok(())
+}
+*/
+
+/// Do connect in the background
+fn relay_connect_async(connection: Arc<SignerConnection>, handle: Handle) -> Result<(), Error> {
+    let connect_id_keys_clone = connection.app_id_keys.clone();
+    let connection_clone = connection.clone();
+    handle.spawn(async move {
+        let res = relay_connect(connection_clone.clone(), &connect_id_keys_clone).await;
+        connection_clone.record_result(&res);
+    });
+    Ok(())
+}
+
+fn relay_disconnect_blocking(relay_client: Client, handle: Handle) -> Result<(), Error> {
+    let (tx, rx) = channel::bounded(1);
+    let relay_client_clone = relay_client.clone();
+    handle.spawn(async move {
+        let disconn_res = relay_disconnect(relay_client_clone).await;
+        let _ = tx.send(disconn_res);
+    });
+    rx.recv()?
+}
+
+/// The event to sign for a sign request: the modified version if any, else the requested one;
+/// with the delegation tag added if a delegation is given, and it permits the event for the signer
+fn event_to_sign(
+    requested: &UnsignedEvent,
+    modified: Option<&UnsignedEvent>,
+    delegation: Option<&DelegationInfo>,
+    signer_pubkey: &XOnlyPublicKey,
+) -> UnsignedEvent {
+    let event = modified.unwrap_or(requested);
+    let has_delegation_tag = event
+        .tags
+        .iter()
+        .any(|t| matches!(t, Tag::Delegation { .. }));
+    match delegation {
+        Some(d) if !has_delegation_tag && d.permits(signer_pubkey, event) => d.apply(event),
+        _ => event.clone(),
+    }
+}
+
+/// The event with the given author, id updated (unchanged if it is the author already)
+fn with_author(event: &UnsignedEvent, author: &XOnlyPublicKey) -> UnsignedEvent {
+    if event.pubkey == *author {
+        return event.clone();
+    }
+    let mut rebuilt = event.clone();
+    rebuilt.pubkey = *author;
+    rebuilt.id = EventId::new(
+        &rebuilt.pubkey,
+        rebuilt.created_at,
+        &rebuilt.kind,
+        &rebuilt.tags,
+        &rebuilt.content,
+    );
+    rebuilt
+}
+
+/// Response with the whole signed event, for events that differ from the requested one
+fn signed_event_response(
+    req_id: &str,
+    event: UnsignedEvent,
+    key_signer: &KeySigner,
+) -> Result<Message, Error> {
+    if !event_id_matches(&event) {
+        return Err(Error::SignerEventIdMismatch);
+    }
+    let signature = key_signer.sign(event.id.as_bytes().to_vec())?;
+    let signed = event.add_signature(signature)?;
+    Ok(Message::Response {
+        id: req_id.to_string(),
+        result: Some(json!(signed)),
+        error: None,
+    })
+}
+
+/// Parse the connect URI input ('nostrconnect://...'); blank input is an error
+fn parse_connect_uri(uri_str: &str) -> Result<NostrConnectURI, Error> {
+    Ok(NostrConnectURI::from_str(require_input(
+        uri_str,
+        "Connect URI",
+    )?)?)
+}
+
+/// Relays contacted for a connection: the connection relay, then the response relays,
+/// without duplicates (trailing slash ignored)
+fn contacted_relays(relay_str: &str, response_relays: &[String]) -> Vec<String> {
+    let mut relays: Vec<String> = Vec::new();
+    for relay in std::iter::once(relay_str).chain(response_relays.iter().map(String::as_str)) {
+        let relay = relay.trim_end_matches('/');
+        if !relays.iter().any(|r| r == relay) {
+            relays.push(relay.to_string());
+        }
+    }
+    relays
+}
+
+/// Put together an error response message for a request
+fn error_response(req_id: &str, error: &str) -> Message {
+    Message::Response {
+        id: req_id.to_string(),
+        result: None,
+        error: Some(error.to_string()),
+    }
+}
+
+/// NIP-46 request methods handled by the signer, as reported by `describe`;
+/// capabilities turned off in the settings are not included.
+/// Delegation (`delegate`) is handled too, but advertised only to clients granted it at pairing.
+pub(crate) fn supported_methods(settings: &SignerSettings) -> Vec<String> {
+    let mut methods: Vec<String> = ["describe", "get_public_key", "sign_event"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    if settings.allow_sign_raw {
+        methods.push(SIGN_RAW_METHOD.to_string());
+    }
+    methods
+}
+
+/// If the message is a "sign raw" request, the 32-byte value to sign (or error if it is invalid);
+/// None for other messages
+fn sign_raw_value(msg: &Message) -> Option<Result<[u8; 32], Error>> {
+    match msg {
+        Message::Request { method, params, .. } if method == SIGN_RAW_METHOD => Some(
+            params
+                .first()
+                .and_then(|p| p.as_str())
+                .and_then(|s| hex::decode(s).ok())
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                .ok_or(Error::SignerRawInvalid),
+        ),
+        _ => None,
+    }
+}
+
+/// Response for a "sign raw" request: the Schnorr signature of the value, in hex
+fn sign_raw_response(
+    req_id: &str,
+    value: &[u8; 32],
+    key_signer: &KeySigner,
+) -> Result<Message, Error> {
+    let signature = key_signer.sign(value.to_vec())?;
+    Ok(Message::Response {
+        id: req_id.to_string(),
+        result: Some(Value::String(signature.to_string())),
+        error: None,
+    })
+}
+
+/// Handle a "sign raw" request (only if allowed): queue it for user approval,
+/// or reject it right away if the value is invalid
+async fn handle_sign_raw_request(
+    connection: &SignerConnection,
+    msg: &Message,
+    req_id: &str,
+    value: Result<[u8; 32], Error>,
+    sender_pubkey: &XOnlyPublicKey,
+) -> Result<(), Error> {
+    match value {
+        Err(e) => {
+            connection
+                .status
+                .set(&format!("Warning: Raw signing request rejected, {}", e));
+            connection
+                .send(&error_response(req_id, &e.to_string()), sender_pubkey)
+                .await?;
+        }
+        Ok(_) => {
+            // This request needs user processing, store it, notify it
+            connection.add_request(msg.clone(), *sender_pubkey);
+            connection.event_queue.notify(Event::SignerNewRequest)?;
+            connection
+                .status
+                .set("New RAW signing request received, check it carefully");
+        }
+    }
+    Ok(())
+}
+
+/// Error response for a request that could not be handled, if rejecting those is enabled:
+/// its method is unsupported, or its parameters are invalid
+fn unsupported_method_response(msg: &Message, settings: &SignerSettings) -> Option<Message> {
+    match msg {
+        Message::Request { id, method, .. } if settings.reject_unknown_methods => {
+            let error = if supported_methods(settings).contains(method) || method == DELEGATE_METHOD
+            {
+                format!("Invalid request for method '{}'", method)
+            } else {
+                format!("Unsupported method '{}'", method)
+            };
+            Some(error_response(id, &error))
+        }
+        _ => None,
+    }
+}
+
+/// Send an error response for an unsupported request, so the client fails fast;
+/// ignore it if rejecting is disabled
+async fn reject_unsupported_request(
+    connection: &SignerConnection,
+    msg: &Message,
+    sender_pubkey: &XOnlyPublicKey,
+) -> Result<(), Error> {
+    match unsupported_method_response(msg, &connection.settings) {
+        None => eprintln!("DEBUG: Unsupported {}, ignoring", message_method(msg)),
+        Some(err_msg) => {
+            connection.status.set(&format!(
+                "Warning: Unsupported {}, rejected",
+                message_method(msg)
+            ));
+            connection.send(&err_msg, sender_pubkey).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Current hour of the day, in local time
+fn local_hour() -> u32 {
+    chrono::Local::now().hour()
+}
+
+/// Check that the kind policy does not refuse signing the event
+fn check_kind_policy(
+    unsigned_event: &UnsignedEvent,
+    settings: &SignerSettings,
+) -> Result<(), Error> {
+    match settings.approval_action(unsigned_event.kind) {
+        ApprovalAction::Reject => Err(Error::SignerKindRejected(kind_label(unsigned_event.kind))),
+        ApprovalAction::Queue | ApprovalAction::AutoApprove => Ok(()),
+    }
+}
+
+/// Sign a request auto-approved by the kind policy right away, with the connection key,
+/// and send the response (an error response if it is refused, e.g. tampered)
+async fn auto_approve_request(
+    connection: &SignerConnection,
+    msg: &Message,
+    sender_pubkey: &XOnlyPublicKey,
+    reason: &str,
+) -> Result<(), Error> {
+    let req = SignatureReqest::new(msg.clone(), *sender_pubkey);
+    let response = match connection.approved_response(&req, &connection.key_signer(), None) {
+        Ok(response) => {
+            connection.status.set(&format!(
+                "Auto-approved by {}: {}",
+                reason,
+                req.description()
+            ));
+            response
+        }
+        Err(e) => {
+            connection.status.set(&format!(
+                "Warning: Auto-approved signing request refused, {}",
+                e
+            ));
+            match msg {
+                Message::Request { id, .. } => Some(error_response(id, &e.to_string())),
+                Message::Response { .. } => None,
+            }
+        }
+    };
+    if let Some(response_msg) = response {
+        connection.send(&response_msg, sender_pubkey).await?;
+        connection.record_latency(req.received_at.elapsed());
+    }
+    Ok(())
+}
+
+/// Check that the event content of a sign request is not too large
+fn check_content_size(unsigned_event: &UnsignedEvent, max_size: usize) -> Result<(), Error> {
+    let size = unsigned_event.content.len();
+    if size > max_size {
+        return Err(Error::SignerContentTooLarge(size, max_size));
+    }
+    Ok(())
+}
+
+/// Check that the author of the event to sign is in the allow-list (npub or hex); empty allows all
+fn check_author_allowed(
+    unsigned_event: &UnsignedEvent,
+    allowed_authors: &[String],
+) -> Result<(), Error> {
+    if allowed_authors.is_empty() {
+        return Ok(());
+    }
+    let allowed = allowed_authors.iter().any(|a| {
+        let a = a.trim();
+        XOnlyPublicKey::from_bech32(a)
+            .ok()
+            .or_else(|| XOnlyPublicKey::from_str(a).ok())
+            == Some(unsigned_event.pubkey)
+    });
+    if !allowed {
+        return Err(Error::SignerAuthorNotAllowed(
+            unsigned_event.pubkey.to_bech32().unwrap_or_default(),
+        ));
+    }
+    Ok(())
+}
+
+fn message_method(msg: &Message) -> String {
+    match &msg {
+        Message::Request { method, .. } => format!("request {method}"),
+        Message::Response { .. } => "response".to_string(),
+    }
+}
+
+/// Start event handling loop in the background, asynchrnous.
+/// The loop holds only a weak reference, it stops when the connection is dropped,
+/// or when the event queue is closed (UI is gone).
+fn start_handler_loop(connection: &Arc<SignerConnection>, handle: Handle) -> JoinHandle<()> {
+    let weak_connection = Arc::downgrade(connection);
+    let relay_client = connection.relay_client.clone();
+    let stop_rx = connection.stop_handler.subscribe();
+    let closed_rx = connection.event_queue.closed_receiver();
+    handle.spawn(async move {
+        let res =
+            wait_and_handle_messages(weak_connection.clone(), relay_client, stop_rx, closed_rx)
+                .await;
+        if let (Err(_), Some(connection)) = (&res, weak_connection.upgrade()) {
+            connection.record_result(&res);
+        }
+    })
+}
+
+/// Subscription filters for the handler loop: Nostr Connect messages to us
+fn handler_filters(keys: &Keys) -> Vec<Filter> {
+    vec![Filter::new()
+        .pubkey(keys.public_key())
+        .kind(Kind::NostrConnect)
+        .since(Timestamp::now() - Duration::from_secs(10))]
+}
+
+/// Subscription filters of the connection, or the handler filters if it is gone
+fn current_filters(connection: &Weak<SignerConnection>, keys: &Keys) -> Vec<Filter> {
+    match connection.upgrade() {
+        Some(c) => c.subscription_filters(),
+        None => handler_filters(keys),
+    }
+}
+
+/// NIP-42 AUTH message answering a relay challenge, signed with the app ID keys
+fn auth_response(keys: &Keys, relay_url: &Url, challenge: &str) -> Result<ClientMessage, Error> {
+    let event = EventBuilder::auth(challenge, relay_url.clone()).to_event(keys)?;
+    Ok(ClientMessage::new_auth(event))
+}
+
+async fn wait_and_handle_messages(
+    connection: Weak<SignerConnection>,
+    relay_client: Client,
+    mut stop_rx: watch::Receiver<bool>,
+    mut queue_closed_rx: watch::Receiver<bool>,
+) -> Result<(), Error> {
+    let keys = relay_client.keys();
+
+    // subscribing waits for the relay, it may never connect: stop also meanwhile
+    let filters = current_filters(&connection, &keys);
+    tokio::select! {
+        _ = stop_rx.changed() => {
+            eprintln!("DEBUG: Handler loop stopped before subscribing");
+            return Ok(());
+        }
+        _ = relay_client.subscribe(filters) => {}
+    }
+    eprintln!("DEBUG: Subscribed to relay events ...");
+    eprintln!("DEBUG: Waiting for messages ...");
+
+    let mut notifications = relay_client.notifications();
+    loop {
+        if *queue_closed_rx.borrow() {
+            eprintln!("DEBUG: Event queue closed, stopping handler loop");
+            break;
+        }
+        let keepalive_wait = match connection.upgrade() {
+            None => break,
+            Some(c) => c.keepalive_wait(Instant::now()),
+        };
+        let keepalive_due = async {
+            match keepalive_wait {
+                None => std::future::pending::<()>().await,
+                Some(wait) => tokio::time::sleep(wait).await,
+            }
+        };
+        let notification = tokio::select! {
+            // stop requested, or connection dropped
+            _ = stop_rx.changed() => break,
+            // renew the subscription, so the relays keep the session
+            _ = keepalive_due => {
+                let filters = current_filters(&connection, &keys);
+                tokio::select! {
+                    _ = stop_rx.changed() => break,
+                    _ = relay_client.subscribe(filters) => {}
+                }
+                if let Some(c) = connection.upgrade() {
+                    c.record_keepalive(Instant::now());
+                }
+                continue;
+            }
+            // queue closed (checked at loop start), or dropped
+            res = queue_closed_rx.changed() => match res {
+                Ok(_) => continue,
+                Err(_) => break,
+            },
+            n = notifications.recv() => n,
+        };
+        let notification = match notification {
+            Ok(n) => n,
+            Err(_) => {
+                notifications = relay_client.notifications();
+                continue;
+            }
+        };
+        if let RelayPoolNotification::Event(_url, event) = &notification {
+            if let Some(c) = connection.upgrade() {
+                c.record_viewed_event(event);
+            }
+        }
+        match notification {
+            RelayPoolNotification::Event(_url, event) if event.kind == Kind::NostrConnect => {
+                let connection = match connection.upgrade() {
+                    None => break,
+                    Some(c) => c,
+                };
+                match decrypt(&keys.secret_key()?, &event.pubkey, &event.content) {
+                    Ok(msg) => {
+                        let msg = Message::from_json(msg)?;
+                        match handle_request(connection, &msg, &event.pubkey).await {
+                            Err(Error::InternalEventQueueClosed) => {
+                                eprintln!("DEBUG: Event queue closed, stopping handler loop");
+                                break;
+                            }
+                            res => res?,
+                        }
+                    }
+                    Err(e) => eprintln!("DEBUG: Impossible to decrypt NIP46 message: {e}"),
+                }
+            }
+            RelayPoolNotification::Message(
+                url,
+                RelayMessage::Ok {
+                    event_id,
+                    status,
+                    message,
+                },
+            ) => {
+                let connection = match connection.upgrade() {
+                    None => break,
+                    Some(c) => c,
+                };
+                handle_relay_ok(&connection, &url, &event_id, status, &message);
+            }
+            RelayPoolNotification::Message(url, RelayMessage::Auth { challenge }) => {
+                // NIP-42: authenticate with the app ID keys, and subscribe again
+                // (subscription may have been refused before authentication)
+                eprintln!("DEBUG: AUTH challenge received from relay {url}");
+                let res = match auth_response(&keys, &url, &challenge) {
+                    Ok(auth_msg) => relay_client
+                        .send_msg_to(url.to_string(), auth_msg)
+                        .await
+                        .map_err(Error::from),
+                    Err(e) => Err(e),
+                };
+                match res {
+                    // a failed AUTH only affects this relay, keep handling messages
+                    Err(e) => {
+                        if let Some(c) = connection.upgrade() {
+                            c.status
+                                .set(&format!("Warning: AUTH to relay {} failed, {}", url, e));
+                        }
+                    }
+                    Ok(_) => {
+                        relay_client
+                            .subscribe(current_filters(&connection, &keys))
+                            .await
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    // close the subscription, so the relays stop sending events (don't wait for unreachable ones)
+    for relay in relay_client.relays().await.values() {
+        let _ = relay.unsubscribe(false).await;
+    }
+    eprintln!("DEBUG: Handler loop stopped");
+    Ok(())
+}
+
+/// Report the relay's OK message for a sent response (only responses are sent on the connection):
+/// rejections with the reason (e.g. "rate-limited: ..."), acceptances only if enabled
+fn handle_relay_ok(
+    connection: &SignerConnection,
+    relay_url: &Url,
+    event_id: &EventId,
+    accepted: bool,
+    message: &str,
+) {
+    let id = shortened_text(&event_id.to_hex(), 12);
+    if !accepted {
+        connection.status.set(&format!(
+            "Warning: Response {} rejected by relay {}: '{}'",
+            id, relay_url, message
+        ));
+    } else if connection.settings.show_relay_accepted {
+        connection
+            .status
+            .set(&format!("Response {} accepted by relay {}", id, relay_url));
+    }
+}
+
+/// Put together response message for a request
+/// Ok(None) is a valid return value
+fn response_for_message(
+    req_id: &String,
+    req: &Request,
+    key_signer: &KeySigner,
+    settings: &SignerSettings,
+) -> Result<Option<Message>, Error> {
+    match req {
+        // In stealth mode describe is only answered once approved
+        Request::Describe if settings.stealth_mode => Ok(None),
+        Request::Describe => {
+            eprintln!("DEBUG: Describe received");
+            Ok(Some(Message::response(
+                req_id.to_string(),
+                Response::Describe(supported_methods(settings)),
+            )))
+        }
+        Request::GetPublicKey => {
+            // Return the signer pubkey
+            eprintln!("DEBUG: GetPublicKey received");
+            Ok(Some(Message::response(
+                req_id.clone(),
+                Response::GetPublicKey(key_signer.get_public_key()),
+            )))
+        }
+        Request::SignEvent(unsigned_event) => {
+            if !event_id_matches(unsigned_event) {
+                return Err(Error::SignerEventIdMismatch);
+            }
+            let unsigned_id = unsigned_event.id;
+            let signature = key_signer.sign(unsigned_id.as_bytes().to_vec())?;
+            Ok(Some(Message::response(
+                req_id.clone(),
+                Response::SignEvent(signature),
+            )))
+        }
+        Request::Delegate {
+            public_key,
+            conditions,
+        } => {
+            let delegation_tag =
+                DelegationTag::new(&key_signer.keys, public_key.clone(), conditions.clone())?;
+            let delegator_result = DelegationResult {
+                from: key_signer.get_public_key(),
+                to: public_key.clone(),
+                cond: conditions.clone(),
+                sig: delegation_tag.signature(),
+            };
+            Ok(Some(Message::response(
+                req_id.clone(),
+                Response::Delegate(delegator_result),
+            )))
+        }
+        _ => Ok(None),
+    }
+}
+
+async fn handle_request(
+    connection: Arc<SignerConnection>,
+    msg: &Message,
+    sender_pubkey: &XOnlyPublicKey,
+) -> Result<(), Error> {
+    handle_request_at(connection, msg, sender_pubkey, local_hour()).await
+}
+
+/// Handle a request, at the given hour of the day (local time), for the auto-approve hours
+async fn handle_request_at(
+    connection: Arc<SignerConnection>,
+    msg: &Message,
+    sender_pubkey: &XOnlyPublicKey,
+    local_hour: u32,
+) -> Result<(), Error> {
+    eprintln!("DEBUG: New message received {}", message_method(msg));
+    connection.touch_activity(Instant::now());
+
+    match msg {
+        Message::Request { id, .. } => {
+            if let Err(e) = connection.check_permission(msg) {
+                // Reject right away, don't queue it
+                connection
+                    .status
+                    .set(&format!("Warning: Request rejected, {}", e));
+                connection
+                    .send(&error_response(id, &e.to_string()), sender_pubkey)
+                    .await?;
+                return Ok(());
+            }
+            if let Ok(req) = &msg.to_request() {
+                match req {
+                    // First handle requests that need user interaction
+                    Request::SignEvent(unsigned_event) => {
+                        if let Err(e) = check_kind_policy(unsigned_event, &connection.settings)
+                            .and_then(|_| {
+                                check_content_size(
+                                    unsigned_event,
+                                    connection.settings.max_content_size,
+                                )
+                            })
+                        {
+                            // Reject right away, don't queue it
+                            connection
+                                .status
+                                .set(&format!("Warning: Signing request rejected, {}", e));
+                            let err_msg = error_response(id, &e.to_string());
+                            connection.send(&err_msg, sender_pubkey).await?;
+                            return Ok(());
+                        }
+                        let trusted = connection.is_auto_approved(sender_pubkey);
+                        // With a public-key-only connection key (fresh unlock) it has to be approved
+                        if connection.key_signer().can_sign()
+                            && connection.settings.auto_approves(
+                                unsigned_event.kind,
+                                trusted,
+                                local_hour,
+                            )
+                            && !connection.has_pending_from(sender_pubkey)
+                        {
+                            let reason = match connection
+                                .settings
+                                .approval_action_at(unsigned_event.kind, local_hour)
+                            {
+                                ApprovalAction::AutoApprove => "kind policy",
+                                _ => "trusted app",
+                            };
+                            auto_approve_request(&connection, msg, sender_pubkey, reason).await?;
+                            return Ok(());
+                        }
+                        // This request needs user processing (or waits for earlier ones), store it, notify it
+                        connection.add_request(msg.clone(), *sender_pubkey);
+                        connection.event_queue.notify(Event::SignerNewRequest)?;
+                        connection.status.set("New Signing request received");
+                    }
+                    Request::Delegate {
+                        public_key: _,
+                        conditions: _,
+                    } => {
+                        // This request needs user processing, store it, notify it
+                        connection.add_request(msg.clone(), *sender_pubkey);
+                        connection.event_queue.notify(Event::SignerNewRequest)?;
+                        connection
+                            .status
+                            .set("New Signing/Delegate request received");
+                    }
+                    Request::Describe if connection.settings.stealth_mode => {
+                        // Stealth mode: don't reveal the signer without approval
+                        connection.add_request(msg.clone(), *sender_pubkey);
+                        connection.event_queue.notify(Event::SignerNewRequest)?;
+                        connection.status.set("New Describe request received");
+                    }
+                    _ if connection.has_pending_from(sender_pubkey) => {
+                        // Keep the response order: answered after the earlier requests
+                        connection.add_request(msg.clone(), *sender_pubkey);
+                        connection.event_queue.notify(Event::SignerNewRequest)?;
+                    }
+                    _ => {
+                        // Non-interactive requests: try to create response, send it
+                        let key_signer = &connection.key_signer();
+                        let response_message = match req {
+                            Request::Describe => Some(connection.describe_response(id)),
+                            _ => response_for_message(id, req, key_signer, &connection.settings)?,
+                        };
+                        match response_message {
+                            Some(m) => {
+                                // We return a response message right away
+                                connection.send(&m, sender_pubkey).await?;
+                            }
+                            None => {
+                                eprintln!("ERROR: Could not handle request {:?}", msg.to_request());
+                                reject_unsupported_request(&connection, msg, sender_pubkey).await?;
+                            }
+                        }
+                    }
+                }
+            } else if let (true, Some(value)) =
+                (connection.settings.allow_sign_raw, sign_raw_value(msg))
+            {
+                handle_sign_raw_request(&connection, msg, id, value, sender_pubkey).await?;
+            } else {
+                eprintln!("DEBUG: Could not extract Request");
+                reject_unsupported_request(&connection, msg, sender_pubkey).await?;
+            }
+        }
+        Message::Response { id, error, .. } => {
+            // We don't send requests, so a response can't belong to anything outstanding; log it
+            let error_str = match error {
+                Some(e) => format!(", error '{}'", e),
+                None => String::new(),
+            };
+            connection.status.set(&format!(
+                "Warning: Unexpected response received, ignoring, id {}{}",
+                id, error_str
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        auth_response, check_author_allowed, connect_message, describe_modifications,
+        referenced_entities, ClientMessage, Error, EventBuilder, Handle, RelayMessage, Tag,
+        VIEWER_MAX_EVENTS,
+    };
+    use super::{
+        connect_response_relays, error_response, event_id_matches, event_to_sign, handle_relay_ok,
+        handle_request, handle_request_at, render_note_preview, response_for_message, run_limited,
+        send_message, sign_raw_response, sign_raw_value, start_handler_loop, supported_methods,
+        unsupported_method_response, AbortHandle, ApprovalAction, Client, ConnectionStatus,
+        Duration, Instant, KeySigner, Keys, Message, Options, PendingRequestExport, Request,
+        SignatureReqest, Signer, SignerConnection, SignerSettings, StatusMessages, XOnlyPublicKey,
+        PREVIEW_CONTENT_LEN, SIGNED_IDS_MAX_COUNT,
+    };
+    use crate::base::encrypt::{Encrypt, EncryptionAlgorithm};
+    use crate::model::delegator::{DelegationInfo, Delegator};
+    use crate::model::display_settings::DisplayFormat;
+    use crate::model::keystore::Keystore;
+    use crate::model::keystr_model::EventQueue;
+    use crate::model::mock_relay::{wait_until, MockRelay};
+    use crate::model::signer_settings::{ApproveHours, Permission, Permissions};
+    use nostr::hashes::{sha256, Hash};
+    use nostr::prelude::SECP256K1;
+    use nostr::prelude::{
+        Condition, Conditions, DelegationTag, EventId, FromBech32, KeyPair, Secp256k1, SecretKey,
+        Timestamp, ToBech32, UnsignedEvent,
+    };
+    use nostr::secp256k1::schnorr::Signature;
+    use nostr_sdk::prelude::Url;
+    use std::fs;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const NSEC1: &str = "nsec1lfeqz504rd4hc824kmts9qkl5qz7t9md694cd3vr5zevmpne5weqp2thmp";
+    const NPUB2: &str = "npub1c82zv3aj04l8dmxlxywx5fsg6ngt5nyvwa9j0eqk03ntg2t2jtxqngn7ry";
+    /// Id of the test event with content "Hello, World!"
+    const EVENTHEX: &str = "63745d0d2eb9261435138627ef427502bbb4f559bba177c9e502f7ab44484786";
+    /// Wait limit for the mock relay tests
+    const RELAY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_response_for_message_describe() {
+        let req: Request = Request::Describe;
+        let req_id: String = "id001".to_string();
+        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(sk),
+        };
+        let resp_msg =
+            response_for_message(&req_id, &req, &key_signer, &SignerSettings::default()).unwrap();
+        assert!(resp_msg.is_some());
+        assert_eq!(resp_msg.unwrap().as_json(), "{\"error\":null,\"id\":\"id001\",\"result\":[\"describe\",\"get_public_key\",\"sign_event\"]}");
+    }
+
+    #[test]
+    fn test_describe_returns_supported_methods() {
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(SecretKey::from_bech32(NSEC1).unwrap()),
+        };
+        let settings = SignerSettings::default();
+        let resp_msg = response_for_message(
+            &"id001".to_string(),
+            &Request::Describe,
+            &key_signer,
+            &settings,
+        )
+        .unwrap()
+        .unwrap();
+        match resp_msg {
+            Message::Response {
+                result: Some(result),
+                ..
+            } => {
+                let methods: Vec<String> = serde_json::from_value(result).unwrap();
+                assert_eq!(methods, supported_methods(&settings));
+            }
+            _ => panic!("Expected describe response"),
+        }
+
+        // a supported method with invalid params is not reported as unsupported
+        let settings = SignerSettings {
+            reject_unknown_methods: true,
+            ..Default::default()
+        };
+        let invalid = Message::Request {
+            id: "id002".to_string(),
+            method: "sign_event".to_string(),
+            params: vec![],
+        };
+        match unsupported_method_response(&invalid, &settings) {
+            Some(Message::Response { error, .. }) => {
+                assert_eq!(error.unwrap(), "Invalid request for method 'sign_event'")
+            }
+            _ => panic!("Expected error response"),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_stealth_mode_describe() {
+        let mut conn = test_connection();
+        conn.settings.stealth_mode = true;
+        let conn = Arc::new(conn);
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let key_signer = conn.key_signer();
+        assert!(response_for_message(
+            &"id001".to_string(),
+            &Request::Describe,
+            &key_signer,
+            &conn.settings
+        )
+        .unwrap()
+        .is_none());
+
+        // not answered (sending would fail without relay), queued for approval
+        handle_request(conn.clone(), &Message::request(Request::Describe), &sender)
+            .await
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 1);
+        assert!(conn.answer_ready_requests().is_empty());
+        assert_eq!(conn.get_pending_count(), 1);
+        assert!(conn
+            .get_first_request_description()
+            .starts_with("Capabilities requested"));
+
+        // once approved, the capabilities are sent
+        let req = conn.take_first_request().unwrap();
+        match conn.approved_response(&req, &key_signer, None).unwrap() {
+            Some(Message::Response {
+                result: Some(result),
+                ..
+            }) => {
+                let methods: Vec<String> = serde_json::from_value(result).unwrap();
+                assert!(methods.contains(&"describe".to_string()));
+            }
+            _ => panic!("Expected describe response"),
+        }
+    }
+
+    #[test]
+    fn test_supported_methods_excludes_disabled() {
+        let methods = supported_methods(&SignerSettings::default());
+        assert!(methods.contains(&"sign_event".to_string()));
+        assert!(!methods.contains(&"sign_raw".to_string()));
+
+        let settings = SignerSettings {
+            allow_sign_raw: true,
+            ..Default::default()
+        };
+        assert!(supported_methods(&settings).contains(&"sign_raw".to_string()));
+    }
+
+    #[test]
+    fn test_response_for_message_getpublickey() {
+        let req: Request = Request::GetPublicKey;
+        let req_id: String = "id001".to_string();
+        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(sk),
+        };
+        let resp_msg =
+            response_for_message(&req_id, &req, &key_signer, &SignerSettings::default()).unwrap();
+        assert!(resp_msg.is_some());
+        assert_eq!(resp_msg.unwrap().as_json(), "{\"error\":null,\"id\":\"id001\",\"result\":\"dd73f1d141b01badbb4049c5bcaa2cd261501c0c356774fada3db425c7d6e413\"}");
+    }
+
+    #[test]
+    fn test_response_for_message_signevent() {
+        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
+        let (pubkey, _parity) =
+            XOnlyPublicKey::from_keypair(&KeyPair::from_secret_key(&Secp256k1::default(), &sk));
+        let unisgned_event: UnsignedEvent = UnsignedEvent {
+            id: EventId::from_hex(EVENTHEX).unwrap(),
+            pubkey,
+            created_at: Timestamp::from(1686693500),
+            kind: nostr::Kind::TextNote,
+            tags: vec![],
+            content: "Hello, World!".to_string(),
+        };
+        let req: Request = Request::SignEvent(unisgned_event);
+        let req_id: String = "id001".to_string();
+        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(sk),
+        };
+        let resp_msg =
+            response_for_message(&req_id, &req, &key_signer, &SignerSettings::default()).unwrap();
+        assert!(resp_msg.is_some());
+        // Cannot compare json, as signature changes
+        assert_eq!(resp_msg.as_ref().unwrap().is_request(), false);
+        match resp_msg.unwrap() {
+            nostr::nips::nip46::Message::Response { id, result, error } => {
+                assert_eq!(id, "id001");
+                assert!(error.is_none());
+                assert!(result.is_some());
+                match result.as_ref().unwrap() {
+                    serde_json::Value::String(s) => {
+                        // sig changes, cannot compare to constant, check only length
+                        assert_eq!(s.len(), 128);
+                    }
+                    _ => {
+                        panic!("Wrong result value")
+                    }
+                }
+            }
+            _ => {
+                panic!("Wrong response")
+            }
+        }
+    }
+
+    #[test]
+    fn test_response_for_message_signevent_tampered() {
+        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
+        let (pubkey, _parity) =
+            XOnlyPublicKey::from_keypair(&KeyPair::from_secret_key(&Secp256k1::default(), &sk));
+        let unisgned_event: UnsignedEvent = UnsignedEvent {
+            id: EventId::from_hex(EVENTHEX).unwrap(),
+            pubkey,
+            created_at: Timestamp::from(1686693500),
+            kind: nostr::Kind::TextNote,
+            tags: vec![],
+            // content differs from the one the id was computed for
+            content: "Hello, Tampered World!".to_string(),
+        };
+        let req: Request = Request::SignEvent(unisgned_event);
+        let req_id: String = "id001".to_string();
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(sk),
+        };
+        let resp = response_for_message(&req_id, &req, &key_signer, &SignerSettings::default());
+        assert!(resp.is_err());
+        assert_eq!(
+            resp.err().unwrap().to_string(),
+            "Event id does not match event content, request may be tampered"
+        );
+    }
+
+    #[test]
+    fn test_is_event_id_valid() {
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let valid = SignatureReqest {
+            req: test_sign_request("Hello, World!"),
+            sender_pubkey: sender,
+            received_at: Instant::now(),
+            modified: None,
+            expiry_notified: false,
+        };
+        assert!(valid.is_event_id_valid());
+        assert!(!valid.description().contains("TAMPERED"));
+        let tampered = SignatureReqest {
+            req: test_sign_request_with_id(
+                "Hello, Tampered World!",
+                Some(EventId::from_hex(EVENTHEX).unwrap()),
+            ),
+            sender_pubkey: sender,
+            received_at: Instant::now(),
+            modified: None,
+            expiry_notified: false,
+        };
+        assert!(!tampered.is_event_id_valid());
+        assert!(tampered.description().contains("TAMPERED"));
+    }
+
+    #[test]
+    fn test_response_for_message_delegate() {
+        let delegatee_pubkey = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let mut conditions: Conditions = Conditions::default();
+        conditions.add(Condition::Kind(1));
+        conditions.add(Condition::CreatedBefore(1686693500));
+        let req: Request = Request::Delegate {
+            public_key: delegatee_pubkey,
+            conditions,
+        };
+        let req_id: String = "id001".to_string();
+        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(sk),
+        };
+        let resp_msg =
+            response_for_message(&req_id, &req, &key_signer, &SignerSettings::default()).unwrap();
+        assert!(resp_msg.is_some());
+        // Cannot compare json, as signature changes
+        assert_eq!(resp_msg.as_ref().unwrap().is_request(), false);
+        match resp_msg.unwrap() {
+            nostr::nips::nip46::Message::Response { id, result, error } => {
+                assert_eq!(id, "id001");
+                assert!(error.is_none());
+                assert!(result.is_some());
+                match result.as_ref().unwrap() {
+                    serde_json::Value::Object(o) => {
+                        assert_eq!(o["cond"].to_string(), "\"kind=1&created_at<1686693500\"");
+                        assert_eq!(
+                            o["from"].to_string(),
+                            "\"dd73f1d141b01badbb4049c5bcaa2cd261501c0c356774fada3db425c7d6e413\""
+                        );
+                        assert_eq!(
+                            o["to"].to_string(),
+                            "\"c1d42647b27d7e76ecdf311c6a2608d4d0ba4c8c774b27e4167c66b4296a92cc\""
+                        );
+                        // sig changes, cannot compare to constant, check only length
+                        let siglen = o["sig"].to_string().len();
+                        assert!(siglen > 128 && siglen < 132);
+                    }
+                    _ => {
+                        panic!("Wrong result value")
+                    }
+                }
+            }
+            _ => {
+                panic!("Wrong response")
+            }
+        }
+    }
+
+    fn test_connection() -> SignerConnection {
+        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(sk),
+        };
+        SignerConnection::new(
+            "wss://relay.example.com".to_string(),
+            XOnlyPublicKey::from_bech32(NPUB2).unwrap(),
+            &Keys::generate(),
+            &key_signer,
+            &SignerSettings::default(),
+            StatusMessages::new(),
+        )
+    }
+
+    fn test_unsigned_event_with_id(content: &str, id: Option<EventId>) -> UnsignedEvent {
+        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
+        let (pubkey, _parity) =
+            XOnlyPublicKey::from_keypair(&KeyPair::from_secret_key(&Secp256k1::default(), &sk));
+        let created_at = Timestamp::from(1686693500);
+        let kind = nostr::Kind::TextNote;
+        UnsignedEvent {
+            id: id.unwrap_or(EventId::new(&pubkey, created_at, &kind, &[], content)),
+            pubkey,
+            created_at,
+            kind,
+            tags: vec![],
+            content: content.to_string(),
+        }
+    }
+
+    fn test_sign_request_with_id(content: &str, id: Option<EventId>) -> Message {
+        Message::request(Request::SignEvent(test_unsigned_event_with_id(content, id)))
+    }
+
+    fn test_sign_request(content: &str) -> Message {
+        test_sign_request_with_id(content, None)
+    }
+
+    #[tokio::test]
+    async fn test_request_raw_json() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let msg = test_sign_request("A");
+        conn.add_request(msg.clone(), sender);
+        let raws = conn.get_request_raw_jsons();
+        assert_eq!(raws.len(), 1);
+        assert!(raws[0].contains("\"sign_event\""));
+
+        let parsed = Message::from_json(&raws[0]).unwrap();
+        assert_eq!(parsed.id(), msg.id());
+        assert_eq!(parsed.as_json(), msg.as_json());
+        match (parsed.to_request().unwrap(), msg.to_request().unwrap()) {
+            (Request::SignEvent(p), Request::SignEvent(m)) => {
+                assert_eq!(p.id, m.id);
+                assert_eq!(p.content, m.content);
+            }
+            _ => panic!("not a sign request"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_pending() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        conn.inject_request(test_unsigned_event_with_id("A", None), sender)
+            .unwrap();
+        conn.inject_request(test_unsigned_event_with_id("B", None), sender)
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "keystr-pending-export-test-{}.json",
+            std::process::id()
+        ));
+        assert_eq!(conn.export_pending(&path).unwrap(), 2);
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("nsec"));
+        let exported: Vec<PendingRequestExport> = serde_json::from_str(&content).unwrap();
+        assert_eq!(exported.len(), 2);
+        assert_eq!(exported[0].description, conn.get_request_descriptions()[0]);
+        assert_eq!(exported[1].raw_json, conn.get_request_raw_jsons()[1]);
+        assert_eq!(exported[0].kind, Some(1));
+        assert_eq!(exported[1].sender, NPUB2);
+        // pending requests are kept
+        assert_eq!(conn.get_pending_count(), 2);
+
+        let _res = fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_full_content() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        assert!(conn.get_first_request_full_content().is_none());
+
+        let long_content = "word ".repeat(100);
+        conn.add_request(test_sign_request(&long_content), sender);
+        let desc = conn.get_first_request_description();
+        assert!(!desc.contains(&long_content));
+        assert!(desc.ends_with("..'"));
+        assert_eq!(conn.get_first_request_full_content().unwrap(), long_content);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_last_error() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        assert!(conn.get_last_error().is_none());
+
+        // sending fails without relay
+        let msg = test_sign_request("A");
+        assert!(conn.send_blocking(&msg, &sender).is_err());
+        assert!(conn.get_last_error().is_some());
+        assert!(conn.send(&msg, &sender).await.is_err());
+        assert!(conn.get_last_error().is_some());
+
+        // a later success clears it
+        conn.record_result(&Ok(()));
+        assert!(conn.get_last_error().is_none());
+
+        conn.record_result::<()>(&Err(Error::SignerRawInvalid));
+        assert_eq!(
+            conn.get_last_error().unwrap(),
+            Error::SignerRawInvalid.to_string()
+        );
+        conn.clear_last_error();
+        assert!(conn.get_last_error().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_event_viewer() {
+        let conn = test_connection();
+        let our_pubkey = conn.app_id_keys.public_key();
+        let sender_keys = Keys::generate();
+        let event_to_us = |content: &str| {
+            EventBuilder::new(
+                nostr::Kind::EncryptedDirectMessage,
+                content,
+                &[Tag::PubKey(our_pubkey, None)],
+            )
+            .to_event(&sender_keys)
+            .unwrap()
+        };
+        assert!(conn.get_viewed_events().is_none());
+        // closed: not listed
+        conn.record_viewed_event(&event_to_us("A"));
+        assert_eq!(conn.subscription_filters().len(), 1);
+
+        conn.set_viewer_open(true, Handle::current());
+        assert!(conn.is_viewer_open());
+        assert_eq!(conn.subscription_filters().len(), 2);
+        conn.record_viewed_event(&event_to_us("B"));
+        conn.record_viewed_event(
+            &EventBuilder::new_text_note("not to us", &[])
+                .to_event(&sender_keys)
+                .unwrap(),
+        );
+        conn.record_viewed_event(&event_to_us("C"));
+        let events = conn.get_viewed_events().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, nostr::Kind::EncryptedDirectMessage);
+        assert_eq!(events[0].sender, sender_keys.public_key());
+
+        for i in 0..VIEWER_MAX_EVENTS {
+            conn.record_viewed_event(&event_to_us(&i.to_string()));
+        }
+        assert_eq!(conn.get_viewed_events().unwrap().len(), VIEWER_MAX_EVENTS);
+
+        // close: list dropped, subscription back to the handler only
+        conn.set_viewer_open(false, Handle::current());
+        assert!(conn.get_viewed_events().is_none());
+        assert_eq!(conn.subscription_filters().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_move_request() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        for c in ["A", "B", "C", "D"] {
+            conn.add_request(test_sign_request(c), sender);
+        }
+        conn.move_request(2, 0).unwrap();
+        conn.move_request(1, 3).unwrap();
+        let descs = conn.get_request_descriptions();
+        assert_eq!(descs.len(), 4);
+        let order: Vec<String> = descs
+            .iter()
+            .map(|d| d.chars().rev().nth(1).unwrap().to_string())
+            .collect();
+        assert_eq!(order, vec!["C", "B", "D", "A"]);
+
+        // out of bounds, no change
+        assert!(conn.move_request(4, 0).is_err());
+        assert!(conn.move_request(0, 4).is_err());
+        assert_eq!(conn.get_pending_count(), 4);
+        assert_eq!(conn.get_request_descriptions(), descs);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_inject_request_process_reject() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let key_signer = conn.key_signer();
+        conn.inject_request(test_unsigned_event_with_id("A", None), sender)
+            .unwrap();
+        conn.inject_request(
+            test_unsigned_event_with_id("B", Some(EventId::from_hex(EVENTHEX).unwrap())),
+            sender,
+        )
+        .unwrap();
+        conn.inject_request(test_unsigned_event_with_id("C", None), sender)
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 3);
+        assert_eq!(
+            conn.get_first_request_description(),
+            "Signature requested for note: 'A'"
+        );
+
+        // process (sending the response fails without relay, it is reported)
+        assert!(matches!(
+            conn.action_first_req_process(&key_signer, None),
+            Err(Error::RelayClientError(_))
+        ));
+        assert!(conn.get_last_error().is_some());
+        assert_eq!(conn.get_pending_count(), 2);
+
+        // tampered one is refused, but removed
+        assert!(!conn.is_first_request_event_id_valid());
+        assert!(conn.action_first_req_process(&key_signer, None).is_err());
+        assert_eq!(conn.get_pending_count(), 1);
+
+        // reject
+        conn.action_first_req_remove();
+        assert_eq!(conn.get_pending_count(), 0);
+    }
+
+    #[test]
+    fn test_check_author_allowed() {
+        let event = test_unsigned_event_with_id("A", None);
+        let author_npub = event.pubkey.to_bech32().unwrap();
+        // no restriction
+        assert!(check_author_allowed(&event, &[]).is_ok());
+        // allowed, as npub or hex
+        assert!(check_author_allowed(&event, &[NPUB2.to_string(), author_npub.clone()]).is_ok());
+        assert!(check_author_allowed(&event, &[event.pubkey.to_string()]).is_ok());
+        // not in the list
+        assert!(matches!(
+            check_author_allowed(&event, &[NPUB2.to_string(), "invalid".to_string()]),
+            Err(Error::SignerAuthorNotAllowed(npub)) if npub == author_npub
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_process_disallowed_author_refused() {
+        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
+        let key_signer = KeySigner {
+            keys: Keys::new(sk),
+        };
+        let settings = SignerSettings {
+            allowed_authors: vec![NPUB2.to_string()],
+            ..Default::default()
+        };
+        let conn = SignerConnection::new(
+            "wss://relay.example.com".to_string(),
+            XOnlyPublicKey::from_bech32(NPUB2).unwrap(),
+            &Keys::generate(),
+            &key_signer,
+            &settings,
+            StatusMessages::new(),
+        );
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        conn.inject_request(test_unsigned_event_with_id("A", None), sender)
+            .unwrap();
+        assert!(matches!(
+            conn.action_first_req_process(&key_signer, None),
+            Err(Error::SignerAuthorNotAllowed(_))
+        ));
+        // refused, but removed
+        assert_eq!(conn.get_pending_count(), 0);
+    }
+
+    #[test]
+    fn test_canonical_serialization_hashes_to_id() {
+        let unsigned_event = test_unsigned_event_with_id("Hello, World!", None);
+        let req = SignatureReqest {
+            req: Message::request(Request::SignEvent(unsigned_event.clone())),
+            sender_pubkey: XOnlyPublicKey::from_bech32(NPUB2).unwrap(),
+            received_at: Instant::now(),
+            modified: None,
+            expiry_notified: false,
+        };
+        let serialized = req.canonical_serialization().unwrap();
+        assert!(serialized.starts_with(&format!(
+            "[0,\"{}\",1686693500,1,[],",
+            unsigned_event.pubkey
+        )));
+        let hash = sha256::Hash::hash(serialized.as_bytes());
+        assert_eq!(EventId::from(hash), unsigned_event.id);
+        assert_eq!(unsigned_event.id.to_hex(), EVENTHEX);
+
+        // modified version is serialized
+        let modified = test_unsigned_event_with_id("Modified", None);
+        let req = SignatureReqest {
+            modified: Some(modified.clone()),
+            ..req
+        };
+        let hash = sha256::Hash::hash(req.canonical_serialization().unwrap().as_bytes());
+        assert_eq!(EventId::from(hash), modified.id);
+
+        // not a sign request
+        let req = SignatureReqest {
+            req: Message::request(Request::Describe),
+            modified: None,
+            ..req
+        };
+        assert!(matches!(
+            req.canonical_serialization(),
+            Err(Error::SignerNotSignRequest)
+        ));
+    }
+
+    #[test]
+    fn test_render_note_preview() {
+        assert_eq!(render_note_preview("Hello, World!"), "Hello, World!");
+        assert_eq!(
+            render_note_preview(&format!(
+                "GM nostr:{}, see nostr:note1fntxtkcy9pjwucqwa9mddn7v03wwwsu9j330jj350nvhpky2tuaspk6nqc.",
+                NPUB2
+            )),
+            "GM @npub1c82zv3a.., see @note1fntxtkc..."
+        );
+        // unknown or incomplete mentions are kept
+        assert_eq!(
+            render_note_preview("nostr: and nostr:xyz1abc"),
+            "nostr: and nostr:xyz1abc"
+        );
+        // multi-line: whitespace collapsed, repeated and trailing empty lines removed
+        assert_eq!(
+            render_note_preview("\n\n  First   line\t here \r\n\n\n\nSecond\n  \n"),
+            "First line here\n\nSecond"
+        );
+    }
+
+    #[test]
+    fn test_note_preview_multi_byte() {
+        let content = format!("{}ありがとう 🤙🏻", "🌶".repeat(PREVIEW_CONTENT_LEN - 3));
+        let mut event = test_unsigned_event_with_id(&content, None);
+        event.id = EventId::new(&event.pubkey, event.created_at, &event.kind, &[], &content);
+        let req = SignatureReqest::new(
+            Message::request(Request::SignEvent(event)),
+            XOnlyPublicKey::from_bech32(NPUB2).unwrap(),
+        );
+        assert_eq!(
+            req.description(),
+            format!(
+                "Signature requested for note: '{}ありが..'",
+                "🌶".repeat(PREVIEW_CONTENT_LEN - 3)
+            )
+        );
+    }
+
+    #[test]
+    fn test_auth_response() {
+        let app_id_keys = Keys::generate();
+        let url = Url::parse("wss://private-relay.example.com").unwrap();
+        match auth_response(&app_id_keys, &url, "challenge-123").unwrap() {
+            ClientMessage::Auth(event) => {
+                assert!(event.verify().is_ok());
+                assert_eq!(event.kind, nostr::Kind::Authentication);
+                assert_eq!(event.pubkey, app_id_keys.public_key());
+                assert!(event
+                    .tags
+                    .contains(&Tag::Challenge("challenge-123".to_string())));
+                assert!(event.tags.contains(&Tag::Relay(url)));
+            }
+            _ => panic!("Wrong message"),
+        }
+    }
+
+    #[test]
+    fn test_describe_modifications() {
+        let original = test_unsigned_event_with_id("Hello", None);
+        assert!(describe_modifications(&original, &original).is_empty());
+
+        let pubkey = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let mut modified = original.clone();
+        modified.created_at = Timestamp::from(1686693600);
+        modified.tags = vec![Tag::PubKey(pubkey, None)];
+        modified.id = EventId::new(
+            &modified.pubkey,
+            modified.created_at,
+            &modified.kind,
+            &modified.tags,
+            &modified.content,
+        );
+        assert_eq!(
+            describe_modifications(&original, &modified),
+            vec![
+                format!("id: {} -> {}", original.id, modified.id),
+                "created_at: 1686693500 -> 1686693600".to_string(),
+                format!("added tag: p, {}", pubkey),
+            ]
+        );
+        assert_eq!(
+            describe_modifications(&modified, &original)[2],
+            format!("removed tag: p, {}", pubkey)
+        );
+
+        // multi-byte content is cut at character boundaries
+        let mut modified = original.clone();
+        modified.content = "Grüße 🤙🏻 ".repeat(10);
+        assert_eq!(
+            describe_modifications(&original, &modified)[0],
+            format!(
+                "content: 'Hello' -> '{}..'",
+                modified.content.chars().take(40).collect::<String>()
+            )
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_modify_first_request() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        conn.inject_request(test_unsigned_event_with_id("A", None), sender)
+            .unwrap();
+        assert!(conn.get_first_request_modifications().is_empty());
+
+        let mut modified = test_unsigned_event_with_id("A", None);
+        modified.created_at = Timestamp::from(1686693600);
+        // id not updated, refused
+        assert!(conn.modify_first_request(modified.clone()).is_err());
+        modified.id = EventId::new(
+            &modified.pubkey,
+            modified.created_at,
+            &modified.kind,
+            &modified.tags,
+            &modified.content,
+        );
+        conn.modify_first_request(modified).unwrap();
+        assert_eq!(conn.get_first_request_modifications().len(), 2);
+        // sending fails here, as there is no relay
+        assert!(conn
+            .action_first_req_process(&conn.key_signer(), None)
+            .is_err());
+        assert_eq!(conn.get_pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_bump_first_request_time() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        assert!(conn
+            .bump_first_request_time(Timestamp::from(1686693600))
+            .is_err());
+        let requested = test_unsigned_event_with_id("A", None);
+        conn.add_request(
+            Message::request(Request::SignEvent(requested.clone())),
+            sender,
+        );
+        conn.bump_first_request_time(Timestamp::from(1686693600))
+            .unwrap();
+        assert!(conn
+            .get_first_request_modifications()
+            .contains(&"created_at: 1686693500 -> 1686693600".to_string()));
+
+        // the client gets the modified event signed, not just a signature
+        let req = conn.take_first_request().unwrap();
+        match conn
+            .approved_response(&req, &conn.key_signer(), None)
+            .unwrap()
+        {
+            Some(Message::Response {
+                result: Some(result),
+                error: None,
+                ..
+            }) => {
+                let event: nostr::Event = serde_json::from_value(result).unwrap();
+                assert!(event.verify().is_ok());
+                assert_eq!(event.created_at, Timestamp::from(1686693600));
+                assert_ne!(event.id, requested.id);
+                assert_eq!(event.pubkey, requested.pubkey);
+                assert_eq!(event.content, requested.content);
+            }
+            _ => panic!("Expected signed event response"),
+        }
+
+        // only sign requests can be modified
+        conn.add_request(Message::request(Request::GetPublicKey), sender);
+        assert!(matches!(
+            conn.bump_first_request_time(Timestamp::from(1686693600)),
+            Err(Error::SignerNotSignRequest)
+        ));
+    }
+
+    #[test]
+    fn test_referenced_entities() {
+        let mut unsigned = test_unsigned_event_with_id("Reply", None);
+        let event_id = EventId::from_hex(EVENTHEX).unwrap();
+        let pubkey = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        unsigned.tags = vec![
+            Tag::Event(event_id, None, None),
+            Tag::Hashtag("nostr".to_string()),
+            Tag::PubKey(pubkey, None),
+        ];
+        let refs = referenced_entities(&unsigned);
+        assert_eq!(refs.len(), 2);
+        assert!(refs[0].starts_with("note1"));
+        assert_eq!(EventId::from_bech32(&refs[0]).unwrap(), event_id);
+        assert_eq!(refs[1], NPUB2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_request_order_per_sender() {
+        let conn = Arc::new(test_connection());
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let other = Keys::generate().public_key();
+        let get_pubkey = |id: &str| Message::Request {
+            id: id.to_string(),
+            method: "get_public_key".to_string(),
+            params: Vec::new(),
+        };
+        let response_ids = |responses: Vec<Message>| -> Vec<String> {
+            responses
+                .iter()
+                .map(|m| match m {
+                    Message::Response { id, .. } => id.clone(),
+                    _ => panic!("Expected response"),
+                })
+                .collect()
+        };
+
+        handle_request(conn.clone(), &test_sign_request("A"), &sender)
+            .await
+            .unwrap();
+        // queued behind the pending request of the same sender
+        handle_request(conn.clone(), &get_pubkey("id2"), &sender)
+            .await
+            .unwrap();
+        handle_request(conn.clone(), &get_pubkey("id3"), &sender)
+            .await
+            .unwrap();
+        // other sender: answered right away (sending fails, as there is no relay)
+        assert!(handle_request(conn.clone(), &get_pubkey("other"), &other)
+            .await
+            .is_err());
+        handle_request(conn.clone(), &test_sign_request("B"), &sender)
+            .await
+            .unwrap();
+        handle_request(conn.clone(), &get_pubkey("id5"), &sender)
+            .await
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 5);
+
+        // nothing is answered ahead of an earlier request
+        assert!(conn.answer_ready_requests().is_empty());
+        assert_eq!(conn.get_pending_count(), 5);
+
+        // after the first one, the following ones are answered in order, up to the next sign request
+        let _ = conn.take_first_request();
+        assert_eq!(
+            response_ids(conn.answer_ready_requests()),
+            vec!["id2", "id3"]
+        );
+        assert_eq!(
+            conn.get_first_request_description(),
+            "Signature requested for note: 'B'"
+        );
+        let _ = conn.take_first_request();
+        assert_eq!(response_ids(conn.answer_ready_requests()), vec!["id5"]);
+        assert_eq!(conn.get_pending_count(), 0);
+
+        // processing also answers them
+        handle_request(conn.clone(), &test_sign_request("C"), &sender)
+            .await
+            .unwrap();
+        handle_request(conn.clone(), &get_pubkey("id7"), &sender)
+            .await
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 2);
+        // sending fails here, as there is no relay
+        assert!(conn
+            .action_first_req_process(&conn.key_signer(), None)
+            .is_err());
+        assert_eq!(conn.get_pending_count(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_auto_approve_trusted_app() {
+        let sign_request_of_kind = |kind: u64, content: &str| {
+            let mut event = test_unsigned_event_with_id(content, None);
+            event.kind = nostr::Kind::from(kind);
+            event.id = EventId::new(&event.pubkey, event.created_at, &event.kind, &[], content);
+            (event.id, Message::request(Request::SignEvent(event)))
+        };
+        let conn = Arc::new(test_connection());
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let other = Keys::generate().public_key();
+        conn.add_auto_approve(sender);
+        assert!(conn.is_auto_approved(&sender));
+        assert!(!conn.is_auto_approved(&other));
+
+        // trusted: signed right away (sending fails here, as there is no relay)
+        let (note_id, note) = sign_request_of_kind(1, "note");
+        assert!(handle_request(conn.clone(), &note, &sender).await.is_err());
+        assert_eq!(conn.get_pending_count(), 0);
+        assert_eq!(conn.get_signed_event_ids(), vec![note_id]);
+        assert!(conn
+            .status
+            .get_last()
+            .contains("Auto-approved by trusted app"));
+
+        // not trusted: queued
+        let (_, note2) = sign_request_of_kind(1, "note 2");
+        handle_request(conn.clone(), &note2, &other).await.unwrap();
+        assert_eq!(conn.get_pending_count(), 1);
+
+        // sensitive kind: queued also when trusted
+        let (_, metadata) = sign_request_of_kind(0, "{}");
+        handle_request(conn.clone(), &metadata, &sender)
+            .await
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 2);
+        assert_eq!(conn.get_request_senders(), vec![other, sender]);
+
+        // not trusted anymore: queued
+        conn.remove_auto_approve(&sender);
+        let (_, note3) = sign_request_of_kind(1, "note 3");
+        handle_request(conn.clone(), &note3, &sender).await.unwrap();
+        assert_eq!(conn.get_pending_count(), 3);
+        assert_eq!(conn.get_signed_event_ids(), vec![note_id]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_auto_approve_public_key_only() {
+        let sign_request_of_kind = |kind: u64, content: &str| {
+            let mut event = test_unsigned_event_with_id(content, None);
+            event.kind = nostr::Kind::from(kind);
+            event.id = EventId::new(&event.pubkey, event.created_at, &event.kind, &[], content);
+            Message::request(Request::SignEvent(event))
+        };
+        // connection key without the secret key, as with fresh unlock for each signature
+        let key_signer = KeySigner {
+            keys: Keys::from_public_key(XOnlyPublicKey::from_bech32(NPUB2).unwrap()),
+        };
+        let mut conn = SignerConnection::new(
+            "wss://relay.example.com".to_string(),
+            XOnlyPublicKey::from_bech32(NPUB2).unwrap(),
+            &Keys::generate(),
+            &key_signer,
+            &SignerSettings::default(),
+            StatusMessages::new(),
+        );
+        conn.settings.kind_policy = [(7, ApprovalAction::AutoApprove)].into_iter().collect();
+        let conn = Arc::new(conn);
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        conn.add_auto_approve(sender);
+
+        // trusted app, and auto-approved kind: both queued for approval
+        handle_request(conn.clone(), &sign_request_of_kind(1, "note"), &sender)
+            .await
+            .unwrap();
+        handle_request(conn.clone(), &sign_request_of_kind(7, "+"), &sender)
+            .await
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 2);
+
+        // not answered as ready either
+        let _ = conn.take_first_request();
+        assert!(conn.answer_ready_requests().is_empty());
+        assert_eq!(conn.get_pending_count(), 1);
+        assert!(conn.get_signed_event_ids().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_kind_policy() {
+        let sign_request_of_kind = |kind: u64, content: &str| {
+            let mut event = test_unsigned_event_with_id(content, None);
+            event.kind = nostr::Kind::from(kind);
+            event.id = EventId::new(&event.pubkey, event.created_at, &event.kind, &[], content);
+            (event.id, Message::request(Request::SignEvent(event)))
+        };
+        let mut conn = test_connection();
+        conn.settings.kind_policy = [
+            (4, ApprovalAction::Queue),
+            (7, ApprovalAction::AutoApprove),
+            (5, ApprovalAction::Reject),
+        ]
+        .into_iter()
+        .collect();
+        let conn = Arc::new(conn);
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let other = Keys::generate().public_key();
+
+        // reject: not queued, error response sent (fails here, as there is no relay)
+        let (_, deletion) = sign_request_of_kind(5, "delete");
+        assert!(handle_request(conn.clone(), &deletion, &sender)
+            .await
+            .is_err());
+        assert_eq!(conn.get_pending_count(), 0);
+        assert!(conn
+            .status
+            .get_last()
+            .contains("Signing kind 5 events is not allowed by the kind policy"));
+
+        // queue, explicit or not listed
+        let (dm_id, dm) = sign_request_of_kind(4, "dm");
+        handle_request(conn.clone(), &dm, &sender).await.unwrap();
+        let (note_id, note) = sign_request_of_kind(1, "note");
+        handle_request(conn.clone(), &note, &sender).await.unwrap();
+        assert_eq!(conn.get_pending_count(), 2);
+
+        // auto-approve: signed right away (sending fails here)
+        let (reaction_id, reaction) = sign_request_of_kind(7, "+");
+        assert!(handle_request(conn.clone(), &reaction, &other)
+            .await
+            .is_err());
+        assert_eq!(conn.get_pending_count(), 2);
+        assert_eq!(conn.get_signed_event_ids(), vec![reaction_id]);
+
+        // auto-approve, but behind pending requests of the sender: signed after them, in order
+        let (reaction2_id, reaction2) = sign_request_of_kind(7, "-");
+        handle_request(conn.clone(), &reaction2, &sender)
+            .await
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 3);
+        let key_signer = conn.key_signer();
+        // sending fails here, as there is no relay
+        assert!(conn.action_first_req_process(&key_signer, None).is_err());
+        assert_eq!(conn.get_pending_count(), 2);
+        assert!(conn.action_first_req_process(&key_signer, None).is_err());
+        assert_eq!(conn.get_pending_count(), 0);
+        assert_eq!(
+            conn.get_signed_event_ids(),
+            vec![reaction2_id, note_id, dm_id, reaction_id]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_granted_permissions_round_trip() {
+        let mut signer = Signer::new(&Keys::generate(), StatusMessages::new());
+        signer.connect_uri_input = "nostrconnect://b889ff5b1513b641e2a139f661a661364979c5beee91842f8f0ef42ab558e9d4?relay=ws%3A%2F%2F127.0.0.1%3A9%2F&metadata=%7B%22name%22%3A%22Example%22%7D".to_string();
+        let client = XOnlyPublicKey::from_str(
+            "b889ff5b1513b641e2a139f661a661364979c5beee91842f8f0ef42ab558e9d4",
+        )
+        .unwrap();
+
+        // not restricted: all methods
+        assert_eq!(signer.pairing_permissions().unwrap(), (client, None));
+
+        signer.grant.restrict = true;
+        signer.grant.sign_event = true;
+        signer.grant.sign_event_kinds_input = "1, 7".to_string();
+        let (_, granted) = signer.pairing_permissions().unwrap();
+        let granted = granted.unwrap();
+        assert_eq!(granted.to_string(), "sign_event:1,sign_event:7");
+
+        // encoded in the connect message
+        let signer_pubkey = Keys::generate().public_key();
+        let msg = connect_message(&signer_pubkey, Some(&granted));
+        let params = match &msg {
+            Message::Request { method, params, .. } if method == "connect" => params.clone(),
+            _ => panic!("not a connect request"),
+        };
+        assert_eq!(params.len(), 2);
+        let sent = Permissions::parse(params[1].as_str().unwrap()).unwrap();
+        assert_eq!(sent, granted);
+        // without restriction, the plain connect request
+        assert!(matches!(
+            connect_message(&signer_pubkey, None).to_request().unwrap(),
+            Request::Connect(pk) if pk == signer_pubkey
+        ));
+
+        // stored as the connection policy
+        let mut settings = SignerSettings::default();
+        settings
+            .client_permissions
+            .insert(client.to_string(), granted.clone());
+        let settings: SignerSettings =
+            serde_json::from_str(&serde_json::to_string(&settings).unwrap()).unwrap();
+        assert_eq!(settings.permissions_for(&client), Some(&sent));
+        let key_signer = KeySigner {
+            keys: Keys::new(SecretKey::from_bech32(NSEC1).unwrap()),
+        };
+        let conn = Arc::new(SignerConnection::new(
+            "ws://127.0.0.1:9".to_string(),
+            client,
+            &Keys::generate(),
+            &key_signer,
+            &settings,
+            StatusMessages::new(),
+        ));
+        assert_eq!(conn.permissions, Some(granted));
+
+        // enforced: a granted kind is queued, others are rejected (sending fails here)
+        let sign_request_of_kind = |kind: u64| {
+            let mut event = test_unsigned_event_with_id("x", None);
+            event.kind = nostr::Kind::from(kind);
+            event.id = EventId::new(&event.pubkey, event.created_at, &event.kind, &[], "x");
+            Message::request(Request::SignEvent(event))
+        };
+        handle_request(conn.clone(), &sign_request_of_kind(1), &client)
+            .await
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 1);
+        assert!(
+            handle_request(conn.clone(), &sign_request_of_kind(0), &client)
+                .await
+                .is_err()
+        );
+        assert_eq!(conn.get_pending_count(), 1);
+        assert!(conn
+            .status
+            .get_last()
+            .contains("Permission 'sign_event:0' was not granted"));
+        let delegate = Message::request(Request::Delegate {
+            public_key: Keys::generate().public_key(),
+            conditions: Conditions::from_str("kind=1").unwrap(),
+        });
+        assert!(handle_request(conn.clone(), &delegate, &client)
+            .await
+            .is_err());
+        assert_eq!(conn.get_pending_count(), 1);
+        assert!(conn.check_permission(&delegate).is_err());
+
+        // describe advertises the granted methods only
+        match conn.describe_response("1") {
+            Message::Response {
+                result: Some(result),
+                ..
+            } => {
+                let methods: Vec<String> = serde_json::from_value(result).unwrap();
+                assert_eq!(methods, vec!["describe", "get_public_key", "sign_event"]);
+            }
+            _ => panic!("not a describe response"),
+        }
+        // delegation advertised once granted
+        let mut settings = settings.clone();
+        settings.client_permissions.insert(
+            client.to_string(),
+            Permissions::parse("sign_event:1,delegate").unwrap(),
+        );
+        let conn = SignerConnection::new(
+            "ws://127.0.0.1:9".to_string(),
+            client,
+            &Keys::generate(),
+            &key_signer,
+            &settings,
+            StatusMessages::new(),
+        );
+        match conn.describe_response("2") {
+            Message::Response {
+                result: Some(result),
+                ..
+            } => {
+                let methods: Vec<String> = serde_json::from_value(result).unwrap();
+                assert_eq!(
+                    methods,
+                    vec!["describe", "get_public_key", "sign_event", "delegate"]
+                );
+            }
+            _ => panic!("not a describe response"),
+        }
+
+        // invalid kind input
+        signer.grant.sign_event_kinds_input = "1, note".to_string();
+        assert!(signer.pairing_permissions().is_err());
+        assert_eq!(
+            Permission::parse("sign_event:1").unwrap(),
+            Permission::new("sign_event", Some(1))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_auto_approve_hours() {
+        let reaction = |content: &str| {
+            let mut event = test_unsigned_event_with_id(content, None);
+            event.kind = nostr::Kind::Reaction;
+            event.id = EventId::new(&event.pubkey, event.created_at, &event.kind, &[], content);
+            (event.id, Message::request(Request::SignEvent(event)))
+        };
+        let mut conn = test_connection();
+        conn.settings.kind_policy = [(7, ApprovalAction::AutoApprove)].into_iter().collect();
+        conn.settings.auto_approve_hours = Some(ApproveHours { start: 9, end: 17 });
+        let conn = Arc::new(conn);
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let other = Keys::generate().public_key();
+
+        // inside the window: signed right away (sending fails here)
+        let (inside_id, inside) = reaction("+");
+        assert!(handle_request_at(conn.clone(), &inside, &sender, 10)
+            .await
+            .is_err());
+        assert_eq!(conn.get_pending_count(), 0);
+        assert_eq!(conn.get_signed_event_ids(), vec![inside_id]);
+
+        // outside the window: queued for approval
+        let (_, outside) = reaction("-");
+        handle_request_at(conn.clone(), &outside, &sender, 20)
+            .await
+            .unwrap();
+        let (_, early) = reaction("!");
+        handle_request_at(conn.clone(), &early, &other, 8)
+            .await
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 2);
+        assert_eq!(conn.get_signed_event_ids(), vec![inside_id]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reject_all() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        assert_eq!(conn.action_reject_all(), 0);
+        for c in ["A", "B", "C"] {
+            conn.inject_request(test_unsigned_event_with_id(c, None), sender)
+                .unwrap();
+        }
+        assert_eq!(conn.get_pending_count(), 3);
+        assert_eq!(conn.action_reject_all(), 3);
+        assert_eq!(conn.get_pending_count(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reject_first() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        assert!(conn.action_first_req_reject().is_ok());
+        for c in ["A", "B"] {
+            conn.inject_request(test_unsigned_event_with_id(c, None), sender)
+                .unwrap();
+        }
+        // sending fails, as there is no relay, but the request is removed
+        assert!(conn.action_first_req_reject().is_err());
+        assert_eq!(conn.get_pending_count(), 1);
+        assert_eq!(
+            conn.get_first_request_description(),
+            "Signature requested for note: 'B'"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_latency_recorded() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let key_signer = conn.key_signer();
+        assert!(conn.get_latency_stats().is_none());
+
+        conn.inject_request(test_unsigned_event_with_id("A", None), sender)
+            .unwrap();
+        // recorded also if sending fails, as here without relay
+        assert!(conn.action_first_req_process(&key_signer, None).is_err());
+        let (last, average) = conn.get_latency_stats().unwrap();
+        assert!(last > Duration::ZERO);
+        assert_eq!(average, last);
+
+        // rejected requests are not counted
+        conn.inject_request(test_unsigned_event_with_id("B", None), sender)
+            .unwrap();
+        conn.action_first_req_remove();
+        assert_eq!(conn.get_latency_stats().unwrap().0, last);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_signed_event_ids_recorded() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let key_signer = conn.key_signer();
+        assert!(conn.get_signed_event_ids().is_empty());
+
+        let event = test_unsigned_event_with_id("A", None);
+        conn.inject_request(event.clone(), sender).unwrap();
+        // signed, even if sending fails here, as there is no relay
+        assert!(conn.action_first_req_process(&key_signer, None).is_err());
+        assert_eq!(conn.get_signed_event_ids(), vec![event.id]);
+
+        // rejected and refused ones are not recorded
+        conn.inject_request(test_unsigned_event_with_id("B", None), sender)
+            .unwrap();
+        conn.action_first_req_remove();
+        conn.inject_request(
+            test_unsigned_event_with_id("C", Some(EventId::from_hex(EVENTHEX).unwrap())),
+            sender,
+        )
+        .unwrap();
+        assert!(conn.action_first_req_process(&key_signer, None).is_err());
+        assert_eq!(conn.get_signed_event_ids(), vec![event.id]);
+
+        // capped, oldest dropped
+        for i in 0..SIGNED_IDS_MAX_COUNT {
+            conn.record_signed(test_unsigned_event_with_id(&i.to_string(), None).id);
+        }
+        let ids = conn.get_signed_event_ids();
+        assert_eq!(ids.len(), SIGNED_IDS_MAX_COUNT);
+        assert!(!ids.contains(&event.id));
+        assert_eq!(
+            ids[0],
+            test_unsigned_event_with_id(&(SIGNED_IDS_MAX_COUNT - 1).to_string(), None).id
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rotate_app_id_and_reconnect() {
+        let app_id_1 = Keys::generate();
+        let app_id_2 = Keys::generate();
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(SecretKey::from_bech32(NSEC1).unwrap()),
+        };
+        let mut signer = Signer::new(&app_id_1, StatusMessages::new());
+        // unreachable relay, connection is not needed
+        signer
+            .connect("nostrconnect://b889ff5b1513b641e2a139f661a661364979c5beee91842f8f0ef42ab558e9d4?relay=ws%3A%2F%2F127.0.0.1%3A9%2F&metadata=%7B%22name%22%3A%22Example%22%7D", &key_signer, &SignerSettings::default(), false)
+            .unwrap();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let old_conn = signer.connection.clone().unwrap();
+        old_conn.add_request(test_sign_request("A"), sender);
+        old_conn.add_request(test_sign_request("B"), sender);
+        assert_eq!(
+            old_conn.relay_client.keys().public_key(),
+            app_id_1.public_key()
+        );
+
+        signer.rotate_app_id_and_reconnect(&app_id_2).unwrap();
+
+        let conn = signer.connection.clone().unwrap();
+        assert_eq!(conn.app_id_keys.public_key(), app_id_2.public_key());
+        assert_eq!(conn.relay_client.keys().public_key(), app_id_2.public_key());
+        assert_eq!(conn.client_pubkey, old_conn.client_pubkey);
+        assert_eq!(conn.get_pending_count(), 2);
+        assert_eq!(
+            conn.get_request_descriptions(),
+            vec![
+                "Signature requested for note: 'A'".to_string(),
+                "Signature requested for note: 'B'".to_string()
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_relays_for_connect() {
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(SecretKey::from_bech32(NSEC1).unwrap()),
+        };
+        let uri = "nostrconnect://b889ff5b1513b641e2a139f661a661364979c5beee91842f8f0ef42ab558e9d4?relay=ws%3A%2F%2F127.0.0.1%3A9%2F&metadata=%7B%22name%22%3A%22Example%22%7D";
+        let settings = SignerSettings {
+            response_relays: vec![
+                "ws://127.0.0.1:9/".to_string(),
+                "ws://127.0.0.1:10".to_string(),
+            ],
+            ..SignerSettings::default()
+        };
+        let preview = Signer::relays_for_connect(uri, &settings).unwrap();
+        assert_eq!(preview, vec!["ws://127.0.0.1:9", "ws://127.0.0.1:10"]);
+
+        let mut signer = Signer::new(&Keys::generate(), StatusMessages::new());
+        signer.connect(uri, &key_signer, &settings, false).unwrap();
+        // the relays are added to the client in the background
+        let relay_client = signer.connection.as_ref().unwrap().relay_client.clone();
+        let mut added = Vec::new();
+        for _ in 0..100 {
+            added = relay_client
+                .relays()
+                .await
+                .keys()
+                .map(|u| u.as_str().trim_end_matches('/').to_string())
+                .collect::<Vec<String>>();
+            if added.len() >= preview.len() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        added.sort();
+        let mut expected = preview.clone();
+        expected.sort();
+        assert_eq!(added, expected);
+
+        assert!(Signer::relays_for_connect("not a uri", &settings).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_connect_same_uri_twice() {
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(SecretKey::from_bech32(NSEC1).unwrap()),
+        };
+        let uri = "nostrconnect://b889ff5b1513b641e2a139f661a661364979c5beee91842f8f0ef42ab558e9d4?relay=ws%3A%2F%2F127.0.0.1%3A9%2F&metadata=%7B%22name%22%3A%22Example%22%7D";
+        let mut signer = Signer::new(&Keys::generate(), StatusMessages::new());
+
+        signer
+            .connect(uri, &key_signer, &SignerSettings::default(), false)
+            .unwrap();
+        let first = signer.connection.clone().unwrap();
+        first
+            .inject_request(
+                test_unsigned_event_with_id("A", None),
+                XOnlyPublicKey::from_bech32(NPUB2).unwrap(),
+            )
+            .unwrap();
+
+        // refused if still connecting, or reconnected if it dropped; never duplicated
+        let res = signer.connect(uri, &key_signer, &SignerSettings::default(), false);
+        assert!(matches!(
+            res,
+            Ok(()) | Err(Error::SignerDuplicateConnection)
+        ));
+        let second = signer.connection.clone().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(second.get_pending_count(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_force_disconnect_reconnect() {
+        async fn wait_for_handler(
+            conn: &SignerConnection,
+            not: Option<&AbortHandle>,
+        ) -> AbortHandle {
+            for _i in 0..100 {
+                if let Some(h) = conn.handler.lock().unwrap().clone() {
+                    if not.map(|n| n.id()) != Some(h.id()) {
+                        return h;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            panic!("Handler loop not started");
+        }
+
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(SecretKey::from_bech32(NSEC1).unwrap()),
+        };
+        let uri = "nostrconnect://b889ff5b1513b641e2a139f661a661364979c5beee91842f8f0ef42ab558e9d4?relay=ws%3A%2F%2F127.0.0.1%3A9%2F&metadata=%7B%22name%22%3A%22Example%22%7D";
+        let mut signer = Signer::new(&Keys::generate(), StatusMessages::new());
+        signer
+            .connect(uri, &key_signer, &SignerSettings::default(), false)
+            .unwrap();
+        let conn = signer.connection.clone().unwrap();
+        let first_handler = wait_for_handler(&conn, None).await;
+
+        conn.force_disconnect_for_test().await.unwrap();
+        // relay status is updated in the background
+        for _i in 0..100 {
+            if matches!(
+                signer.get_connection_status(),
+                ConnectionStatus::NotConnected
+            ) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(matches!(
+            signer.get_connection_status(),
+            ConnectionStatus::NotConnected
+        ));
+        // not stopped like on disconnect
+        assert!(!first_handler.is_finished());
+
+        // reconnect: same connection, handler loop (subscription) started again, only one
+        signer
+            .connect(uri, &key_signer, &SignerSettings::default(), false)
+            .unwrap();
+        assert!(Arc::ptr_eq(&conn, signer.connection.as_ref().unwrap()));
+        let second_handler = wait_for_handler(&conn, Some(&first_handler)).await;
+        for _i in 0..100 {
+            if first_handler.is_finished() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(first_handler.is_finished());
+        assert!(!second_handler.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_connect_self_check() {
+        let uri = "nostrconnect://b889ff5b1513b641e2a139f661a661364979c5beee91842f8f0ef42ab558e9d4?relay=ws%3A%2F%2F127.0.0.1%3A9%2F&metadata=%7B%22name%22%3A%22Example%22%7D";
+        let broken_signer = KeySigner {
+            keys: Keys::from_public_key(XOnlyPublicKey::from_bech32(NPUB2).unwrap()),
+        };
+        let mut signer = Signer::new(&Keys::generate(), StatusMessages::new());
+        match signer.connect(uri, &broken_signer, &SignerSettings::default(), false) {
+            Err(Error::KeySelfCheckFailed) => {}
+            _ => panic!("Connection should be refused"),
+        }
+        assert!(signer.connection.is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_connect_fresh_unlock() {
+        let relay = MockRelay::start().await;
+        let uri = format!(
+            "nostrconnect://b889ff5b1513b641e2a139f661a661364979c5beee91842f8f0ef42ab558e9d4?relay={}&metadata=%7B%22name%22%3A%22Example%22%7D",
+            relay.url().replace(':', "%3A").replace('/', "%2F")
+        );
+        // key requiring the password for each signature: connected with the public key only
+        let sk = SecretKey::from_bech32(NSEC1).unwrap();
+        let encrypted =
+            Encrypt::encrypt_key(&sk, "password", 4, EncryptionAlgorithm::default()).unwrap();
+        let mut keystore = Keystore::new();
+        keystore
+            .import_encrypted_secret_key(&hex::encode(encrypted), false)
+            .unwrap();
+        let key_signer = KeySigner {
+            keys: Keys::from_public_key(Keys::new(sk).public_key()),
+        };
+        let mut signer = Signer::new(&Keys::generate(), StatusMessages::new());
+        signer
+            .connect(&uri, &key_signer, &SignerSettings::default(), true)
+            .unwrap();
+        let conn = signer.connection.clone().unwrap();
+        assert!(!conn.key_signer().can_sign());
+
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        conn.add_request(test_sign_request("A"), sender);
+        assert!(
+            wait_until(RELAY_TIMEOUT, || matches!(
+                signer.get_connection_status(),
+                ConnectionStatus::Connected(_)
+            ))
+            .await
+        );
+
+        // approved with the key unlocked by the password
+        keystore.decrypt_password_input = "password".to_string();
+        let fresh_signer = keystore.fresh_unlock_signer().unwrap();
+        let mut status = StatusMessages::new();
+        signer.pending_process_first_action(Some(fresh_signer), None, &mut status);
+        assert!(status.get_last().starts_with("Processed request"));
+        assert_eq!(conn.get_pending_count(), 0);
+        assert_eq!(conn.get_signed_event_ids().len(), 1);
+        assert!(
+            relay
+                .wait_for_received(RELAY_TIMEOUT, |msgs| msgs
+                    .iter()
+                    .any(|m| matches!(m, ClientMessage::Event(_))))
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_limited_max_in_flight() {
+        let relays: Vec<String> = (1..=5)
+            .map(|i| format!("wss://relay{}.example.com", i))
+            .collect();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+        // counting mock connect
+        let mock_connect = |_relay: String| {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            let done = done.clone();
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                done.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        };
+
+        run_limited(&relays, 2, mock_connect).await.unwrap();
+        assert_eq!(done.load(Ordering::SeqCst), 5);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+
+        // no limit
+        max_in_flight.store(0, Ordering::SeqCst);
+        run_limited(&relays, 0, mock_connect).await.unwrap();
+        assert_eq!(done.load(Ordering::SeqCst), 10);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 5);
+
+        // errors are returned, after all are run
+        let res = run_limited(&relays, 2, |_relay| async {
+            Err(Error::InvalidRelayUrl("x".to_string()))
+        })
+        .await;
+        assert!(matches!(res, Err(Error::InvalidRelayUrl(_))));
+    }
+
+    #[tokio::test]
+    async fn test_display_name_persisted() {
+        let conn = test_connection();
+        let client_pubkey = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        assert_eq!(conn.get_display_name(DisplayFormat::Bech32), NPUB2);
+
+        conn.set_display_name("My phone Amethyst".to_string());
+        assert_eq!(
+            conn.get_display_name(DisplayFormat::Bech32),
+            "My phone Amethyst"
+        );
+
+        // persisted in settings, restored on a new connection to the same client
+        let mut settings = SignerSettings::default();
+        settings.connection_names.insert(
+            client_pubkey.to_string(),
+            conn.display_name.lock().unwrap().clone().unwrap(),
+        );
+        let json = serde_json::to_string(&settings).unwrap();
+        let restored: SignerSettings = serde_json::from_str(&json).unwrap();
+        let conn2 = SignerConnection::new(
+            "wss://relay.example.com".to_string(),
+            client_pubkey,
+            &Keys::generate(),
+            &conn.key_signer(),
+            &restored,
+            StatusMessages::new(),
+        );
+        assert_eq!(
+            conn2.get_display_name(DisplayFormat::Bech32),
+            "My phone Amethyst"
+        );
+
+        // cleared: falls back to metadata name, then npub
+        let mut conn3 = test_connection();
+        conn3.metadata_name = Some("Amethyst".to_string());
+        assert_eq!(conn3.get_display_name(DisplayFormat::Bech32), "Amethyst");
+        conn3.set_display_name("Phone".to_string());
+        conn3.set_display_name(" ".to_string());
+        assert_eq!(conn3.get_display_name(DisplayFormat::Bech32), "Amethyst");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_request_expiry_warning() {
+        let mut signer = Signer::new(&Keys::generate(), StatusMessages::new());
+        let mut status = StatusMessages::new();
+        let expiry = Duration::from_secs(120);
+        let lead_time = Duration::from_secs(30);
+        let conn = Arc::new(test_connection());
+        signer.connection = Some(conn.clone());
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        conn.add_request(test_sign_request("A"), sender);
+        let received = conn.requests.lock().unwrap()[0].received_at;
+        let at = |secs: u64| received + Duration::from_secs(secs);
+
+        // disabled
+        assert!(!signer.check_request_expiry(at(600), Duration::ZERO, lead_time, &mut status));
+        assert_eq!(conn.get_pending_count(), 1);
+
+        // before the lead time: nothing
+        assert!(!signer.check_request_expiry(at(89), expiry, lead_time, &mut status));
+        assert_eq!(conn.get_pending_count(), 1);
+
+        // at the lead time before expiry: notified, once
+        assert!(signer.check_request_expiry(at(90), expiry, lead_time, &mut status));
+        assert!(status
+            .get_last()
+            .contains("1 pending request(s) expire in 30 seconds"));
+        assert_eq!(conn.get_pending_count(), 1);
+        assert!(!signer.check_request_expiry(at(100), expiry, lead_time, &mut status));
+
+        // a later request is notified at its own time
+        conn.add_request(test_sign_request("B"), sender);
+        let received_b = conn.requests.lock().unwrap()[1].received_at;
+        let lead_b = received_b + expiry - lead_time;
+        assert!(signer.check_request_expiry(lead_b.max(at(110)), expiry, lead_time, &mut status));
+
+        // expired: pruned, rejected (sending fails here)
+        assert!(!signer.check_request_expiry(at(120), expiry, lead_time, &mut status));
+        assert_eq!(conn.get_pending_count(), 1);
+        assert!(status
+            .get_last()
+            .contains("1 pending request(s) expired after 120 seconds"));
+
+        // no lead time: expires without notification
+        conn.add_request(test_sign_request("C"), sender);
+        let received_c = conn.requests.lock().unwrap()[1].received_at;
+        assert!(!signer.check_request_expiry(
+            received_c + expiry - Duration::from_secs(1),
+            expiry,
+            Duration::ZERO,
+            &mut status
+        ));
+        assert!(!signer.check_request_expiry(
+            received_c + expiry,
+            expiry,
+            Duration::ZERO,
+            &mut status
+        ));
+        assert_eq!(conn.get_pending_count(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_disconnect_if_idle() {
+        let mut signer = Signer::new(&Keys::generate(), StatusMessages::new());
+        let mut status = StatusMessages::new();
+        let timeout = Duration::from_secs(60);
+        let conn = Arc::new(test_connection());
+        let start = Instant::now();
+        conn.touch_activity(start);
+        signer.connection = Some(conn.clone());
+
+        // below threshold, or disabled
+        assert!(!signer.disconnect_if_idle(start + Duration::from_secs(59), timeout, &mut status));
+        assert!(!signer.disconnect_if_idle(
+            start + Duration::from_secs(600),
+            Duration::ZERO,
+            &mut status
+        ));
+        // pending request keeps it
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        conn.add_request(test_sign_request("A"), sender);
+        assert!(!signer.disconnect_if_idle(start + timeout, timeout, &mut status));
+        conn.action_first_req_remove();
+        // activity resets the idle time
+        conn.touch_activity(start + Duration::from_secs(30));
+        assert!(!signer.disconnect_if_idle(start + timeout, timeout, &mut status));
+        assert!(signer.connection.is_some());
+
+        // idle over threshold
+        assert!(signer.disconnect_if_idle(start + Duration::from_secs(90), timeout, &mut status));
+        assert!(signer.connection.is_none());
+        assert!(status.get_last().contains("idle"));
+        assert!(!signer.disconnect_if_idle(start + timeout * 10, timeout, &mut status));
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_wait() {
+        // disabled by default
+        let conn = test_connection();
+        assert_eq!(conn.keepalive_wait(Instant::now()), None);
+
+        let settings = SignerSettings {
+            keepalive_secs: 30,
+            ..Default::default()
+        };
+        let conn = SignerConnection::new(
+            "wss://relay.example.com".to_string(),
+            XOnlyPublicKey::from_bech32(NPUB2).unwrap(),
+            &Keys::generate(),
+            &conn.key_signer(),
+            &settings,
+            StatusMessages::new(),
+        );
+        let start = Instant::now();
+        conn.record_keepalive(start);
+        assert_eq!(
+            conn.keepalive_wait(start + Duration::from_secs(10)),
+            Some(Duration::from_secs(20))
+        );
+        // due at the interval, and when overdue
+        assert_eq!(
+            conn.keepalive_wait(start + Duration::from_secs(30)),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            conn.keepalive_wait(start + Duration::from_secs(45)),
+            Some(Duration::ZERO)
+        );
+        // next one an interval after the last
+        conn.record_keepalive(start + Duration::from_secs(45));
+        assert_eq!(
+            conn.keepalive_wait(start + Duration::from_secs(45)),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    fn test_sign_raw_request(value_hex: &str) -> Message {
+        Message::Request {
+            id: "raw001".to_string(),
+            method: "sign_raw".to_string(),
+            params: vec![serde_json::Value::String(value_hex.to_string())],
+        }
+    }
+
+    #[test]
+    fn test_sign_raw_value() {
+        let msg = test_sign_raw_request(EVENTHEX);
+        let value = sign_raw_value(&msg).unwrap().unwrap();
+        assert_eq!(hex::encode(value), EVENTHEX);
+
+        let key_signer = KeySigner {
+            keys: Keys::new(SecretKey::from_bech32(NSEC1).unwrap()),
+        };
+        match sign_raw_response("raw001", &value, &key_signer).unwrap() {
+            Message::Response {
+                id,
+                result: Some(serde_json::Value::String(sig)),
+                error: None,
+            } => {
+                assert_eq!(id, "raw001");
+                let sig = nostr::secp256k1::schnorr::Signature::from_str(&sig).unwrap();
+                let message = nostr::secp256k1::Message::from_slice(&value).unwrap();
+                assert!(SECP256K1
+                    .verify_schnorr(&sig, &message, &key_signer.get_public_key())
+                    .is_ok());
+            }
+            _ => panic!("Expected signature response"),
+        }
+
+        // wrong length, not hex, missing
+        for invalid in [&EVENTHEX[..62], &format!("{}00", EVENTHEX), "", "zz"] {
+            assert!(matches!(
+                sign_raw_value(&test_sign_raw_request(invalid)),
+                Some(Err(Error::SignerRawInvalid))
+            ));
+        }
+        let no_params = Message::Request {
+            id: "raw002".to_string(),
+            method: "sign_raw".to_string(),
+            params: vec![],
+        };
+        assert!(matches!(
+            sign_raw_value(&no_params),
+            Some(Err(Error::SignerRawInvalid))
+        ));
+        // not sign raw
+        assert!(sign_raw_value(&test_sign_request("A")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_handle_sign_raw_request() {
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+
+        // not allowed: ignored
+        let conn = Arc::new(test_connection());
+        handle_request(conn.clone(), &test_sign_raw_request(EVENTHEX), &sender)
+            .await
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 0);
+
+        // allowed: queued, with a warning
+        let mut conn = test_connection();
+        conn.settings.allow_sign_raw = true;
+        let conn = Arc::new(conn);
+        handle_request(conn.clone(), &test_sign_raw_request(EVENTHEX), &sender)
+            .await
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 1);
+        assert!(conn
+            .get_first_request_description()
+            .starts_with("WARNING: RAW signature requested"));
+
+        // wrong length: rejected right away (sending fails without relay)
+        let _res = handle_request(conn.clone(), &test_sign_raw_request("0102"), &sender).await;
+        assert_eq!(conn.get_pending_count(), 1);
+    }
+
+    #[test]
+    fn test_event_to_sign_applies_selected_delegation() {
+        let signer_keys = Keys::new(SecretKey::from_bech32(NSEC1).unwrap());
+        let signer_pubkey = signer_keys.public_key();
+        let new_delegation = |cond: &str| {
+            let tag = DelegationTag::new(
+                &Keys::generate(),
+                signer_pubkey,
+                Conditions::from_str(cond).unwrap(),
+            )
+            .unwrap();
+            DelegationInfo::from_tag_str(&tag.to_string()).unwrap()
+        };
+        let mut delegator = Delegator::new();
+        for cond in ["kind=1", "kind=1&created_at>1600000000", "kind=7"] {
+            delegator.delegations.push(new_delegation(cond));
+        }
+        let event = test_unsigned_event_with_id("A", None);
+        let delegation_pk = |e: &UnsignedEvent| {
+            e.tags.iter().find_map(|t| match t {
+                Tag::Delegation { delegator_pk, .. } => Some(*delegator_pk),
+                _ => None,
+            })
+        };
+
+        // the selected one is applied, event id is updated
+        for i in 0..2 {
+            delegator.select_delegation(Some(i)).unwrap();
+            let signed = event_to_sign(
+                &event,
+                None,
+                delegator.get_selected_delegation(),
+                &signer_pubkey,
+            );
+            assert_eq!(
+                delegation_pk(&signed),
+                Some(delegator.delegations[i].delegator_pubkey())
+            );
+            assert_eq!(signed.tags.len(), 1);
+            assert_ne!(signed.id, event.id);
+            assert!(event_id_matches(&signed));
+        }
 
-fn message_method(msg: &Message) -> String {
-    match &msg {
-        Message::Request { method, .. } => format!("request {method}"),
-        Message::Response { .. } => "response".to_string(),
+        // conditions don't permit the event: not applied
+        delegator.select_delegation(Some(2)).unwrap();
+        let signed = event_to_sign(
+            &event,
+            None,
+            delegator.get_selected_delegation(),
+            &signer_pubkey,
+        );
+        assert_eq!(delegation_pk(&signed), None);
+        assert_eq!(signed.id, event.id);
+
+        // other delegatee: not applied
+        delegator.select_delegation(Some(0)).unwrap();
+        let other = Keys::generate().public_key();
+        let signed = event_to_sign(&event, None, delegator.get_selected_delegation(), &other);
+        assert_eq!(delegation_pk(&signed), None);
+
+        // none selected
+        delegator.select_delegation(None).unwrap();
+        let signed = event_to_sign(
+            &event,
+            None,
+            delegator.get_selected_delegation(),
+            &signer_pubkey,
+        );
+        assert_eq!(signed.id, event.id);
     }
-}
 
-/// Start event handling loop in the background, asynchrnous, fire-and-forget
-// TODO: Close loop on disconnect!
-fn start_handler_loop(connection: Arc<SignerConnection>, handle: Handle) -> Result<(), Error> {
-    // let (tx, rx) = channel::bounded(1);
-    let connection_clone = connection.clone();
-    handle.spawn(async move {
-        let _res = wait_and_handle_messages(connection_clone).await;
-        // let _ = tx.send(res);
-    });
-    // rx.recv()?
-    Ok(())
-}
+    #[tokio::test]
+    async fn test_signer_pending_count() {
+        let mut signer = Signer::new(&Keys::generate(), StatusMessages::new());
+        assert_eq!(signer.get_pending_count(), 0);
 
-async fn wait_and_handle_messages(connection: Arc<SignerConnection>) -> Result<(), Error> {
-    let relay_client = &connection.relay_client;
-    let keys = relay_client.keys();
+        let conn = Arc::new(test_connection());
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        conn.inject_request(test_unsigned_event_with_id("test1", None), sender)
+            .unwrap();
+        conn.inject_request(test_unsigned_event_with_id("test2", None), sender)
+            .unwrap();
+        signer.connection = Some(conn.clone());
+        assert_eq!(signer.get_pending_count(), 2);
+    }
 
-    relay_client
-        .subscribe(vec![Filter::new()
-            .pubkey(keys.public_key())
-            .kind(Kind::NostrConnect)
-            .since(Timestamp::now() - Duration::from_secs(10))])
-        .await;
-    println!("DEBUG: Subscribed to relay events ...");
-    println!("DEBUG: Waiting for messages ...");
+    #[tokio::test]
+    async fn test_handle_request_content_size_limit() {
+        let conn = Arc::new(test_connection());
+        let max_size = conn.settings.max_content_size;
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
 
-    loop {
-        let mut notifications = relay_client.notifications();
-        while let Ok(notification) = notifications.recv().await {
-            if let RelayPoolNotification::Event(_url, event) = notification {
-                if event.kind == Kind::NostrConnect {
-                    match decrypt(&keys.secret_key()?, &event.pubkey, &event.content) {
-                        Ok(msg) => {
-                            let msg = Message::from_json(msg)?;
-                            let _ = handle_request(connection.clone(), &msg, &event.pubkey).await?;
-                        }
-                        Err(e) => eprintln!("DEBUG: Impossible to decrypt NIP46 message: {e}"),
-                    }
-                }
+        // oversized, rejected (sending the error response fails without relay, ignore it)
+        let oversized = "x".repeat(max_size + 1);
+        let _res = handle_request(conn.clone(), &test_sign_request(&oversized), &sender).await;
+        assert_eq!(conn.get_pending_count(), 0);
+
+        // normal, queued
+        let normal = "x".repeat(max_size);
+        handle_request(conn.clone(), &test_sign_request(&normal), &sender)
+            .await
+            .unwrap();
+        assert_eq!(conn.get_pending_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_unknown_method() {
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let msg = Message::Request {
+            id: "req001".to_string(),
+            method: "unknown_method".to_string(),
+            params: Vec::new(),
+        };
+
+        // off: ignored, nothing sent
+        let conn = Arc::new(test_connection());
+        assert!(!conn.settings.reject_unknown_methods);
+        assert!(unsupported_method_response(&msg, &conn.settings).is_none());
+        handle_request(conn.clone(), &msg, &sender).await.unwrap();
+
+        // on: error response is sent (fails here, as there is no relay)
+        let mut conn = test_connection();
+        conn.settings.reject_unknown_methods = true;
+        let conn = Arc::new(conn);
+        match unsupported_method_response(&msg, &conn.settings) {
+            Some(Message::Response { id, result, error }) => {
+                assert_eq!(id, "req001");
+                assert!(result.is_none());
+                assert_eq!(error.unwrap(), "Unsupported method 'unknown_method'");
             }
+            _ => panic!("Expected error response"),
         }
+        assert!(matches!(
+            handle_request(conn.clone(), &msg, &sender).await,
+            Err(Error::RelayClientError(_))
+        ));
+        assert_eq!(
+            conn.status.get_last(),
+            "Warning: Unsupported request unknown_method, rejected"
+        );
     }
-    // relay_client.unsubscribe().await;
-}
 
-/// Put together response message for a request
-/// Ok(None) is a valid return value
-fn response_for_message(
-    req_id: &String,
-    req: &Request,
-    key_signer: &KeySigner,
-) -> Result<Option<Message>, Error> {
-    match req {
-        Request::Describe => {
-            println!("DEBUG: Describe received");
-            let values = ["describe", "get_public_key", "sign_event"]
-                .to_vec()
-                .iter()
-                .map(|s| s.to_string())
-                .collect();
-            Ok(Some(Message::response(
-                req_id.to_string(),
-                Response::Describe(values),
-            )))
-        }
-        Request::GetPublicKey => {
-            // Return the signer pubkey
-            println!("DEBUG: GetPublicKey received");
-            Ok(Some(Message::response(
-                req_id.clone(),
-                Response::GetPublicKey(key_signer.get_public_key()),
-            )))
-        }
-        Request::SignEvent(unsigned_event) => {
-            let unsigned_id = unsigned_event.id;
-            let signature = key_signer.sign(unsigned_id.as_bytes().to_vec())?;
-            Ok(Some(Message::response(
-                req_id.clone(),
-                Response::SignEvent(signature),
-            )))
-        }
-        Request::Delegate {
-            public_key,
-            conditions,
-        } => {
-            let delegation_tag =
-                DelegationTag::new(&key_signer.keys, public_key.clone(), conditions.clone())?;
-            let delegator_result = DelegationResult {
-                from: key_signer.get_public_key(),
-                to: public_key.clone(),
-                cond: conditions.clone(),
-                sig: delegation_tag.signature(),
-            };
-            Ok(Some(Message::response(
-                req_id.clone(),
-                Response::Delegate(delegator_result),
-            )))
+    #[tokio::test]
+    async fn test_connect_response_relays() {
+        let mut conn = test_connection();
+        conn.settings.response_relays = vec![
+            "ws://127.0.0.1:10".to_string(),
+            "not a relay".to_string(),
+            "ws://127.0.0.1:11".to_string(),
+        ];
+        conn.settings.max_concurrent_connects = 1;
+        conn.relay_client
+            .add_relay("ws://127.0.0.1:9", None)
+            .await
+            .unwrap();
+        connect_response_relays(&conn).await;
+
+        // the bad relay is logged, the others are added
+        assert!(conn
+            .status
+            .get_last()
+            .contains("Could not add response relay not a relay"));
+        let relays = conn.relay_client.relays().await;
+        assert_eq!(relays.len(), 3);
+        for r in ["ws://127.0.0.1:9", "ws://127.0.0.1:10", "ws://127.0.0.1:11"] {
+            assert!(relays.contains_key(&Url::parse(r).unwrap()));
         }
-        _ => Ok(None),
+
+        // added once, not again on send
+        let msg = error_response("id001", "test");
+        let client = Client::with_opts(&Keys::generate(), Options::new().wait_for_send(false));
+        client.add_relay("ws://127.0.0.1:9", None).await.unwrap();
+        send_message(&client, &msg, &conn.client_pubkey)
+            .await
+            .unwrap();
+        assert_eq!(client.relays().await.len(), 1);
     }
-}
 
-async fn handle_request(
-    connection: Arc<SignerConnection>,
-    msg: &Message,
-    sender_pubkey: &XOnlyPublicKey,
-) -> Result<(), Error> {
-    println!("DEBUG: New message received {}", message_method(msg));
-
-    if let Message::Request { id, .. } = msg {
-        if let Ok(req) = &msg.to_request() {
-            match req {
-                // First handle requests that need user interaction
-                Request::SignEvent(_) => {
-                    // This request needs user processing, store it, notify it
-                    connection.add_request(msg.clone(), sender_pubkey.clone());
-                    EVENT_QUEUE.push(Event::SignerNewRequest)?;
-                    connection.status.set("New Signing request received");
-                }
-                Request::Delegate {
-                    public_key: _,
-                    conditions: _,
-                } => {
-                    // This request needs user processing, store it, notify it
-                    connection.add_request(msg.clone(), sender_pubkey.clone());
-                    EVENT_QUEUE.push(Event::SignerNewRequest)?;
-                    connection
-                        .status
-                        .set("New Signing/Delegate request received");
-                }
-                _ => {
-                    // Non-interactive requests: try to create response, send it
-                    let key_signer = &connection.key_signer;
-                    let response_message = response_for_message(id, req, key_signer)?;
-                    match response_message {
-                        Some(m) => {
-                            // We return a response message right away
-                            let relay_client = &connection.relay_client;
-                            let _ = send_message(relay_client, &m, sender_pubkey).await?;
-                        }
-                        None => {
-                            println!("ERROR: Could not handle request {:?}", msg.to_request());
-                        }
-                    }
-                }
+    #[tokio::test]
+    async fn test_drop_stops_handler_loop() {
+        let conn = Arc::new(test_connection());
+        let handler = start_handler_loop(&conn, tokio::runtime::Handle::current());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!handler.is_finished());
+
+        drop(conn);
+        tokio::time::timeout(Duration::from_secs(5), handler)
+            .await
+            .expect("Handler loop did not stop")
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_disconnect_stops_handler_loop() {
+        let key_signer: KeySigner = KeySigner {
+            keys: Keys::new(SecretKey::from_bech32(NSEC1).unwrap()),
+        };
+        let uri = "nostrconnect://b889ff5b1513b641e2a139f661a661364979c5beee91842f8f0ef42ab558e9d4?relay=ws%3A%2F%2F127.0.0.1%3A9%2F&metadata=%7B%22name%22%3A%22Example%22%7D";
+        let mut signer = Signer::new(&Keys::generate(), StatusMessages::new());
+        signer
+            .connect(uri, &key_signer, &SignerSettings::default(), false)
+            .unwrap();
+        // the connection is still referenced after disconnect, like by the UI
+        let conn = signer.connection.clone().unwrap();
+        let mut handler = None;
+        for _i in 0..100 {
+            handler = conn.handler.lock().unwrap().clone();
+            if handler.is_some() {
+                break;
             }
-        } else {
-            println!("DEBUG: Could not extract Request, ignoring");
+            tokio::time::sleep(Duration::from_millis(20)).await;
         }
-    } else {
-        println!("DEBUG: Not a Request, ignoring");
+        let handler = handler.expect("Handler loop not started");
+        assert!(!handler.is_finished());
+
+        signer.disconnect_action(&mut StatusMessages::new());
+        assert!(signer.connection.is_none());
+        for _i in 0..250 {
+            if handler.is_finished() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(handler.is_finished());
     }
-    Ok(())
-}
 
-#[cfg(test)]
-mod test {
-    use super::{response_for_message, KeySigner, Keys, Request, XOnlyPublicKey};
-    use nostr::prelude::{
-        Condition, Conditions, EventId, FromBech32, KeyPair, Secp256k1, SecretKey, Timestamp,
-        UnsignedEvent,
-    };
+    #[tokio::test]
+    async fn test_closed_event_queue_stops_handler_loop() {
+        let mut conn = test_connection();
+        conn.event_queue = Arc::new(EventQueue::new());
+        let conn = Arc::new(conn);
+        let handler = start_handler_loop(&conn, tokio::runtime::Handle::current());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!handler.is_finished());
 
-    const NSEC1: &str = "nsec1lfeqz504rd4hc824kmts9qkl5qz7t9md694cd3vr5zevmpne5weqp2thmp";
-    const NPUB2: &str = "npub1c82zv3aj04l8dmxlxywx5fsg6ngt5nyvwa9j0eqk03ntg2t2jtxqngn7ry";
-    const EVENTHEX: &str = "0b1c1aa42d25eab6f022febcea00e858b034f73ac4229aa82554b8cb3d8f94f5";
+        conn.event_queue.close();
+        tokio::time::timeout(Duration::from_secs(5), handler)
+            .await
+            .expect("Handler loop did not stop")
+            .unwrap();
+        // connection is still alive, new requests are refused
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        assert!(matches!(
+            conn.inject_request(test_unsigned_event_with_id("test", None), sender),
+            Err(Error::InternalEventQueueClosed)
+        ));
+    }
 
-    #[test]
-    fn test_response_for_message_describe() {
-        let req: Request = Request::Describe;
-        let req_id: String = "id001".to_string();
-        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
-        let key_signer: KeySigner = KeySigner {
-            keys: Keys::new(sk),
-        };
-        let resp_msg = response_for_message(&req_id, &req, &key_signer).unwrap();
-        assert!(resp_msg.is_some());
-        assert_eq!(resp_msg.unwrap().as_json(), "{\"error\":null,\"id\":\"id001\",\"result\":[\"describe\",\"get_public_key\",\"sign_event\"]}");
+    #[tokio::test]
+    async fn test_handle_request_response_logged() {
+        let conn = Arc::new(test_connection());
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let msg = error_response("resp042", "some error");
+        handle_request(conn.clone(), &msg, &sender).await.unwrap();
+        assert_eq!(conn.get_pending_count(), 0);
+        assert_eq!(
+            conn.status.get_last(),
+            "Warning: Unexpected response received, ignoring, id resp042, error 'some error'"
+        );
     }
 
-    #[test]
-    fn test_response_for_message_getpublickey() {
-        let req: Request = Request::GetPublicKey;
-        let req_id: String = "id001".to_string();
-        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
-        let key_signer: KeySigner = KeySigner {
-            keys: Keys::new(sk),
+    #[tokio::test]
+    async fn test_handle_relay_ok() {
+        let conn = test_connection();
+        let url = Url::parse("wss://relay.example.com").unwrap();
+        let event_id = EventId::from_hex(EVENTHEX).unwrap();
+        conn.status.set("Before");
+
+        // accepted, not shown by default
+        handle_relay_ok(&conn, &url, &event_id, true, "");
+        assert_eq!(conn.status.get_last(), "Before");
+
+        // rejection reason surfaced
+        handle_relay_ok(&conn, &url, &event_id, false, "rate-limited: slow down");
+        assert_eq!(
+            conn.status.get_last(),
+            "Warning: Response 63745d0d2eb9.. rejected by relay wss://relay.example.com/: 'rate-limited: slow down'"
+        );
+
+        let settings = SignerSettings {
+            show_relay_accepted: true,
+            ..Default::default()
         };
-        let resp_msg = response_for_message(&req_id, &req, &key_signer).unwrap();
-        assert!(resp_msg.is_some());
-        assert_eq!(resp_msg.unwrap().as_json(), "{\"error\":null,\"id\":\"id001\",\"result\":\"dd73f1d141b01badbb4049c5bcaa2cd261501c0c356774fada3db425c7d6e413\"}");
+        let conn = SignerConnection::new(
+            "wss://relay.example.com".to_string(),
+            XOnlyPublicKey::from_bech32(NPUB2).unwrap(),
+            &Keys::generate(),
+            &conn.key_signer(),
+            &settings,
+            StatusMessages::new(),
+        );
+        handle_relay_ok(&conn, &url, &event_id, true, "");
+        assert_eq!(
+            conn.status.get_last(),
+            "Response 63745d0d2eb9.. accepted by relay wss://relay.example.com/"
+        );
     }
 
     #[test]
-    fn test_response_for_message_signevent() {
-        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
-        let (pubkey, _parity) =
-            XOnlyPublicKey::from_keypair(&KeyPair::from_secret_key(&Secp256k1::default(), &sk));
-        let unisgned_event: UnsignedEvent = UnsignedEvent {
-            id: EventId::from_hex(EVENTHEX).unwrap(),
-            pubkey,
-            created_at: Timestamp::from(1686693500),
-            kind: nostr::Kind::TextNote,
+    fn test_response_for_message_signevent_override_key() {
+        let mut keystore = Keystore::new();
+        keystore
+            .import_mnemonic("oil oil oil oil oil oil oil oil oil oil oil oil", false)
+            .unwrap();
+        let override_signer = keystore.derive_child(1).unwrap();
+        assert!(override_signer.can_sign());
+        let override_pubkey = override_signer.get_public_key();
+        assert_ne!(override_pubkey, keystore.get_public_key().unwrap());
+
+        let created_at = Timestamp::from(1686693500);
+        let kind = nostr::Kind::TextNote;
+        let content = "Hello from alt";
+        let id = EventId::new(&override_pubkey, created_at, &kind, &[], content);
+        let req = Request::SignEvent(UnsignedEvent {
+            id,
+            pubkey: override_pubkey,
+            created_at,
+            kind,
             tags: vec![],
-            content: "Hello, World!".to_string(),
-        };
-        let req: Request = Request::SignEvent(unisgned_event);
-        let req_id: String = "id001".to_string();
-        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
-        let key_signer: KeySigner = KeySigner {
-            keys: Keys::new(sk),
+            content: content.to_string(),
+        });
+        let resp_msg = response_for_message(
+            &"id001".to_string(),
+            &req,
+            &override_signer,
+            &SignerSettings::default(),
+        )
+        .unwrap()
+        .unwrap();
+        match resp_msg {
+            nostr::nips::nip46::Message::Response { result, .. } => {
+                let sig = Signature::from_str(result.unwrap().as_str().unwrap()).unwrap();
+                let msg = nostr::secp256k1::Message::from_slice(id.as_bytes()).unwrap();
+                assert!(SECP256K1
+                    .verify_schnorr(&sig, &msg, &override_pubkey)
+                    .is_ok());
+            }
+            _ => panic!("Wrong response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_approved_response_override_key_rebuilds_event() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let requested = test_unsigned_event_with_id("Hello from alt", None);
+        conn.add_request(
+            Message::request(Request::SignEvent(requested.clone())),
+            sender,
+        );
+        let override_signer = KeySigner {
+            keys: Keys::generate(),
         };
-        let resp_msg = response_for_message(&req_id, &req, &key_signer).unwrap();
-        assert!(resp_msg.is_some());
-        // Cannot compare json, as signature changes
-        assert_eq!(resp_msg.as_ref().unwrap().is_request(), false);
-        match resp_msg.unwrap() {
-            nostr::nips::nip46::Message::Response { id, result, error } => {
-                assert_eq!(id, "id001");
-                assert!(error.is_none());
-                assert!(result.is_some());
-                match result.as_ref().unwrap() {
-                    serde_json::Value::String(s) => {
-                        // sig changes, cannot compare to constant, check only length
-                        assert_eq!(s.len(), 128);
-                    }
-                    _ => {
-                        panic!("Wrong result value")
-                    }
-                }
+        let override_pubkey = override_signer.get_public_key();
+        assert_ne!(override_pubkey, requested.pubkey);
+
+        let req = conn.take_first_request().unwrap();
+        match conn
+            .approved_response(&req, &override_signer, None)
+            .unwrap()
+        {
+            Some(Message::Response {
+                result: Some(result),
+                error: None,
+                ..
+            }) => {
+                let event: nostr::Event = serde_json::from_value(result).unwrap();
+                assert!(event.verify().is_ok());
+                assert_eq!(event.pubkey, override_pubkey);
+                assert_ne!(event.id, requested.id);
+                assert_eq!(event.content, requested.content);
+                assert_eq!(event.created_at, requested.created_at);
             }
-            _ => {
-                panic!("Wrong response")
+            _ => panic!("Expected signed event response"),
+        }
+
+        // with the connection key only the signature is returned
+        conn.add_request(
+            Message::request(Request::SignEvent(requested.clone())),
+            sender,
+        );
+        let req = conn.take_first_request().unwrap();
+        match conn
+            .approved_response(&req, &conn.key_signer(), None)
+            .unwrap()
+        {
+            Some(Message::Response {
+                result: Some(result),
+                ..
+            }) => {
+                let sig = Signature::from_str(result.as_str().unwrap()).unwrap();
+                let msg = nostr::secp256k1::Message::from_slice(requested.id.as_bytes()).unwrap();
+                assert!(SECP256K1
+                    .verify_schnorr(&sig, &msg, &requested.pubkey)
+                    .is_ok());
             }
+            _ => panic!("Expected signature response"),
         }
     }
 
-    #[test]
-    fn test_response_for_message_delegate() {
-        let delegatee_pubkey = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
-        let mut conditions: Conditions = Conditions::default();
-        conditions.add(Condition::Kind(1));
-        conditions.add(Condition::CreatedBefore(1686693500));
-        let req: Request = Request::Delegate {
-            public_key: delegatee_pubkey,
-            conditions,
-        };
-        let req_id: String = "id001".to_string();
-        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
-        let key_signer: KeySigner = KeySigner {
-            keys: Keys::new(sk),
+    /// Connection on the mock relay, connected, with the handler loop started, and subscribed
+    async fn mock_relay_connection(
+        relay: &MockRelay,
+        app_id_keys: &Keys,
+        settings: &SignerSettings,
+    ) -> Arc<SignerConnection> {
+        let key_signer = KeySigner {
+            keys: Keys::new(SecretKey::from_bech32(NSEC1).unwrap()),
         };
-        let resp_msg = response_for_message(&req_id, &req, &key_signer).unwrap();
-        assert!(resp_msg.is_some());
-        // Cannot compare json, as signature changes
-        assert_eq!(resp_msg.as_ref().unwrap().is_request(), false);
-        match resp_msg.unwrap() {
-            nostr::nips::nip46::Message::Response { id, result, error } => {
-                assert_eq!(id, "id001");
-                assert!(error.is_none());
-                assert!(result.is_some());
-                match result.as_ref().unwrap() {
-                    serde_json::Value::Object(o) => {
-                        assert_eq!(o["cond"].to_string(), "\"kind=1&created_at<1686693500\"");
-                        assert_eq!(
-                            o["from"].to_string(),
-                            "\"dd73f1d141b01badbb4049c5bcaa2cd261501c0c356774fada3db425c7d6e413\""
-                        );
-                        assert_eq!(
-                            o["to"].to_string(),
-                            "\"c1d42647b27d7e76ecdf311c6a2608d4d0ba4c8c774b27e4167c66b4296a92cc\""
-                        );
-                        // sig changes, cannot compare to constant, check only length
-                        let siglen = o["sig"].to_string().len();
-                        assert!(siglen > 128 && siglen < 132);
-                    }
-                    _ => {
-                        panic!("Wrong result value")
-                    }
-                }
-            }
-            _ => {
-                panic!("Wrong response")
+        let conn = Arc::new(SignerConnection::new(
+            relay.url(),
+            XOnlyPublicKey::from_bech32(NPUB2).unwrap(),
+            app_id_keys,
+            &key_signer,
+            settings,
+            StatusMessages::new(),
+        ));
+        conn.relay_client
+            .add_relay(relay.url().as_str(), None)
+            .await
+            .unwrap();
+        conn.relay_client.connect().await;
+        conn.restart_handler_loop(tokio::runtime::Handle::current());
+        assert!(wait_until(RELAY_TIMEOUT, || relay.req_count() >= 1).await);
+        conn
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_relay_auth() {
+        let relay = MockRelay::start().await;
+        let app_id_keys = Keys::generate();
+        let conn = mock_relay_connection(&relay, &app_id_keys, &SignerSettings::default()).await;
+
+        relay.send(RelayMessage::new_auth("challenge-123"));
+        assert!(
+            relay
+                .wait_for_received(RELAY_TIMEOUT, |msgs| msgs.iter().any(|m| matches!(
+                    m,
+                    ClientMessage::Auth(event) if event.verify().is_ok()
+                        && event.pubkey == app_id_keys.public_key()
+                        && event.tags.iter().any(|t| t.as_vec().contains(&"challenge-123".to_string()))
+                )))
+                .await
+        );
+        // subscribed again after authenticating
+        assert!(wait_until(RELAY_TIMEOUT, || relay.req_count() >= 2).await);
+        conn.stop_handler_loop();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_stop_handler_loop_after_subscribing() {
+        let relay = MockRelay::start().await;
+        let conn =
+            mock_relay_connection(&relay, &Keys::generate(), &SignerSettings::default()).await;
+        let handler = conn.handler.lock().unwrap().clone().unwrap();
+        assert!(!handler.is_finished());
+
+        conn.stop_handler_loop();
+        assert!(wait_until(RELAY_TIMEOUT, || handler.is_finished()).await);
+        // unsubscribed from the relay
+        assert!(
+            relay
+                .wait_for_received(RELAY_TIMEOUT, |msgs| msgs
+                    .iter()
+                    .any(|m| matches!(m, ClientMessage::Close(_))))
+                .await
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_relay_auth_failure_continues() {
+        let relay = MockRelay::start().await;
+        // without secret key the AUTH response can't be signed
+        let app_id_keys = Keys::from_public_key(Keys::generate().public_key());
+        let conn = mock_relay_connection(&relay, &app_id_keys, &SignerSettings::default()).await;
+
+        relay.send(RelayMessage::new_auth("challenge-123"));
+        assert!(
+            wait_until(RELAY_TIMEOUT, || conn
+                .status
+                .get_last()
+                .starts_with("Warning: AUTH to relay"))
+            .await
+        );
+        assert!(conn.get_last_error().is_none());
+
+        // still handling relay messages
+        let event_id = EventId::from_hex(EVENTHEX).unwrap();
+        relay.send(RelayMessage::new_ok(event_id, false, "blocked: test"));
+        assert!(
+            wait_until(RELAY_TIMEOUT, || conn
+                .status
+                .get_last()
+                .contains("rejected by relay"))
+            .await
+        );
+        assert!(!relay
+            .received()
+            .iter()
+            .any(|m| matches!(m, ClientMessage::Auth(_))));
+        conn.stop_handler_loop();
+    }
+
+    #[tokio::test]
+    async fn test_approved_response_with_delegation() {
+        let conn = test_connection();
+        let sender = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let signer_pubkey = conn.key_signer().get_public_key();
+        let delegator_keys = Keys::generate();
+        let tag = DelegationTag::new(
+            &delegator_keys,
+            signer_pubkey,
+            Conditions::from_str("kind=1").unwrap(),
+        )
+        .unwrap();
+        let delegation = DelegationInfo::from_tag_str(&tag.to_string()).unwrap();
+        let requested = test_unsigned_event_with_id("A", None);
+        conn.add_request(
+            Message::request(Request::SignEvent(requested.clone())),
+            sender,
+        );
+
+        let req = conn.take_first_request().unwrap();
+        match conn
+            .approved_response(&req, &conn.key_signer(), Some(&delegation))
+            .unwrap()
+        {
+            Some(Message::Response {
+                result: Some(result),
+                error: None,
+                ..
+            }) => {
+                // a signature over the requested id would not verify against the delegated event,
+                // the whole event is returned
+                assert!(result.as_str().is_none());
+                let event: nostr::Event = serde_json::from_value(result).unwrap();
+                assert!(event.verify().is_ok());
+                assert_ne!(event.id, requested.id);
+                assert_eq!(event.pubkey, requested.pubkey);
+                assert_eq!(event.content, requested.content);
+                assert_eq!(event.created_at, requested.created_at);
+                assert!(event.tags.iter().any(|t| matches!(
+                    t,
+                    Tag::Delegation { delegator_pk, .. } if *delegator_pk == delegator_keys.public_key()
+                )));
+                assert_eq!(conn.get_signed_event_ids(), vec![event.id]);
             }
+            _ => panic!("Expected signed event response"),
         }
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_relay_ok_round_trip() {
+        let relay = MockRelay::start().await;
+        let settings = SignerSettings {
+            show_relay_accepted: true,
+            ..Default::default()
+        };
+        let conn = mock_relay_connection(&relay, &Keys::generate(), &settings).await;
+        let msg = error_response("id001", "test");
+
+        relay.set_ok_reply(false, "rate-limited: slow down");
+        conn.send(&msg, &conn.client_pubkey).await.unwrap();
+        assert!(
+            wait_until(RELAY_TIMEOUT, || conn.status.get_last().ends_with(
+                &format!(
+                    "rejected by relay {}/: 'rate-limited: slow down'",
+                    relay.url()
+                )
+            ))
+            .await
+        );
+
+        relay.set_ok_reply(true, "");
+        conn.send(&msg, &conn.client_pubkey).await.unwrap();
+        assert!(
+            wait_until(RELAY_TIMEOUT, || conn
+                .status
+                .get_last()
+                .ends_with(&format!("accepted by relay {}/", relay.url())))
+            .await
+        );
+        conn.stop_handler_loop();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_keepalive_renews_subscription() {
+        let relay = MockRelay::start().await;
+        let settings = SignerSettings {
+            keepalive_secs: 1,
+            ..Default::default()
+        };
+        let conn = mock_relay_connection(&relay, &Keys::generate(), &settings).await;
+        let last_keepalive = *conn.last_keepalive.lock().unwrap();
+        let reqs = relay.req_count();
+
+        assert!(wait_until(RELAY_TIMEOUT, || relay.req_count() > reqs).await);
+        assert!(
+            wait_until(RELAY_TIMEOUT, || *conn.last_keepalive.lock().unwrap()
+                > last_keepalive)
+            .await
+        );
+        conn.stop_handler_loop();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_viewer_shows_relay_events() {
+        let relay = MockRelay::start().await;
+        let app_id_keys = Keys::generate();
+        let conn = mock_relay_connection(&relay, &app_id_keys, &SignerSettings::default()).await;
+        let reqs = relay.req_count();
+        conn.set_viewer_open(true, tokio::runtime::Handle::current());
+        // subscribed again, with the viewer filter
+        assert!(wait_until(RELAY_TIMEOUT, || relay.req_count() > reqs).await);
+
+        let sender_keys = Keys::generate();
+        let other = EventBuilder::new_text_note("Not to us", &[])
+            .to_event(&sender_keys)
+            .unwrap();
+        relay.send_event(other);
+        let to_us =
+            EventBuilder::new_text_note("To us", &[Tag::PubKey(app_id_keys.public_key(), None)])
+                .to_event(&sender_keys)
+                .unwrap();
+        relay.send_event(to_us);
+
+        assert!(
+            wait_until(RELAY_TIMEOUT, || conn
+                .get_viewed_events()
+                .map_or(false, |e| !e.is_empty()))
+            .await
+        );
+        let viewed = conn.get_viewed_events().unwrap();
+        assert_eq!(viewed.len(), 1);
+        assert_eq!(viewed[0].sender, sender_keys.public_key());
+        assert_eq!(viewed[0].kind, nostr::Kind::TextNote);
+        conn.stop_handler_loop();
+    }
 }
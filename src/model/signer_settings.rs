@@ -0,0 +1,366 @@
+use crate::base::error::Error;
+
+use nostr::prelude::{Kind, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Default maximum event content size accepted in a sign request, in bytes
+const DEFAULT_MAX_CONTENT_SIZE: usize = 64 * 1024;
+/// Default maximum number of relay connection attempts in flight at once
+const DEFAULT_MAX_CONCURRENT_CONNECTS: usize = 4;
+/// Default bind address of the health endpoint, local only
+const DEFAULT_HEALTH_BIND_ADDRESS: &str = "127.0.0.1:8089";
+/// Kinds queued for approval by default also from trusted apps: profile metadata and contact list,
+/// as signing them overwrites the previous version
+const DEFAULT_TRUSTED_CONFIRM_KINDS: &[u64] = &[0, 3];
+/// Methods any paired client may call, not subject to granted permissions
+const ALWAYS_GRANTED_METHODS: &[&str] = &["describe", "get_public_key", "connect", "disconnect"];
+
+/// Default handling of sign requests, before asking the user
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ApprovalAction {
+    /// Queue for user approval
+    #[default]
+    Queue,
+    /// Sign without asking
+    AutoApprove,
+    /// Refuse without asking
+    Reject,
+}
+
+/// Hours of the day (local time) when auto-approve applies, e.g. office hours 9-17
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ApproveHours {
+    /// First hour of the window (0-23)
+    pub start: u32,
+    /// Hour the window ends (exclusive, 0-24); smaller than start for a window over midnight
+    pub end: u32,
+}
+
+impl ApproveHours {
+    /// Whether the hour of the day (0-23) is inside the window
+    pub fn contains(&self, hour: u32) -> bool {
+        if self.start <= self.end {
+            hour >= self.start && hour < self.end
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+}
+
+/// A permission granted to a client at pairing: a request method, optionally limited to
+/// one event kind (for sign_event). NIP-46 form: "method" or "method:kind", e.g. "sign_event:1"
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Permission {
+    pub method: String,
+    pub kind: Option<u64>,
+}
+
+/// Permissions granted to a client at pairing; requests not covered are rejected
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Permissions(pub BTreeSet<Permission>);
+
+impl Permission {
+    pub fn new(method: &str, kind: Option<u64>) -> Self {
+        Self {
+            method: method.to_string(),
+            kind,
+        }
+    }
+
+    /// Parse the "method" or "method:kind" form
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let s = s.trim();
+        let invalid = || Error::SignerPermissionInvalid(s.to_string());
+        let (method, kind) = match s.split_once(':') {
+            None => (s, None),
+            Some((method, kind)) => (method, Some(kind.parse::<u64>().map_err(|_e| invalid())?)),
+        };
+        if method.is_empty() || !method.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+            return Err(invalid());
+        }
+        Ok(Self::new(method, kind))
+    }
+
+    /// Whether the permission covers the request method, for the event kind (if a sign request)
+    fn covers(&self, method: &str, kind: Option<Kind>) -> bool {
+        self.method == method
+            && match (self.kind, kind) {
+                (None, _) => true,
+                (Some(k), Some(kind)) => k == kind.as_u64(),
+                (Some(_), None) => false,
+            }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            None => write!(f, "{}", self.method),
+            Some(kind) => write!(f, "{}:{}", self.method, kind),
+        }
+    }
+}
+
+impl Permissions {
+    /// Parse the NIP-46 permissions string, comma-separated, e.g. "sign_event:1,delegate"
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        s.split(',')
+            .filter(|p| !p.trim().is_empty())
+            .map(Permission::parse)
+            .collect::<Result<BTreeSet<_>, _>>()
+            .map(Self)
+    }
+
+    /// Whether a request of the method (for sign requests: of the event kind) is allowed
+    pub fn allows(&self, method: &str, kind: Option<Kind>) -> bool {
+        ALWAYS_GRANTED_METHODS.contains(&method) || self.0.iter().any(|p| p.covers(method, kind))
+    }
+}
+
+impl fmt::Display for Permissions {
+    /// NIP-46 permissions string, comma-separated
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self.0.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+/// Signer-related settings
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SignerSettings {
+    /// Sign requests with larger event content (in bytes) are rejected
+    pub max_content_size: usize,
+    /// Additional relays to send responses to (besides the connection relay),
+    /// to increase the chance the client receives them.
+    /// Note: unreachable relays can delay sending responses.
+    pub response_relays: Vec<String>,
+    /// Maximum number of relay connection attempts in flight at once, the rest are queued;
+    /// 0 for unlimited
+    pub max_concurrent_connects: usize,
+    /// Send an error response for unsupported request methods, instead of ignoring them
+    pub reject_unknown_methods: bool,
+    /// User-assigned display names of paired clients, by client pubkey (hex)
+    pub connection_names: BTreeMap<String, String>,
+    /// Disconnect the signer after this many seconds without messages from the client,
+    /// 0 to never disconnect
+    pub idle_disconnect_secs: u64,
+    /// Accept "sign_raw" requests (extension), signing a client-provided 32-byte hash.
+    /// Powerful: the signed content cannot be checked, so it is off by default.
+    pub allow_sign_raw: bool,
+    /// Only sign events whose author pubkey (npub or hex) is in this list, reject others;
+    /// empty for no restriction
+    pub allowed_authors: Vec<String>,
+    /// Show the relays' OK message also for accepted responses; rejections are always shown
+    pub show_relay_accepted: bool,
+    /// Approving (signing) requests is only possible while the app window is focused,
+    /// so a request can't be approved without being seen
+    pub require_focus_to_approve: bool,
+    /// Renew the subscription on the relays every this many seconds, so idle relays
+    /// don't drop the session; 0 to disable
+    pub keepalive_secs: u64,
+    /// Serve an HTTP health endpoint (`/healthz`), reporting whether the signer is ready:
+    /// key unlocked and relay connected. Read at startup.
+    pub health_endpoint: bool,
+    /// Address (host:port) the health endpoint listens on
+    pub health_bind_address: String,
+    /// Handling of sign requests by event kind (number), for all connections;
+    /// kinds not listed are queued for approval
+    pub kind_policy: BTreeMap<u64, ApprovalAction>,
+    /// Auto-approve by the kind policy only during these hours (local time), queue for approval
+    /// outside them; None for any time
+    pub auto_approve_hours: Option<ApproveHours>,
+    /// Show the entire content of sign requests, not only the truncated preview
+    pub show_full_content: bool,
+    /// Permissions granted at pairing, by client pubkey (hex); clients not listed may use
+    /// all supported methods
+    pub client_permissions: BTreeMap<String, Permissions>,
+    /// Pending requests expire (are rejected) this many seconds after receiving them,
+    /// 0 to never expire
+    pub request_expiry_secs: u64,
+    /// Notify (and flash the window) this many seconds before a pending request expires,
+    /// as a last chance to act; 0 for no notification
+    pub expiry_warning_secs: u64,
+    /// Sign requests of these kinds are queued for approval also from trusted apps
+    /// (auto-approve list); empty to sign all kinds from trusted apps without asking
+    pub trusted_confirm_kinds: BTreeSet<u64>,
+    /// Stealth mode: don't answer "describe" requests automatically, as the answer reveals
+    /// the app as a signer; they are queued for approval instead
+    pub stealth_mode: bool,
+}
+
+impl Default for SignerSettings {
+    fn default() -> Self {
+        Self {
+            max_content_size: DEFAULT_MAX_CONTENT_SIZE,
+            response_relays: Vec::new(),
+            max_concurrent_connects: DEFAULT_MAX_CONCURRENT_CONNECTS,
+            reject_unknown_methods: false,
+            connection_names: BTreeMap::new(),
+            idle_disconnect_secs: 0,
+            allow_sign_raw: false,
+            allowed_authors: Vec::new(),
+            show_relay_accepted: false,
+            require_focus_to_approve: false,
+            keepalive_secs: 0,
+            health_endpoint: false,
+            health_bind_address: DEFAULT_HEALTH_BIND_ADDRESS.to_string(),
+            kind_policy: BTreeMap::new(),
+            auto_approve_hours: None,
+            show_full_content: false,
+            client_permissions: BTreeMap::new(),
+            request_expiry_secs: 0,
+            expiry_warning_secs: 0,
+            trusted_confirm_kinds: DEFAULT_TRUSTED_CONFIRM_KINDS.iter().copied().collect(),
+            stealth_mode: false,
+        }
+    }
+}
+
+impl SignerSettings {
+    /// Permissions granted to the client at pairing, None if not restricted
+    pub fn permissions_for(&self, client_pubkey: &XOnlyPublicKey) -> Option<&Permissions> {
+        self.client_permissions.get(&client_pubkey.to_string())
+    }
+
+    /// Handling of sign requests for events of the kind, from the kind policy
+    pub fn approval_action(&self, kind: Kind) -> ApprovalAction {
+        self.kind_policy
+            .get(&kind.as_u64())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Handling of sign requests for events of the kind at the hour of the day (local time):
+    /// auto-approve outside the auto-approve hours is queued for approval
+    pub fn approval_action_at(&self, kind: Kind, local_hour: u32) -> ApprovalAction {
+        match (self.approval_action(kind), self.auto_approve_hours) {
+            (ApprovalAction::AutoApprove, Some(hours)) if !hours.contains(local_hour) => {
+                ApprovalAction::Queue
+            }
+            (action, _) => action,
+        }
+    }
+
+    /// Whether a sign request for the kind is signed without asking: auto-approved by the kind policy,
+    /// or coming from a trusted app (auto-approve list), unless the kind is to be confirmed or rejected
+    pub fn auto_approves(&self, kind: Kind, trusted: bool, local_hour: u32) -> bool {
+        match self.approval_action_at(kind, local_hour) {
+            ApprovalAction::AutoApprove => true,
+            ApprovalAction::Reject => false,
+            ApprovalAction::Queue => {
+                trusted && !self.trusted_confirm_kinds.contains(&kind.as_u64())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_auto_approves_trusted() {
+        let mut settings = SignerSettings::default();
+        assert!(!settings.auto_approves(Kind::TextNote, false, 12));
+        assert!(settings.auto_approves(Kind::TextNote, true, 12));
+        // sensitive kinds still need confirmation
+        assert!(!settings.auto_approves(Kind::Metadata, true, 12));
+        assert!(!settings.auto_approves(Kind::ContactList, true, 12));
+        settings.trusted_confirm_kinds.clear();
+        assert!(settings.auto_approves(Kind::Metadata, true, 12));
+        // rejected kinds are not signed, also for trusted apps
+        settings.kind_policy.insert(1, ApprovalAction::Reject);
+        assert!(!settings.auto_approves(Kind::TextNote, true, 12));
+        settings.kind_policy.insert(1, ApprovalAction::AutoApprove);
+        assert!(settings.auto_approves(Kind::TextNote, false, 12));
+    }
+
+    #[test]
+    fn test_permissions() {
+        let perms = Permissions::parse("sign_event:1, sign_event:7,delegate,").unwrap();
+        assert_eq!(perms.0.len(), 3);
+        assert!(perms.0.contains(&Permission::new("sign_event", Some(7))));
+        // round-trip
+        assert_eq!(perms.to_string(), "delegate,sign_event:1,sign_event:7");
+        assert_eq!(Permissions::parse(&perms.to_string()).unwrap(), perms);
+
+        assert!(perms.allows("sign_event", Some(Kind::TextNote)));
+        assert!(perms.allows("sign_event", Some(Kind::Reaction)));
+        assert!(!perms.allows("sign_event", Some(Kind::Metadata)));
+        assert!(!perms.allows("sign_event", None));
+        assert!(perms.allows("delegate", None));
+        assert!(!perms.allows("sign_raw", None));
+        // always allowed
+        assert!(perms.allows("describe", None));
+        assert!(perms.allows("get_public_key", None));
+
+        // sign_event without kind covers all kinds
+        let all_kinds = Permissions::parse("sign_event").unwrap();
+        assert!(all_kinds.allows("sign_event", Some(Kind::Metadata)));
+        assert!(!all_kinds.allows("delegate", None));
+
+        // nothing granted, only the basic methods
+        let none = Permissions::parse("").unwrap();
+        assert!(none.0.is_empty());
+        assert!(!none.allows("sign_event", Some(Kind::TextNote)));
+        assert!(none.allows("describe", None));
+
+        assert!(matches!(
+            Permissions::parse("sign_event:note"),
+            Err(Error::SignerPermissionInvalid(s)) if s == "sign_event:note"
+        ));
+        assert!(Permissions::parse(":1").is_err());
+        assert!(Permissions::parse("sign event").is_err());
+    }
+
+    #[test]
+    fn test_approval_action_at() {
+        let office = ApproveHours { start: 9, end: 17 };
+        assert!(!office.contains(8));
+        assert!(office.contains(9));
+        assert!(office.contains(16));
+        assert!(!office.contains(17));
+        let night = ApproveHours { start: 22, end: 6 };
+        assert!(night.contains(23));
+        assert!(night.contains(0));
+        assert!(!night.contains(6));
+        assert!(!night.contains(12));
+
+        let mut settings = SignerSettings {
+            kind_policy: [
+                (7, ApprovalAction::AutoApprove),
+                (5, ApprovalAction::Reject),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let reaction = Kind::from(7);
+        // any time, if no hours are set
+        assert_eq!(
+            settings.approval_action_at(reaction, 3),
+            ApprovalAction::AutoApprove
+        );
+        settings.auto_approve_hours = Some(office);
+        assert_eq!(
+            settings.approval_action_at(reaction, 10),
+            ApprovalAction::AutoApprove
+        );
+        assert_eq!(
+            settings.approval_action_at(reaction, 20),
+            ApprovalAction::Queue
+        );
+        // other actions are not affected
+        assert_eq!(
+            settings.approval_action_at(Kind::from(5), 20),
+            ApprovalAction::Reject
+        );
+        assert_eq!(
+            settings.approval_action_at(Kind::from(1), 10),
+            ApprovalAction::Queue
+        );
+    }
+}
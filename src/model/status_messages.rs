@@ -1,40 +1,128 @@
-use crate::base::error::Error;
+use crate::base::error::{Error, ErrorDetail};
+use crate::model::activity_log::{ActivityLog, Entry};
 use crate::model::keystr_model::{Event, EVENT_QUEUE};
 
+use serde_json::json;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const STATUS_MAX_LINES: usize = 10;
+/// Characters allowed in a bech32 string, after the prefix
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const NSEC_PREFIX: &str = "nsec1";
+/// Length of a secret key in hex
+const HEX_KEY_LEN: usize = 64;
+
+/// If set, status lines are printed as JSON objects (for scripting), otherwise human-readable.
+/// Diagnostic output goes to stderr, so stdout has only the status lines.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_json_output(json: bool) {
+    JSON_OUTPUT.store(json, Ordering::Relaxed);
+}
+
+/// Destination of mirrored status lines (stderr, or other for testing)
+pub(crate) type StatusSink = Box<dyn Write + Send + Sync>;
+
+/// Severity of a status line
+#[derive(Clone, Copy)]
+enum StatusLevel {
+    Info,
+    Error,
+}
 
 #[derive(Clone)]
 pub(crate) struct StatusMessages {
     status_lines: Arc<RwLock<Vec<String>>>,
+    /// Details of the last error
+    last_error: Arc<RwLock<Option<ErrorDetail>>>,
+    /// All lines, for the log view; optionally also persisted to disk
+    activity_log: Arc<RwLock<ActivityLog>>,
+    /// If set, lines are also mirrored here, with timestamp and severity
+    mirror: Arc<RwLock<Option<StatusSink>>>,
 }
 
 impl StatusMessages {
     pub fn new() -> Self {
         Self {
             status_lines: Arc::new(RwLock::new(Vec::new())),
+            last_error: Arc::new(RwLock::new(None)),
+            activity_log: Arc::new(RwLock::new(ActivityLog::new())),
+            mirror: Arc::new(RwLock::new(None)),
         }
     }
 
+    /// Enable (or disable, with None) persisting status lines to the log file in the given folder
+    pub fn set_activity_log_dir(&self, dir: Option<&Path>) {
+        self.activity_log.write().unwrap().set_persist_dir(dir);
+    }
+
+    /// Number of activity log entries available for the log view
+    pub fn activity_log_len(&self) -> usize {
+        self.activity_log.read().unwrap().len()
+    }
+
+    /// A page of the activity log, newest first
+    pub fn activity_log_page(&self, offset: usize, limit: usize) -> Vec<Entry> {
+        self.activity_log
+            .read()
+            .unwrap()
+            .page(offset, limit)
+            .to_vec()
+    }
+
+    /// Enable (or disable, with None) mirroring status lines to a sink, e.g. stderr
+    pub fn set_mirror(&self, sink: Option<StatusSink>) {
+        *self.mirror.write().unwrap() = sink;
+    }
+
     pub fn set(&self, s: &str) {
+        self.add_line(StatusLevel::Info, s);
+    }
+
+    fn add_line(&self, level: StatusLevel, s: &str) {
         let mut lines = self.status_lines.write().unwrap();
         if lines.len() > STATUS_MAX_LINES {
             lines.remove(0);
         }
         lines.push(s.to_string());
         // also print on stdout
-        println!("| {}", s);
-        // also send UI notification
-        let _ = EVENT_QUEUE.push(Event::StatusUpdate);
+        println!(
+            "{}",
+            format_output_line(JSON_OUTPUT.load(Ordering::Relaxed), level, s)
+        );
+        // also log (and persist); don't report failure through status, would recurse
+        if let Err(e) = self.activity_log.write().unwrap().add(s) {
+            eprintln!("WARNING: Activity log write failed, {}", e);
+        }
+        // also mirror; don't report failure through status, would recurse
+        if let Some(sink) = self.mirror.write().unwrap().as_mut() {
+            if let Err(e) = writeln!(sink, "{}", format_mirror_line(level, s)) {
+                eprintln!("WARNING: Status mirror write failed, {}", e);
+            }
+        }
+        // also send UI notification; don't report through status, would recurse
+        if let Err(e) = EVENT_QUEUE.notify(Event::StatusUpdate) {
+            eprintln!("WARNING: Status UI notification failed, {}", e);
+        }
     }
 
     pub fn set_error(&mut self, es: &str) {
-        self.set(&format!("Error: {}!", es.to_string()));
+        *self.last_error.write().unwrap() = Some(ErrorDetail::from_message(es));
+        self.add_line(StatusLevel::Error, &format!("Error: {}!", es));
     }
 
     pub fn set_error_err(&mut self, e: &Error) {
         self.set_error(&e.to_string());
+        // store details with source chain
+        *self.last_error.write().unwrap() = Some(ErrorDetail::from_app_error(e));
+    }
+
+    pub fn get_last_error(&self) -> Option<ErrorDetail> {
+        self.last_error.read().unwrap().clone()
     }
 
     pub fn get_last(&self) -> String {
@@ -50,3 +138,194 @@ impl StatusMessages {
         }
     }
 }
+
+impl StatusLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StatusLevel::Info => "info",
+            StatusLevel::Error => "error",
+        }
+    }
+}
+
+/// Mask anything looking like a secret key (nsec, or 64 hex digits), it should never be output.
+/// Note: hex event ids and public keys are masked too, they can't be told apart.
+pub(crate) fn redact_secrets(s: &str) -> String {
+    redact_hex_keys(&redact_nsecs(s))
+}
+
+fn redact_nsecs(s: &str) -> String {
+    let mut res = String::new();
+    let mut rest = s;
+    while let Some(pos) = rest.find(NSEC_PREFIX) {
+        let after = &rest[pos + NSEC_PREFIX.len()..];
+        let key_len = after
+            .find(|c| !BECH32_CHARSET.contains(c))
+            .unwrap_or(after.len());
+        res.push_str(&rest[..pos]);
+        res.push_str(NSEC_PREFIX);
+        res.push_str("***");
+        rest = &after[key_len..];
+    }
+    res.push_str(rest);
+    res
+}
+
+/// Mask standalone runs of exactly 64 hex digits
+fn redact_hex_keys(s: &str) -> String {
+    let mut res = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric()) {
+        let word_len = rest[start..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len() - start);
+        let word = &rest[start..start + word_len];
+        res.push_str(&rest[..start]);
+        if word.len() == HEX_KEY_LEN && word.chars().all(|c| c.is_ascii_hexdigit()) {
+            res.push_str("***");
+        } else {
+            res.push_str(word);
+        }
+        rest = &rest[start + word_len..];
+    }
+    res.push_str(rest);
+    res
+}
+
+/// Put together a status line for stdout, human-readable or JSON
+fn format_output_line(json: bool, level: StatusLevel, message: &str) -> String {
+    let message = redact_secrets(message);
+    if json {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        json!({
+            "time": time,
+            "level": level.as_str(),
+            "message": message,
+        })
+        .to_string()
+    } else {
+        format!("| {}", message)
+    }
+}
+
+/// Put together a mirrored status line: timestamp, severity, message (secrets redacted)
+fn format_mirror_line(level: StatusLevel, message: &str) -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    format!("{} [{}] {}", time, level.as_str(), redact_secrets(message))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nostr::prelude::Url;
+
+    #[test]
+    fn test_format_output_line_json() {
+        let line = format_output_line(true, StatusLevel::Info, "Signer connected");
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["level"], "info");
+        assert_eq!(parsed["message"], "Signer connected");
+        assert!(parsed["time"].as_u64().unwrap() > 0);
+
+        let line = format_output_line(true, StatusLevel::Error, "Error: failed!");
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["level"], "error");
+
+        assert_eq!(
+            format_output_line(false, StatusLevel::Info, "Signer connected"),
+            "| Signer connected"
+        );
+    }
+
+    #[test]
+    fn test_format_output_line_no_secret() {
+        let secret = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        let message = format!("Imported {}, done", secret);
+        for json in [false, true] {
+            let line = format_output_line(json, StatusLevel::Info, &message);
+            assert!(!line.contains(secret));
+            assert!(line.contains("nsec1***, done"));
+        }
+    }
+
+    /// Sink writing into a shared buffer
+    struct BufferSink(Arc<RwLock<Vec<u8>>>);
+
+    impl Write for BufferSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mirror() {
+        let buffer = Arc::new(RwLock::new(Vec::new()));
+        let mut status = StatusMessages::new();
+        status.set("Not mirrored");
+        status.set_mirror(Some(Box::new(BufferSink(buffer.clone()))));
+        status.set("Signer connected");
+        status
+            .set_error("Imported nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae");
+        status.set_mirror(None);
+        status.set("Not mirrored either");
+
+        let output = String::from_utf8(buffer.read().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" [info] Signer connected"));
+        assert!(lines[1].ends_with(" [error] Error: Imported nsec1***!"));
+        assert!(lines[0].split(' ').next().unwrap().parse::<u64>().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_redact_secrets_hex() {
+        let secret = "b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17";
+        assert_eq!(
+            redact_secrets(&format!("Imported {}, done", secret)),
+            "Imported ***, done"
+        );
+        assert_eq!(redact_secrets(&format!("key:{}!", secret)), "key:***!");
+        // shorter or longer hex, and other words, are kept
+        let short = &secret[..12];
+        assert_eq!(redact_secrets(short), short);
+        let long = format!("{}00", secret);
+        assert_eq!(redact_secrets(&long), long);
+        assert_eq!(
+            redact_secrets("Signer connected (relay: wss://relay.example.com)"),
+            "Signer connected (relay: wss://relay.example.com)"
+        );
+    }
+
+    #[test]
+    fn test_last_error_detail() {
+        let mut status = StatusMessages::new();
+        assert!(status.get_last_error().is_none());
+
+        status.set_error("Simple error");
+        let detail = status.get_last_error().unwrap();
+        assert_eq!(detail.message, "Simple error");
+        assert!(detail.sources.is_empty());
+
+        let url_err = Url::parse("not a url").err().unwrap();
+        let err = Error::RelayClientError(nostr_sdk::client::Error::Url(url_err));
+        status.set_error_err(&err);
+        let detail = status.get_last_error().unwrap();
+        assert_eq!(detail.message, err.to_string());
+        assert_eq!(detail.sources, vec![url_err.to_string()]);
+        assert_eq!(
+            detail.to_text(),
+            format!("{} | caused by: {}", err, url_err)
+        );
+    }
+}
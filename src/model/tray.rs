@@ -0,0 +1,261 @@
+use crate::base::error::Error;
+use crate::model::status_messages::StatusMessages;
+
+#[cfg(all(feature = "tray", target_os = "linux"))]
+use crate::model::keystr_model::{Event, EVENT_QUEUE};
+#[cfg(all(feature = "tray", target_os = "linux"))]
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Quick actions of the tray icon, passed to the UI through the event queue
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrayAction {
+    /// Show (restore) the app window
+    Open,
+    /// Process the first pending request
+    ApproveNext,
+}
+
+/// An item of the tray menu
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(not(all(feature = "tray", target_os = "linux")), allow(dead_code))]
+pub(crate) struct TrayMenuItem {
+    pub label: String,
+    pub action: TrayAction,
+    pub enabled: bool,
+}
+
+/// What the tray icon shows: the pending request count, and the quick actions
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TrayState {
+    pub title: String,
+    pub pending: usize,
+    pub menu: Vec<TrayMenuItem>,
+}
+
+#[cfg_attr(not(all(feature = "tray", target_os = "linux")), allow(dead_code))]
+impl TrayState {
+    pub fn new(title: &str, pending: usize) -> Self {
+        let approve_label = match pending {
+            0 => "Approve next".to_string(),
+            n => format!("Approve next ({} pending)", n),
+        };
+        Self {
+            title: title.to_string(),
+            pending,
+            menu: vec![
+                TrayMenuItem {
+                    label: "Open".to_string(),
+                    action: TrayAction::Open,
+                    enabled: true,
+                },
+                TrayMenuItem {
+                    label: approve_label,
+                    action: TrayAction::ApproveNext,
+                    enabled: pending > 0,
+                },
+            ],
+        }
+    }
+
+    /// Tooltip text, with the pending request count
+    pub fn tooltip(&self) -> String {
+        match self.pending {
+            0 => "No pending requests".to_string(),
+            1 => "1 pending request".to_string(),
+            n => format!("{} pending requests", n),
+        }
+    }
+
+    /// Pending requests need the attention of the user
+    pub fn needs_attention(&self) -> bool {
+        self.pending > 0
+    }
+}
+
+/// System tray icon, shown while the tray mode is on.
+/// Supported on Linux (StatusNotifierItem), with the 'tray' feature.
+#[derive(Default)]
+pub(crate) struct Tray {
+    /// Handle of the tray service, set once it is running
+    #[cfg(all(feature = "tray", target_os = "linux"))]
+    handle: Arc<Mutex<Option<ksni::Handle<TrayIcon>>>>,
+    /// State shown last
+    state: Option<TrayState>,
+}
+
+#[cfg(all(feature = "tray", target_os = "linux"))]
+impl Tray {
+    /// Start the tray icon in the background; must be called within the Tokio runtime.
+    /// Errors of the start are reported to the status.
+    pub fn start(&mut self, state: TrayState, mut status: StatusMessages) -> Result<(), Error> {
+        use ksni::TrayMethods;
+
+        if self.handle().is_some() {
+            return Ok(());
+        }
+        let handle = self.handle.clone();
+        let icon = TrayIcon {
+            state: state.clone(),
+        };
+        tokio::spawn(async move {
+            match icon.spawn().await {
+                Err(e) => status.set_error(&format!("Could not start the tray icon: {}", e)),
+                Ok(h) => {
+                    *lock_handle(&handle) = Some(h);
+                    status.set("Tray icon shown");
+                }
+            }
+        });
+        self.state = Some(state);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(h) = lock_handle(&self.handle).take() {
+            let _awaiter = h.shutdown();
+        }
+        self.state = None;
+    }
+
+    /// Whether the tray icon is shown; if not, the window should not be hidden
+    pub fn is_running(&self) -> bool {
+        matches!(self.handle(), Some(h) if !h.is_closed())
+    }
+
+    /// Show the new state, if changed
+    pub fn update(&mut self, state: TrayState) {
+        if self.state.as_ref() == Some(&state) {
+            return;
+        }
+        if let Some(h) = self.handle() {
+            let new_state = state.clone();
+            tokio::spawn(async move {
+                let _res = h.update(|icon| icon.state = new_state).await;
+            });
+            self.state = Some(state);
+        }
+    }
+
+    fn handle(&self) -> Option<ksni::Handle<TrayIcon>> {
+        lock_handle(&self.handle).clone()
+    }
+}
+
+#[cfg(not(all(feature = "tray", target_os = "linux")))]
+impl Tray {
+    pub fn start(&mut self, _state: TrayState, _status: StatusMessages) -> Result<(), Error> {
+        Err(Error::TrayNotAvailable)
+    }
+
+    pub fn stop(&mut self) {
+        self.state = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        false
+    }
+
+    pub fn update(&mut self, _state: TrayState) {}
+}
+
+#[cfg(all(feature = "tray", target_os = "linux"))]
+fn lock_handle<T>(handle: &Mutex<T>) -> MutexGuard<'_, T> {
+    match handle.lock() {
+        Ok(l) => l,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// The tray service item
+#[cfg(all(feature = "tray", target_os = "linux"))]
+struct TrayIcon {
+    state: TrayState,
+}
+
+#[cfg(all(feature = "tray", target_os = "linux"))]
+impl ksni::Tray for TrayIcon {
+    fn id(&self) -> String {
+        env!("CARGO_PKG_NAME").into()
+    }
+
+    fn title(&self) -> String {
+        self.state.title.clone()
+    }
+
+    fn icon_name(&self) -> String {
+        "dialog-password".into()
+    }
+
+    fn status(&self) -> ksni::Status {
+        if self.state.needs_attention() {
+            ksni::Status::NeedsAttention
+        } else {
+            ksni::Status::Active
+        }
+    }
+
+    fn tool_tip(&self) -> ksni::ToolTip {
+        ksni::ToolTip {
+            title: self.state.title.clone(),
+            description: self.state.tooltip(),
+            ..Default::default()
+        }
+    }
+
+    /// Clicking the icon restores the window
+    fn activate(&mut self, _x: i32, _y: i32) {
+        send_action(TrayAction::Open);
+    }
+
+    fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+        self.state
+            .menu
+            .iter()
+            .map(|item| {
+                let action = item.action;
+                ksni::menu::StandardItem {
+                    label: item.label.clone(),
+                    enabled: item.enabled,
+                    activate: Box::new(move |_| send_action(action)),
+                    ..Default::default()
+                }
+                .into()
+            })
+            .collect()
+    }
+}
+
+/// Pass a tray action to the UI
+#[cfg(all(feature = "tray", target_os = "linux"))]
+fn send_action(action: TrayAction) {
+    let _res = EVENT_QUEUE.notify(Event::TrayAction(action));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tray_state() {
+        let idle = TrayState::new("Keystr", 0);
+        assert_eq!(idle.tooltip(), "No pending requests");
+        assert!(!idle.needs_attention());
+        assert_eq!(idle.menu.len(), 2);
+        assert_eq!(idle.menu[0].action, TrayAction::Open);
+        assert!(idle.menu[0].enabled);
+        assert_eq!(idle.menu[1].action, TrayAction::ApproveNext);
+        assert_eq!(idle.menu[1].label, "Approve next");
+        assert!(!idle.menu[1].enabled);
+
+        let one = TrayState::new("Keystr — 1 pending", 1);
+        assert_eq!(one.title, "Keystr — 1 pending");
+        assert_eq!(one.tooltip(), "1 pending request");
+
+        let busy = TrayState::new("Keystr", 3);
+        assert_eq!(busy.tooltip(), "3 pending requests");
+        assert!(busy.needs_attention());
+        assert_eq!(busy.menu[1].label, "Approve next (3 pending)");
+        assert!(busy.menu[1].enabled);
+        assert_ne!(busy, idle);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::base::error::Error;
+use crate::model::status_messages::StatusMessages;
+
+use nostr::prelude::{Keys, ToBech32};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Characters allowed in a bech32 string (after the 'npub1' part)
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const NPUB_HRP_PREFIX: &str = "npub1";
+
+/// Outcome of a vanity key search: found key, or canceled; with the number of keys tried
+pub(crate) enum VanityOutcome {
+    Found(Keys, u64),
+    Canceled(u64),
+}
+
+/// A vanity key search running in a background thread, can be canceled
+pub(crate) struct VanitySearch {
+    cancel: Arc<AtomicBool>,
+    handle: JoinHandle<VanityOutcome>,
+}
+
+/// Model for vanity key generation: keys with npub starting with a chosen prefix
+pub(crate) struct Vanity {
+    pub prefix_input: String,
+    search: Option<VanitySearch>,
+    found: Option<Keys>,
+}
+
+/// Generate random keys until the npub starts with the prefix, or the cancel flag is set.
+/// The flag is checked before each try.
+fn search(prefix: &str, cancel: &AtomicBool) -> VanityOutcome {
+    let full_prefix = format!("{}{}", NPUB_HRP_PREFIX, prefix);
+    let mut tries = 0;
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return VanityOutcome::Canceled(tries);
+        }
+        let keys = Keys::generate();
+        tries += 1;
+        if let Ok(npub) = keys.public_key().to_bech32() {
+            if npub.starts_with(&full_prefix) {
+                return VanityOutcome::Found(keys, tries);
+            }
+        }
+    }
+}
+
+impl VanitySearch {
+    /// Start search in a background thread
+    pub fn start(prefix: &str, status: StatusMessages) -> Result<Self, Error> {
+        if prefix.is_empty() || !prefix.chars().all(|c| BECH32_CHARSET.contains(c)) {
+            return Err(Error::KeyInvalidVanityPrefix);
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancel_clone = cancel.clone();
+        let prefix = prefix.to_string();
+        let handle = std::thread::spawn(move || {
+            let outcome = search(&prefix, &cancel_clone);
+            if let VanityOutcome::Found(_, tries) = &outcome {
+                status.set(&format!("Vanity key found after {} tries", tries));
+            }
+            outcome
+        });
+        Ok(Self { cancel, handle })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Cancel the search (if still running) and wait for the thread to exit
+    pub fn cancel_and_join(self) -> VanityOutcome {
+        self.cancel.store(true, Ordering::Relaxed);
+        match self.handle.join() {
+            Ok(outcome) => outcome,
+            Err(_) => VanityOutcome::Canceled(0),
+        }
+    }
+}
+
+impl Vanity {
+    pub fn new() -> Self {
+        Self {
+            prefix_input: String::new(),
+            search: None,
+            found: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.search.as_ref().map_or(false, |s| !s.is_finished())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.search.as_ref().map_or(false, |s| s.is_finished())
+    }
+
+    pub fn start_action(&mut self, status: &mut StatusMessages) {
+        if self.search.is_some() {
+            status.set_error("Vanity search is already running");
+            return;
+        }
+        match VanitySearch::start(&self.prefix_input, status.clone()) {
+            Err(e) => status.set_error_err(&e),
+            Ok(s) => {
+                self.found = None;
+                self.search = Some(s);
+                status.set(&format!(
+                    "Vanity search started for prefix '{}'",
+                    self.prefix_input
+                ));
+            }
+        }
+    }
+
+    /// Stop the search: cancel it if still running, keep the found key (if any)
+    pub fn stop_action(&mut self, status: &mut StatusMessages) {
+        if let Some(s) = self.search.take() {
+            match s.cancel_and_join() {
+                VanityOutcome::Found(keys, _tries) => self.found = Some(keys),
+                VanityOutcome::Canceled(tries) => status.set(&format!(
+                    "Vanity search canceled after {} tries, no key found",
+                    tries
+                )),
+            }
+        }
+    }
+
+    pub fn has_found(&self) -> bool {
+        self.found.is_some()
+    }
+
+    pub fn take_found(&mut self) -> Option<Keys> {
+        self.found.take()
+    }
+}
+
+impl Drop for Vanity {
+    /// Don't leave a search thread running
+    fn drop(&mut self) {
+        if let Some(s) = self.search.take() {
+            let _outcome = s.cancel_and_join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_search_canceled_before_start() {
+        let cancel = AtomicBool::new(true);
+        match search("qqqqqqqqqqqq", &cancel) {
+            VanityOutcome::Canceled(tries) => assert_eq!(tries, 0),
+            VanityOutcome::Found(..) => panic!("Should be canceled"),
+        }
+    }
+
+    #[test]
+    fn test_search_found() {
+        let cancel = AtomicBool::new(false);
+        match search("q", &cancel) {
+            VanityOutcome::Found(keys, tries) => {
+                assert!(tries >= 1);
+                assert!(keys.public_key().to_bech32().unwrap().starts_with("npub1q"));
+            }
+            VanityOutcome::Canceled(_) => panic!("Should be found"),
+        }
+    }
+
+    #[test]
+    fn test_vanity_search_cancel() {
+        // practically never found
+        let s = VanitySearch::start("qqqqqqqqqqqq", StatusMessages::new()).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!s.is_finished());
+        match s.cancel_and_join() {
+            VanityOutcome::Canceled(tries) => assert!(tries >= 1),
+            VanityOutcome::Found(..) => panic!("Should be canceled"),
+        }
+    }
+
+    #[test]
+    fn test_vanity_search_invalid_prefix() {
+        assert!(VanitySearch::start("", StatusMessages::new()).is_err());
+        // 'b' is not in the bech32 charset
+        assert!(VanitySearch::start("abc", StatusMessages::new()).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+/// Steps of the first-run wizard, in order
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum WizardStep {
+    /// Generate or import a key
+    Key,
+    /// Set password for saving the secret key
+    Password,
+    /// Optionally add relays for signer responses
+    Relays,
+}
+
+/// Model for the first-run wizard, guiding a new user through the basic setup
+pub(crate) struct Wizard {
+    step: Option<WizardStep>,
+    /// Input for additional signer response relays, comma-separated
+    pub relays_input: String,
+}
+
+impl Wizard {
+    /// Wizard is active only if first run has not been completed yet
+    pub fn new(first_run_completed: bool) -> Self {
+        Self {
+            step: if first_run_completed {
+                None
+            } else {
+                Some(WizardStep::Key)
+            },
+            relays_input: String::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn is_active(&self) -> bool {
+        self.step.is_some()
+    }
+
+    pub fn step(&self) -> Option<WizardStep> {
+        self.step
+    }
+
+    /// Advance to the next step, returns true if the wizard has been completed
+    pub fn next(&mut self) -> bool {
+        self.step = match self.step {
+            Some(WizardStep::Key) => Some(WizardStep::Password),
+            Some(WizardStep::Password) => Some(WizardStep::Relays),
+            Some(WizardStep::Relays) | None => None,
+        };
+        self.step.is_none()
+    }
+
+    pub fn skip(&mut self) {
+        self.step = None;
+    }
+
+    /// Relays entered, parsed from the comma-separated input
+    pub fn get_relays(&self) -> Vec<String> {
+        self.relays_input
+            .split(',')
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_wizard_steps() {
+        let mut w = Wizard::new(false);
+        assert_eq!(w.step(), Some(WizardStep::Key));
+        assert!(!w.next());
+        assert_eq!(w.step(), Some(WizardStep::Password));
+        assert!(!w.next());
+        assert_eq!(w.step(), Some(WizardStep::Relays));
+        assert!(w.next());
+        assert!(!w.is_active());
+
+        assert!(!Wizard::new(true).is_active());
+    }
+
+    #[test]
+    fn test_wizard_get_relays() {
+        let mut w = Wizard::new(false);
+        assert!(w.get_relays().is_empty());
+        w.relays_input = " wss://relay1.example.com, ,wss://relay2.example.com ".to_string();
+        assert_eq!(
+            w.get_relays(),
+            vec![
+                "wss://relay1.example.com".to_string(),
+                "wss://relay2.example.com".to_string()
+            ]
+        );
+    }
+}
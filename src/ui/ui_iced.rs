@@ -1,12 +1,32 @@
-use crate::model::keystr_model::{Action, Confirmation, Event, KeystrModel, Modal, EVENT_QUEUE};
-use crate::model::security_settings::{SecurityLevel, SECURITY_LEVELS};
-use crate::model::signer::ConnectionStatus;
+use crate::base::build_info::{build_info, format_nips};
+use crate::base::encrypt::{EncryptionAlgorithm, ENCRYPTION_ALGORITHMS};
+use crate::base::storage::Storage;
+use crate::model::display_settings::{kind_label, DisplayFormat, DISPLAY_FORMATS};
+use crate::model::keystr_model::{Action, Event, KeystrModel, Modal, EVENT_QUEUE};
+use crate::model::new_identity::NewIdentityStep;
+use crate::model::security_settings::{PersistStatus, SecurityLevel, SECURITY_LEVELS};
+use crate::model::signer::{supported_methods, ConnectionStatus, SignerConnection};
+use crate::model::tray::TrayAction;
+use crate::model::wizard::WizardStep;
 use crate::ui::dialog::Dialog;
 
 use iced::widget::qr_code::QRCode;
-use iced::widget::{button, column, container, pick_list, row, text, text_input};
-use iced::{executor, subscription};
-use iced::{Alignment, Application, Command, Element, Length, Subscription, Theme};
+use iced::widget::{
+    button, checkbox, column, container, pick_list, row, scrollable, text, text_input,
+};
+use iced::{executor, subscription, window};
+use iced::{Alignment, Application, Color, Command, Element, Length, Subscription, Theme};
+use nostr::prelude::{ToBech32, XOnlyPublicKey};
+use std::time::Duration;
+
+/// Interval of the periodic model status checks
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Height of the scrollable area showing the full content of a request, large content is scrolled
+const FULL_CONTENT_HEIGHT: f32 = 200.0;
+/// Number of activity log entries shown initially, and added by "Load more"
+const ACTIVITY_LOG_PAGE_SIZE: usize = 20;
+/// Height of the scrollable activity log view
+const ACTIVITY_LOG_HEIGHT: f32 = 200.0;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Tab {
@@ -18,23 +38,62 @@ pub enum Tab {
 #[derive(Debug, Clone)]
 pub(crate) enum Message {
     ChangedReadonly(String),
+    CopyToClipboard(String),
+    KeysNip05Input(String),
+    SignerDisplayNameInput(String),
+    KeysRelayListInput(String),
+    SettingsRelaysImportInput(String),
     ModelAction(Action),
     ModelEvent(Event),
     NoOp,
     Refresh,
+    /// Periodic tick for the model's status checks
+    StatusPoll,
     SecurityLevelChange(SecurityLevel),
+    AutoSaveToggle(bool),
+    EncryptionAlgorithmChange(EncryptionAlgorithm),
+    ActivityLogToggle(bool),
+    StatusMirrorToggle(bool),
+    TrayModeToggle(bool),
+    SignerAllowSignRawToggle(bool),
+    SignerStealthModeToggle(bool),
+    SignerRequireFocusToggle(bool),
+    SignerShowFullContentSettingToggle(bool),
+    /// Require the password for each signature with the current key
+    SignerFreshUnlockToggle(bool),
+    /// Show the full content for the current request only
+    SignerShowFullContentToggle(bool),
+    /// App window gained (true) or lost (false) the focus
+    WindowFocusChanged(bool),
+    /// The window close button was pressed: hide to the tray if it is shown, exit otherwise
+    WindowCloseRequested,
+    DisplayFormatChange(DisplayFormat),
     TabSelect(Tab),
+    ToggleErrorDetail,
+    ToggleActivityLog,
+    ActivityLogLoadMore,
 
     KeysPubkeyInput(String),
     KeysToggleHideSecretKey,
     KeysSecretkeyInput(String),
     KeysMnemonicInput(String),
+    KeysClientExportInput(String),
+    KeysClientExportPasswordInput(String),
     KeysDecryptPasswordInput(String),
+    KeysVanityPrefixInput(String),
+    KeysNoteContentInput(String),
+    KeysNoteCreatedAtInput(String),
+    KeysNoteRecipientInput(String),
+    WizardRelaysInput(String),
+    NewIdentityBackupToggle(bool),
     KeysSavePasswordInput(String),
     KeysSaveRepeatPasswordInput(String),
+    KeysVerifyPasswordInput(String),
+    KeysOldPasswordInput(String),
 
     DelegateDeeChanged(String),
     DelegateKindChanged(String),
+    DelegationImportInput(String),
     DelegateTimeStartChanged(String),
     DelegateTimeEndChanged(String),
     DelegateTimeDaysChanged(String),
@@ -42,12 +101,23 @@ pub(crate) enum Message {
 
     QRCode(String),
     QRCodeClose,
+    QrPngPathInput(String),
     SignerUriInput(String),
+    SignerChildAccountInput(String),
+    SignerGrantRestrictToggle(bool),
+    SignerGrantSignEventToggle(bool),
+    SignerGrantKindsInput(String),
+    SignerGrantDelegateToggle(bool),
+    SignerGrantSignRawToggle(bool),
+    SignerSignOverrideAccountInput(String),
 }
 
 pub(crate) struct KeystrApp {
     pub model: KeystrModel,
     current_tab: Tab,
+    show_error_detail: bool,
+    /// Number of activity log entries shown (newest first), 0 if the log view is hidden
+    activity_log_shown: usize,
 }
 
 impl KeystrApp {
@@ -55,6 +125,8 @@ impl KeystrApp {
         Self {
             model: KeystrModel::init(),
             current_tab: Tab::Keys,
+            show_error_detail: false,
+            activity_log_shown: 0,
         }
     }
 
@@ -70,24 +142,97 @@ impl KeystrApp {
         .into()
     }
 
+    /// Row for copying the non-secret key summary, with optional NIP-05
+    fn public_summary_view(&self, label_width: Length) -> Element<'_, Message> {
+        let (copy_json, copy_text) = match self.model.own_keys.public_summary() {
+            Err(_) => (None, None),
+            Ok(summary) => (summary.to_json().ok(), Some(summary.to_text())),
+        };
+        let mut copy_json_button = button("Copy JSON");
+        if let Some(s) = copy_json {
+            copy_json_button = copy_json_button.on_press(Message::CopyToClipboard(s));
+        }
+        let mut copy_text_button = button("Copy text");
+        if let Some(s) = copy_text {
+            copy_text_button = copy_text_button.on_press(Message::CopyToClipboard(s));
+        }
+        row![
+            column![text("Summary:").size(15)]
+                .align_items(Alignment::Start)
+                .width(label_width)
+                .padding(0),
+            text_input(
+                "NIP-05 identifier (optional)",
+                &self.model.own_keys.nip05_input,
+                Message::KeysNip05Input,
+            )
+            .size(15),
+            copy_json_button,
+            copy_text_button,
+        ]
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0)
+        .into()
+    }
+
+    /// If there are unsaved changes but saving is not possible, explain why,
+    /// with the control to fix it (the password inputs are below)
+    fn persist_status_view(&self) -> Element<'_, Message> {
+        if !self.model.own_keys.has_unsaved_change {
+            return column![].into();
+        }
+        let status = self
+            .model
+            .settings
+            .security
+            .persist_status(!self.model.own_keys.save_password_input.is_empty());
+        let mut panel = column![].spacing(5).padding(0);
+        if let Some(message) = status.message() {
+            panel = panel.push(text(message).size(15));
+        }
+        if status == PersistStatus::BlockedByLevel {
+            panel = panel.push(
+                pick_list(
+                    SECURITY_LEVELS,
+                    Some(self.model.settings.security.security_level),
+                    Message::SecurityLevelChange,
+                )
+                .text_size(15),
+            );
+        }
+        panel.into()
+    }
+
     fn tab_keys(&self) -> Element<Message> {
         let label_width = Length::Fixed(150.0);
+        let display_format = self.model.settings.display_format;
+        let pubkey_display = self.model.own_keys.get_public_key_display(display_format);
 
-        let unlock_ui = if self.model.own_keys.is_encrypted_secret_key_set() {
-            column![row![
-                text("Password is needed to unlock secret key:").size(15),
-                text_input(
-                    "enter password that was used for encrypting secret key",
-                    &self.model.own_keys.decrypt_password_input,
-                    Message::KeysDecryptPasswordInput,
-                )
-                .password()
-                .size(15),
-                button("Unlock").on_press(Message::ModelAction(Action::KeysUnlock)),
+        let unlock_ui = if self.model.own_keys.is_encrypted_secret_key_set()
+            && !self.model.own_keys.is_secret_key_set()
+        {
+            let saved_pubkey = match &self.model.saved_pubkey {
+                None => "(not available)".to_string(),
+                Some(pk) => display_format.format_public_key(pk),
+            };
+            column![
+                text(format!("Saved key:  {}", saved_pubkey)).size(15),
+                row![
+                    text("Password is needed to unlock secret key:").size(15),
+                    text_input(
+                        "enter password that was used for encrypting secret key",
+                        &self.model.own_keys.decrypt_password_input,
+                        Message::KeysDecryptPasswordInput,
+                    )
+                    .password()
+                    .size(15),
+                    button("Unlock").on_press(Message::ModelAction(Action::KeysUnlock)),
+                ]
+                .align_items(Alignment::Fill)
+                .spacing(5)
+                .padding(0)
             ]
-            .align_items(Alignment::Fill)
-            .spacing(5)
-            .padding(0)]
         } else {
             column![]
         }
@@ -99,14 +244,27 @@ impl KeystrApp {
             text("Own Keys").size(25),
             unlock_ui,
             row![
-                column![text("Public key (npub):").size(15)]
+                column![text(format!("Public key ({}):", display_format)).size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                button("QR").on_press(Message::QRCode(pubkey_display.clone())),
+                text_input("public key", &pubkey_display, Message::ChangedReadonly,).size(15),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text(format!("  ({}):", display_format.other())).size(15)]
                     .align_items(Alignment::Start)
                     .width(label_width)
                     .padding(0),
-                button("QR").on_press(Message::QRCode(self.model.own_keys.get_npub())),
                 text_input(
-                    "npub public key",
-                    &self.model.own_keys.get_npub(),
+                    "public key",
+                    &self
+                        .model
+                        .own_keys
+                        .get_public_key_display(display_format.other()),
                     Message::ChangedReadonly,
                 )
                 .size(15),
@@ -114,6 +272,7 @@ impl KeystrApp {
             .align_items(Alignment::Fill)
             .spacing(5)
             .padding(0),
+            self.public_summary_view(label_width),
             row![
                 column![text("Secret key (nsec):").size(15)]
                     .align_items(Alignment::Start)
@@ -147,16 +306,91 @@ impl KeystrApp {
                 "(no changes)"
             })
             .size(15),
+            self.persist_status_view(),
             iced::widget::rule::Rule::horizontal(5),
             row![
                 button("Load").on_press(Message::ModelAction(Action::KeysLoad)),
                 button("Save").on_press(Message::ModelAction(Action::KeysSave)),
                 button("Generate new keypair").on_press(Message::ModelAction(Action::KeysGenerate)),
+                button("Quick new identity")
+                    .on_press(Message::ModelAction(Action::NewIdentityStart)),
                 button("Clear keys").on_press(Message::ModelAction(Action::KeysClear)),
+                button("Audit").on_press(Message::ModelAction(Action::KeysAudit)),
+                button("Benchmark").on_press(Message::ModelAction(Action::KeysBenchmarkSign)),
             ]
             .align_items(Alignment::Fill)
             .spacing(5)
             .padding(0),
+            row![
+                text("Relay list (NIP-65):").size(15),
+                text_input(
+                    "relays, comma-separated, with optional read/write marker; empty for response relays",
+                    &self.model.own_keys.relay_list_input,
+                    Message::KeysRelayListInput,
+                )
+                .size(15),
+                button("Publish").on_press(Message::ModelAction(Action::KeysPublishRelayList)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                text("Vanity npub prefix:").size(15),
+                text_input(
+                    "prefix, after 'npub1'",
+                    &self.model.vanity.prefix_input,
+                    Message::KeysVanityPrefixInput,
+                )
+                .size(15),
+                if self.model.vanity.is_running() {
+                    button("Stop").on_press(Message::ModelAction(Action::KeysVanityStop))
+                } else if self.model.vanity.is_finished() {
+                    button("Use found key").on_press(Message::ModelAction(Action::KeysVanityStop))
+                } else {
+                    button("Search").on_press(Message::ModelAction(Action::KeysVanityStart))
+                },
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                text("Sign note:").size(15),
+                text_input(
+                    "note content",
+                    &self.model.note_signer.content_input,
+                    Message::KeysNoteContentInput,
+                )
+                .size(15),
+                text_input(
+                    "created_at (unix time, optional)",
+                    &self.model.note_signer.created_at_input,
+                    Message::KeysNoteCreatedAtInput,
+                )
+                .size(15)
+                .width(Length::Fixed(220.0)),
+                button("Sign").on_press(Message::ModelAction(Action::KeysSignNote)),
+                match self.model.note_signer.signed_json() {
+                    Some(json) => button("Copy signed").on_press(Message::CopyToClipboard(json)),
+                    None => button("Copy signed"),
+                },
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                text("Or as direct message to:").size(15),
+                text_input(
+                    "recipient npub or hex",
+                    &self.model.note_signer.recipient_input,
+                    Message::KeysNoteRecipientInput,
+                )
+                .size(15),
+                button("Encrypt and sign DM")
+                    .on_press(Message::ModelAction(Action::KeysSignDm)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
             text("Password to encrypt secret key:").size(15),
             row![
                 column![text("Password:").size(15),]
@@ -190,6 +424,41 @@ impl KeystrApp {
             .align_items(Alignment::Fill)
             .spacing(5)
             .padding(0),
+            row![
+                column![text("Current password:").size(15),]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "current password of the saved key, to change it to the one above",
+                    &self.model.own_keys.old_password_input,
+                    Message::KeysOldPasswordInput,
+                )
+                .password()
+                .size(15),
+                button("Change password")
+                    .on_press(Message::ModelAction(Action::KeysChangePassword)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("Verify backup:").size(15),]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "password of the saved key",
+                    &self.model.own_keys.verify_password_input,
+                    Message::KeysVerifyPasswordInput,
+                )
+                .password()
+                .size(15),
+                button("Verify").on_press(Message::ModelAction(Action::KeysVerifyBackup)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
             iced::widget::rule::Rule::horizontal(5),
             row![
                 text_input(
@@ -208,7 +477,7 @@ impl KeystrApp {
             row![
                 text_input(
                     "npub or hex for secret key import",
-                    &self.model.own_keys.secret_key_input,
+                    self.model.own_keys.secret_key_input.as_str(),
                     Message::KeysSecretkeyInput,
                 )
                 .password()
@@ -235,6 +504,29 @@ impl KeystrApp {
             .spacing(5)
             .padding(0),
             iced::widget::rule::Rule::horizontal(5),
+            row![
+                text_input(
+                    "Encrypted key exported by another client (ncryptsec, NIP-49, or JSON)",
+                    self.model.own_keys.client_export_input.as_str(),
+                    Message::KeysClientExportInput,
+                )
+                .password()
+                .size(15),
+                text_input(
+                    "Export password",
+                    &self.model.own_keys.client_export_password_input,
+                    Message::KeysClientExportPasswordInput,
+                )
+                .password()
+                .size(15)
+                .width(Length::Fixed(150.0)),
+                button("Import Client Export")
+                    .on_press(Message::ModelAction(Action::KeysImportClientExport)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            iced::widget::rule::Rule::horizontal(5),
         ]
         .align_items(Alignment::Fill)
         .spacing(5)
@@ -243,7 +535,23 @@ impl KeystrApp {
         .into()
     }
 
+    /// Prompt shown instead of a tab's controls if its feature is not available
+    fn empty_state_view(&self, title: &str, guidance: String) -> Element<'_, Message> {
+        column![
+            text(title).size(25),
+            text(guidance).size(15),
+            button("Go to Keys").on_press(Message::TabSelect(Tab::Keys)),
+        ]
+        .spacing(5)
+        .padding(20)
+        .max_width(600)
+        .into()
+    }
+
     fn tab_delegate(&self) -> Element<Message> {
+        if let Some(guidance) = self.model.readiness().guidance("delegator") {
+            return self.empty_state_view("Delegate", guidance);
+        }
         let label_width = Length::Fixed(150.0);
         column![
             text("Delegate").size(25),
@@ -356,7 +664,12 @@ impl KeystrApp {
             .spacing(5)
             .padding(0),
             iced::widget::rule::Rule::horizontal(5),
-            button("Create Delegation").on_press(Message::ModelAction(Action::DelegateSign)),
+            row![
+                button("Create Delegation").on_press(Message::ModelAction(Action::DelegateSign)),
+                button("Test").on_press(Message::ModelAction(Action::DelegateSelfTest)),
+            ]
+            .spacing(5)
+            .padding(0),
             row![
                 column![text("Signature:").size(15),]
                     .align_items(Alignment::Start)
@@ -380,6 +693,8 @@ impl KeystrApp {
                 Message::ChangedReadonly,
             )
             .size(15),
+            iced::widget::rule::Rule::horizontal(5),
+            self.received_delegations_view(),
         ]
         .align_items(Alignment::Fill)
         .spacing(5)
@@ -388,11 +703,128 @@ impl KeystrApp {
         .into()
     }
 
+    /// Password input for signing with a key that needs the password for each signature
+    fn fresh_unlock_view(&self) -> Element<'_, Message> {
+        if !self.model.requires_fresh_unlock() {
+            return column![].into();
+        }
+        row![
+            text("Password, needed for each signature:").size(15),
+            text_input(
+                "enter the password of the key to sign",
+                &self.model.own_keys.decrypt_password_input,
+                Message::KeysDecryptPasswordInput,
+            )
+            .password()
+            .size(15),
+        ]
+        .align_items(Alignment::Center)
+        .spacing(5)
+        .padding(0)
+        .into()
+    }
+
+    /// Delegations received from others, the selected one is applied when signing requests
+    fn received_delegations_view(&self) -> Element<'_, Message> {
+        let delegator = &self.model.delegator;
+        let mut list = column![
+            text("Received delegations -- applied when signing, if conditions permit:").size(15),
+            row![
+                text_input(
+                    "paste delegation tag received from a delegator",
+                    &delegator.delegation_import_input,
+                    Message::DelegationImportInput,
+                )
+                .size(15),
+                button("Add").on_press(Message::ModelAction(Action::DelegationAdd)),
+                button("Use none").on_press(Message::ModelAction(Action::DelegationSelect(None))),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+        ]
+        .spacing(5)
+        .padding(0);
+        for (i, d) in delegator.delegations.iter().enumerate() {
+            let selected = delegator.selected_delegation == Some(i);
+            let mut select = button(if selected { "Selected" } else { "Select" });
+            if !selected {
+                select = select.on_press(Message::ModelAction(Action::DelegationSelect(Some(i))));
+            }
+            list = list.push(
+                row![select, text(d.description()).size(15)]
+                    .align_items(Alignment::Center)
+                    .spacing(5)
+                    .padding(0),
+            );
+        }
+        list.into()
+    }
+
+    /// Permissions to grant to the client when pairing; all supported methods unless restricted
+    fn grant_permissions_view(&self) -> Element<'_, Message> {
+        let grant = &self.model.signer.grant;
+        let restrict = checkbox(
+            "Grant only selected permissions to the client",
+            grant.restrict,
+            Message::SignerGrantRestrictToggle,
+        )
+        .text_size(15);
+        if !grant.restrict {
+            return restrict.into();
+        }
+        let mut panel = column![
+            restrict,
+            row![
+                checkbox(
+                    "Sign events",
+                    grant.sign_event,
+                    Message::SignerGrantSignEventToggle
+                )
+                .text_size(15),
+                text_input(
+                    "kinds, e.g. 1, 7 (all if empty)",
+                    &grant.sign_event_kinds_input,
+                    Message::SignerGrantKindsInput,
+                )
+                .size(15),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            checkbox(
+                "Delegate",
+                grant.delegate,
+                Message::SignerGrantDelegateToggle
+            )
+            .text_size(15),
+        ]
+        .spacing(5)
+        .padding(0);
+        if self.model.settings.signer.allow_sign_raw {
+            panel = panel.push(
+                checkbox(
+                    "Sign raw hashes",
+                    grant.sign_raw,
+                    Message::SignerGrantSignRawToggle,
+                )
+                .text_size(15),
+            );
+        }
+        panel.into()
+    }
+
     fn tab_signer(&self) -> Element<Message> {
         let conn_status = &self.model.signer.get_connection_status();
 
         let connection_content: Element<Message> = match conn_status {
             ConnectionStatus::NotConnected => {
+                // a key needing the password for each signature is connected locked
+                if let Some(guidance) = self.model.readiness().guidance("signer") {
+                    if !self.model.requires_fresh_unlock() {
+                        return self.empty_state_view("Signer", guidance);
+                    }
+                }
                 column![
                     text(format!("Status:  {}", "Not connected")).size(15),
                     text("Enter NostrConnect URI:").size(15),
@@ -410,11 +842,77 @@ impl KeystrApp {
                     .spacing(5)
                     .padding(0),
                     row![
-                        button("Connect").on_press(Message::ModelAction(Action::SignerConnect)),
+                        text("Derived key account (optional, needs mnemonic imported in this session):").size(15),
+                        text_input(
+                            "NIP-06 account index",
+                            &self.model.signer.child_account_input,
+                            Message::SignerChildAccountInput,
+                        )
+                        .size(15),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(5)
+                    .padding(0),
+                    self.grant_permissions_view(),
+                    row![
+                        if self.model.check_can_connect_signer().is_ok() {
+                            button("Connect").on_press(Message::ModelAction(Action::SignerConnect))
+                        } else {
+                            // watch-only key, can't act as a signer
+                            button("Connect")
+                        },
                         button("Refresh").on_press(Message::Refresh),
                     ]
                     .spacing(5)
                     .padding(0),
+                    row![
+                        text(format!(
+                            "Response relays ({}):",
+                            self.model.settings.signer.response_relays.len()
+                        ))
+                        .size(15),
+                        text_input(
+                            "paste relay list event (kind 3/10002) or JSON list of relays",
+                            &self.model.settings.relays_import_input,
+                            Message::SettingsRelaysImportInput,
+                        )
+                        .size(15),
+                        button("Import relays")
+                            .on_press(Message::ModelAction(Action::SettingsImportRelays)),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(5)
+                    .padding(0),
+                    checkbox(
+                        "Allow signing raw hashes (advanced, dangerous: signed content cannot be checked)",
+                        self.model.settings.signer.allow_sign_raw,
+                        Message::SignerAllowSignRawToggle
+                    )
+                    .text_size(15),
+                    checkbox(
+                        "Stealth mode: answer capability (describe) requests only when approved",
+                        self.model.settings.signer.stealth_mode,
+                        Message::SignerStealthModeToggle
+                    )
+                    .text_size(15),
+                    checkbox(
+                        "Approve requests only while this window is focused",
+                        self.model.settings.signer.require_focus_to_approve,
+                        Message::SignerRequireFocusToggle
+                    )
+                    .text_size(15),
+                    checkbox(
+                        "Always show the full content of sign requests",
+                        self.model.settings.signer.show_full_content,
+                        Message::SignerShowFullContentSettingToggle
+                    )
+                    .text_size(15),
+                    checkbox(
+                        "Require the password for each signature with this key (the key is not kept unlocked)",
+                        self.model.requires_fresh_unlock(),
+                        Message::SignerFreshUnlockToggle
+                    )
+                    .text_size(15),
                 ]
                 // .align_items(Alignment::Fill)
                 .spacing(5)
@@ -447,6 +945,8 @@ impl KeystrApp {
                     } else {
                         // There are pending requests, show them
                         let first_req_desc = conn.get_first_request_description();
+                        let raw_jsons = conn.get_request_raw_jsons();
+                        let senders = self.model.signer.get_pending_senders();
                         column![
                             text(&format!(
                                 "There is a request ({})",
@@ -455,21 +955,47 @@ impl KeystrApp {
                             .size(15),
                             column![
                                 text(first_req_desc).size(15),
+                                self.full_content_view(conn.get_first_request_full_content()),
+                                self.modifications_view(conn.get_first_request_modifications()),
+                                self.references_view(conn.get_first_request_references()),
+                                self.serialization_view(
+                                    conn.get_first_request_canonical_serialization()
+                                ),
+                                self.fresh_unlock_view(),
                                 row![
-                                    button("SIGN").on_press(Message::ModelAction(
-                                        Action::SignerPendingProcessFirst
-                                    )),
+                                    if self.model.check_can_approve().is_ok() {
+                                        button("SIGN").on_press(Message::ModelAction(
+                                            Action::SignerPendingProcessFirst,
+                                        ))
+                                    } else {
+                                        button("SIGN")
+                                    },
                                     button("Ignore").on_press(Message::ModelAction(
                                         Action::SignerPendingIgnoreFirst
                                     )),
+                                    button("Reject").on_press(Message::ModelAction(
+                                        Action::SignerPendingRejectFirst
+                                    )),
+                                    button("Reject all").on_press(Message::ModelAction(
+                                        Action::SignerPendingRejectAll
+                                    )),
                                     button("Disconnect")
                                         .on_press(Message::ModelAction(Action::SignerDisconnect)),
+                                    button("Copy raw").on_press(Message::CopyToClipboard(
+                                        raw_jsons.first().cloned().unwrap_or_default()
+                                    )),
+                                    self.trust_app_view(senders.first()),
                                 ]
                                 .spacing(5)
                                 .padding(0)
                             ]
                             .spacing(5)
-                            .padding(0)
+                            .padding(0),
+                            self.pending_queue_view(
+                                &conn.get_request_descriptions(),
+                                &raw_jsons,
+                                &senders
+                            ),
                         ]
                         .spacing(5)
                         .padding(0)
@@ -477,10 +1003,43 @@ impl KeystrApp {
                     text(&format!(
                         "Status:  Connected, through relay '{}' to client '{}'",
                         conn.relay_str,
-                        conn.get_client_npub(),
+                        conn.get_display_name(self.model.settings.display_format),
                     ))
                     .size(15),
-                    button("Disconnect").on_press(Message::ModelAction(Action::SignerDisconnect)),
+                    row![
+                        text("Client name:").size(15),
+                        text_input(
+                            "friendly name for this client, e.g. 'My phone'",
+                            &self.model.signer.display_name_input,
+                            Message::SignerDisplayNameInput,
+                        )
+                        .size(15),
+                        button("Set name")
+                            .on_press(Message::ModelAction(Action::SignerSetDisplayName)),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(5)
+                    .padding(0),
+                    text(match conn.get_latency_stats() {
+                        None => "Signing latency:  -".to_string(),
+                        Some((last, average)) => format!(
+                            "Signing latency:  last {} ms, average {} ms",
+                            last.as_millis(),
+                            average.as_millis()
+                        ),
+                    })
+                    .size(15),
+                    self.signed_ids_view(conn),
+                    self.connection_error_view(conn),
+                    self.event_viewer_view(conn),
+                    row![
+                        button("Disconnect")
+                            .on_press(Message::ModelAction(Action::SignerDisconnect)),
+                        button("Rotate relay identity")
+                            .on_press(Message::ModelAction(Action::SignerRotateAppId)),
+                    ]
+                    .spacing(5)
+                    .padding(0),
                 ]
                 // .align_items(Alignment::Fill)
                 .spacing(5)
@@ -489,18 +1048,451 @@ impl KeystrApp {
             }
         };
 
-        column![text("Signer").size(25), connection_content]
-            // .align_items(Alignment::Fill)
+        column![
+            text("Signer").size(25),
+            connection_content,
+            text(format!(
+                "Supported methods:  {}",
+                supported_methods(&self.model.settings.signer).join(", ")
+            ))
+            .size(12),
+        ]
+        // .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(20)
+        .max_width(600)
+        .into()
+    }
+
+    /// Ids of the events signed through the connection in this session, newest first
+    fn signed_ids_view(&self, conn: &SignerConnection) -> Element<'_, Message> {
+        let ids = conn.get_signed_event_ids();
+        let mut col =
+            column![text(format!("Signed events (this session):  {}", ids.len())).size(15)]
+                .spacing(2)
+                .padding(0);
+        for id in ids {
+            col = col.push(text(id.to_bech32().unwrap_or_else(|_| id.to_hex())).size(12));
+        }
+        col.into()
+    }
+
+    /// Relay events addressed to the signer (if the viewer is open), to check it is reachable
+    fn event_viewer_view(&self, conn: &SignerConnection) -> Element<'_, Message> {
+        let events = match conn.get_viewed_events() {
+            None => {
+                return button("Show relay events")
+                    .on_press(Message::ModelAction(Action::SignerToggleEventViewer))
+                    .into()
+            }
+            Some(events) => events,
+        };
+        let mut col = column![row![
+            text(format!(
+                "Recent relay events to the signer:  {}",
+                events.len()
+            ))
+            .size(15),
+            button("Hide").on_press(Message::ModelAction(Action::SignerToggleEventViewer)),
+        ]
+        .align_items(Alignment::Center)
+        .spacing(5)
+        .padding(0)]
+        .spacing(2)
+        .padding(0);
+        let format = self.model.settings.display_format;
+        for event in events {
+            col = col.push(
+                text(format!(
+                    "{}, created_at {}, from {}",
+                    kind_label(event.kind),
+                    event.created_at.as_u64(),
+                    format.format_public_key(&event.sender)
+                ))
+                .size(12),
+            );
+        }
+        col.into()
+    }
+
+    /// Last error on the connection (if any), shown until cleared or resolved
+    fn connection_error_view(&self, conn: &SignerConnection) -> Element<'_, Message> {
+        match conn.get_last_error() {
+            None => column![].into(),
+            Some(error) => row![
+                text(format!("Last error on this connection:  {}", error))
+                    .size(15)
+                    .style(Color::from_rgb(0.9, 0.2, 0.1)),
+                button("Clear").on_press(Message::ModelAction(Action::SignerClearError)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0)
+            .into(),
+        }
+    }
+
+    /// List of all pending requests, with buttons to reorder them and to copy their raw JSON
+    fn pending_queue_view(
+        &self,
+        descriptions: &[String],
+        raw_jsons: &[String],
+        senders: &[(XOnlyPublicKey, bool)],
+    ) -> Element<'_, Message> {
+        if descriptions.len() < 2 {
+            return column![].into();
+        }
+        let last = descriptions.len() - 1;
+        let mut list = column![text("Pending queue:").size(15)]
             .spacing(5)
-            .padding(20)
-            .max_width(600)
+            .padding(0);
+        for (i, desc) in descriptions.iter().enumerate() {
+            let mut up = button("Up");
+            if i > 0 {
+                up = up.on_press(Message::ModelAction(Action::SignerPendingMove(i, i - 1)));
+            }
+            let mut down = button("Down");
+            if i < last {
+                down = down.on_press(Message::ModelAction(Action::SignerPendingMove(i, i + 1)));
+            }
+            let mut copy_raw = button("Copy raw");
+            if let Some(raw) = raw_jsons.get(i) {
+                copy_raw = copy_raw.on_press(Message::CopyToClipboard(raw.clone()));
+            }
+            list = list.push(
+                row![
+                    up,
+                    down,
+                    copy_raw,
+                    self.trust_app_view(senders.get(i)),
+                    text(format!("{}. {}", i + 1, desc)).size(15)
+                ]
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(0),
+            );
+        }
+        list.into()
+    }
+
+    /// Checkbox to trust the app sending a request (sender pubkey, whether trusted):
+    /// its sign requests are signed without asking for the rest of the session
+    fn trust_app_view(&self, sender: Option<&(XOnlyPublicKey, bool)>) -> Element<'_, Message> {
+        match sender {
+            None => column![].into(),
+            Some(&(pubkey, trusted)) => checkbox("Trust this app", trusted, move |b| {
+                Message::ModelAction(Action::SignerSetAutoApprove(pubkey, b))
+            })
+            .text_size(15)
+            .into(),
+        }
+    }
+
+    /// Changes made to the event before signing, if any
+    fn modifications_view(&self, modifications: Vec<String>) -> Element<'_, Message> {
+        let mut list = column![].spacing(5).padding(0);
+        if !modifications.is_empty() {
+            list = list.push(text("MODIFIED before signing:").size(15));
+        }
+        for m in modifications {
+            list = list.push(text(format!("  {}", m)).size(15));
+        }
+        list.into()
+    }
+
+    /// Full content of a sign request, if enabled (globally or for this request), scrollable
+    fn full_content_view(&self, content: Option<String>) -> Element<'_, Message> {
+        let content = match content {
+            None => return column![].into(),
+            Some(c) => c,
+        };
+        let full_content =
+            scrollable(text(content).size(12)).height(Length::Fixed(FULL_CONTENT_HEIGHT));
+        if self.model.settings.signer.show_full_content {
+            return full_content.into();
+        }
+        let toggle = checkbox(
+            "Show full content",
+            self.model.signer.show_full_content,
+            Message::SignerShowFullContentToggle,
+        )
+        .text_size(15);
+        if self.model.signer.show_full_content {
+            column![toggle, full_content]
+        } else {
+            column![toggle]
+        }
+        .spacing(5)
+        .padding(0)
+        .into()
+    }
+
+    /// Entities referenced by a request (note/npub), each with a copy button
+    fn references_view(&self, references: Vec<String>) -> Element<'_, Message> {
+        let mut list = column![].spacing(5).padding(0);
+        if !references.is_empty() {
+            list = list.push(text("Referenced:").size(15));
+        }
+        for r in references {
+            list = list.push(
+                row![
+                    button("Copy").on_press(Message::CopyToClipboard(r.clone())),
+                    text(r).size(15),
+                ]
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(0),
+            );
+        }
+        list.into()
+    }
+
+    /// Canonical serialization of the event to sign (hashed to form its id), with a copy button
+    fn serialization_view(&self, serialization: Option<String>) -> Element<'_, Message> {
+        match serialization {
+            None => column![].into(),
+            Some(s) => column![
+                row![
+                    text("Serialized event (its sha256 is the id):").size(15),
+                    button("Copy").on_press(Message::CopyToClipboard(s.clone())),
+                ]
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(0),
+                text(s).size(12),
+            ]
+            .spacing(5)
+            .padding(0)
+            .into(),
+        }
+    }
+
+    /// Version and build information, can be copied (as JSON) for bug reports
+    fn about_view(&self) -> Element<'_, Message> {
+        let info = build_info();
+        column![
+            row![
+                text(format!("About: {}", info)).size(12),
+                button("Copy build info").on_press(Message::CopyToClipboard(info.to_json())),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            text(format!(
+                "Implemented NIPs: {}",
+                format_nips(&info.implemented_nips)
+            ))
+            .size(12),
+        ]
+        .spacing(5)
+        .padding(0)
+        .into()
+    }
+
+    /// Warning if the current key has not been saved, so it is not taken as permanent
+    fn ephemeral_key_banner(&self) -> Element<'_, Message> {
+        if !self.model.own_keys.is_ephemeral() {
+            return column![].into();
+        }
+        text("Unsaved key \u{2014} will be lost on exit")
+            .size(20)
+            .style(Color::from_rgb(0.9, 0.2, 0.1))
+            .into()
+    }
+
+    /// Expandable panel with details of the last error, copyable
+    fn error_detail_view(&self) -> Element<'_, Message> {
+        let detail = match self.model.last_error() {
+            None => return column![].into(),
+            Some(d) => d,
+        };
+        if !self.show_error_detail {
+            return button("Show last error details")
+                .on_press(Message::ToggleErrorDetail)
+                .into();
+        }
+        let mut panel = column![
+            button("Hide error details").on_press(Message::ToggleErrorDetail),
+            text(&detail.message).size(15),
+        ]
+        .spacing(5)
+        .padding(0);
+        for s in &detail.sources {
+            panel = panel.push(text(format!("  caused by: {}", s)).size(15));
+        }
+        if let Some(help) = detail.help {
+            panel = panel.push(text(format!("Help: {}", help)).size(15));
+        }
+        panel
+            .push(text_input("error detail", &detail.to_text(), Message::ChangedReadonly).size(15))
             .into()
     }
 
+    /// Activity log, newest first, loaded page by page
+    fn activity_log_view(&self) -> Element<'_, Message> {
+        if self.activity_log_shown == 0 {
+            return button("Show activity log")
+                .on_press(Message::ToggleActivityLog)
+                .into();
+        }
+        let total = self.model.status.activity_log_len();
+        let mut entries = column![].spacing(2).padding(0);
+        for entry in self
+            .model
+            .status
+            .activity_log_page(0, self.activity_log_shown)
+        {
+            entries = entries.push(text(entry.to_line()).size(13));
+        }
+        let mut load_more = button("Load more");
+        if total > self.activity_log_shown {
+            load_more = load_more.on_press(Message::ActivityLogLoadMore);
+        }
+        column![
+            row![
+                button("Hide activity log").on_press(Message::ToggleActivityLog),
+                load_more,
+                text(format!(
+                    "{} of {} entries",
+                    self.activity_log_shown.min(total),
+                    total
+                ))
+                .size(15),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            scrollable(entries).height(Length::Fixed(ACTIVITY_LOG_HEIGHT)),
+        ]
+        .spacing(5)
+        .padding(0)
+        .into()
+    }
+
+    /// First-run wizard, shown instead of the tabs
+    fn wizard_view(&self, step: WizardStep) -> Element<'_, Message> {
+        let step_content = match step {
+            WizardStep::Key => column![
+                text("Step 1: Generate a new key, or import an existing one").size(15),
+                row![
+                    button("Generate new keypair")
+                        .on_press(Message::ModelAction(Action::KeysGenerate)),
+                    text_input(
+                        "secret key (nsec) to import",
+                        self.model.own_keys.secret_key_input.as_str(),
+                        Message::KeysSecretkeyInput,
+                    )
+                    .password()
+                    .size(15),
+                    button("Import").on_press(Message::ModelAction(Action::KeysImportSecretkey)),
+                ]
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(0),
+            ],
+            WizardStep::Password => column![
+                text("Step 2: Set a password for saving the secret key encrypted").size(15),
+                text_input(
+                    "password",
+                    &self.model.own_keys.save_password_input,
+                    Message::KeysSavePasswordInput,
+                )
+                .password()
+                .size(15),
+                text_input(
+                    "repeat password",
+                    &self.model.own_keys.save_repeat_password_input,
+                    Message::KeysSaveRepeatPasswordInput,
+                )
+                .password()
+                .size(15),
+                button("Save").on_press(Message::ModelAction(Action::KeysSave)),
+            ],
+            WizardStep::Relays => column![
+                text("Step 3 (optional): Additional relays for signer responses").size(15),
+                text_input(
+                    "wss://relay1.example.com, wss://relay2.example.com",
+                    &self.model.wizard.relays_input,
+                    Message::WizardRelaysInput,
+                )
+                .size(15),
+            ],
+        }
+        .spacing(5)
+        .padding(0);
+        column![
+            text("Welcome to Keystr").size(25),
+            step_content,
+            row![
+                button(if step == WizardStep::Relays {
+                    "Finish"
+                } else {
+                    "Next"
+                })
+                .on_press(Message::ModelAction(Action::WizardNext)),
+                button("Skip setup").on_press(Message::ModelAction(Action::WizardSkip)),
+            ]
+            .spacing(5)
+            .padding(0),
+        ]
+        .spacing(5)
+        .padding(20)
+        .max_width(600)
+        .into()
+    }
+
+    /// Quick new identity flow, shown instead of the tabs
+    fn new_identity_view(&self, step: NewIdentityStep) -> Element<'_, Message> {
+        let step_content = match step {
+            NewIdentityStep::Backup => column![
+                text("Step 1: Write down these words, they are shown only once!").size(15),
+                text(self.model.new_identity.mnemonic().unwrap_or_default()).size(20),
+                text("Anyone with these words has full control of the identity; without them a lost key cannot be restored.").size(15),
+                checkbox(
+                    "I have written down the words and keep them safe",
+                    self.model.new_identity.backup_confirmed,
+                    Message::NewIdentityBackupToggle
+                )
+                .text_size(15),
+                button("Next").on_press(Message::ModelAction(Action::NewIdentityBackupDone)),
+            ],
+            NewIdentityStep::Password => column![
+                text("Step 2: Set a password for saving the secret key encrypted").size(15),
+                text_input(
+                    "password",
+                    &self.model.own_keys.save_password_input,
+                    Message::KeysSavePasswordInput,
+                )
+                .password()
+                .size(15),
+                text_input(
+                    "repeat password",
+                    &self.model.own_keys.save_repeat_password_input,
+                    Message::KeysSaveRepeatPasswordInput,
+                )
+                .password()
+                .size(15),
+                button("Save").on_press(Message::ModelAction(Action::NewIdentitySave)),
+            ],
+        }
+        .spacing(5)
+        .padding(0);
+        column![
+            text("New Identity").size(25),
+            step_content,
+            button("Cancel").on_press(Message::ModelAction(Action::NewIdentityCancel)),
+        ]
+        .spacing(5)
+        .padding(20)
+        .max_width(600)
+        .into()
+    }
+
     fn view_dialog(&self, modal: &Modal) -> Element<Message> {
         container(match modal {
-            Modal::Confirmation(Confirmation::KeysClearBeforeAction(_)) => column![
-                text("Remove existing keys?").size(25),
+            Modal::Confirmation(conf) => column![
+                text(&conf.title).size(25),
+                text(&conf.body).size(15),
                 iced::widget::rule::Rule::horizontal(5),
                 row![
                     button("Yes").on_press(Message::ModelAction(Action::ConfirmationYes)),
@@ -520,6 +1512,21 @@ impl KeystrApp {
                 text("QR Code").size(25),
                 QRCode::new(&self.model.get_qr_code().as_ref().unwrap().1).cell_size(6),
                 text(qr_content).size(15),
+                row![
+                    text_input(
+                        &format!(
+                            "File to save the image to (default {})",
+                            Storage::qr_png_file().display()
+                        ),
+                        &self.model.qr_png_path_input,
+                        Message::QrPngPathInput,
+                    )
+                    .size(15),
+                    button("Save PNG").on_press(Message::ModelAction(Action::QrSavePng)),
+                ]
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(0),
                 button("Close").on_press(Message::QRCodeClose),
             ]
             .align_items(Alignment::Fill)
@@ -527,25 +1534,68 @@ impl KeystrApp {
             .spacing(5)
             .padding(20),
 
-            Modal::SignerRequest(desc) => column![
-                text("Sign Request").size(25),
-                text("You have received a request to SIGN an event/post:").size(15),
-                text(desc).size(15),
-                iced::widget::rule::Rule::horizontal(5),
-                row![
-                    button("SIGN")
-                        .on_press(Message::ModelAction(Action::SignerPendingProcessFirst)),
-                    button("Ignore")
-                        .on_press(Message::ModelAction(Action::SignerPendingIgnoreFirst)),
-                    button("Disconnect").on_press(Message::ModelAction(Action::SignerDisconnect)),
+            Modal::SignerRequest(desc) => {
+                let raw_jsons = self.model.signer.get_pending_raw_jsons();
+                let senders = self.model.signer.get_pending_senders();
+                column![
+                    text("Sign Request").size(25),
+                    text("You have received a request to SIGN an event/post:").size(15),
+                    text(desc).size(15),
+                    self.full_content_view(self.model.signer.get_first_request_full_content()),
+                    self.modifications_view(self.model.signer.get_first_request_modifications()),
+                    self.references_view(self.model.signer.get_first_request_references()),
+                    self.serialization_view(
+                        self.model
+                            .signer
+                            .get_first_request_canonical_serialization()
+                    ),
+                    iced::widget::rule::Rule::horizontal(5),
+                    text_input(
+                        "sign with derived key account (optional)",
+                        &self.model.signer.sign_override_account_input,
+                        Message::SignerSignOverrideAccountInput,
+                    )
+                    .size(15),
+                    row![
+                        if self.model.signer.is_first_request_event_id_valid()
+                            && self.model.check_can_approve().is_ok()
+                        {
+                            button("SIGN")
+                                .on_press(Message::ModelAction(Action::SignerPendingProcessFirst))
+                        } else {
+                            // tampered request, or window not focused: signing is not offered
+                            button("SIGN")
+                        },
+                        button("Ignore")
+                            .on_press(Message::ModelAction(Action::SignerPendingIgnoreFirst)),
+                        button("Reject")
+                            .on_press(Message::ModelAction(Action::SignerPendingRejectFirst)),
+                        button("Bump time")
+                            .on_press(Message::ModelAction(Action::SignerPendingBumpTime)),
+                        button("Reject all")
+                            .on_press(Message::ModelAction(Action::SignerPendingRejectAll)),
+                        button("Export")
+                            .on_press(Message::ModelAction(Action::SignerPendingExport)),
+                        button("Disconnect")
+                            .on_press(Message::ModelAction(Action::SignerDisconnect)),
+                        button("Copy raw").on_press(Message::CopyToClipboard(
+                            raw_jsons.first().cloned().unwrap_or_default()
+                        )),
+                        self.trust_app_view(senders.first()),
+                    ]
+                    .spacing(5)
+                    .padding(0),
+                    self.pending_queue_view(
+                        &self.model.signer.get_pending_descriptions(),
+                        &raw_jsons,
+                        &senders,
+                    ),
                 ]
+                .align_items(Alignment::Fill)
+                .width(Length::Fill)
                 .spacing(5)
-                .padding(0)
-            ]
-            .align_items(Alignment::Fill)
-            .width(Length::Fill)
-            .spacing(5)
-            .padding(20),
+                .padding(20)
+            }
         })
         .width(Length::Fixed(300.0))
         .padding(10)
@@ -566,17 +1616,75 @@ impl KeystrApp {
                     Message::SecurityLevelChange
                 )
                 .text_size(15),
+                checkbox(
+                    "Auto-save keys after generate/import",
+                    self.model.settings.security.auto_save,
+                    Message::AutoSaveToggle
+                )
+                .text_size(15),
+                row![
+                    text("Key encryption:").size(15),
+                    pick_list(
+                        ENCRYPTION_ALGORITHMS,
+                        Some(self.model.settings.security.encryption_algorithm),
+                        Message::EncryptionAlgorithmChange
+                    )
+                    .text_size(15),
+                ]
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(0),
+                checkbox(
+                    "Persist activity log to disk (secrets redacted)",
+                    self.model.settings.persist_activity_log,
+                    Message::ActivityLogToggle
+                )
+                .text_size(15),
+                checkbox(
+                    "Mirror status messages to stderr (secrets redacted)",
+                    self.model.settings.mirror_status_to_stderr,
+                    Message::StatusMirrorToggle
+                )
+                .text_size(15),
+                checkbox(
+                    "Show a tray icon with the pending requests; closing the window hides it to the tray",
+                    self.model.settings.tray_mode,
+                    Message::TrayModeToggle
+                )
+                .text_size(15),
+                row![
+                    text("Key display format:").size(15),
+                    pick_list(
+                        DISPLAY_FORMATS,
+                        Some(self.model.settings.display_format),
+                        Message::DisplayFormatChange
+                    )
+                    .text_size(15),
+                    button("Reset settings to defaults")
+                        .on_press(Message::ModelAction(Action::SettingsReset)),
+                ]
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(0),
+                self.about_view(),
                 iced::widget::rule::Rule::horizontal(5),
                 self.tab_selector(),
                 iced::widget::rule::Rule::horizontal(5),
                 text(&format!("| {}", &self.model.status.get_last_n(3))).size(15),
                 text(&format!("| {}", &self.model.status.get_last_n(2))).size(15),
                 text(&format!("| {}", &self.model.status.get_last())).size(15),
+                self.error_detail_view(),
+                self.activity_log_view(),
+                self.ephemeral_key_banner(),
                 iced::widget::rule::Rule::horizontal(5),
-                match self.current_tab {
-                    Tab::Keys => self.tab_keys(),
-                    Tab::Delegate => self.tab_delegate(),
-                    Tab::Signer => self.tab_signer(),
+                match (self.model.wizard.step(), self.model.new_identity.step()) {
+                    (Some(step), _) => self.wizard_view(step),
+                    (None, Some(step)) => self.new_identity_view(step),
+                    (None, None) => match self.current_tab {
+                        Tab::Keys => self.tab_keys(),
+                        Tab::Delegate => self.tab_delegate(),
+                        Tab::Signer => self.tab_signer(),
+                    },
                 },
                 iced::widget::rule::Rule::horizontal(5),
             ]
@@ -617,11 +1725,11 @@ impl Application for KeystrApp {
     }
 
     fn title(&self) -> String {
-        String::from("Keystr")
+        self.model.window_title()
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        subscription::unfold(
+        let events = subscription::unfold(
             std::any::TypeId::of::<KeystrModel>(),
             SubscriptionState::Uninited,
             move |state| async move {
@@ -629,17 +1737,31 @@ impl Application for KeystrApp {
                     SubscriptionState::Uninited => (None, SubscriptionState::Inited),
                     SubscriptionState::Inited => match EVENT_QUEUE.pop() {
                         Err(e) => {
-                            println!("DEBUG: Subscription: error {:?}", e);
+                            eprintln!("DEBUG: Subscription: error {:?}", e);
                             (None, SubscriptionState::Inited)
                         }
                         Ok(event) => {
-                            println!("DEBUG: Subscription: Got event {:?}", event);
+                            eprintln!("DEBUG: Subscription: Got event {:?}", event);
                             (Some(Message::ModelEvent(event)), SubscriptionState::Inited)
                         }
                     },
                 }
             },
-        )
+        );
+        let poll = iced::time::every(STATUS_POLL_INTERVAL).map(|_| Message::StatusPoll);
+        let focus = subscription::events_with(|event, _status| match event {
+            iced::Event::Window(iced::window::Event::Focused) => {
+                Some(Message::WindowFocusChanged(true))
+            }
+            iced::Event::Window(iced::window::Event::Unfocused) => {
+                Some(Message::WindowFocusChanged(false))
+            }
+            iced::Event::Window(iced::window::Event::CloseRequested) => {
+                Some(Message::WindowCloseRequested)
+            }
+            _ => None,
+        });
+        Subscription::batch(vec![events, poll, focus])
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
@@ -647,6 +1769,14 @@ impl Application for KeystrApp {
             Message::TabSelect(t) => {
                 self.current_tab = t;
             }
+            Message::ToggleErrorDetail => self.show_error_detail = !self.show_error_detail,
+            Message::ToggleActivityLog => {
+                self.activity_log_shown = match self.activity_log_shown {
+                    0 => ACTIVITY_LOG_PAGE_SIZE,
+                    _ => 0,
+                }
+            }
+            Message::ActivityLogLoadMore => self.activity_log_shown += ACTIVITY_LOG_PAGE_SIZE,
             Message::ModelAction(action) => {
                 self.model.action(action);
             }
@@ -654,10 +1784,22 @@ impl Application for KeystrApp {
             Message::KeysToggleHideSecretKey => {
                 self.model.own_keys.hide_secret_key = !self.model.own_keys.hide_secret_key
             }
-            Message::KeysSecretkeyInput(s) => self.model.own_keys.secret_key_input = s,
+            Message::KeysSecretkeyInput(s) => self.model.own_keys.secret_key_input = s.into(),
             Message::KeysMnemonicInput(s) => self.model.own_keys.mnemonic_input = s,
+            Message::KeysClientExportInput(s) => self.model.own_keys.client_export_input = s.into(),
+            Message::KeysClientExportPasswordInput(s) => {
+                self.model.own_keys.client_export_password_input = s
+            }
             Message::KeysDecryptPasswordInput(s) => self.model.own_keys.decrypt_password_input = s,
+            Message::KeysVanityPrefixInput(s) => self.model.vanity.prefix_input = s,
+            Message::KeysNoteContentInput(s) => self.model.note_signer.content_input = s,
+            Message::KeysNoteCreatedAtInput(s) => self.model.note_signer.created_at_input = s,
+            Message::KeysNoteRecipientInput(s) => self.model.note_signer.recipient_input = s,
+            Message::WizardRelaysInput(s) => self.model.wizard.relays_input = s,
+            Message::NewIdentityBackupToggle(b) => self.model.new_identity.backup_confirmed = b,
             Message::KeysSavePasswordInput(s) => self.model.own_keys.save_password_input = s,
+            Message::KeysVerifyPasswordInput(s) => self.model.own_keys.verify_password_input = s,
+            Message::KeysOldPasswordInput(s) => self.model.own_keys.old_password_input = s,
             Message::KeysSaveRepeatPasswordInput(s) => {
                 self.model.own_keys.save_repeat_password_input = s
             }
@@ -667,6 +1809,7 @@ impl Application for KeystrApp {
                     self.model.status.set_error(&e.to_string());
                 }
             }
+            Message::DelegationImportInput(s) => self.model.delegator.delegation_import_input = s,
             Message::DelegateKindChanged(s) => {
                 self.model.delegator.kind_condition_input = s;
                 if let Err(e) = self.model.delegator.validate_and_update() {
@@ -686,18 +1829,78 @@ impl Application for KeystrApp {
                 self.model.delegator.time_cond_days = s;
             }
             Message::SecurityLevelChange(l) => self.model.settings.set_security_level(l),
+            Message::AutoSaveToggle(b) => self.model.settings.set_auto_save(b),
+            Message::EncryptionAlgorithmChange(a) => {
+                self.model.settings.set_encryption_algorithm(a)
+            }
+            Message::ActivityLogToggle(b) => self.model.set_persist_activity_log(b),
+            Message::TrayModeToggle(b) => self.model.set_tray_mode(b),
+            Message::StatusMirrorToggle(b) => self.model.set_mirror_status_to_stderr(b),
+            Message::SignerAllowSignRawToggle(b) => {
+                self.model.settings.set_signer_allow_sign_raw(b)
+            }
+            Message::SignerStealthModeToggle(b) => self.model.settings.set_signer_stealth_mode(b),
+            Message::SignerRequireFocusToggle(b) => {
+                self.model.settings.set_signer_require_focus_to_approve(b)
+            }
+            Message::SignerShowFullContentSettingToggle(b) => {
+                self.model.settings.set_signer_show_full_content(b)
+            }
+            Message::SignerShowFullContentToggle(b) => self.model.signer.show_full_content = b,
+            Message::SignerFreshUnlockToggle(b) => self.model.set_fresh_unlock(b),
+            Message::WindowFocusChanged(focused) => self.model.set_window_focused(focused),
+            Message::WindowCloseRequested => {
+                return if self.model.is_tray_running() {
+                    window::change_mode(window::Mode::Hidden)
+                } else {
+                    window::close()
+                };
+            }
+            Message::DisplayFormatChange(f) => self.model.settings.set_display_format(f),
             Message::SignerUriInput(s) => self.model.signer.connect_uri_input = s,
+            Message::SignerGrantRestrictToggle(b) => self.model.signer.grant.restrict = b,
+            Message::SignerGrantSignEventToggle(b) => self.model.signer.grant.sign_event = b,
+            Message::SignerGrantKindsInput(s) => self.model.signer.grant.sign_event_kinds_input = s,
+            Message::SignerGrantDelegateToggle(b) => self.model.signer.grant.delegate = b,
+            Message::SignerGrantSignRawToggle(b) => self.model.signer.grant.sign_raw = b,
+            Message::SignerChildAccountInput(s) => self.model.signer.child_account_input = s,
+            Message::SignerSignOverrideAccountInput(s) => {
+                self.model.signer.sign_override_account_input = s
+            }
             Message::ChangedReadonly(_s) => {}
+            Message::CopyToClipboard(s) => return iced::clipboard::write(s),
+            Message::KeysNip05Input(s) => self.model.own_keys.nip05_input = s,
+            Message::SignerDisplayNameInput(s) => self.model.signer.display_name_input = s,
+            Message::KeysRelayListInput(s) => self.model.own_keys.relay_list_input = s,
+            Message::SettingsRelaysImportInput(s) => self.model.settings.relays_import_input = s,
+            Message::ModelEvent(Event::TrayAction(TrayAction::Open)) => {
+                return Command::batch(vec![
+                    window::change_mode(window::Mode::Windowed),
+                    window::gain_focus(),
+                ]);
+            }
+            Message::ModelEvent(Event::TrayAction(TrayAction::ApproveNext)) => {
+                self.model.action(Action::SignerPendingProcessFirst);
+                self.model.update_tray();
+            }
+            Message::ModelEvent(Event::SignerRequestExpiring) => {
+                self.model.update_tray();
+                return window::request_user_attention(Some(window::UserAttention::Critical));
+            }
             Message::ModelEvent(_) => {
-                // just do a refresh, no extra action needed here
+                // refresh, and show the new pending count in the tray
+                self.model.update_tray();
             }
             Message::QRCode(qr_content) => self.model.set_qr_code(qr_content),
             Message::QRCodeClose => self.model.reset_qr_code(),
+            Message::QrPngPathInput(s) => self.model.qr_png_path_input = s,
             Message::NoOp => {}
             Message::Refresh => {
                 // a message refreshes the UI, no extra action needed here
             }
+            Message::StatusPoll => self.model.status_poll(),
         }
+        self.model.update_saved_pubkey();
         Command::none()
     }
 